@@ -449,7 +449,7 @@ impl View for SecretEditView {
             Event::Key(Key::Left) if self.cursor > 0 => {
                 let len = self.content[..self.cursor]
                     .graphemes(true)
-                    .last()
+                    .next_back()
                     .unwrap()
                     .len();
                 let cursor = self.cursor - len;
@@ -467,7 +467,7 @@ impl View for SecretEditView {
             Event::Key(Key::Backspace) if self.cursor > 0 => {
                 let len = self.content[..self.cursor]
                     .graphemes(true)
-                    .last()
+                    .next_back()
                     .unwrap()
                     .len();
                 self.cursor -= len;
@@ -2,8 +2,8 @@ use std::io::Read;
 
 use base64::prelude::*;
 use clap::{Parser, ValueEnum};
-use wden::bitwarden::cipher::Cipher;
 use wden::bitwarden::cipher::{self, Pbkdf};
+use wden::bitwarden::cipher::{Cipher, PrivateKey};
 
 #[derive(ValueEnum, Clone)]
 #[clap(rename_all = "lower")]
@@ -83,7 +83,8 @@ fn main() -> Result<(), anyhow::Error> {
         let cipher = opts.cipher.unwrap().parse::<Cipher>()?;
         let decrypted_cipher = if let Some(priv_key_cipher) = opts.private_key_cipher {
             let der_priv_key = priv_key_cipher.parse::<Cipher>()?.decrypt(&keys)?.into();
-            cipher.decrypt_with_private_key(&der_priv_key)?
+            let priv_key = PrivateKey::from_der(&der_priv_key)?;
+            cipher.decrypt_with_private_key(&priv_key)?
         } else {
             cipher.decrypt(&keys)?
         };
@@ -1,32 +1,51 @@
 use super::apikey::ApiKey;
-use super::cipher::{Cipher, KeyDerivationFunction, PbkdfParameters};
+use super::cipher::{Cipher, EncType, KeyDerivationFunction, PbkdfParameters};
+use super::pinning;
+use super::send::{NewTextSend, SendItem};
 use super::server::ServerConfiguration;
 use anyhow::{bail, Error};
 use base64::prelude::*;
+use clap::ValueEnum;
 use reqwest;
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_repr::Deserialize_repr;
 use std::convert::TryInto;
+use std::fmt;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::{collections::HashMap, convert::TryFrom};
+use zeroize::Zeroizing;
 
 const APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
-#[allow(clippy::enum_variant_names)]
 enum DeviceType {
+    WindowsDesktop = 6,
+    MacOsDesktop = 7,
+    LinuxDesktop = 8,
     WindowsCLI = 23,
     MacOsCLI = 24,
     LinuxCLI = 25,
 }
 
-const fn get_device_type() -> DeviceType {
-    if cfg!(windows) {
-        DeviceType::WindowsCLI
-    } else if cfg!(target_os = "macos") {
-        DeviceType::MacOsCLI
-    } else {
-        DeviceType::LinuxCLI
+/// The kind of client wden reports itself as when logging in
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum DeviceClass {
+    /// Command line client
+    #[default]
+    Cli,
+    /// Desktop app
+    Desktop,
+}
+
+const fn get_device_type(device_class: DeviceClass) -> DeviceType {
+    match device_class {
+        DeviceClass::Cli if cfg!(windows) => DeviceType::WindowsCLI,
+        DeviceClass::Cli if cfg!(target_os = "macos") => DeviceType::MacOsCLI,
+        DeviceClass::Cli => DeviceType::LinuxCLI,
+        DeviceClass::Desktop if cfg!(windows) => DeviceType::WindowsDesktop,
+        DeviceClass::Desktop if cfg!(target_os = "macos") => DeviceType::MacOsDesktop,
+        DeviceClass::Desktop => DeviceType::LinuxDesktop,
     }
 }
 
@@ -40,43 +59,206 @@ const fn get_device_name() -> &'static str {
     }
 }
 
+/// The server responded with 429 Too Many Requests
+#[derive(Debug, thiserror::Error)]
+#[error("The server is limiting the rate of requests (429 Too Many Requests).{}", retry_hint(.retry_after))]
+pub struct RateLimitedError {
+    /// From the Retry-After header
+    pub retry_after: Option<Duration>,
+}
+
+fn retry_hint(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(d) => format!(" Try again in {} seconds.", d.as_secs()),
+        None => " Try again later.".to_owned(),
+    }
+}
+
+/// Retry-After is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = jiff::fmt::rfc2822::parse(value).ok()?;
+    let secs = date.timestamp().as_second() - jiff::Timestamp::now().as_second();
+    Some(Duration::from_secs(secs.max(0).unsigned_abs()))
+}
+
+/// The API endpoints only return JSON (or nothing). An HTML page usually
+/// comes from a reverse proxy or a wrong server URL, and would otherwise
+/// fail later with a confusing deserialization error.
+fn check_not_html(res: &reqwest::Response) -> Result<(), Error> {
+    let is_html = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .is_some_and(|ct| ct.to_ascii_lowercase().starts_with("text/html"));
+    if is_html {
+        bail!(
+            "The server returned an HTML page instead of JSON (status {}) for {}. \
+             Check the server URL, and the configuration of any reverse proxy in front of the server.",
+            res.status().as_u16(),
+            res.url().path()
+        );
+    }
+    Ok(())
+}
+
+/// Responses slower than this are reported to the slow response callback
+pub const SLOW_RESPONSE_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// The endpoints whose response times are measured
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Endpoint {
+    Prelogin,
+    Token,
+    Sync,
+    AttachmentUpload,
+}
+
+impl Endpoint {
+    /// Likely reasons for a slow response from the endpoint
+    pub fn slow_reason(&self) -> &'static str {
+        match self {
+            Endpoint::Sync => "server slow or vault very large",
+            Endpoint::AttachmentUpload => "server slow or file very large",
+            Endpoint::Prelogin | Endpoint::Token => "server slow",
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Endpoint::Prelogin => "prelogin",
+            Endpoint::Token => "token request",
+            Endpoint::Sync => "sync",
+            Endpoint::AttachmentUpload => "attachment upload",
+        })
+    }
+}
+
+type SlowResponseCallback = Arc<dyn Fn(Endpoint, Duration) + Send + Sync>;
+
+/// The last refresh done with a refresh token: the used refresh token
+/// and the refreshed token
+type LastRefresh = Option<(Zeroizing<String>, TokenResponseSuccess)>;
+
 pub struct ApiClient {
     http_client: reqwest::Client,
     api_base_url: Url,
     identity_base_url: Url,
     device_identifier: String,
-    access_token: Option<String>,
+    device_class: DeviceClass,
+    access_token: Option<Zeroizing<String>>,
+    pinned_spki_hashes: Vec<String>,
+    on_slow_response: Option<SlowResponseCallback>,
+    /// Held while refreshing the token, so that concurrent refreshes
+    /// with the same refresh token result in one token request
+    refresh_lock: tokio::sync::Mutex<LastRefresh>,
 }
 
 impl ApiClient {
     pub fn new(
         server_config: &ServerConfiguration,
         device_identifier: impl Into<String>,
+        device_class: DeviceClass,
         accept_invalid_certs: bool,
-    ) -> Self {
-        let http_client = reqwest::Client::builder()
+        pinned_spki_hashes: &[String],
+    ) -> Result<Self, Error> {
+        let mut builder = reqwest::Client::builder()
             .user_agent(APP_USER_AGENT)
-            .danger_accept_invalid_certs(accept_invalid_certs)
-            .build()
-            .unwrap();
-        ApiClient {
+            .danger_accept_invalid_certs(accept_invalid_certs);
+        if !pinned_spki_hashes.is_empty() {
+            // The server key is checked during the TLS handshake, so nothing
+            // is sent to a server with a key that is not pinned. Plain HTTP
+            // (e.g. after a redirect) is refused.
+            let tls = pinning::pinning_tls_config(pinned_spki_hashes, accept_invalid_certs)?;
+            builder = builder.use_preconfigured_tls(tls).https_only(true);
+        }
+        let http_client = builder.build()?;
+        Ok(ApiClient {
             http_client,
             api_base_url: server_config.api_base_url(),
             identity_base_url: server_config.identity_base_url(),
             device_identifier: device_identifier.into(),
+            device_class,
             access_token: None,
-        }
+            pinned_spki_hashes: pinned_spki_hashes.to_vec(),
+            on_slow_response: None,
+            refresh_lock: tokio::sync::Mutex::new(None),
+        })
     }
 
     pub fn with_token(
         server_config: &ServerConfiguration,
         device_identifier: impl Into<String>,
+        device_class: DeviceClass,
         token: &str,
         accept_invalid_certs: bool,
+        pinned_spki_hashes: &[String],
+    ) -> Result<Self, Error> {
+        let mut c = Self::new(
+            server_config,
+            device_identifier,
+            device_class,
+            accept_invalid_certs,
+            pinned_spki_hashes,
+        )?;
+        c.access_token = Some(Zeroizing::new(token.to_string()));
+        Ok(c)
+    }
+
+    /// Sets a callback that is called when an endpoint takes longer
+    /// than [`SLOW_RESPONSE_THRESHOLD`] to respond
+    pub fn on_slow_response(
+        mut self,
+        callback: impl Fn(Endpoint, Duration) + Send + Sync + 'static,
     ) -> Self {
-        let mut c = Self::new(server_config, device_identifier, accept_invalid_certs);
-        c.access_token = Some(token.to_string());
-        c
+        self.on_slow_response = Some(Arc::new(callback));
+        self
+    }
+
+    /// Logs the time taken by a request that was started at `start`,
+    /// and reports it if it was slow
+    fn record_timing(&self, endpoint: Endpoint, start: Instant) {
+        let elapsed = start.elapsed();
+        log::debug!("{endpoint} took {} ms", elapsed.as_millis());
+        if elapsed >= SLOW_RESPONSE_THRESHOLD {
+            log::warn!(
+                "Slow response: {endpoint} took {:.1}s",
+                elapsed.as_secs_f32()
+            );
+            if let Some(callback) = &self.on_slow_response {
+                callback(endpoint, elapsed);
+            }
+        }
+    }
+
+    /// Sends the request, and fails if the server returned an HTML page.
+    /// The server key is checked against the pinned keys (if any) by the
+    /// TLS configuration of the client.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, Error> {
+        let request = request.build()?;
+        if request.url().scheme() != "https" {
+            // Fails if there are pins
+            pinning::verify_pinned_cert(
+                None,
+                &request.url().origin().ascii_serialization(),
+                &self.pinned_spki_hashes,
+            )?;
+        }
+        let res = self.http_client.execute(request).await?;
+        check_not_html(&res)?;
+        if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = res
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after);
+            return Err(RateLimitedError { retry_after }.into());
+        }
+        Ok(res)
     }
 
     pub async fn prelogin(&self, user_email: &str) -> Result<PbkdfParameters, Error> {
@@ -85,15 +267,14 @@ impl ApiClient {
 
         let url = self.identity_base_url.join("accounts/prelogin")?;
 
+        let start = Instant::now();
         let res = self
-            .http_client
-            .post(url)
-            .json(&body)
-            .send()
+            .send(self.http_client.post(url).json(&body))
             .await?
             .error_for_status()?;
 
         let res: PreloginResponse = res.json().await?;
+        self.record_timing(Endpoint::Prelogin, start);
         Ok(res.into())
     }
 
@@ -103,18 +284,20 @@ impl ApiClient {
     /// * `username`: User's username. Most often this is the user email.
     /// * `password`: User's master password hash. Not the actual password.
     /// * `two_factor`: Optional tuple describing the second factor type, the second factor token and
-    ///                 whether to token should be "remembered" by the server or not. None if two-factor
-    ///                 is not used.
+    ///   whether to token should be "remembered" by the server or not. None if two-factor
+    ///   is not used.
     /// * `captcha_token`: Token for skipping the captcha check. Either the user's private api key or a captcha
-    ///                    bypass token sent by the server.
+    ///   bypass token sent by the server.
+    /// * `new_device_otp`: The code emailed by the server when it requires new device verification.
     pub async fn get_token(
         &self,
         username: &str,
         password: &str,
         two_factor: Option<(TwoFactorProviderType, &str, bool)>,
         captcha_token: Option<&str>,
+        new_device_otp: Option<&str>,
     ) -> Result<TokenResponse, Error> {
-        let device_type = (get_device_type() as i8).to_string();
+        let device_type = (get_device_type(self.device_class) as i8).to_string();
         let mut body = HashMap::new();
         body.insert("grant_type", "password");
         body.insert("username", username);
@@ -141,21 +324,28 @@ impl ApiClient {
             body.insert("captchaResponse", ct);
         }
 
+        if let Some(otp) = new_device_otp {
+            body.insert("newDeviceOtp", otp);
+        }
+
         let url = self.identity_base_url.join("connect/token")?;
 
+        let start = Instant::now();
         let res = self
-            .http_client
-            .post(url)
-            .form(&body)
-            // As of October 2021, Bitwarden (prod) wants the email as base64-encoded in a header
-            // for some security reason
-            .header("auth-email", BASE64_URL_SAFE.encode(username))
-            .header("device-type", &device_type)
-            // As of May 2024, Bitwarden wants these Bitwarden-Client- headers as well
-            .header("Bitwarden-Client-Name", "wden")
-            .header("Bitwarden-Client-Version", env!("CARGO_PKG_VERSION"))
-            .send()
+            .send(
+                self.http_client
+                    .post(url)
+                    .form(&body)
+                    // As of October 2021, Bitwarden (prod) wants the email as base64-encoded in a header
+                    // for some security reason
+                    .header("auth-email", BASE64_URL_SAFE.encode(username))
+                    .header("device-type", &device_type)
+                    // As of May 2024, Bitwarden wants these Bitwarden-Client- headers as well
+                    .header("Bitwarden-Client-Name", "wden")
+                    .header("Bitwarden-Client-Version", env!("CARGO_PKG_VERSION")),
+            )
             .await?;
+        self.record_timing(Endpoint::Token, start);
 
         if res.status() == 400 {
             log::info!("{:?}", &res);
@@ -183,6 +373,8 @@ impl ApiClient {
                 return Ok(TokenResponse::TwoFactorRequired(providers, captcha_bypass));
             } else if body.contains_key("HCaptcha_SiteKey") {
                 return Ok(TokenResponse::CaptchaRequired);
+            } else if let Some(verification) = Verification::from_error(&body) {
+                return Ok(TokenResponse::VerificationRequired(verification));
             } else {
                 // The error models often include the error message,
                 // so try to get and show it.
@@ -212,7 +404,7 @@ impl ApiClient {
         &self,
         api_key: &ApiKey,
     ) -> Result<TokenResponseSuccess, Error> {
-        let device_type = (get_device_type() as i8).to_string();
+        let device_type = (get_device_type(self.device_class) as i8).to_string();
         let mut body = HashMap::new();
         body.insert("grant_type", "client_credentials");
         body.insert("username", &api_key.email);
@@ -225,19 +417,22 @@ impl ApiClient {
 
         let url = self.identity_base_url.join("connect/token")?;
 
+        let start = Instant::now();
         let res = self
-            .http_client
-            .post(url)
-            .form(&body)
-            // As of October 2021, Bitwarden (prod) wants the email as base64-encoded in a header
-            // for some security reason
-            .header("auth-email", BASE64_URL_SAFE.encode(&api_key.email))
-            .header("device-type", &device_type)
-            // As of May 2024, Bitwarden wants these Bitwarden-Client- headers as well
-            .header("Bitwarden-Client-Name", "wden")
-            .header("Bitwarden-Client-Version", env!("CARGO_PKG_VERSION"))
-            .send()
+            .send(
+                self.http_client
+                    .post(url)
+                    .form(&body)
+                    // As of October 2021, Bitwarden (prod) wants the email as base64-encoded in a header
+                    // for some security reason
+                    .header("auth-email", BASE64_URL_SAFE.encode(&api_key.email))
+                    .header("device-type", &device_type)
+                    // As of May 2024, Bitwarden wants these Bitwarden-Client- headers as well
+                    .header("Bitwarden-Client-Name", "wden")
+                    .header("Bitwarden-Client-Version", env!("CARGO_PKG_VERSION")),
+            )
             .await?;
+        self.record_timing(Endpoint::Token, start);
 
         if res.status() == 400 {
             log::info!("{:?}", &res);
@@ -265,20 +460,30 @@ impl ApiClient {
         Ok(res)
     }
 
+    /// Refreshes the token with the API key, or with the refresh token.
+    /// The server rotates the refresh token, so concurrent refreshes with
+    /// the same refresh token wait for the first one and get its result.
     pub async fn refresh_token(
         &self,
         token: &TokenResponseSuccess,
         api_key: Option<&ApiKey>,
     ) -> Result<TokenResponse, Error> {
+        let mut last_refresh = self.refresh_lock.lock().await;
         if let Some(ak) = api_key {
             let res = self.get_token_with_api_key(ak).await?;
             return Ok(TokenResponse::Success(Box::new(res)));
         }
 
+        if let (Some((used, refreshed)), Some(rt)) = (&*last_refresh, &token.refresh_token) {
+            if used == rt {
+                return Ok(TokenResponse::Success(Box::new(refreshed.clone())));
+            }
+        }
+
         let mut body = HashMap::new();
         if let Some(rt) = token.refresh_token.as_ref() {
             body.insert("grant_type", "refresh_token");
-            body.insert("refresh_token", rt);
+            body.insert("refresh_token", rt.as_str());
             body.insert("client_id", "cli");
         } else {
             bail!("Refresh token or api key not present while trying to refresh");
@@ -286,7 +491,9 @@ impl ApiClient {
 
         let url = self.identity_base_url.join("connect/token")?;
 
-        let res = self.http_client.post(url).form(&body).send().await?;
+        let start = Instant::now();
+        let res = self.send(self.http_client.post(url).form(&body)).await?;
+        self.record_timing(Endpoint::Token, start);
 
         let refresh_res = res
             .error_for_status()?
@@ -301,32 +508,564 @@ impl ApiClient {
         res.token_timestamp = refresh_res.token_timestamp;
         res.expires_in = refresh_res.expires_in;
 
+        if let Some(rt) = &token.refresh_token {
+            *last_refresh = Some((rt.clone(), res.clone()));
+        }
         Ok(TokenResponse::Success(Box::new(res)))
     }
 
     pub async fn sync(&self) -> Result<SyncResponse, Error> {
+        let json = self.sync_json().await?;
+        Ok(SyncResponse::from_json(&json)?)
+    }
+
+    /// Returns the sync response as unparsed JSON, e.g. for caching it
+    pub async fn sync_json(&self) -> Result<serde_json::Value, Error> {
         assert!(self.access_token.is_some());
         let mut url = self.api_base_url.join("sync")?;
         url.set_query(Some("excludeDomains=true"));
+        let start = Instant::now();
+        let res = self
+            .send(
+                self.http_client
+                    .get(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap()),
+            )
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.record_timing(Endpoint::Sync, start);
+
+        Ok(res)
+    }
+
+    /// Creates a new personal secure note. Returns the id of the created item.
+    pub async fn create_secure_note(&self, name: &Cipher, notes: &Cipher) -> Result<String, Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join("ciphers")?;
+        let body = secure_note_request_body(name, notes, None);
+
+        let res = self
+            .send(
+                self.http_client
+                    .post(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap())
+                    .json(&body),
+            )
+            .await?
+            .error_for_status()?
+            .json::<CreatedItemResponse>()
+            .await?;
+
+        Ok(res.id)
+    }
+
+    /// Creates a new item. Organization items are created in the given
+    /// collections. Returns the id of the created item.
+    pub async fn create_cipher(
+        &self,
+        item: &NewCipher,
+        organization: Option<(&str, &[String])>,
+    ) -> Result<String, Error> {
+        assert!(self.access_token.is_some());
+        let body = new_cipher_request_body(item, organization.map(|(org_id, _)| org_id));
+        let (url, body) = match organization {
+            None => (self.api_base_url.join("ciphers")?, body),
+            Some((_, collection_ids)) => (
+                self.api_base_url.join("ciphers/create")?,
+                serde_json::json!({ "cipher": body, "collectionIds": collection_ids }),
+            ),
+        };
+
+        let res = self
+            .send(
+                self.http_client
+                    .post(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap())
+                    .json(&body),
+            )
+            .await?
+            .error_for_status()?
+            .json::<CreatedItemResponse>()
+            .await?;
+
+        Ok(res.id)
+    }
+
+    /// Moves an item to the trash. Items in the trash can be restored
+    /// (or deleted permanently) in the other Bitwarden clients.
+    pub async fn soft_delete_cipher(&self, id: &str) -> Result<(), Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join(&format!("ciphers/{id}/delete"))?;
+
+        self.send(
+            self.http_client
+                .put(url)
+                .bearer_auth(self.access_token.as_deref().unwrap()),
+        )
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Restores an item from the trash
+    pub async fn restore_cipher(&self, id: &str) -> Result<(), Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join(&format!("ciphers/{id}/restore"))?;
+
+        self.send(
+            self.http_client
+                .put(url)
+                .bearer_auth(self.access_token.as_deref().unwrap()),
+        )
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Uploads an attachment to an item. The file name and the key must be
+    /// encrypted with the item keys, and the contents with the key.
+    /// Returns the id of the attachment.
+    pub async fn create_attachment(
+        &self,
+        cipher_id: &str,
+        file_name: &Cipher,
+        key: &Cipher,
+        contents: Vec<u8>,
+    ) -> Result<String, Error> {
+        assert!(self.access_token.is_some());
+        let access_token = self.access_token.as_deref().unwrap();
+        let url = self
+            .api_base_url
+            .join(&format!("ciphers/{cipher_id}/attachment/v2"))?;
+        let body = serde_json::json!({
+            "key": key.encode(),
+            "fileName": file_name.encode(),
+            "fileSize": contents.len(),
+            "adminRequest": false,
+        });
+
+        let start = Instant::now();
+        let res: AttachmentUploadResponse = self
+            .send(
+                self.http_client
+                    .post(url)
+                    .bearer_auth(access_token)
+                    .json(&body),
+            )
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let upload_res = match res.file_upload_type {
+            FILE_UPLOAD_TYPE_DIRECT => {
+                let url = self.api_base_url.join(&format!(
+                    "ciphers/{cipher_id}/attachment/{}",
+                    res.attachment_id
+                ))?;
+                let part = reqwest::multipart::Part::bytes(contents).file_name(file_name.encode());
+                let form = reqwest::multipart::Form::new().part("data", part);
+                self.send(
+                    self.http_client
+                        .post(url)
+                        .bearer_auth(access_token)
+                        .multipart(form),
+                )
+                .await
+                .and_then(|r| Ok(r.error_for_status()?))
+            }
+            FILE_UPLOAD_TYPE_AZURE => {
+                // The url is a signed blob storage url. The pinned keys
+                // apply to it too, so the key of the storage has to be
+                // pinned for the upload to succeed.
+                self.send(
+                    self.http_client
+                        .put(&res.url)
+                        .header("x-ms-version", "2020-04-08")
+                        .header("x-ms-blob-type", "BlockBlob")
+                        .body(contents),
+                )
+                .await
+                .and_then(|r| Ok(r.error_for_status()?))
+            }
+            t => Err(anyhow::anyhow!("Unsupported attachment upload type {t}")),
+        };
+
+        if let Err(e) = upload_res {
+            // Don't leave an attachment without contents in the item
+            let url = self.api_base_url.join(&format!(
+                "ciphers/{cipher_id}/attachment/{}",
+                res.attachment_id
+            ))?;
+            let delete_res = self
+                .send(self.http_client.delete(url).bearer_auth(access_token))
+                .await
+                .and_then(|r| Ok(r.error_for_status()?));
+            if let Err(de) = delete_res {
+                log::warn!("Removing the failed attachment failed: {de}");
+            }
+            return Err(e.context("Uploading the attachment failed"));
+        }
+        self.record_timing(Endpoint::AttachmentUpload, start);
+
+        Ok(res.attachment_id)
+    }
+
+    /// Updates an existing personal secure note.
+    ///
+    /// The server refuses the update if the item has been changed after
+    /// `last_known_revision_date`.
+    pub async fn update_secure_note(
+        &self,
+        id: &str,
+        name: &Cipher,
+        notes: &Cipher,
+        last_known_revision_date: Option<&str>,
+    ) -> Result<(), Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join(&format!("ciphers/{id}"))?;
+        let body = secure_note_request_body(name, notes, last_known_revision_date);
+
         let res = self
-            .http_client
-            .get(url)
-            .bearer_auth(self.access_token.as_ref().unwrap())
-            .send()
+            .send(
+                self.http_client
+                    .put(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap())
+                    .json(&body),
+            )
+            .await?;
+
+        if res.status() == reqwest::StatusCode::BAD_REQUEST {
+            let text = res.text().await.unwrap_or_default();
+            bail!("Updating item failed: {text}");
+        }
+        res.error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Creates a new collection in an organization. The name must be
+    /// encrypted with the organization key. Returns the id of the created
+    /// collection.
+    pub async fn create_collection(
+        &self,
+        organization_id: &str,
+        name: &Cipher,
+    ) -> Result<String, Error> {
+        assert!(self.access_token.is_some());
+        let url = self
+            .api_base_url
+            .join(&format!("organizations/{organization_id}/collections"))?;
+        let body = serde_json::json!({
+            "name": name,
+            "externalId": null,
+            "groups": [],
+            "users": [],
+        });
+
+        let res = self
+            .send(
+                self.http_client
+                    .post(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap())
+                    .json(&body),
+            )
             .await?
             .error_for_status()?
-            .json::<SyncResponseInternal>()
+            .json::<CreatedItemResponse>()
+            .await?;
+
+        Ok(res.id)
+    }
+
+    /// Members of the organization who have accepted their invitation
+    /// and are waiting to be confirmed by an admin
+    pub async fn get_accepted_org_users(
+        &self,
+        organization_id: &str,
+    ) -> Result<Vec<OrganizationUser>, Error> {
+        assert!(self.access_token.is_some());
+        let url = self
+            .api_base_url
+            .join(&format!("organizations/{organization_id}/users"))?;
+
+        let res = self
+            .send(
+                self.http_client
+                    .get(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap()),
+            )
+            .await?
+            .error_for_status()?
+            .json::<ListResponse<OrganizationUser>>()
+            .await?;
+
+        Ok(res
+            .data
+            .into_iter()
+            .filter(|u| u.status == ORG_USER_STATUS_ACCEPTED)
+            .collect())
+    }
+
+    /// The DER-encoded RSA public key of a user
+    pub async fn get_user_public_key(&self, user_id: &str) -> Result<Vec<u8>, Error> {
+        assert!(self.access_token.is_some());
+        let url = self
+            .api_base_url
+            .join(&format!("users/{user_id}/public-key"))?;
+
+        let res = self
+            .send(
+                self.http_client
+                    .get(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap()),
+            )
             .await?
-            .into();
+            .error_for_status()?
+            .json::<PublicKeyResponse>()
+            .await?;
+
+        Ok(BASE64_STANDARD.decode(res.public_key)?)
+    }
+
+    /// Confirms an accepted member. The key is the organization key,
+    /// encrypted with the public key of the member.
+    pub async fn confirm_org_user(
+        &self,
+        organization_id: &str,
+        org_user_id: &str,
+        key: &Cipher,
+    ) -> Result<(), Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join(&format!(
+            "organizations/{organization_id}/users/{org_user_id}/confirm"
+        ))?;
+        let body = serde_json::json!({ "key": key });
+
+        self.send(
+            self.http_client
+                .post(url)
+                .bearer_auth(self.access_token.as_deref().unwrap())
+                .json(&body),
+        )
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn list_sends(&self) -> Result<Vec<SendItem>, Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join("sends")?;
+
+        let res = self
+            .send(
+                self.http_client
+                    .get(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap()),
+            )
+            .await?
+            .error_for_status()?
+            .json::<ListResponse<SendItem>>()
+            .await?;
+
+        Ok(res.data)
+    }
+
+    pub async fn create_send(&self, new_send: &NewTextSend) -> Result<SendItem, Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join("sends")?;
+
+        let res = self
+            .send(
+                self.http_client
+                    .post(url)
+                    .bearer_auth(self.access_token.as_deref().unwrap())
+                    .json(&new_send.request_body()),
+            )
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
 
         Ok(res)
     }
+
+    pub async fn delete_send(&self, id: &str) -> Result<(), Error> {
+        assert!(self.access_token.is_some());
+        let url = self.api_base_url.join(&format!("sends/{id}"))?;
+
+        self.send(
+            self.http_client
+                .delete(url)
+                .bearer_auth(self.access_token.as_deref().unwrap()),
+        )
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+fn secure_note_request_body(
+    name: &Cipher,
+    notes: &Cipher,
+    last_known_revision_date: Option<&str>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "type": 2,
+        "name": name,
+        "notes": notes,
+        "secureNote": { "type": 0 },
+        "favorite": false,
+        "reprompt": 0,
+        "organizationId": null,
+        "folderId": null,
+        "lastKnownRevisionDate": last_known_revision_date,
+    })
+}
+
+/// Encrypted fields of a new item. Empty fields are `None`.
+#[derive(Serialize, Deserialize)]
+pub struct NewCipher {
+    pub name: Cipher,
+    pub notes: Option<Cipher>,
+    pub data: NewCipherData,
+    #[serde(default)]
+    pub fields: Vec<NewCustomField>,
+}
+
+/// Text or hidden custom field of a new item
+#[derive(Serialize, Deserialize)]
+pub struct NewCustomField {
+    pub name: Cipher,
+    pub value: Option<Cipher>,
+    pub hidden: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum NewCipherData {
+    Login {
+        username: Option<Cipher>,
+        password: Option<Cipher>,
+        uri: Option<Cipher>,
+    },
+    SecureNote,
+    Card {
+        cardholder_name: Option<Cipher>,
+        number: Option<Cipher>,
+        exp_month: Option<Cipher>,
+        exp_year: Option<Cipher>,
+        code: Option<Cipher>,
+    },
+}
+
+impl NewCipherData {
+    /// Whether an existing item is of the same type as this new item
+    pub fn same_type_as(&self, data: &CipherData) -> bool {
+        matches!(
+            (self, data),
+            (NewCipherData::Login { .. }, CipherData::Login(_))
+                | (NewCipherData::SecureNote, CipherData::SecureNote)
+                | (NewCipherData::Card { .. }, CipherData::Card(_))
+        )
+    }
+}
+
+fn new_cipher_request_body(item: &NewCipher, organization_id: Option<&str>) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "name": item.name,
+        "notes": item.notes,
+        "favorite": false,
+        "reprompt": 0,
+        "organizationId": organization_id,
+        "folderId": null,
+        "fields": item.fields.iter().map(|f| serde_json::json!({
+            "type": if f.hidden { 1 } else { 0 },
+            "name": f.name,
+            "value": f.value,
+            "linkedId": null,
+        })).collect::<Vec<_>>(),
+    });
+    let (item_type, key, data) = match &item.data {
+        NewCipherData::Login {
+            username,
+            password,
+            uri,
+        } => (
+            1,
+            "login",
+            serde_json::json!({
+                "username": username,
+                "password": password,
+                "uris": uri.as_ref().map(|u| vec![serde_json::json!({ "uri": u, "match": null })]),
+            }),
+        ),
+        NewCipherData::SecureNote => (2, "secureNote", serde_json::json!({ "type": 0 })),
+        NewCipherData::Card {
+            cardholder_name,
+            number,
+            exp_month,
+            exp_year,
+            code,
+        } => (
+            3,
+            "card",
+            serde_json::json!({
+                "cardholderName": cardholder_name,
+                "number": number,
+                "expMonth": exp_month,
+                "expYear": exp_year,
+                "code": code,
+            }),
+        ),
+    };
+    body["type"] = item_type.into();
+    body[key] = data;
+    body
 }
 
 pub enum TokenResponse {
     Success(Box<TokenResponseSuccess>),
     TwoFactorRequired(Vec<TwoFactorProviderType>, Option<String>),
     CaptchaRequired,
+    VerificationRequired(Verification),
+}
+
+/// Verifications the server requires by email before allowing the login
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verification {
+    /// A code is emailed for logging in on a new device
+    NewDevice,
+    /// The email address of the account must be verified first
+    Email,
+}
+
+impl Verification {
+    fn from_error(body: &HashMap<String, serde_json::Value>) -> Option<Self> {
+        let messages = [
+            body.get("error_description").and_then(|d| d.as_str()),
+            body.get("ErrorModel")
+                .and_then(|em| em.get("Message"))
+                .and_then(|m| m.as_str()),
+        ];
+        messages.into_iter().flatten().find_map(|msg| {
+            let msg = msg.to_lowercase();
+            if msg.contains("new device verification required") {
+                Some(Verification::NewDevice)
+            } else if msg.contains("email verification required")
+                || msg.contains("verify your email")
+            {
+                Some(Verification::Email)
+            } else {
+                None
+            }
+        })
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -336,9 +1075,9 @@ pub struct TokenResponseSuccess {
     #[serde(alias = "PrivateKey")]
     #[serde(alias = "privateKey")]
     pub private_key: Cipher,
-    pub access_token: String,
+    pub access_token: Zeroizing<String>,
     expires_in: u32,
-    pub refresh_token: Option<String>,
+    pub refresh_token: Option<Zeroizing<String>>,
     #[serde(alias = "TwoFactorToken")]
     #[serde(alias = "twoFactorToken")]
     pub two_factor_token: Option<String>,
@@ -367,6 +1106,30 @@ impl TokenResponseSuccess {
     pub fn pbkdf_parameters(&self) -> Option<PbkdfParameters> {
         self.kdf_parameters.as_ref().map(|x| x.clone().into())
     }
+
+    /// A token for the offline mode, holding only the encrypted user keys.
+    /// It cannot be used for API calls.
+    pub fn offline(key: Cipher, private_key: Cipher) -> Self {
+        TokenResponseSuccess {
+            key,
+            private_key,
+            access_token: Zeroizing::default(),
+            expires_in: 0,
+            refresh_token: None,
+            two_factor_token: None,
+            token_timestamp: token_response_timestamp(),
+            kdf_parameters: None,
+        }
+    }
+
+    /// A token restored from a remembered session. It has already expired,
+    /// so it gets refreshed with the refresh token before the first API call.
+    pub fn remembered(key: Cipher, private_key: Cipher, refresh_token: Zeroizing<String>) -> Self {
+        TokenResponseSuccess {
+            refresh_token: Some(refresh_token),
+            ..Self::offline(key, private_key)
+        }
+    }
 }
 
 fn token_response_timestamp() -> Instant {
@@ -468,6 +1231,60 @@ struct SyncResponseInternal {
     profile: Profile,
     #[serde(alias = "Collections")]
     collections: Vec<Collection>,
+    #[serde(default)]
+    #[serde(alias = "Policies")]
+    policies: Vec<Policy>,
+    #[serde(default)]
+    #[serde(alias = "Folders")]
+    folders: Vec<Folder>,
+}
+
+const POLICY_TYPE_MAXIMUM_VAULT_TIMEOUT: u8 = 9;
+
+/// Organization policy from the sync response
+#[derive(Deserialize, Debug, Clone)]
+pub struct Policy {
+    #[serde(alias = "organizationId")]
+    #[serde(alias = "OrganizationId")]
+    pub organization_id: String,
+    #[serde(alias = "Type")]
+    #[serde(alias = "type")]
+    pub policy_type: u8,
+    #[serde(default)]
+    #[serde(alias = "Enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    #[serde(alias = "Data")]
+    data: Option<serde_json::Value>,
+}
+
+impl Policy {
+    pub fn name(&self) -> &'static str {
+        match self.policy_type {
+            0 => "Require two-step login",
+            1 => "Master password requirements",
+            2 => "Password generator",
+            3 => "Single organization",
+            4 => "Require single sign-on",
+            5 => "Remove individual vault",
+            6 => "Remove Send",
+            7 => "Send options",
+            8 => "Account recovery administration",
+            9 => "Vault timeout",
+            10 => "Remove individual vault export",
+            11 => "Activate autofill",
+            _ => "Unknown policy",
+        }
+    }
+
+    /// The maximum vault timeout, if this is an enabled vault timeout policy
+    pub fn maximum_vault_timeout(&self) -> Option<Duration> {
+        if !self.enabled || self.policy_type != POLICY_TYPE_MAXIMUM_VAULT_TIMEOUT {
+            return None;
+        }
+        let minutes = self.data.as_ref()?.get("minutes")?.as_u64()?;
+        Some(Duration::from_secs(minutes * 60))
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -481,6 +1298,15 @@ pub struct Collection {
     pub name: Cipher,
 }
 
+/// Personal folder, the name is encrypted with the user key
+#[derive(Deserialize, Debug)]
+pub struct Folder {
+    #[serde(alias = "Id")]
+    pub id: String,
+    #[serde(alias = "Name")]
+    pub name: Cipher,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Profile {
     #[serde(alias = "Organizations")]
@@ -498,12 +1324,54 @@ pub struct Organization {
     pub key: Cipher,
     #[serde(alias = "Name")]
     pub name: String,
+    /// The user's role: 0 = owner, 1 = admin, 2 = user, 3 = manager,
+    /// 4 = custom
+    #[serde(default, rename = "type", alias = "Type")]
+    pub user_type: Option<u8>,
+    /// Membership status: 0 = invited, 1 = accepted, 2 = confirmed
+    #[serde(default, alias = "Status")]
+    pub status: Option<u8>,
+}
+
+impl Organization {
+    /// The organization key is only shared with the user once an admin
+    /// has confirmed the membership. Servers that don't send the status
+    /// only list confirmed organizations.
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self.status, None | Some(2))
+    }
+
+    pub fn status_description(&self) -> &'static str {
+        match self.status {
+            Some(0) => "Invited",
+            Some(1) => "Waiting for confirmation by an admin",
+            _ => "Confirmed",
+        }
+    }
+
+    /// Owners and admins can create collections
+    pub fn can_manage_collections(&self) -> bool {
+        self.enabled && matches!(self.user_type, Some(0 | 1))
+    }
+
+    /// Owners and admins can confirm new members
+    pub fn can_manage_users(&self) -> bool {
+        self.enabled && self.is_confirmed() && matches!(self.user_type, Some(0 | 1))
+    }
 }
 
 pub struct SyncResponse {
     pub ciphers: Vec<CipherItem>,
     pub profile: Profile,
     pub collections: Vec<Collection>,
+    pub policies: Vec<Policy>,
+    pub folders: Vec<Folder>,
+}
+
+impl SyncResponse {
+    pub fn from_json(json: &serde_json::Value) -> Result<Self, serde_json::Error> {
+        SyncResponseInternal::deserialize(json).map(Into::into)
+    }
 }
 
 impl From<SyncResponseInternal> for SyncResponse {
@@ -512,6 +1380,8 @@ impl From<SyncResponseInternal> for SyncResponse {
             ciphers: sri.ciphers.into_iter().map(|cii| cii.into()).collect(),
             profile: sri.profile,
             collections: sri.collections,
+            policies: sri.policies,
+            folders: sri.folders,
         }
     }
 }
@@ -535,6 +1405,9 @@ struct CipherItemInternal {
     card: Option<CardItem>,
     #[serde(alias = "Identity")]
     identity: Option<IdentityItem>,
+    #[serde(alias = "sshKey")]
+    #[serde(alias = "SshKey")]
+    ssh_key: Option<SshKeyItem>,
     #[serde(alias = "Favorite")]
     favorite: bool,
     #[serde(alias = "CollectionIds")]
@@ -543,17 +1416,93 @@ struct CipherItemInternal {
     #[serde(alias = "organizationId")]
     #[serde(alias = "OrganizationId")]
     organization_id: Option<String>,
+    #[serde(default)]
+    #[serde(alias = "folderId")]
+    #[serde(alias = "FolderId")]
+    folder_id: Option<String>,
     #[serde(alias = "Key")]
     key: Option<Cipher>,
+    #[serde(default)]
+    #[serde(alias = "RevisionDate")]
+    #[serde(alias = "revisionDate")]
+    revision_date: Option<String>,
+    #[serde(default)]
+    #[serde(alias = "Fields")]
+    fields: Option<Vec<CustomField>>,
+    #[serde(default)]
+    #[serde(alias = "Attachments")]
+    attachments: Option<Vec<Attachment>>,
+    #[serde(default)]
+    #[serde(alias = "Reprompt")]
+    reprompt: Option<u8>,
+    #[serde(default)]
+    #[serde(alias = "passwordHistory")]
+    #[serde(alias = "PasswordHistory")]
+    password_history: Option<Vec<PasswordHistoryEntry>>,
 }
 
-#[derive(Debug)]
+#[derive(Deserialize, Debug)]
+struct ListResponse<T> {
+    #[serde(alias = "Data")]
+    data: Vec<T>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PublicKeyResponse {
+    #[serde(alias = "publicKey")]
+    #[serde(alias = "PublicKey")]
+    public_key: String,
+}
+
+const ORG_USER_STATUS_ACCEPTED: u8 = 1;
+
+/// A member of an organization, as listed for the admins
+#[derive(Deserialize, Debug)]
+pub struct OrganizationUser {
+    /// Id of the membership, used when confirming
+    #[serde(alias = "Id")]
+    pub id: String,
+    #[serde(alias = "userId")]
+    #[serde(alias = "UserId")]
+    pub user_id: Option<String>,
+    #[serde(alias = "Email")]
+    pub email: String,
+    #[serde(default, alias = "Name")]
+    pub name: Option<String>,
+    #[serde(alias = "Status")]
+    status: u8,
+}
+
+const FILE_UPLOAD_TYPE_DIRECT: u8 = 0;
+const FILE_UPLOAD_TYPE_AZURE: u8 = 1;
+
+#[derive(Deserialize, Debug)]
+struct AttachmentUploadResponse {
+    #[serde(alias = "attachmentId")]
+    #[serde(alias = "AttachmentId")]
+    attachment_id: String,
+    #[serde(default)]
+    #[serde(alias = "Url")]
+    url: String,
+    #[serde(alias = "fileUploadType")]
+    #[serde(alias = "FileUploadType")]
+    file_upload_type: u8,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedItemResponse {
+    #[serde(alias = "Id")]
+    id: String,
+}
+
+#[derive(Debug, Clone)]
 pub enum CipherData {
     None,
     Login(Box<LoginItem>),
     Card(Box<CardItem>),
     Identity(Box<IdentityItem>),
     SecureNote,
+    SshKey(Box<SshKeyItem>),
 }
 
 impl From<CipherItemInternal> for CipherItem {
@@ -566,18 +1515,28 @@ impl From<CipherItemInternal> for CipherItem {
             favorite: cii.favorite,
             collection_ids: cii.collection_ids,
             organization_id: cii.organization_id,
+            folder_id: cii.folder_id,
+            revision_date: cii.revision_date,
+            fields: cii.fields.unwrap_or_default(),
+            attachments: cii.attachments.unwrap_or_default(),
+            reprompt: cii.reprompt == Some(1),
+            password_history: cii.password_history.unwrap_or_default(),
+            // An item without the data of its type (e.g. from a server
+            // that doesn't support the type yet) is shown without it
             data: match cii.cipher_type {
-                1 => CipherData::Login(Box::new(cii.login.unwrap())),
-                2 => CipherData::SecureNote,
-                3 => CipherData::Card(Box::new(cii.card.unwrap())),
-                4 => CipherData::Identity(Box::new(cii.identity.unwrap())),
-                _ => CipherData::None,
-            },
+                1 => cii.login.map(|l| CipherData::Login(Box::new(l))),
+                2 => Some(CipherData::SecureNote),
+                3 => cii.card.map(|c| CipherData::Card(Box::new(c))),
+                4 => cii.identity.map(|i| CipherData::Identity(Box::new(i))),
+                5 => cii.ssh_key.map(|k| CipherData::SshKey(Box::new(k))),
+                _ => None,
+            }
+            .unwrap_or(CipherData::None),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CipherItem {
     pub id: String,
     pub name: Cipher,
@@ -587,9 +1546,170 @@ pub struct CipherItem {
     pub favorite: bool,
     pub collection_ids: Vec<String>,
     pub organization_id: Option<String>,
+    pub folder_id: Option<String>,
+    pub revision_date: Option<String>,
+    pub fields: Vec<CustomField>,
+    pub attachments: Vec<Attachment>,
+    /// The master password is asked again before showing the item
+    pub reprompt: bool,
+    pub password_history: Vec<PasswordHistoryEntry>,
 }
 
-#[derive(Deserialize, Debug)]
+impl CipherItem {
+    /// All encrypted values of the item
+    pub fn encrypted_values(&self) -> Vec<&Cipher> {
+        let mut values = vec![&self.name, &self.notes];
+        values.extend(&self.key);
+        for field in &self.fields {
+            values.extend([&field.name, &field.value]);
+        }
+        match &self.data {
+            CipherData::Login(l) => {
+                values.extend([&l.username, &l.password, &l.uri, &l.totp]);
+                values.extend(l.uris().into_iter().map(|(uri, _)| uri));
+            }
+            CipherData::Card(c) => values.extend([
+                &c.brand,
+                &c.cardholder_name,
+                &c.code,
+                &c.exp_month,
+                &c.exp_year,
+                &c.number,
+            ]),
+            CipherData::Identity(i) => values.extend([
+                &i.address_1,
+                &i.address_2,
+                &i.address_3,
+                &i.city,
+                &i.company,
+                &i.country,
+                &i.email,
+                &i.first_name,
+                &i.last_name,
+                &i.license_number,
+                &i.middle_name,
+                &i.passport_number,
+                &i.phone,
+                &i.postal_code,
+                &i.ssn,
+                &i.state,
+                &i.title,
+                &i.username,
+            ]),
+            CipherData::SshKey(k) => values.extend([&k.private_key, &k.public_key, &k.fingerprint]),
+            CipherData::SecureNote | CipherData::None => {}
+        }
+        values
+    }
+
+    /// The legacy encryption types used in the item, without duplicates
+    pub fn legacy_enc_types(&self) -> Vec<EncType> {
+        let mut types: Vec<_> = self
+            .encrypted_values()
+            .into_iter()
+            .filter_map(Cipher::enc_type)
+            .filter(EncType::is_legacy)
+            .collect();
+        types.sort_by_key(EncType::as_u8);
+        types.dedup();
+        types
+    }
+
+    /// When the password of a login was last changed. Old items may not
+    /// have the password revision date, but the password history tells
+    /// when the previous password was replaced.
+    pub fn password_changed_date(&self) -> Option<&str> {
+        let CipherData::Login(login) = &self.data else {
+            return None;
+        };
+        login.password_revision_date.as_deref().or_else(|| {
+            self.password_history
+                .iter()
+                .map(|h| h.last_used_date.as_str())
+                .max()
+        })
+    }
+}
+
+/// A previous password of a login. The old passwords themselves are not
+/// kept.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PasswordHistoryEntry {
+    /// When the password was replaced
+    #[serde(alias = "lastUsedDate")]
+    #[serde(alias = "LastUsedDate")]
+    pub last_used_date: String,
+}
+
+/// Attachment metadata. The attachment contents are not downloaded.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Attachment {
+    /// Size in bytes. Usually a string, but accept numbers too.
+    #[serde(default)]
+    #[serde(alias = "Size")]
+    size: Option<serde_json::Value>,
+}
+
+impl Attachment {
+    pub fn size(&self) -> u64 {
+        match &self.size {
+            Some(serde_json::Value::String(s)) => s.parse().unwrap_or_default(),
+            Some(serde_json::Value::Number(n)) => n.as_u64().unwrap_or_default(),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CustomField {
+    #[serde(default)]
+    #[serde(alias = "Name")]
+    pub name: Cipher,
+    #[serde(default)]
+    #[serde(alias = "Value")]
+    pub value: Cipher,
+    /// 0 = text, 1 = hidden, 2 = boolean, 3 = linked
+    #[serde(default)]
+    #[serde(rename = "type", alias = "Type")]
+    pub field_type: u8,
+    /// The item field that a linked field refers to
+    #[serde(default)]
+    #[serde(alias = "linkedId")]
+    #[serde(alias = "LinkedId")]
+    pub linked_id: Option<u32>,
+}
+
+impl CustomField {
+    pub fn is_hidden(&self) -> bool {
+        self.field_type == 1
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        self.field_type == 2
+    }
+
+    pub fn is_linked(&self) -> bool {
+        self.field_type == 3
+    }
+
+    /// Name of the login or card field that a linked field refers to
+    pub fn linked_field_name(&self) -> Option<&'static str> {
+        let name = match self.linked_id? {
+            100 => "Username",
+            101 => "Password",
+            300 => "Card holder",
+            301 => "Expiration month",
+            302 => "Expiration year",
+            303 => "Code",
+            304 => "Brand",
+            305 => "Number",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct LoginItem {
     #[serde(default)]
     #[serde(alias = "Username")]
@@ -600,9 +1720,91 @@ pub struct LoginItem {
     #[serde(default)]
     #[serde(alias = "Uri")]
     pub uri: Cipher,
+    #[serde(default)]
+    #[serde(alias = "Uris")]
+    uris: Option<Vec<LoginItemUri>>,
+    #[serde(default)]
+    #[serde(alias = "Totp")]
+    pub totp: Cipher,
+    #[serde(default)]
+    #[serde(alias = "PasswordRevisionDate")]
+    #[serde(alias = "passwordRevisionDate")]
+    pub password_revision_date: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+impl LoginItem {
+    /// All URIs of the login. Items created by old clients only have
+    /// the single `uri`.
+    pub fn uris(&self) -> Vec<(&Cipher, Option<UriMatchType>)> {
+        match &self.uris {
+            Some(uris) if !uris.is_empty() => uris
+                .iter()
+                .map(|u| (&u.uri, u.match_type.and_then(UriMatchType::from_repr)))
+                .collect(),
+            _ => vec![(&self.uri, None)],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct LoginItemUri {
+    #[serde(default)]
+    #[serde(alias = "Uri")]
+    uri: Cipher,
+    #[serde(default)]
+    #[serde(rename = "match", alias = "Match")]
+    match_type: Option<u8>,
+}
+
+/// How a login URI is matched against websites. Without a match type,
+/// the default of the account (usually base domain) is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UriMatchType {
+    BaseDomain,
+    Host,
+    StartsWith,
+    Exact,
+    RegularExpression,
+    Never,
+}
+
+impl UriMatchType {
+    fn from_repr(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(UriMatchType::BaseDomain),
+            1 => Some(UriMatchType::Host),
+            2 => Some(UriMatchType::StartsWith),
+            3 => Some(UriMatchType::Exact),
+            4 => Some(UriMatchType::RegularExpression),
+            5 => Some(UriMatchType::Never),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            UriMatchType::BaseDomain => 0,
+            UriMatchType::Host => 1,
+            UriMatchType::StartsWith => 2,
+            UriMatchType::Exact => 3,
+            UriMatchType::RegularExpression => 4,
+            UriMatchType::Never => 5,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            UriMatchType::BaseDomain => "base domain",
+            UriMatchType::Host => "host",
+            UriMatchType::StartsWith => "starts with",
+            UriMatchType::Exact => "exact",
+            UriMatchType::RegularExpression => "regular expression",
+            UriMatchType::Never => "never",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct CardItem {
     #[serde(default)]
     #[serde(alias = "Brand")]
@@ -628,7 +1830,7 @@ pub struct CardItem {
     pub number: Cipher,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct IdentityItem {
     #[serde(default)]
     #[serde(alias = "Address1")]
@@ -694,3 +1896,417 @@ pub struct IdentityItem {
     #[serde(alias = "Username")]
     pub username: Cipher,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SshKeyItem {
+    #[serde(default)]
+    #[serde(alias = "privateKey")]
+    #[serde(alias = "PrivateKey")]
+    pub private_key: Cipher,
+    #[serde(default)]
+    #[serde(alias = "publicKey")]
+    #[serde(alias = "PublicKey")]
+    pub public_key: Cipher,
+    #[serde(default)]
+    #[serde(alias = "keyFingerprint")]
+    #[serde(alias = "KeyFingerprint")]
+    pub fingerprint: Cipher,
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    /// Client for a mock server, for responses that a real server
+    /// can't be made to send on demand
+    async fn mock_client() -> (MockServer, ApiClient) {
+        let server = MockServer::start().await;
+        let url = Url::parse(&server.uri()).unwrap();
+        let client = ApiClient::with_token(
+            &ServerConfiguration::single_host(url),
+            "device",
+            DeviceClass::Cli,
+            "token",
+            false,
+            &[],
+        )
+        .unwrap();
+        (server, client)
+    }
+
+    async fn mock_token_error(server: &MockServer, status: u16, body: serde_json::Value) {
+        Mock::given(method("POST"))
+            .and(path("/identity/connect/token"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(body))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_token_captcha_required() {
+        let (server, client) = mock_client().await;
+        mock_token_error(
+            &server,
+            400,
+            serde_json::json!({ "HCaptcha_SiteKey": "site-key" }),
+        )
+        .await;
+
+        let res = client
+            .get_token("a@example.com", "hash", None, None, None)
+            .await;
+        assert!(matches!(res, Ok(TokenResponse::CaptchaRequired)));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_two_factor_required() {
+        let (server, client) = mock_client().await;
+        mock_token_error(
+            &server,
+            400,
+            serde_json::json!({
+                "TwoFactorProviders": [0, "1"],
+                "CaptchaBypassToken": "bypass",
+            }),
+        )
+        .await;
+
+        let res = client
+            .get_token("a@example.com", "hash", None, None, None)
+            .await;
+        let Ok(TokenResponse::TwoFactorRequired(providers, bypass)) = res else {
+            panic!("Expected a two-factor response");
+        };
+        assert_eq!(
+            providers,
+            [
+                TwoFactorProviderType::Authenticator,
+                TwoFactorProviderType::Email
+            ]
+        );
+        assert_eq!(bypass.as_deref(), Some("bypass"));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_new_device_verification_required() {
+        let (server, client) = mock_client().await;
+        mock_token_error(
+            &server,
+            400,
+            serde_json::json!({
+                "error": "invalid_grant",
+                "error_description": "new device verification required",
+                "ErrorModel": { "Message": "new device verification required" },
+            }),
+        )
+        .await;
+
+        let res = client
+            .get_token("a@example.com", "hash", None, None, None)
+            .await;
+        assert!(matches!(
+            res,
+            Ok(TokenResponse::VerificationRequired(Verification::NewDevice))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_error_model_message() {
+        let (server, client) = mock_client().await;
+        mock_token_error(
+            &server,
+            400,
+            serde_json::json!({
+                "ErrorModel": { "Message": "Username or password is incorrect. Try again." },
+            }),
+        )
+        .await;
+
+        let err = client
+            .get_token("a@example.com", "hash", None, None, None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Username or password is incorrect. Try again."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_rate_limited() {
+        let (server, client) = mock_client().await;
+        Mock::given(method("GET"))
+            .and(path("/api/sync"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .mount(&server)
+            .await;
+
+        let err = client.sync().await.err().unwrap();
+        let rate_limited = err.downcast_ref::<RateLimitedError>().unwrap();
+        assert_eq!(rate_limited.retry_after, Some(Duration::from_secs(30)));
+        assert!(err.to_string().contains("Try again in 30 seconds"), "{err}");
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        // Dates in the past mean that the request can be retried now
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_remembered_token_is_refreshed() {
+        let token = TokenResponseSuccess::remembered(
+            Cipher::Empty,
+            Cipher::Empty,
+            Zeroizing::new("refresh".to_owned()),
+        );
+        assert!(token.should_refresh());
+        assert_eq!(
+            token.refresh_token.as_deref().map(String::as_str),
+            Some("refresh")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_token_refresh() {
+        let (server, client) = mock_client().await;
+        // The server rotates the refresh token, so only the first
+        // refresh with the old one would succeed
+        Mock::given(method("POST"))
+            .and(path("/identity/connect/token"))
+            .and(body_string_contains("refresh_token=old-refresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Key": "",
+                "PrivateKey": "",
+                "access_token": "new-access",
+                "expires_in": 3600,
+                "refresh_token": "new-refresh",
+            })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        mock_token_error(
+            &server,
+            400,
+            serde_json::json!({ "error": "invalid_grant" }),
+        )
+        .await;
+
+        let token = TokenResponseSuccess {
+            refresh_token: Some(Zeroizing::new("old-refresh".to_owned())),
+            ..TokenResponseSuccess::offline(Cipher::Empty, Cipher::Empty)
+        };
+        let (a, b) = tokio::join!(
+            client.refresh_token(&token, None),
+            client.refresh_token(&token, None)
+        );
+
+        // Both get the token of the one refresh
+        for res in [a, b] {
+            let TokenResponse::Success(new_token) = res.unwrap() else {
+                panic!("Expected a token");
+            };
+            assert_eq!(*new_token.access_token, "new-access");
+            assert_eq!(
+                new_token.refresh_token.as_deref().map(String::as_str),
+                Some("new-refresh")
+            );
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_item_with_legacy_enc_types() {
+        let (server, client) = mock_client().await;
+        Mock::given(method("GET"))
+            .and(path("/api/sync"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ciphers": [{
+                    "id": "legacy",
+                    "type": 1,
+                    "name": "0.AAAA|BBBB",
+                    "favorite": false,
+                    "collectionIds": [],
+                    "login": { "password": "1.AAAA|BBBB|CCCC" },
+                }, {
+                    "id": "current",
+                    "type": 2,
+                    "name": "2.AAAA|BBBB|CCCC",
+                    "favorite": false,
+                    "collectionIds": [],
+                }],
+                "profile": { "organizations": [] },
+                "collections": [],
+            })))
+            .mount(&server)
+            .await;
+
+        let sync = client.sync().await.unwrap();
+        let legacy: Vec<_> = sync
+            .ciphers
+            .iter()
+            .filter(|c| !c.legacy_enc_types().is_empty())
+            .collect();
+        assert_eq!(legacy.len(), 1);
+        assert_eq!(legacy[0].id, "legacy");
+        assert_eq!(
+            legacy[0].legacy_enc_types(),
+            [EncType::AesCbc256B64, EncType::AesCbc128HmacSha256B64]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_attachment_direct_upload() {
+        let (server, client) = mock_client().await;
+        Mock::given(method("POST"))
+            .and(path("/api/ciphers/item-id/attachment/v2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "attachmentId": "attachment-id",
+                "url": "/ciphers/item-id/attachment/attachment-id",
+                "fileUploadType": 0,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/ciphers/item-id/attachment/attachment-id"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let id = client
+            .create_attachment("item-id", &Cipher::Empty, &Cipher::Empty, vec![1, 2, 3])
+            .await
+            .unwrap();
+        assert_eq!(id, "attachment-id");
+    }
+
+    #[tokio::test]
+    async fn test_restore_cipher() {
+        let (server, client) = mock_client().await;
+        Mock::given(method("PUT"))
+            .and(path("/api/ciphers/item-id/restore"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        client.restore_cipher("item-id").await.unwrap();
+        assert!(client.restore_cipher("other-id").await.is_err());
+    }
+
+    #[test]
+    fn test_new_cipher_request_body() {
+        let item = NewCipher {
+            name: Cipher::Empty,
+            notes: None,
+            data: NewCipherData::SecureNote,
+            fields: vec![
+                NewCustomField {
+                    name: Cipher::Empty,
+                    value: None,
+                    hidden: false,
+                },
+                NewCustomField {
+                    name: Cipher::Empty,
+                    value: None,
+                    hidden: true,
+                },
+            ],
+        };
+
+        let body = new_cipher_request_body(&item, Some("org"));
+        assert_eq!(body["type"], 2);
+        assert_eq!(body["organizationId"], "org");
+        assert_eq!(body["secureNote"]["type"], 0);
+        let field_types: Vec<_> = body["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["type"].as_u64().unwrap())
+            .collect();
+        assert_eq!(field_types, [0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_html_response_is_an_error() {
+        let (server, client) = mock_client().await;
+        Mock::given(method("GET"))
+            .and(path("/api/sync"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw("<html></html>", "text/html; charset=utf-8"),
+            )
+            .mount(&server)
+            .await;
+
+        let err = client.sync().await.err().unwrap();
+        assert!(err.to_string().contains("HTML page"), "{err}");
+    }
+
+    #[test]
+    fn test_password_changed_date() {
+        let item = |login: serde_json::Value, history: serde_json::Value| {
+            let cii: CipherItemInternal = serde_json::from_value(serde_json::json!({
+                "id": "item",
+                "type": 1,
+                "favorite": false,
+                "collectionIds": [],
+                "login": login,
+                "passwordHistory": history,
+            }))
+            .unwrap();
+            CipherItem::from(cii)
+        };
+
+        let history = serde_json::json!([
+            { "lastUsedDate": "2023-05-01T10:00:00.000Z", "password": "2.x|y|z" },
+            { "lastUsedDate": "2024-02-01T10:00:00.000Z", "password": "2.x|y|z" },
+        ]);
+        assert_eq!(
+            item(serde_json::json!({}), history.clone()).password_changed_date(),
+            Some("2024-02-01T10:00:00.000Z")
+        );
+        assert_eq!(
+            item(
+                serde_json::json!({ "passwordRevisionDate": "2024-03-01T10:00:00.000Z" }),
+                history
+            )
+            .password_changed_date(),
+            Some("2024-03-01T10:00:00.000Z")
+        );
+        assert_eq!(
+            item(serde_json::json!({}), serde_json::Value::Null).password_changed_date(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_item_without_type_data() {
+        for cipher_type in 1..=5 {
+            let cii: CipherItemInternal = serde_json::from_value(serde_json::json!({
+                "id": "item",
+                "type": cipher_type,
+                "favorite": false,
+                "collectionIds": [],
+            }))
+            .unwrap();
+            let item = CipherItem::from(cii);
+            let expected_none = cipher_type != 2;
+            assert_eq!(matches!(item.data, CipherData::None), expected_none);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Debug audit of decrypted data that leaves the cipher module as plain
+//! `Vec<u8>` or `String` values, outside `Zeroizing` wrappers. When
+//! enabled, the decrypting helpers record their callers, and the report
+//! lists the call sites. Used for checking memory hygiene, e.g. when
+//! adding features.
+
+use std::{
+    collections::BTreeMap,
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDS: Mutex<Records> = Mutex::new(BTreeMap::new());
+
+/// Call counts by file, line and kind
+type Records = BTreeMap<(&'static str, u32, &'static str), usize>;
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Records the caller of the decrypting function. `kind` describes
+/// the type the decrypted data is returned as.
+#[track_caller]
+pub(super) fn record(kind: &'static str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    add_record(&mut RECORDS.lock().unwrap(), Location::caller(), kind);
+}
+
+fn add_record(records: &mut Records, location: &'static Location<'static>, kind: &'static str) {
+    *records
+        .entry((location.file(), location.line(), kind))
+        .or_default() += 1;
+}
+
+/// Returns the audit report, or None if the audit is not enabled
+pub fn report() -> Option<String> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(format_report(&RECORDS.lock().unwrap()))
+}
+
+fn format_report(records: &Records) -> String {
+    let mut report =
+        String::from("Decrypted data converted to plain values outside Zeroizing wrappers:\n");
+    if records.is_empty() {
+        report.push_str("  (none)\n");
+    }
+    for ((file, line, kind), count) in records.iter() {
+        report.push_str(&format!("  {file}:{line}  {kind}  x{count}\n"));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The global state is left alone, so that the audit stays disabled
+    // for the other tests
+    #[test]
+    fn test_audit_report() {
+        let mut records = Records::new();
+        assert!(format_report(&records).contains("(none)"));
+
+        let location = Location::caller();
+        add_record(&mut records, location, "String");
+        add_record(&mut records, location, "String");
+
+        let report = format_report(&records);
+        assert!(report.contains(&format!("{}:{}  String  x2", file!(), location.line())));
+        assert!(!report.contains("(none)"));
+    }
+}
@@ -0,0 +1,53 @@
+//! Constant-time checks for secret-dependent data, such as MACs and
+//! verifiers. All such comparisons should go through this module instead
+//! of `==`, which returns early on the first differing byte.
+
+use hmac::{digest::MacError, Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+/// Compares two byte slices in constant time. Only the lengths,
+/// which are not secret, affect the timing.
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Computes HMAC-SHA256 over the data parts, and compares it to the
+/// given MAC in constant time.
+pub fn verify_hmac_sha256(key: &[u8], data: &[&[u8]], mac: &[u8]) -> Result<(), MacError> {
+    let mut hmac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in data {
+        hmac.update(part);
+    }
+
+    if eq(&hmac.finalize().into_bytes(), mac) {
+        Ok(())
+    } else {
+        Err(MacError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eq() {
+        assert!(eq(b"", b""));
+        assert!(eq(b"abc", b"abc"));
+        assert!(!eq(b"abc", b"abd"));
+        assert!(!eq(b"abc", b"abcd"));
+    }
+
+    #[test]
+    fn test_verify_hmac_sha256() {
+        let key = [7u8; 32];
+        let mut hmac = Hmac::<Sha256>::new_from_slice(&key).unwrap();
+        hmac.update(b"ivciphertext");
+        let mac = hmac.finalize().into_bytes();
+
+        assert!(verify_hmac_sha256(&key, &[b"iv", b"ciphertext"], &mac).is_ok());
+        assert!(verify_hmac_sha256(&key, &[b"iv", b"other"], &mac).is_err());
+        assert!(verify_hmac_sha256(&key, &[b"iv", b"ciphertext"], &mac[..16]).is_err());
+    }
+}
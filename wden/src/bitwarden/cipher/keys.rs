@@ -1,11 +1,17 @@
 use std::pin::Pin;
 
+use anyhow::Context;
 use hkdf::Hkdf;
-use sha2::Sha256;
+use rand::RngCore;
+use rsa::{
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256};
 use base64::prelude::*;
-use zeroize::{ZeroizeOnDrop, Zeroizing};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-use super::{get_pbkdf, Cipher, CipherError, PbkdfParameters};
+use super::{get_pbkdf, secure_buffer::SecureBuffer, Cipher, CipherError, PbkdfParameters};
 
 
 const CREDENTIAL_LEN: usize = 256 / 8;
@@ -21,8 +27,7 @@ impl MasterKey {
         self.0.as_mut_slice()
     }
 
-    #[cfg(test)]
-    pub(super) fn from_base64(b64_data: &str) -> Result<Self, base64::DecodeSliceError> {
+    pub fn from_base64(b64_data: &str) -> Result<Self, base64::DecodeSliceError> {
         let mut key = Self::new();
 
         let len = BASE64_STANDARD.decode_slice(b64_data, key.0.as_mut_slice())?;
@@ -35,10 +40,28 @@ impl MasterKey {
         }
     }
 
-    #[cfg(test)]
-    pub(super) fn base64_encoded(&self) -> Zeroizing<String> {
+    pub fn base64_encoded(&self) -> Zeroizing<String> {
         BASE64_STANDARD.encode(self.0.as_slice()).into()
     }
+
+    /// Encrypts the master key for storing it outside of wden, e.g. in
+    /// the platform keystore
+    pub fn encrypt(&self, keys: &EncMacKeys) -> Result<Cipher, CipherError> {
+        Cipher::encrypt(self.0.as_slice(), keys)
+    }
+
+    /// Reverses [`MasterKey::encrypt`]
+    pub fn decrypt(cipher: &Cipher, keys: &EncMacKeys) -> Result<Self, CipherError> {
+        let mut buf = Zeroizing::new(vec![0u8; cipher.ct_len()].into_boxed_slice());
+        let dec = cipher.decrypt_to(keys, &mut buf)?;
+        if dec.len() != CREDENTIAL_LEN {
+            return Err(CipherError::InvalidKeyLength);
+        }
+
+        let mut master_key = Self::new();
+        master_key.buf_mut().copy_from_slice(dec);
+        Ok(master_key)
+    }
 }
 
 #[derive(Clone, ZeroizeOnDrop)]
@@ -59,11 +82,48 @@ impl Default for MasterPasswordHash {
     }
 }
 
+/// Storage of a symmetric key: a normal allocation, or a slot in the
+/// locked memory pool for the keys that are kept for the whole session
+enum KeyBuffer {
+    Heap(Pin<Box<[u8; CREDENTIAL_LEN]>>),
+    Locked(SecureBuffer),
+}
+
+impl KeyBuffer {
+    fn heap() -> Self {
+        KeyBuffer::Heap(Box::pin([0u8; CREDENTIAL_LEN]))
+    }
+
+    fn locked() -> Self {
+        KeyBuffer::Locked(SecureBuffer::new())
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            KeyBuffer::Heap(b) => b.as_slice(),
+            KeyBuffer::Locked(b) => b.as_slice(),
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            KeyBuffer::Heap(b) => b.as_mut_slice(),
+            KeyBuffer::Locked(b) => b.as_mut_slice(),
+        }
+    }
+}
+
+impl Zeroize for KeyBuffer {
+    fn zeroize(&mut self) {
+        self.as_mut_slice().zeroize();
+    }
+}
+
 #[derive(ZeroizeOnDrop)]
-pub struct EncryptionKey(Pin<Box<[u8; CREDENTIAL_LEN]>>);
+pub struct EncryptionKey(KeyBuffer);
 impl EncryptionKey {
     fn new() -> Self {
-        Self(Box::pin([0u8; CREDENTIAL_LEN]))
+        Self(KeyBuffer::heap())
     }
 
     pub(super) fn data(&self) -> &[u8] {
@@ -72,10 +132,10 @@ impl EncryptionKey {
 }
 
 #[derive(ZeroizeOnDrop)]
-pub struct MacKey(Pin<Box<[u8; CREDENTIAL_LEN]>>);
+pub struct MacKey(KeyBuffer);
 impl MacKey {
     fn new() -> Self {
-        Self(Box::pin([0u8; CREDENTIAL_LEN]))
+        Self(KeyBuffer::heap())
     }
 
     pub(super) fn data(&self) -> &[u8] {
@@ -96,6 +156,111 @@ impl EncMacKeys {
     }
 }
 
+/// Master key kept encrypted in memory with an ephemeral session key.
+///
+/// The plaintext master key is only unwrapped transiently when
+/// it's needed, e.g. for decrypting the user's symmetric keys. This
+/// limits how long it is present in memory in plaintext form. The
+/// session key is kept in the locked memory pool, apart from the
+/// wrapped key.
+pub struct WrappedMasterKey {
+    session_keys: EncMacKeys,
+    wrapped_key: Cipher,
+}
+
+impl WrappedMasterKey {
+    pub fn wrap(master_key: &MasterKey) -> Result<Self, CipherError> {
+        let session_keys = generate_session_keys();
+        let wrapped_key = Cipher::encrypt(master_key.0.as_slice(), &session_keys)?;
+        Ok(Self {
+            session_keys,
+            wrapped_key,
+        })
+    }
+
+    pub fn unwrap_key(&self) -> Result<MasterKey, CipherError> {
+        let mut buf = Zeroizing::new(vec![0u8; self.wrapped_key.ct_len()].into_boxed_slice());
+        let dec = self.wrapped_key.decrypt_to(&self.session_keys, &mut buf)?;
+        if dec.len() != CREDENTIAL_LEN {
+            return Err(CipherError::InvalidKeyLength);
+        }
+
+        let mut master_key = MasterKey::new();
+        master_key.buf_mut().copy_from_slice(dec);
+        Ok(master_key)
+    }
+}
+
+/// The user's symmetric keys kept encrypted in memory with an ephemeral
+/// session key, for profiles that drop the master key after unlocking.
+pub struct WrappedSymmetricKeys {
+    session_keys: EncMacKeys,
+    wrapped_keys: Cipher,
+}
+
+impl WrappedSymmetricKeys {
+    pub fn wrap(keys: &EncMacKeys) -> Result<Self, CipherError> {
+        let mut full_key = Zeroizing::new([0u8; 2 * CREDENTIAL_LEN]);
+        full_key[..CREDENTIAL_LEN].copy_from_slice(keys.enc().data());
+        full_key[CREDENTIAL_LEN..].copy_from_slice(keys.mac().data());
+
+        let session_keys = generate_session_keys();
+        let wrapped_keys = Cipher::encrypt(full_key.as_slice(), &session_keys)?;
+        Ok(Self {
+            session_keys,
+            wrapped_keys,
+        })
+    }
+
+    pub fn unwrap_keys(&self) -> Result<EncMacKeys, CipherError> {
+        let mut buf = Zeroizing::new(vec![0u8; self.wrapped_keys.ct_len()].into_boxed_slice());
+        let dec = self.wrapped_keys.decrypt_to(&self.session_keys, &mut buf)?;
+        extract_enc_mac_keys(dec)
+    }
+}
+
+/// Mixes the contents of a local keyfile into the master key with HKDF.
+///
+/// The result is not used for encryption. It's stored in the profile
+/// to verify that the same keyfile is present when unlocking.
+pub fn create_keyfile_verifier(master_key: &MasterKey, keyfile_contents: &[u8]) -> String {
+    type HkdfSha256 = Hkdf<Sha256>;
+
+    let salt = Sha256::digest(keyfile_contents);
+    let hk = HkdfSha256::new(Some(salt.as_slice()), master_key.0.as_slice());
+
+    let mut verifier = Zeroizing::new([0u8; CREDENTIAL_LEN]);
+    hk.expand("wden-keyfile-verifier".as_bytes(), verifier.as_mut_slice())
+        .unwrap();
+
+    BASE64_STANDARD.encode(verifier.as_slice())
+}
+
+/// Derives keys from the contents of a local keyfile with HKDF. The keys
+/// encrypt what is stored locally for unlocking (the user key in the
+/// offline vault cache, the master key in the platform keystore), so those
+/// can't be used without the keyfile. Only the keyfile is used, because
+/// the keys are needed before the master key is known.
+pub fn create_keyfile_keys(keyfile_contents: &[u8]) -> EncMacKeys {
+    type HkdfSha256 = Hkdf<Sha256>;
+
+    let hk = HkdfSha256::new(Some("wden-keyfile".as_bytes()), keyfile_contents);
+    let mut full_key = Zeroizing::new([0u8; 2 * CREDENTIAL_LEN]);
+    hk.expand("wden-keyfile-keys".as_bytes(), full_key.as_mut_slice())
+        .unwrap();
+
+    extract_enc_mac_keys(full_key.as_slice()).unwrap()
+}
+
+fn generate_session_keys() -> EncMacKeys {
+    let mut enc_key = EncryptionKey(KeyBuffer::locked());
+    let mut mac_key = MacKey(KeyBuffer::locked());
+    let mut rng = rand::thread_rng();
+    rng.fill_bytes(enc_key.0.as_mut_slice());
+    rng.fill_bytes(mac_key.0.as_mut_slice());
+    EncMacKeys::new(enc_key, mac_key)
+}
+
 // Private key is in DER format
 #[derive(ZeroizeOnDrop)]
 pub struct DerPrivateKey(Vec<u8>);
@@ -110,6 +275,56 @@ impl DerPrivateKey {
     }
 }
 
+/// Parsed RSA private key. Parsing the DER is relatively expensive,
+/// so this should be kept around when decrypting many org keys.
+pub struct PrivateKey(pub(super) RsaPrivateKey);
+impl PrivateKey {
+    pub fn from_der(der: &DerPrivateKey) -> Result<Self, CipherError> {
+        let key =
+            RsaPrivateKey::from_pkcs8_der(der.data()).context("Reading RSA private key failed")?;
+        Ok(PrivateKey(key))
+    }
+}
+
+/// RSA public key of another user, e.g. for sharing an organization key
+pub struct PublicKey(pub(super) RsaPublicKey);
+impl PublicKey {
+    pub fn from_der(der: &[u8]) -> Result<Self, CipherError> {
+        let key =
+            RsaPublicKey::from_public_key_der(der).context("Reading RSA public key failed")?;
+        Ok(PublicKey(key))
+    }
+}
+
+/// 5 words from the EFF long word list carry more than 64 bits
+const FINGERPRINT_PHRASE_WORDS: usize = 5;
+
+/// Derives the fingerprint phrase of the public key of a user, the same as
+/// the official clients show. Comparing the phrase with the user makes sure
+/// the server did not hand out some other key.
+pub fn fingerprint_phrase(public_key_der: &[u8], user_id: &str) -> String {
+    let key_hash = Sha256::digest(public_key_der);
+    let hk = Hkdf::<Sha256>::from_prk(key_hash.as_slice()).unwrap();
+    let mut fingerprint = [0u8; 32];
+    hk.expand(user_id.as_bytes(), &mut fingerprint).unwrap();
+
+    // The fingerprint is read as a big-endian number, and the words are
+    // picked by dividing it with the list length repeatedly
+    let words = &eff_wordlist::large::LIST;
+    let word_count = words.len() as u32;
+    let mut phrase = Vec::with_capacity(FINGERPRINT_PHRASE_WORDS);
+    for _ in 0..FINGERPRINT_PHRASE_WORDS {
+        let mut remainder = 0u32;
+        for b in fingerprint.iter_mut() {
+            let current = (remainder << 8) | u32::from(*b);
+            *b = (current / word_count) as u8;
+            remainder = current % word_count;
+        }
+        phrase.push(words[remainder as usize].1);
+    }
+    phrase.join("-")
+}
+
 pub fn create_master_key(
     user_email: &str,
     user_password: &str,
@@ -157,13 +372,47 @@ pub fn decrypt_item_keys(
 }
 
 pub fn decrypt_org_keys(
-    private_key: &DerPrivateKey,
+    private_key: &PrivateKey,
     org_key_cipher: &Cipher,
 ) -> Result<EncMacKeys, CipherError> {
     let dec_cipher = org_key_cipher.decrypt_with_private_key(private_key)?;
     extract_enc_mac_keys(&dec_cipher)
 }
 
+/// Encrypts the organization key for a new member of the organization
+pub fn encrypt_org_keys(
+    org_keys: &EncMacKeys,
+    public_key: &PublicKey,
+) -> Result<Cipher, CipherError> {
+    let mut full_key = Zeroizing::new([0u8; 2 * CREDENTIAL_LEN]);
+    full_key[..CREDENTIAL_LEN].copy_from_slice(org_keys.enc().data());
+    full_key[CREDENTIAL_LEN..].copy_from_slice(org_keys.mac().data());
+    Cipher::encrypt_with_public_key(full_key.as_slice(), public_key)
+}
+
+/// Generates new random keys, e.g. for an attachment. Returns the keys,
+/// and the keys encrypted with `keys` for storing on the server.
+pub fn generate_encrypted_keys(keys: &EncMacKeys) -> Result<(EncMacKeys, Cipher), CipherError> {
+    let mut full_key = Zeroizing::new([0u8; 2 * CREDENTIAL_LEN]);
+    rand::thread_rng().fill_bytes(full_key.as_mut_slice());
+    let new_keys = extract_enc_mac_keys(full_key.as_slice())?;
+    let encrypted = Cipher::encrypt(full_key.as_slice(), keys)?;
+    Ok((new_keys, encrypted))
+}
+
+/// Derives the keys of a password protected export with PBKDF2. Unlike
+/// with master keys, the salt is used as is, without lowercasing.
+pub fn derive_export_keys(password: &str, salt: &str, iterations: u32) -> EncMacKeys {
+    let mut key = MasterKey::new();
+    pbkdf2::pbkdf2_hmac::<Sha256>(
+        password.as_bytes(),
+        salt.as_bytes(),
+        iterations,
+        key.buf_mut(),
+    );
+    expand_master_key(&key)
+}
+
 pub fn extract_enc_mac_keys(full_key: &[u8]) -> Result<EncMacKeys, CipherError> {
     // Enc key and mac key should both be 32 bytes
     if full_key.len() != 2 * CREDENTIAL_LEN {
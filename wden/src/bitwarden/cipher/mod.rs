@@ -6,13 +6,13 @@ use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use hmac::digest::{InvalidLength, MacError};
 use hmac::{Hmac, Mac};
 use rsa::Oaep;
-use rsa::{pkcs8::DecodePrivateKey, RsaPrivateKey};
 use serde::{de, Serialize, Serializer};
 use serde::{Deserialize, Deserializer};
 use sha2::Sha256;
 use std::fmt;
 use std::str::FromStr;
 use thiserror::Error;
+use zeroize::Zeroizing;
 
 mod pbkdf;
 pub use pbkdf::*;
@@ -20,6 +20,11 @@ pub use pbkdf::*;
 mod keys;
 pub use keys::*;
 
+mod secure_buffer;
+
+pub mod audit;
+pub mod constant_time;
+
 
 #[derive(Error, Debug)]
 pub enum CipherError {
@@ -33,6 +38,8 @@ pub enum CipherError {
     InvalidCipherStringFormat,
     #[error("Unknown cipher encryption type {0}")]
     UnknownCipherEncryptionType(String),
+    #[error("Unsupported cipher encryption type {0}")]
+    UnsupportedCipherEncryptionType(u8),
     #[error("Invalid key type for cipher")]
     InvalidKeyTypeForCipher,
     #[error("Invalid key or IV length for encrypting")]
@@ -76,9 +83,40 @@ impl fmt::Debug for Cipher {
 
 impl<'de> Deserialize<'de> for Cipher {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Cipher, D::Error> {
-        Option::deserialize(deserializer)?
-            .map(|s: String| s.parse().map_err(de::Error::custom))
-            .unwrap_or(Ok(Cipher::Empty))
+        deserializer.deserialize_option(CipherVisitor)
+    }
+}
+
+/// Parses cipher strings without keeping intermediate copies around: borrowed
+/// strings are parsed in place, and owned strings are wiped after parsing.
+struct CipherVisitor;
+
+impl<'de> de::Visitor<'de> for CipherVisitor {
+    type Value = Cipher;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a cipher string or null")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Cipher, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Cipher, E> {
+        let v = Zeroizing::new(v);
+        self.visit_str(&v)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Cipher, E> {
+        Ok(Cipher::Empty)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Cipher, E> {
+        Ok(Cipher::Empty)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Cipher, D::Error> {
+        deserializer.deserialize_str(self)
     }
 }
 
@@ -101,50 +139,54 @@ impl FromStr for Cipher {
             .ok_or(CipherError::InvalidCipherStringFormat)?;
         let enc_type = EncType::from_str(enc_type_str)?;
 
-        match (enc_type.has_iv(), enc_type.has_mac()) {
-            (true, true) => {
-                let b64_parts = rest.split('|').collect::<Vec<_>>();
-                if b64_parts.len() != 3 {
-                    return Err(CipherError::InvalidCipherStringFormat);
-                }
-
-                let iv = BASE64_STANDARD
-                    .decode(b64_parts[0])
-                    .or(Err(CipherError::InvalidCipherStringFormat))?;
-                let ct = BASE64_STANDARD
-                    .decode(b64_parts[1])
-                    .or(Err(CipherError::InvalidCipherStringFormat))?;
-                let mac = BASE64_STANDARD
-                    .decode(b64_parts[2])
-                    .or(Err(CipherError::InvalidCipherStringFormat))?;
-
-                Ok(Cipher::Value {
-                    enc_type,
-                    iv,
-                    ct,
-                    mac,
-                })
-            }
-            (false, false) => {
-                let iv = vec![];
-                let mac = vec![];
-                let ct = BASE64_STANDARD
-                    .decode(rest)
-                    .or(Err(CipherError::InvalidCipherStringFormat))?;
-                Ok(Cipher::Value {
-                    enc_type,
-                    iv,
-                    ct,
-                    mac,
-                })
-            }
-            _ => unimplemented!(),
+        if let EncType::Unknown(_) = enc_type {
+            // Keep the payload as is, so that the cipher can be encoded back
+            return Ok(Cipher::Value {
+                enc_type,
+                iv: vec![],
+                ct: rest.as_bytes().to_vec(),
+                mac: vec![],
+            });
         }
+
+        let decode = |part: &str| {
+            BASE64_STANDARD
+                .decode(part)
+                .or(Err(CipherError::InvalidCipherStringFormat))
+        };
+        let b64_parts = rest.split('|').collect::<Vec<_>>();
+        let (iv, ct, mac) = match (enc_type.has_iv(), enc_type.has_mac(), &b64_parts[..]) {
+            (true, true, [iv, ct, mac]) => (decode(iv)?, decode(ct)?, decode(mac)?),
+            (true, false, [iv, ct]) => (decode(iv)?, decode(ct)?, vec![]),
+            (false, true, [ct, mac]) => (vec![], decode(ct)?, decode(mac)?),
+            (false, false, [ct]) => (vec![], decode(ct)?, vec![]),
+            _ => return Err(CipherError::InvalidCipherStringFormat),
+        };
+
+        Ok(Cipher::Value {
+            enc_type,
+            iv,
+            ct,
+            mac,
+        })
     }
 }
 
 impl Cipher {
+    pub fn enc_type(&self) -> Option<EncType> {
+        match self {
+            Self::Empty => None,
+            Self::Value { enc_type, .. } => Some(*enc_type),
+        }
+    }
+
+    #[track_caller]
     pub fn decrypt(&self, keys: &EncMacKeys) -> Result<Vec<u8>, CipherError> {
+        audit::record("Vec<u8>");
+        self.decrypt_unaudited(keys)
+    }
+
+    fn decrypt_unaudited(&self, keys: &EncMacKeys) -> Result<Vec<u8>, CipherError> {
         match self {
             Self::Empty => Ok(vec![]),
             Self::Value { enc_type, .. } => match enc_type {
@@ -157,6 +199,7 @@ impl Cipher {
                     Err(CipherError::InvalidKeyTypeForCipher)
                 }
                 EncType::Rsa2048OaepSha1HmacSha256B64 => Err(CipherError::InvalidKeyTypeForCipher),
+                EncType::Unknown(t) => Err(CipherError::UnsupportedCipherEncryptionType(*t)),
             },
         }
     }
@@ -178,6 +221,7 @@ impl Cipher {
                     Err(CipherError::InvalidKeyTypeForCipher)
                 }
                 EncType::Rsa2048OaepSha1HmacSha256B64 => Err(CipherError::InvalidKeyTypeForCipher),
+                EncType::Unknown(t) => Err(CipherError::UnsupportedCipherEncryptionType(*t)),
             },
         }
     }
@@ -208,18 +252,57 @@ impl Cipher {
         })
     }
 
+    /// Encrypts for the owner of the public key, with the type used for
+    /// sharing organization keys (RSA-2048 OAEP SHA-1)
+    pub fn encrypt_with_public_key(
+        content: &[u8],
+        public_key: &PublicKey,
+    ) -> Result<Self, CipherError> {
+        let padding = Oaep::new::<sha1::Sha1>();
+        let ct = public_key
+            .0
+            .encrypt(&mut rand::thread_rng(), padding, content)
+            .context("RSA encryption failed")?;
+
+        Ok(Self::Value {
+            enc_type: EncType::Rsa2048OaepSha1B64,
+            iv: vec![],
+            ct,
+            mac: vec![],
+        })
+    }
+
+    #[track_caller]
     pub fn decrypt_to_string(&self, keys: &EncMacKeys) -> String {
-        self.decrypt(keys)
+        audit::record("String");
+        self.decrypt_unaudited(keys)
             .inspect_err(|e| log::warn!("Error decrypting cipher: {}", e))
             .ok()
             .and_then(|s| String::from_utf8(s).ok())
             .unwrap_or_default()
     }
 
+    /// Like [`Cipher::decrypt_to_string`], but decrypts into a reusable buffer
+    /// instead of allocating a new string for every cipher. The returned
+    /// value borrows the buffer, so it is wiped with it.
+    pub fn decrypt_to_str_buffered<'a>(
+        &self,
+        keys: &EncMacKeys,
+        buf: &'a mut DecryptBuffer,
+    ) -> &'a str {
+        self.decrypt_to(keys, buf.get(self.ct_len()))
+            .inspect_err(|e| log::warn!("Error decrypting cipher: {}", e))
+            .ok()
+            .and_then(|s| std::str::from_utf8(s).ok())
+            .unwrap_or_default()
+    }
+
+    #[track_caller]
     pub fn decrypt_with_private_key(
         &self,
-        private_key: &DerPrivateKey,
+        private_key: &PrivateKey,
     ) -> Result<Vec<u8>, CipherError> {
+        audit::record("Vec<u8>");
         match self {
             Self::Empty => Ok(vec![]),
             Self::Value { enc_type, .. } => match enc_type {
@@ -234,41 +317,54 @@ impl Cipher {
                 EncType::AesCbc256B64 => Err(CipherError::InvalidKeyTypeForCipher),
                 EncType::AesCbc128HmacSha256B64 => Err(CipherError::InvalidKeyTypeForCipher),
                 EncType::AesCbc256HmacSha256B64 => Err(CipherError::InvalidKeyTypeForCipher),
+                EncType::Unknown(t) => Err(CipherError::UnsupportedCipherEncryptionType(*t)),
             },
         }
     }
 
     fn decrypt_aescbc256(&self, _keys: &EncMacKeys) -> Result<Vec<u8>, CipherError> {
-        unimplemented!()
+        // The keys always have a MAC key. Decrypting without checking the
+        // MAC would let a value of type 2 be relabeled as type 0 to skip
+        // the check. The official clients refuse this as well.
+        Err(CipherError::UnsupportedCipherEncryptionType(
+            EncType::AesCbc256B64.as_u8(),
+        ))
     }
     fn decrypt_aescbc128_hmac_sha256(&self, _keys: &EncMacKeys) -> Result<Vec<u8>, CipherError> {
-        unimplemented!()
+        // Needs 128-bit keys, which the current key types cannot hold.
+        // The official clients do not decrypt this type either.
+        Err(CipherError::UnsupportedCipherEncryptionType(
+            EncType::AesCbc128HmacSha256B64.as_u8(),
+        ))
     }
     fn decrypt_aescbc256_to<'a>(
         &self,
         _keys: &EncMacKeys,
         _buf: &'a mut [u8],
     ) -> Result<&'a [u8], CipherError> {
-        unimplemented!()
+        Err(CipherError::UnsupportedCipherEncryptionType(
+            EncType::AesCbc256B64.as_u8(),
+        ))
     }
     fn decrypt_aescbc128_hmac_sha256_to<'a>(
         &self,
         _keys: &EncMacKeys,
         _buf: &'a mut [u8],
     ) -> Result<&'a [u8], CipherError> {
-        unimplemented!()
+        Err(CipherError::UnsupportedCipherEncryptionType(
+            EncType::AesCbc128HmacSha256B64.as_u8(),
+        ))
     }
     fn decrypt_aescbc256_hmac_sha256(&self, keys: &EncMacKeys) -> Result<Vec<u8>, CipherError> {
         if let Self::Value { iv, ct, mac, .. } = self {
             type Aes256CbcDec = cbc::Decryptor<Aes256>;
-            type HmacSha256 = Hmac<Sha256>;
-
-            let mut hmac = HmacSha256::new_from_slice(keys.mac().data()).unwrap();
 
-            hmac.update(iv);
-            hmac.update(ct);
-            hmac.verify_slice(mac)
-                .map_err(CipherError::MacVerificationFailed)?;
+            constant_time::verify_hmac_sha256(
+                keys.mac().data(),
+                &[iv.as_slice(), ct.as_slice()],
+                mac,
+            )
+            .map_err(CipherError::MacVerificationFailed)?;
 
             let aes = Aes256CbcDec::new_from_slices(keys.enc().data(), iv.as_slice())
                 .context("Initializing AES failed")?;
@@ -290,14 +386,13 @@ impl Cipher {
     ) -> Result<&'a [u8], CipherError> {
         if let Self::Value { iv, ct, mac, .. } = self {
             type Aes256CbcDec = cbc::Decryptor<Aes256>;
-            type HmacSha256 = Hmac<Sha256>;
 
-            let mut hmac = HmacSha256::new_from_slice(keys.mac().data()).unwrap();
-            let data = [&iv[..], &ct[..]].concat();
-
-            hmac.update(&data);
-            hmac.verify_slice(mac)
-                .map_err(CipherError::MacVerificationFailed)?;
+            constant_time::verify_hmac_sha256(
+                keys.mac().data(),
+                &[iv.as_slice(), ct.as_slice()],
+                mac,
+            )
+            .map_err(CipherError::MacVerificationFailed)?;
 
             let aes = Aes256CbcDec::new_from_slices(keys.enc().data(), iv.as_slice())
                 .context("Initializing AES failed")?;
@@ -312,22 +407,24 @@ impl Cipher {
         }
     }
 
-    fn decrypt_rsa2048_oaepsha256(
-        &self,
-        _private_key: &DerPrivateKey,
-    ) -> Result<Vec<u8>, CipherError> {
-        unimplemented!()
-    }
-    fn decrypt_rsa2048_oaepsha1(
-        &self,
-        private_key: &DerPrivateKey,
-    ) -> Result<Vec<u8>, CipherError> {
+    fn decrypt_rsa2048_oaepsha256(&self, private_key: &PrivateKey) -> Result<Vec<u8>, CipherError> {
         if let Self::Value { ct, .. } = self {
-            let rsa_key = RsaPrivateKey::from_pkcs8_der(private_key.data())
-                .context("Reading RSA private key failed")?;
+            let padding = Oaep::new::<Sha256>();
+            let res = private_key
+                .0
+                .decrypt(padding, ct.as_slice())
+                .context("RSA decryption failed")?;
 
+            Ok(res)
+        } else {
+            panic!("Tried to decrypt empty cipher")
+        }
+    }
+    fn decrypt_rsa2048_oaepsha1(&self, private_key: &PrivateKey) -> Result<Vec<u8>, CipherError> {
+        if let Self::Value { ct, .. } = self {
             let padding = Oaep::new::<sha1::Sha1>();
-            let res = rsa_key
+            let res = private_key
+                .0
                 .decrypt(padding, ct.as_slice())
                 .context("RSA decryption failed")?;
 
@@ -336,17 +433,23 @@ impl Cipher {
             panic!("Tried to decrypt empty cipher")
         }
     }
+    // The MAC of the deprecated RSA + HMAC types cannot be verified
+    // without a MAC key, so these are not decrypted
     fn decrypt_rsa2048_oaepsha256_hmacsha256(
         &self,
-        _private_key: &DerPrivateKey,
+        _private_key: &PrivateKey,
     ) -> Result<Vec<u8>, CipherError> {
-        unimplemented!()
+        Err(CipherError::UnsupportedCipherEncryptionType(
+            EncType::Rsa2048OaepSha256HmacSha256B64.as_u8(),
+        ))
     }
     fn decrypt_rsa2048_oaepsha1_hmacsha256(
         &self,
-        _private_key: &DerPrivateKey,
+        _private_key: &PrivateKey,
     ) -> Result<Vec<u8>, CipherError> {
-        unimplemented!()
+        Err(CipherError::UnsupportedCipherEncryptionType(
+            EncType::Rsa2048OaepSha1HmacSha256B64.as_u8(),
+        ))
     }
 
     pub fn encode(&self) -> String {
@@ -358,16 +461,40 @@ impl Cipher {
                 ct,
                 mac,
             } => {
-                let b64_ct = BASE64_STANDARD.encode(ct);
-                match (enc_type.has_mac(), enc_type.has_iv()) {
-                    (true, true) => {
-                        let b64_iv = BASE64_STANDARD.encode(iv);
-                        let b64_mac = BASE64_STANDARD.encode(mac);
-                        format!("{}.{}|{}|{}", *enc_type as u8, b64_iv, b64_ct, b64_mac)
-                    }
-                    (false, false) => format!("{}.{}", *enc_type as u8, b64_ct),
-                    _ => unimplemented!(),
+                if let EncType::Unknown(t) = enc_type {
+                    return format!("{}.{}", t, String::from_utf8_lossy(ct));
+                }
+
+                let mut parts = Vec::with_capacity(3);
+                if enc_type.has_iv() {
+                    parts.push(BASE64_STANDARD.encode(iv));
                 }
+                parts.push(BASE64_STANDARD.encode(ct));
+                if enc_type.has_mac() {
+                    parts.push(BASE64_STANDARD.encode(mac));
+                }
+                format!("{}.{}", enc_type.as_u8(), parts.join("|"))
+            }
+        }
+    }
+
+    /// Binary encoding used for attachment contents: the type byte,
+    /// followed by the iv, the mac and the ciphertext
+    pub fn encode_bytes(&self) -> Vec<u8> {
+        match self {
+            Cipher::Empty => Vec::new(),
+            Cipher::Value {
+                enc_type,
+                iv,
+                ct,
+                mac,
+            } => {
+                let mut buf = Vec::with_capacity(1 + iv.len() + mac.len() + ct.len());
+                buf.push(enc_type.as_u8());
+                buf.extend_from_slice(iv);
+                buf.extend_from_slice(mac);
+                buf.extend_from_slice(ct);
+                buf
             }
         }
     }
@@ -380,28 +507,76 @@ impl Cipher {
     }
 }
 
+/// Scratch buffer for decrypting many ciphers in a row, e.g. one per
+/// thread when building the vault table. Zeroized when dropped.
+#[derive(Default)]
+pub struct DecryptBuffer(Zeroizing<Vec<u8>>);
+
+impl DecryptBuffer {
+    fn get(&mut self, len: usize) -> &mut [u8] {
+        if self.0.len() < len {
+            // Replace instead of resizing, so that the old allocation
+            // gets zeroized and is not just freed by the reallocation
+            self.0 = Zeroizing::new(vec![0u8; len.next_power_of_two()]);
+        }
+        &mut self.0[..len]
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum EncType {
-    AesCbc256B64 = 0,
-    AesCbc128HmacSha256B64 = 1,
-    AesCbc256HmacSha256B64 = 2,
-    Rsa2048OaepSha256B64 = 3,
-    Rsa2048OaepSha1B64 = 4,
-    Rsa2048OaepSha256HmacSha256B64 = 5,
-    Rsa2048OaepSha1HmacSha256B64 = 6,
+    AesCbc256B64,
+    AesCbc128HmacSha256B64,
+    AesCbc256HmacSha256B64,
+    Rsa2048OaepSha256B64,
+    Rsa2048OaepSha1B64,
+    Rsa2048OaepSha256HmacSha256B64,
+    Rsa2048OaepSha1HmacSha256B64,
+    /// A type not supported by wden, e.g. one introduced in a newer server version.
+    /// The payload of such ciphers is kept unparsed, and decrypting them fails.
+    Unknown(u8),
 }
 
 impl EncType {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            EncType::AesCbc256B64 => 0,
+            EncType::AesCbc128HmacSha256B64 => 1,
+            EncType::AesCbc256HmacSha256B64 => 2,
+            EncType::Rsa2048OaepSha256B64 => 3,
+            EncType::Rsa2048OaepSha1B64 => 4,
+            EncType::Rsa2048OaepSha256HmacSha256B64 => 5,
+            EncType::Rsa2048OaepSha1HmacSha256B64 => 6,
+            EncType::Unknown(t) => *t,
+        }
+    }
+
+    /// Types used only by very old items: without a MAC, or with a
+    /// 128-bit key
+    pub fn is_legacy(&self) -> bool {
+        matches!(
+            self,
+            EncType::AesCbc256B64 | EncType::AesCbc128HmacSha256B64
+        )
+    }
+
     fn has_iv(&self) -> bool {
-        self == &EncType::AesCbc256B64
-            || self == &EncType::AesCbc128HmacSha256B64
-            || self == &EncType::AesCbc256HmacSha256B64
+        matches!(
+            self,
+            EncType::AesCbc256B64
+                | EncType::AesCbc128HmacSha256B64
+                | EncType::AesCbc256HmacSha256B64
+        )
     }
 
     fn has_mac(&self) -> bool {
-        self != &EncType::AesCbc256B64
-            && self != &EncType::Rsa2048OaepSha1B64
-            && self != &EncType::Rsa2048OaepSha256B64
+        matches!(
+            self,
+            EncType::AesCbc128HmacSha256B64
+                | EncType::AesCbc256HmacSha256B64
+                | EncType::Rsa2048OaepSha256HmacSha256B64
+                | EncType::Rsa2048OaepSha1HmacSha256B64
+        )
     }
 }
 
@@ -416,7 +591,10 @@ impl FromStr for EncType {
             "4" => Ok(EncType::Rsa2048OaepSha1B64),
             "5" => Ok(EncType::Rsa2048OaepSha256HmacSha256B64),
             "6" => Ok(EncType::Rsa2048OaepSha1HmacSha256B64),
-            _ => Err(CipherError::UnknownCipherEncryptionType(s.to_string())),
+            _ => s
+                .parse()
+                .map(EncType::Unknown)
+                .map_err(|_| CipherError::UnknownCipherEncryptionType(s.to_string())),
         }
     }
 }
@@ -526,6 +704,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_wrapped_master_key_roundtrip() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let wrapped = WrappedMasterKey::wrap(&master_key).unwrap();
+        let unwrapped = wrapped.unwrap_key().unwrap();
+        assert_eq!(
+            unwrapped.base64_encoded().as_str(),
+            testdata::USER_MASTER_KEY_PBKDF2_B64
+        );
+    }
+
+    #[test]
+    fn test_wrapped_symmetric_keys_roundtrip() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+
+        let wrapped = WrappedSymmetricKeys::wrap(&keys).unwrap();
+        let unwrapped = wrapped.unwrap_keys().unwrap();
+        assert_eq!(unwrapped.enc().data(), keys.enc().data());
+        assert_eq!(unwrapped.mac().data(), keys.mac().data());
+    }
+
+    #[test]
+    fn test_create_keyfile_verifier() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let verifier = create_keyfile_verifier(&master_key, b"wden test keyfile");
+        assert_eq!(verifier, "2w+9Jh8shmrnRmgKGycRSOC18u815Z2jVwTGTsHPTaM=");
+
+        let other_verifier = create_keyfile_verifier(&master_key, b"other keyfile");
+        assert_ne!(verifier, other_verifier);
+    }
+
+    #[test]
+    fn test_encrypt_master_key_with_keyfile_keys() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let keys = create_keyfile_keys(b"wden test keyfile");
+        let encrypted = master_key.encrypt(&keys).unwrap();
+
+        let decrypted = MasterKey::decrypt(&encrypted, &keys).unwrap();
+        assert_eq!(
+            *decrypted.base64_encoded(),
+            testdata::USER_MASTER_KEY_PBKDF2_B64
+        );
+        let other_keys = create_keyfile_keys(b"other keyfile");
+        assert!(MasterKey::decrypt(&encrypted, &other_keys).is_err());
+    }
+
     #[test]
     fn test_parse_cipher() {
         let cipher = Cipher::from_str(testdata::TEST_CIPHER_STRING).unwrap();
@@ -535,6 +767,121 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_deserialize_cipher() {
+        let value = serde_json::json!({ "key": testdata::TEST_CIPHER_STRING, "empty": null });
+        let borrowed = Cipher::deserialize(&value["key"]).unwrap();
+        assert_eq!(borrowed.encode(), testdata::TEST_CIPHER_STRING);
+        let owned = Cipher::deserialize(value["key"].clone()).unwrap();
+        assert_eq!(owned.encode(), testdata::TEST_CIPHER_STRING);
+        assert!(matches!(
+            Cipher::deserialize(&value["empty"]).unwrap(),
+            Cipher::Empty
+        ));
+        assert!(Cipher::deserialize(&serde_json::json!(1)).is_err());
+    }
+
+    #[test]
+    fn test_parse_cipher_unknown_enc_type() {
+        let cipher_string = "9.AAAA|BBBB|CCCC";
+        let cipher = Cipher::from_str(cipher_string).unwrap();
+
+        assert!(matches!(cipher, Cipher::Value {enc_type, ..} if enc_type == EncType::Unknown(9)));
+        assert_eq!(cipher.encode(), cipher_string);
+
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+        assert!(matches!(
+            cipher.decrypt(&keys),
+            Err(CipherError::UnsupportedCipherEncryptionType(9))
+        ));
+
+        assert!(Cipher::from_str("x.AAAA").is_err());
+    }
+
+    #[test]
+    fn test_aescbc256_cipher_is_rejected() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+
+        // A type 2 value relabeled as type 0, without the MAC
+        let Cipher::Value { iv, ct, .. } = Cipher::encrypt(b"Test", &keys).unwrap() else {
+            panic!("Encrypting produced an empty cipher");
+        };
+        let cipher_string = format!(
+            "0.{}|{}",
+            BASE64_STANDARD.encode(&iv),
+            BASE64_STANDARD.encode(&ct)
+        );
+
+        let cipher = Cipher::from_str(&cipher_string).unwrap();
+        assert_eq!(cipher.enc_type(), Some(EncType::AesCbc256B64));
+        assert_eq!(cipher.encode(), cipher_string);
+        assert!(matches!(
+            cipher.decrypt(&keys),
+            Err(CipherError::UnsupportedCipherEncryptionType(0))
+        ));
+        let mut buf = DecryptBuffer::default();
+        assert_eq!("", cipher.decrypt_to_str_buffered(&keys, &mut buf));
+
+        assert!(Cipher::from_str("0.AAAA").is_err());
+    }
+
+    #[test]
+    fn test_parse_and_decrypt_unsupported_enc_types() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+        let der_private_key: DerPrivateKey = testdata::USER_PRIVATE_KEY_CIPHER_STRING
+            .parse::<Cipher>()
+            .unwrap()
+            .decrypt(&keys)
+            .unwrap()
+            .into();
+        let private_key = PrivateKey::from_der(&der_private_key).unwrap();
+
+        let aes128 = Cipher::from_str("1.AAAA|BBBB|CCCC").unwrap();
+        assert_eq!(aes128.enc_type(), Some(EncType::AesCbc128HmacSha256B64));
+        assert_eq!(aes128.encode(), "1.AAAA|BBBB|CCCC");
+        assert!(matches!(
+            aes128.decrypt(&keys),
+            Err(CipherError::UnsupportedCipherEncryptionType(1))
+        ));
+        let mut buf = DecryptBuffer::default();
+        assert_eq!("", aes128.decrypt_to_str_buffered(&keys, &mut buf));
+
+        for (cipher_string, enc_type) in [
+            ("5.AAAA|BBBB", EncType::Rsa2048OaepSha256HmacSha256B64),
+            ("6.AAAA|BBBB", EncType::Rsa2048OaepSha1HmacSha256B64),
+        ] {
+            let cipher = Cipher::from_str(cipher_string).unwrap();
+            assert_eq!(cipher.enc_type(), Some(enc_type));
+            assert_eq!(cipher.encode(), cipher_string);
+            assert!(matches!(
+                cipher.decrypt_with_private_key(&private_key),
+                Err(CipherError::UnsupportedCipherEncryptionType(t)) if t == enc_type.as_u8()
+            ));
+            assert!(matches!(
+                cipher.decrypt(&keys),
+                Err(CipherError::InvalidKeyTypeForCipher)
+            ));
+        }
+
+        assert!(Cipher::from_str("5.AAAA").is_err());
+        assert!(Cipher::from_str("1.AAAA|BBBB").is_err());
+    }
+
     #[test]
     fn test_decrypt_cipher_with_user_symmetric_key() {
         let cipher = Cipher::from_str(testdata::TEST_CIPHER_STRING).unwrap();
@@ -554,6 +901,24 @@ mod tests {
         assert_eq!("Test", res);
     }
 
+    #[test]
+    fn test_decrypt_to_str_buffered() {
+        let cipher = Cipher::from_str(testdata::TEST_CIPHER_STRING).unwrap();
+
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+
+        let mut buf = DecryptBuffer::default();
+        assert_eq!("Test", cipher.decrypt_to_str_buffered(&keys, &mut buf));
+        // The buffer is reused for the next cipher
+        assert_eq!("Test", cipher.decrypt_to_str_buffered(&keys, &mut buf));
+        assert_eq!("", Cipher::Empty.decrypt_to_str_buffered(&keys, &mut buf));
+    }
+
     #[test]
     fn test_decrypt_cipher_with_private_key() {
         let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
@@ -570,12 +935,77 @@ mod tests {
             .unwrap()
             .into();
 
+        let private_key = PrivateKey::from_der(&der_private_key).unwrap();
+
         let test_cipher = Cipher::from_str(testdata::TEST_CIPHER_STRING_ASYMMETRIC).unwrap();
-        let res = test_cipher
-            .decrypt_with_private_key(&der_private_key)
-            .unwrap();
+        let res = test_cipher.decrypt_with_private_key(&private_key).unwrap();
         let res = String::from_utf8(res).unwrap();
 
         assert_eq!("Test", res);
     }
+
+    #[test]
+    fn test_encrypt_org_keys_roundtrip() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+
+        let der_private_key: DerPrivateKey = testdata::USER_PRIVATE_KEY_CIPHER_STRING
+            .parse::<Cipher>()
+            .unwrap()
+            .decrypt(&keys)
+            .unwrap()
+            .into();
+        let private_key = PrivateKey::from_der(&der_private_key).unwrap();
+        let public_key = PublicKey(private_key.0.to_public_key());
+
+        // Any key pair works as the "organization key" here
+        let org_key_cipher = encrypt_org_keys(&keys, &public_key).unwrap();
+        let org_keys = decrypt_org_keys(&private_key, &org_key_cipher).unwrap();
+
+        assert_eq!(keys.enc().data(), org_keys.enc().data());
+        assert_eq!(keys.mac().data(), org_keys.mac().data());
+    }
+
+    #[test]
+    fn test_fingerprint_phrase() {
+        // Word indices computed with the algorithm of the official clients
+        let expected = [1616, 2839, 4292, 4522, 3935]
+            .map(|i| eff_wordlist::large::LIST[i].1)
+            .join("-");
+        let user_id = "9c0ce5e0-8e7e-4a44-a1b3-2f5b4d3c6a11";
+        assert_eq!(fingerprint_phrase(b"test public key", user_id), expected);
+        assert_ne!(
+            fingerprint_phrase(b"test public key", "another user"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_generate_encrypted_keys_and_encode_bytes() {
+        let master_key = MasterKey::from_base64(testdata::USER_MASTER_KEY_PBKDF2_B64)
+            .expect("Master key decoding failed");
+        let enc_key = testdata::USER_SYMMETRIC_KEY_CIPHER_STRING
+            .parse()
+            .expect("Parsing symmetric key Cipher failed");
+        let keys = decrypt_symmetric_keys(&enc_key, &master_key).unwrap();
+
+        let (attachment_keys, encrypted_keys) = generate_encrypted_keys(&keys).unwrap();
+        let decrypted_keys = decrypt_item_keys(&keys, &encrypted_keys).unwrap();
+        assert_eq!(attachment_keys.enc().data(), decrypted_keys.enc().data());
+        assert_eq!(attachment_keys.mac().data(), decrypted_keys.mac().data());
+
+        let contents = Cipher::encrypt(b"file contents", &attachment_keys).unwrap();
+        let bytes = contents.encode_bytes();
+        let Cipher::Value { iv, ct, mac, .. } = &contents else {
+            panic!("Expected an encrypted value");
+        };
+        assert_eq!(bytes[0], EncType::AesCbc256HmacSha256B64.as_u8());
+        assert_eq!(&bytes[1..17], iv.as_slice());
+        assert_eq!(&bytes[17..49], mac.as_slice());
+        assert_eq!(&bytes[49..], ct.as_slice());
+    }
 }
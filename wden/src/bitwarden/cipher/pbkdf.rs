@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, Once};
 
 use aes::cipher::generic_array::GenericArray;
 use serde::{Deserialize, Serialize};
@@ -85,6 +85,28 @@ impl Argon2id {
         sha.update(salt);
         sha.finalize()
     }
+
+    /// The argon2 crate (0.5) computes the lanes one after another on the
+    /// calling thread, so the parallelism parameter only affects the result,
+    /// not the speed. Running the lanes on threads needs the `parallel`
+    /// feature of argon2 0.6, which wden does not use yet. Warns once if the
+    /// server parameters assume more cores than the host has, as the other
+    /// clients will be slow there too.
+    fn warn_about_parallelism(&self) {
+        static WARNED: Once = Once::new();
+
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        if self.parallelism as usize > cores {
+            WARNED.call_once(|| {
+                log::warn!(
+                    "Argon2 parallelism is {}, but only {cores} cores are available",
+                    self.parallelism
+                )
+            });
+        }
+    }
 }
 
 impl Pbkdf for Argon2id {
@@ -94,6 +116,7 @@ impl Pbkdf for Argon2id {
         user_password: &str,
     ) -> Result<MasterKey, CipherError> {
         let salt = Self::hashed_salt(user_email.to_lowercase().as_bytes());
+        self.warn_about_parallelism();
 
         let params = argon2::ParamsBuilder::new()
             .m_cost(self.memory_kib)
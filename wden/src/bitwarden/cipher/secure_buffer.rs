@@ -0,0 +1,183 @@
+//! Pool of memory that is locked into RAM (mlock, VirtualLock on Windows),
+//! so that it is never written to swap. On Linux it is also left out of
+//! core dumps. Used for the session keys that wrap the long-lived keys:
+//! they stay in memory for the whole session, apart from the wrapped data.
+//! Slots are zeroized when released.
+
+use std::{
+    alloc::{self, Layout},
+    io,
+    ptr::NonNull,
+    sync::{Mutex, PoisonError},
+};
+
+use zeroize::Zeroize;
+
+pub const SLOT_LEN: usize = 256 / 8;
+// One bit per slot in `Pool::used`
+const SLOT_COUNT: usize = u128::BITS as usize;
+const POOL_LEN: usize = SLOT_LEN * SLOT_COUNT;
+
+enum PoolState {
+    Uninitialized,
+    Ready(Pool),
+    /// Locking memory failed, e.g. because of RLIMIT_MEMLOCK
+    Unavailable,
+}
+
+struct Pool {
+    base: NonNull<[u8; SLOT_LEN]>,
+    used: u128,
+}
+
+// The pool memory is only accessed through the buffers handed out,
+// each of which owns its slot
+unsafe impl Send for Pool {}
+
+static POOL: Mutex<PoolState> = Mutex::new(PoolState::Uninitialized);
+
+impl Pool {
+    fn create() -> PoolState {
+        // Page-aligned, so that no other data shares the locked pages
+        let layout = Layout::from_size_align(POOL_LEN, POOL_LEN).unwrap();
+        // SAFETY: the layout has a non-zero size
+        let Some(base) = NonNull::new(unsafe { alloc::alloc_zeroed(layout) }) else {
+            return PoolState::Unavailable;
+        };
+        if let Err(e) = lock_memory(base.as_ptr(), POOL_LEN) {
+            log::warn!("Locking memory for the session keys failed: {e}");
+            // SAFETY: allocated above with the same layout
+            unsafe { alloc::dealloc(base.as_ptr(), layout) };
+            return PoolState::Unavailable;
+        }
+        // The pool is never freed: it's used until the process exits
+        PoolState::Ready(Pool {
+            base: base.cast(),
+            used: 0,
+        })
+    }
+
+    fn take_slot(&mut self) -> Option<usize> {
+        let slot = self.used.trailing_ones() as usize;
+        if slot == SLOT_COUNT {
+            return None;
+        }
+        self.used |= 1 << slot;
+        Some(slot)
+    }
+}
+
+#[cfg(unix)]
+fn lock_memory(ptr: *mut u8, len: usize) -> io::Result<()> {
+    // SAFETY: the range is a live allocation owned by the pool
+    if unsafe { libc::mlock(ptr.cast(), len) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[cfg(target_os = "linux")]
+    // SAFETY: as above. Failing only means that the pool may end up in core dumps.
+    unsafe {
+        libc::madvise(ptr.cast(), len, libc::MADV_DONTDUMP);
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn lock_memory(ptr: *mut u8, len: usize) -> io::Result<()> {
+    use windows_sys::Win32::System::Memory::VirtualLock;
+    // SAFETY: the range is a live allocation owned by the pool
+    if unsafe { VirtualLock(ptr.cast(), len) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_memory(_ptr: *mut u8, _len: usize) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Key-sized buffer from the locked pool, zeroized when dropped. If the
+/// pool is full or memory can't be locked, the buffer is allocated
+/// normally instead.
+pub struct SecureBuffer {
+    ptr: NonNull<[u8; SLOT_LEN]>,
+    /// `None` for the normal allocation
+    slot: Option<usize>,
+}
+
+// The buffer owns the memory it points to, like a Box
+unsafe impl Send for SecureBuffer {}
+unsafe impl Sync for SecureBuffer {}
+
+impl SecureBuffer {
+    pub fn new() -> Self {
+        let mut pool = POOL.lock().unwrap_or_else(PoisonError::into_inner);
+        if let PoolState::Uninitialized = *pool {
+            *pool = Pool::create();
+        }
+        if let PoolState::Ready(p) = &mut *pool {
+            if let Some(slot) = p.take_slot() {
+                // SAFETY: the slot is within the pool allocation. Released
+                // slots have been zeroized.
+                let ptr = unsafe { p.base.add(slot) };
+                return Self {
+                    ptr,
+                    slot: Some(slot),
+                };
+            }
+            log::warn!("The locked memory pool is full");
+        }
+
+        Self {
+            ptr: NonNull::from(Box::leak(Box::new([0u8; SLOT_LEN]))),
+            slot: None,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: the buffer owns the slot or the allocation
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: the buffer owns the slot or the allocation
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        self.as_mut_slice().zeroize();
+        match self.slot {
+            Some(slot) => {
+                let mut pool = POOL.lock().unwrap_or_else(PoisonError::into_inner);
+                if let PoolState::Ready(p) = &mut *pool {
+                    p.used &= !(1 << slot);
+                }
+            }
+            // SAFETY: leaked from a Box in new()
+            None => drop(unsafe { Box::from_raw(self.ptr.as_ptr()) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secure_buffer() {
+        let mut buf = SecureBuffer::new();
+        assert_eq!(buf.as_slice(), [0u8; SLOT_LEN]);
+        buf.as_mut_slice().fill(0xab);
+        assert_eq!(buf.as_slice(), [0xab; SLOT_LEN]);
+
+        drop(buf);
+
+        // Released slots are zeroed, and buffers are still handed out
+        // when there are more of them than slots
+        let bufs: Vec<_> = (0..SLOT_COUNT + 1).map(|_| SecureBuffer::new()).collect();
+        assert!(bufs.iter().all(|b| b.as_slice() == [0u8; SLOT_LEN]));
+        assert!(bufs.iter().any(|b| b.slot.is_none()));
+    }
+}
@@ -2,4 +2,8 @@ pub mod api;
 pub mod apikey;
 pub mod cipher;
 pub mod keys;
+pub mod notifications;
+pub mod pinning;
+pub mod send;
 pub mod server;
+pub mod totp;
@@ -0,0 +1,296 @@
+//! Client for the notifications hub of the server. The hub is an ASP.NET
+//! SignalR hub, used over a WebSocket with the MessagePack protocol. The
+//! server pushes a message when the vault is changed on another device.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{bail, Context};
+use futures_util::{SinkExt, StreamExt};
+use reqwest::Url;
+use rmpv::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, Connector, MaybeTlsStream, WebSocketStream};
+
+use super::{pinning, server::ServerConfiguration};
+
+/// Ends the JSON handshake messages
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// The server drops clients that have been quiet for 30 seconds
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+const MESSAGE_TYPE_INVOCATION: u64 = 1;
+const MESSAGE_TYPE_PING: u8 = 6;
+const MESSAGE_TYPE_CLOSE: u64 = 7;
+
+/// A ping message: the length prefix and a one-element array
+const PING: [u8; 3] = [0x02, 0x91, MESSAGE_TYPE_PING];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Notification {
+    /// Items, folders or the whole vault were changed
+    VaultChanged,
+    /// The sessions of the user were ended, e.g. after a password change
+    LoggedOut,
+}
+
+impl Notification {
+    fn from_type(notification_type: u64) -> Option<Self> {
+        match notification_type {
+            // Item, folder, vault and organization key updates
+            0..=9 => Some(Notification::VaultChanged),
+            11 => Some(Notification::LoggedOut),
+            // Settings, Sends and login requests
+            _ => None,
+        }
+    }
+}
+
+enum HubMessage {
+    Notification(Notification),
+    Close,
+    Other,
+}
+
+pub struct NotificationsClient {
+    hub_url: Url,
+    device_identifier: String,
+    accept_invalid_certs: bool,
+    pinned_spki_hashes: Vec<String>,
+}
+
+impl NotificationsClient {
+    /// Returns `None` if the address of the hub is not known for the server
+    pub fn new(
+        server_config: &ServerConfiguration,
+        device_identifier: impl Into<String>,
+        accept_invalid_certs: bool,
+        pinned_spki_hashes: &[String],
+    ) -> Option<Self> {
+        let mut hub_url = server_config.notifications_base_url()?.join("hub").ok()?;
+        let scheme = if hub_url.scheme() == "http" {
+            "ws"
+        } else {
+            "wss"
+        };
+        hub_url.set_scheme(scheme).ok()?;
+
+        Some(NotificationsClient {
+            hub_url,
+            device_identifier: device_identifier.into(),
+            accept_invalid_certs,
+            pinned_spki_hashes: pinned_spki_hashes.to_vec(),
+        })
+    }
+
+    /// Connects to the hub and calls `on_notification` for each notification
+    /// until the connection is closed. Changes made on this device are skipped.
+    pub async fn listen(
+        &self,
+        access_token: &str,
+        mut on_notification: impl FnMut(Notification),
+    ) -> anyhow::Result<()> {
+        let mut ws = self.connect(access_token).await?;
+        ws.send(Message::Text(format!(
+            "{{\"protocol\":\"messagepack\",\"version\":1}}{RECORD_SEPARATOR}"
+        )))
+        .await?;
+
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        let mut handshake_done = false;
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    if handshake_done {
+                        ws.send(Message::Binary(PING.to_vec())).await?;
+                    }
+                }
+                message = ws.next() => {
+                    let Some(message) = message else {
+                        return Ok(());
+                    };
+                    match message? {
+                        Message::Text(text) => {
+                            // The handshake response, an empty object unless it failed
+                            let response: serde_json::Value =
+                                serde_json::from_str(text.trim_end_matches(RECORD_SEPARATOR))?;
+                            if let Some(err) = response.get("error") {
+                                bail!("The notifications hub refused the connection: {err}");
+                            }
+                            handshake_done = true;
+                        }
+                        Message::Binary(data) => {
+                            for message in split_messages(&data)? {
+                                match parse_message(message, &self.device_identifier)? {
+                                    HubMessage::Notification(n) => on_notification(n),
+                                    HubMessage::Close => return Ok(()),
+                                    HubMessage::Other => {}
+                                }
+                            }
+                        }
+                        Message::Close(_) => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn connect(
+        &self,
+        access_token: &str,
+    ) -> anyhow::Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let host = self
+            .hub_url
+            .host_str()
+            .context("The notifications hub URL has no host")?;
+        let port = self.hub_url.port_or_known_default().unwrap_or(443);
+        let tcp = TcpStream::connect((host, port))
+            .await
+            .context("Connecting to the notifications hub failed")?;
+
+        let connector = if self.hub_url.scheme() == "wss" {
+            // Same verification and pin checks as the API client. The pins
+            // are checked against the whole chain during the TLS handshake.
+            let tls =
+                pinning::pinning_tls_config(&self.pinned_spki_hashes, self.accept_invalid_certs)?;
+            Connector::Rustls(Arc::new(tls))
+        } else {
+            let origin = self.hub_url.origin().ascii_serialization();
+            pinning::verify_pinned_cert(None, &origin, &self.pinned_spki_hashes)?;
+            Connector::Plain
+        };
+
+        // The token is only sent after the TLS handshake, so after the
+        // server key has been checked
+        let mut url = self.hub_url.clone();
+        url.query_pairs_mut()
+            .append_pair("access_token", access_token);
+        let (ws, _) = tokio_tungstenite::client_async_tls_with_config(
+            url.as_str(),
+            tcp,
+            None,
+            Some(connector),
+        )
+        .await
+        .context("Opening the notifications WebSocket failed")?;
+        Ok(ws)
+    }
+}
+
+/// Splits a binary WebSocket message into hub messages. Each one starts
+/// with its length as a varint.
+fn split_messages(mut data: &[u8]) -> anyhow::Result<Vec<&[u8]>> {
+    let mut messages = vec![];
+    while !data.is_empty() {
+        let mut len = 0usize;
+        let mut shift = 0;
+        loop {
+            let (&b, rest) = data.split_first().context("Truncated hub message")?;
+            data = rest;
+            len |= usize::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 28 {
+                bail!("Invalid hub message length");
+            }
+        }
+        if data.len() < len {
+            bail!("Truncated hub message");
+        }
+        let (message, rest) = data.split_at(len);
+        messages.push(message);
+        data = rest;
+    }
+    Ok(messages)
+}
+
+fn parse_message(mut message: &[u8], device_identifier: &str) -> anyhow::Result<HubMessage> {
+    let value = rmpv::decode::read_value(&mut message).context("Invalid hub message")?;
+    let fields = value.as_array().context("Invalid hub message")?;
+    match fields.first().and_then(Value::as_u64) {
+        Some(MESSAGE_TYPE_INVOCATION) => {}
+        Some(MESSAGE_TYPE_CLOSE) => return Ok(HubMessage::Close),
+        _ => return Ok(HubMessage::Other),
+    }
+
+    // Invocations are [type, headers, invocation id, target, arguments]
+    if fields.get(3).and_then(Value::as_str) != Some("ReceiveMessage") {
+        return Ok(HubMessage::Other);
+    }
+    let Some(payload) = fields
+        .get(4)
+        .and_then(Value::as_array)
+        .and_then(|args| args.first())
+        .and_then(Value::as_map)
+    else {
+        return Ok(HubMessage::Other);
+    };
+    let field = |name: &str| {
+        payload
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(name))
+            .map(|(_, v)| v)
+    };
+
+    if field("ContextId").and_then(Value::as_str) == Some(device_identifier) {
+        return Ok(HubMessage::Other);
+    }
+    Ok(field("Type")
+        .and_then(Value::as_u64)
+        .and_then(Notification::from_type)
+        .map_or(HubMessage::Other, HubMessage::Notification))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(context_id: &str, notification_type: u64) -> Vec<u8> {
+        let payload = Value::Map(vec![
+            ("ContextId".into(), context_id.into()),
+            ("Type".into(), notification_type.into()),
+            ("Payload".into(), Value::Nil),
+        ]);
+        let message = Value::Array(vec![
+            MESSAGE_TYPE_INVOCATION.into(),
+            Value::Map(vec![]),
+            Value::Nil,
+            "ReceiveMessage".into(),
+            Value::Array(vec![payload]),
+        ]);
+        let mut encoded = vec![];
+        rmpv::encode::write_value(&mut encoded, &message).unwrap();
+        let mut framed = vec![encoded.len() as u8];
+        framed.extend(encoded);
+        framed
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let mut data = invocation("other-device", 1);
+        data.extend(invocation("this-device", 0));
+        data.extend(PING);
+        data.extend(invocation("other-device", 11));
+
+        let parsed: Vec<_> = split_messages(&data)
+            .unwrap()
+            .into_iter()
+            .map(|m| match parse_message(m, "this-device").unwrap() {
+                HubMessage::Notification(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                Some(Notification::VaultChanged),
+                None,
+                None,
+                Some(Notification::LoggedOut)
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use base64::prelude::*;
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use x509_cert::{
+    der::{Decode, Encode},
+    Certificate,
+};
+
+use super::cipher::constant_time;
+
+/// Computes the pin of a DER-encoded X.509 certificate: the base64-encoded
+/// SHA-256 hash of its SubjectPublicKeyInfo (the same format as HPKP `pin-sha256`).
+pub fn spki_sha256_pin(cert_der: &[u8]) -> anyhow::Result<String> {
+    let cert = Certificate::from_der(cert_der).context("Parsing server certificate failed")?;
+    let spki_der = cert
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .context("Encoding server certificate public key failed")?;
+
+    Ok(BASE64_STANDARD.encode(Sha256::digest(&spki_der)))
+}
+
+/// Checks that a pin value has the expected format (base64-encoded SHA-256 hash)
+pub fn is_valid_pin(pin: &str) -> bool {
+    BASE64_STANDARD
+        .decode(pin)
+        .map(|h| h.len() == 256 / 8)
+        .unwrap_or(false)
+}
+
+/// Builds a TLS configuration that checks the certificate chain of every
+/// server against the pinned keys during the handshake, in addition to the
+/// normal certificate verification against the system root certificates.
+pub fn pinning_tls_config(
+    pins: &[String],
+    accept_invalid_certs: bool,
+) -> anyhow::Result<ClientConfig> {
+    let provider = Arc::new(crypto::ring::default_provider());
+
+    let webpki = if accept_invalid_certs {
+        None
+    } else {
+        let native = rustls_native_certs::load_native_certs();
+        for e in &native.errors {
+            log::warn!("Loading system root certificates failed: {e}");
+        }
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(native.certs);
+        let verifier = WebPkiServerVerifier::builder_with_provider(roots.into(), provider.clone())
+            .build()
+            .context("Setting up certificate verification failed")?;
+        Some(verifier)
+    };
+
+    let verifier = PinningVerifier {
+        webpki,
+        pins: pins.to_vec(),
+        provider: provider.clone(),
+    };
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+#[derive(Debug)]
+struct PinningVerifier {
+    /// `None` if invalid certificates are accepted. The pins are still checked.
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<String>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+        let chain: Vec<&[u8]> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|c| c.as_ref())
+            .collect();
+        verify_pinned_cert(Some(&chain), &server_name.to_str(), &self.pins)
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Verifies the DER-encoded certificate chain (leaf first) presented by the
+/// server `origin` against the pinned keys. Succeeds if any certificate in the
+/// chain matches a pin. `None` means that the connection is not using TLS.
+///
+/// Always succeeds if there are no pins.
+pub fn verify_pinned_cert(
+    peer_chain: Option<&[&[u8]]>,
+    origin: &str,
+    pins: &[String],
+) -> anyhow::Result<()> {
+    if pins.is_empty() {
+        return Ok(());
+    }
+
+    let peer_chain = match peer_chain {
+        Some(c) if !c.is_empty() => c,
+        _ => bail!(
+            "The connection to {origin} is not using TLS, but the profile has pinned server keys. \
+             Refusing to continue."
+        ),
+    };
+
+    let presented_pins = peer_chain
+        .iter()
+        .map(|c| spki_sha256_pin(c))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if presented_pins.iter().any(|presented| {
+        pins.iter()
+            .any(|p| constant_time::eq(p.as_bytes(), presented.as_bytes()))
+    }) {
+        Ok(())
+    } else {
+        let presented_pin = &presented_pins[0];
+        log::warn!("Server key pin mismatch, presented: {presented_pin}");
+        bail!(
+            "The server {origin} presented a certificate key that does not match the keys pinned \
+             in this profile. The server key may have been changed, or the connection may \
+             be intercepted. Presented key: {presented_pin}"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Self-signed P-256 certificate for CN=wden.test
+    const TEST_CERT_DER_B64: &str = "MIIBfDCCASOgAwIBAgIUX1QWaq7LwwYO4U3xdZgEHyXLBTIwCgYIKoZIzj0EAwIw\
+        FDESMBAGA1UEAwwJd2Rlbi50ZXN0MB4XDTI2MTAxNjAwMTAxOVoXDTM2MTAxMzAwMTAxOVowFDESMBAGA1UEAwwJd2Rl\
+        bi50ZXN0MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAENV07leh1OSFq82V7GHjQ63VuInBzJTfsD4JgXtjLa5eymyiP\
+        bj45gM1s6KMxZch/M/F2PgNMPIlp7vv3hIK5iaNTMFEwHQYDVR0OBBYEFMa7n0e7LE0/v099hnYJ1kV4pY4DMB8GA1Ud\
+        IwQYMBaAFMa7n0e7LE0/v099hnYJ1kV4pY4DMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgZpLUByLf\
+        p/xK6aa+nv95rSzSFF3tP6yQcSc4mF4gickCIBlfF1vx0Lat5EU9dnGq+grnHQad6DyDdkjGJdc8jIp+";
+
+    // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64
+    const TEST_CERT_PIN: &str = "4tyCH+7EvvZXLpg6zUhDoxRlJ2RETsnq5Jc+HIRLAos=";
+
+    // Self-signed P-256 certificate for CN=leaf.wden.test
+    const TEST_LEAF_CERT_DER_B64: &str = "MIIBiDCCAS2gAwIBAgIUR2mHSq/CHyFiE+I0G7WsBMzbCnUwCgYIKoZIzj0EAwIwGTEXMBUGA1UEAwwObGVhZi53ZGVuLn\
+        Rlc3QwHhcNMjYxMDE2MDUwMjU5WhcNMzYxMDEzMDUwMjU5WjAZMRcwFQYDVQQDDA5sZWFmLndkZW4udGVzdDBZMBMGByqG\
+        SM49AgEGCCqGSM49AwEHA0IABD9Hko9I0sPz6CQiH8+zauSHZo2ic8slv5PIzV1nL9JYEOyRltNK/oHD6KAds0TH1RawAV\
+        R1Ki5GE+uQIx6FkeGjUzBRMB0GA1UdDgQWBBSHG3Ulo9nxN2HKjeTSkXD1zC5VIjAfBgNVHSMEGDAWgBSHG3Ulo9nxN2HK\
+        jeTSkXD1zC5VIjAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQCjymG0WCIj9NH43jHf1E8x29A0Pk0vm+\
+        jRppHbR5f4HAIhAK/bMP2Q9dD9P6nmOu8ievXhkAWJhBpBvA1QW3mKJKOX";
+    const TEST_LEAF_CERT_PIN: &str = "LlbaAVuBSBA3qkmM50EnRqnE1JziUk9+beIvMjEvQvc=";
+
+    #[test]
+    fn test_spki_sha256_pin() {
+        let der = BASE64_STANDARD.decode(TEST_CERT_DER_B64).unwrap();
+        let pin = spki_sha256_pin(&der).unwrap();
+        assert_eq!(pin, TEST_CERT_PIN);
+    }
+
+    #[test]
+    fn test_pinning_verifier() {
+        let der = BASE64_STANDARD.decode(TEST_CERT_DER_B64).unwrap();
+        let cert = CertificateDer::from(der);
+        let server_name = ServerName::try_from("wden.test").unwrap();
+        let verifier = |pin: &str| PinningVerifier {
+            webpki: None,
+            pins: vec![pin.to_string()],
+            provider: Arc::new(crypto::ring::default_provider()),
+        };
+
+        assert!(verifier(TEST_CERT_PIN)
+            .verify_server_cert(&cert, &[], &server_name, &[], UnixTime::now())
+            .is_ok());
+        assert!(verifier("dGVzdHRlc3R0ZXN0dGVzdHRlc3R0ZXN0dGVzdHRlc3Q=")
+            .verify_server_cert(&cert, &[], &server_name, &[], UnixTime::now())
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_pinned_cert_chain() {
+        let leaf = BASE64_STANDARD.decode(TEST_LEAF_CERT_DER_B64).unwrap();
+        let intermediate = BASE64_STANDARD.decode(TEST_CERT_DER_B64).unwrap();
+        let server_name = ServerName::try_from("leaf.wden.test").unwrap();
+        let other_pin = "dGVzdHRlc3R0ZXN0dGVzdHRlc3R0ZXN0dGVzdHRlc3Q=".to_string();
+
+        assert_eq!(spki_sha256_pin(&leaf).unwrap(), TEST_LEAF_CERT_PIN);
+
+        // The pin matches only the intermediate certificate
+        let verifier = PinningVerifier {
+            webpki: None,
+            pins: vec![other_pin.clone(), TEST_CERT_PIN.to_string()],
+            provider: Arc::new(crypto::ring::default_provider()),
+        };
+        assert!(verifier
+            .verify_server_cert(
+                &CertificateDer::from(leaf.clone()),
+                &[CertificateDer::from(intermediate.clone())],
+                &server_name,
+                &[],
+                UnixTime::now()
+            )
+            .is_ok());
+
+        let chain: &[&[u8]] = &[&leaf, &intermediate];
+        let pins = |p: &str| vec![p.to_string()];
+        assert!(
+            verify_pinned_cert(Some(chain), "leaf.wden.test", &pins(TEST_LEAF_CERT_PIN)).is_ok()
+        );
+        assert!(verify_pinned_cert(Some(chain), "leaf.wden.test", &pins(TEST_CERT_PIN)).is_ok());
+        assert!(verify_pinned_cert(Some(chain), "leaf.wden.test", &pins(&other_pin)).is_err());
+        assert!(
+            verify_pinned_cert(Some(&chain[..1]), "leaf.wden.test", &pins(TEST_CERT_PIN)).is_err()
+        );
+        assert!(verify_pinned_cert(Some(&[]), "leaf.wden.test", &pins(TEST_CERT_PIN)).is_err());
+        assert!(verify_pinned_cert(None, "leaf.wden.test", &pins(TEST_CERT_PIN)).is_err());
+        assert!(verify_pinned_cert(None, "leaf.wden.test", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_is_valid_pin() {
+        assert!(is_valid_pin(TEST_CERT_PIN));
+        assert!(!is_valid_pin("not base64"));
+        assert!(!is_valid_pin("dGVzdA=="));
+    }
+}
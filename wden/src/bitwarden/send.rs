@@ -0,0 +1,202 @@
+use std::time::{Duration, SystemTime};
+
+use base64::prelude::*;
+use hkdf::Hkdf;
+use jiff::Timestamp;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::{
+    cipher::{extract_enc_mac_keys, Cipher, CipherError, EncMacKeys},
+    server::{BitwardenCloudRegion, ServerConfiguration},
+};
+
+const SEND_TYPE_TEXT: u8 = 0;
+const SEND_KEY_MATERIAL_LEN: usize = 16;
+
+/// Random key material of a Send. It is included in the access URL, and
+/// stored on the server encrypted with the user key.
+pub type SendKeyMaterial = Zeroizing<[u8; SEND_KEY_MATERIAL_LEN]>;
+
+/// A Send, as listed by the server. Only text Sends have contents that
+/// wden can show.
+#[derive(Deserialize, Debug)]
+pub struct SendItem {
+    #[serde(alias = "Id")]
+    pub id: String,
+    #[serde(alias = "accessId")]
+    #[serde(alias = "AccessId")]
+    pub access_id: String,
+    #[serde(rename = "type", alias = "Type")]
+    pub send_type: u8,
+    #[serde(default)]
+    #[serde(alias = "Name")]
+    pub name: Cipher,
+    #[serde(default)]
+    #[serde(alias = "Key")]
+    pub key: Cipher,
+    #[serde(default)]
+    #[serde(alias = "deletionDate")]
+    #[serde(alias = "DeletionDate")]
+    pub deletion_date: String,
+    #[serde(default)]
+    #[serde(alias = "accessCount")]
+    #[serde(alias = "AccessCount")]
+    pub access_count: u32,
+}
+
+impl SendItem {
+    pub fn is_text(&self) -> bool {
+        self.send_type == SEND_TYPE_TEXT
+    }
+
+    /// Decrypts the key material with the user keys, and derives the keys
+    /// of the Send from it
+    pub fn decrypt_keys(
+        &self,
+        user_keys: &EncMacKeys,
+    ) -> Result<(SendKeyMaterial, EncMacKeys), CipherError> {
+        let decrypted = Zeroizing::new(self.key.decrypt(user_keys)?);
+        let mut key_material = SendKeyMaterial::default();
+        if decrypted.len() != key_material.len() {
+            return Err(CipherError::InvalidKeyLength);
+        }
+        key_material.copy_from_slice(&decrypted);
+        let keys = derive_send_keys(key_material.as_slice())?;
+        Ok((key_material, keys))
+    }
+}
+
+/// A new text Send, encrypted and ready to be created on the server
+pub struct NewTextSend {
+    pub name: Cipher,
+    pub key: Cipher,
+    pub text: Cipher,
+    pub hidden: bool,
+    pub deletion_date: String,
+}
+
+impl NewTextSend {
+    /// Encrypts a text Send that is deleted after `lifetime`. Returns the
+    /// Send and its key material for the access URL.
+    pub fn encrypt(
+        name: &str,
+        text: &str,
+        hidden: bool,
+        lifetime: Duration,
+        user_keys: &EncMacKeys,
+    ) -> Result<(Self, SendKeyMaterial), CipherError> {
+        let mut key_material = SendKeyMaterial::default();
+        rand::thread_rng().fill_bytes(key_material.as_mut_slice());
+        let send_keys = derive_send_keys(key_material.as_slice())?;
+
+        let send = NewTextSend {
+            name: Cipher::encrypt(name.as_bytes(), &send_keys)?,
+            key: Cipher::encrypt(key_material.as_slice(), user_keys)?,
+            text: Cipher::encrypt(text.as_bytes(), &send_keys)?,
+            hidden,
+            deletion_date: format_timestamp(SystemTime::now() + lifetime),
+        };
+        Ok((send, key_material))
+    }
+
+    pub fn request_body(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": SEND_TYPE_TEXT,
+            "name": self.name.encode(),
+            "notes": null,
+            "key": self.key.encode(),
+            "maxAccessCount": null,
+            "expirationDate": null,
+            "deletionDate": self.deletion_date,
+            "text": {
+                "text": self.text.encode(),
+                "hidden": self.hidden,
+            },
+            "file": null,
+            "password": null,
+            "disabled": false,
+            "hideEmail": false,
+        })
+    }
+}
+
+/// The keys of a Send are derived from its key material with HKDF
+fn derive_send_keys(key_material: &[u8]) -> Result<EncMacKeys, CipherError> {
+    let hk = Hkdf::<Sha256>::new(Some(b"bitwarden-send"), key_material);
+    let mut full_key = Zeroizing::new([0u8; 64]);
+    hk.expand(b"send", full_key.as_mut_slice())
+        .map_err(|_| CipherError::InvalidKeyLength)?;
+    extract_enc_mac_keys(full_key.as_slice())
+}
+
+/// The link for opening the Send. The key material is in the fragment,
+/// so it is never sent to the server.
+pub fn access_url(
+    server: &ServerConfiguration,
+    access_id: &str,
+    key_material: &SendKeyMaterial,
+) -> Option<String> {
+    let key = BASE64_URL_SAFE_NO_PAD.encode(key_material.as_slice());
+    match server {
+        ServerConfiguration::BitwardenCloud(BitwardenCloudRegion::US) => {
+            Some(format!("https://send.bitwarden.com/#{access_id}/{key}"))
+        }
+        _ => server
+            .web_vault_url()
+            .map(|url| format!("{url}#/send/{access_id}/{key}")),
+    }
+}
+
+/// Formats the time as an ISO 8601 timestamp in UTC
+fn format_timestamp(time: SystemTime) -> String {
+    Timestamp::try_from(time)
+        .unwrap_or(Timestamp::UNIX_EPOCH)
+        .strftime("%Y-%m-%dT%H:%M:%S.000Z")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            "2023-11-14T22:13:20.000Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn test_access_url() {
+        let key_material = SendKeyMaterial::new([0xff; SEND_KEY_MATERIAL_LEN]);
+        assert_eq!(
+            access_url(
+                &ServerConfiguration::cloud(BitwardenCloudRegion::US),
+                "abc",
+                &key_material
+            )
+            .unwrap(),
+            "https://send.bitwarden.com/#abc/_____________________w"
+        );
+        assert_eq!(
+            access_url(
+                &ServerConfiguration::single_host("https://bw.example.com".parse().unwrap()),
+                "abc",
+                &key_material
+            )
+            .unwrap(),
+            "https://bw.example.com/#/send/abc/_____________________w"
+        );
+    }
+}
@@ -6,9 +6,13 @@ use serde::{Deserialize, Serialize};
 
 const BITWARDEN_CLOUD_US_API: &str = "https://api.bitwarden.com";
 const BITWARDEN_CLOUD_US_IDENTITY: &str = "https://identity.bitwarden.com";
+const BITWARDEN_CLOUD_US_WEB_VAULT: &str = "https://vault.bitwarden.com";
+const BITWARDEN_CLOUD_US_NOTIFICATIONS: &str = "https://notifications.bitwarden.com";
 
 const BITWARDEN_CLOUD_EU_API: &str = "https://api.bitwarden.eu";
 const BITWARDEN_CLOUD_EU_IDENTITY: &str = "https://identity.bitwarden.eu";
+const BITWARDEN_CLOUD_EU_WEB_VAULT: &str = "https://vault.bitwarden.eu";
+const BITWARDEN_CLOUD_EU_NOTIFICATIONS: &str = "https://notifications.bitwarden.eu";
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct BaseUrl(Url);
@@ -134,4 +138,112 @@ impl ServerConfiguration {
             } => identity_url.0.clone(),
         }
     }
+
+    /// Address of the web vault. With separate API and identity URLs, the
+    /// web vault is assumed to be next to an API URL ending in `/api/`,
+    /// otherwise it's not known.
+    pub fn web_vault_url(&self) -> Option<Url> {
+        match self {
+            Self::BitwardenCloud(BitwardenCloudRegion::US) => {
+                Some(Url::parse(BITWARDEN_CLOUD_US_WEB_VAULT).unwrap())
+            }
+            Self::BitwardenCloud(BitwardenCloudRegion::EU) => {
+                Some(Url::parse(BITWARDEN_CLOUD_EU_WEB_VAULT).unwrap())
+            }
+            Self::SingleHost { url } => Some(url.0.join("/").unwrap()),
+            Self::ApiAndIdentityHost {
+                api_url,
+                identity_url: _,
+            } => api_url
+                .0
+                .path()
+                .ends_with("/api/")
+                .then(|| api_url.0.join("../").unwrap()),
+        }
+    }
+
+    /// Address of the notifications hub. Like with the web vault, with
+    /// separate API and identity URLs it's only known if the API URL ends
+    /// in `/api/`.
+    pub fn notifications_base_url(&self) -> Option<Url> {
+        match self {
+            Self::BitwardenCloud(BitwardenCloudRegion::US) => {
+                Some(Url::parse(BITWARDEN_CLOUD_US_NOTIFICATIONS).unwrap())
+            }
+            Self::BitwardenCloud(BitwardenCloudRegion::EU) => {
+                Some(Url::parse(BITWARDEN_CLOUD_EU_NOTIFICATIONS).unwrap())
+            }
+            Self::SingleHost { url } => Some(url.0.join("/notifications/").unwrap()),
+            Self::ApiAndIdentityHost {
+                api_url,
+                identity_url: _,
+            } => api_url
+                .0
+                .path()
+                .ends_with("/api/")
+                .then(|| api_url.0.join("../notifications/").unwrap()),
+        }
+    }
+
+    /// True if the server is accessed over plain HTTP, which sends the
+    /// master password hash and the tokens unencrypted
+    pub fn uses_insecure_http(&self) -> bool {
+        [self.api_base_url(), self.identity_base_url()]
+            .iter()
+            .any(|url| url.scheme() == "http")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_vault_url() {
+        let url = |s: &str| Url::parse(s).unwrap();
+
+        let config = ServerConfiguration::cloud(BitwardenCloudRegion::EU);
+        assert_eq!(
+            config.web_vault_url(),
+            Some(url("https://vault.bitwarden.eu/"))
+        );
+
+        let config = ServerConfiguration::single_host(url("https://bw.example.com"));
+        assert_eq!(config.web_vault_url(), Some(url("https://bw.example.com/")));
+
+        let config = ServerConfiguration::separate_hosts(
+            url("https://example.com/vault/api"),
+            url("https://example.com/vault/identity"),
+        );
+        assert_eq!(
+            config.web_vault_url(),
+            Some(url("https://example.com/vault/"))
+        );
+
+        let config = ServerConfiguration::separate_hosts(
+            url("https://api.example.com"),
+            url("https://identity.example.com"),
+        );
+        assert_eq!(config.web_vault_url(), None);
+    }
+
+    #[test]
+    fn test_notifications_base_url() {
+        let url = |s: &str| Url::parse(s).unwrap();
+
+        let config = ServerConfiguration::single_host(url("https://bw.example.com"));
+        assert_eq!(
+            config.notifications_base_url(),
+            Some(url("https://bw.example.com/notifications/"))
+        );
+
+        let config = ServerConfiguration::separate_hosts(
+            url("https://example.com/vault/api"),
+            url("https://example.com/vault/identity"),
+        );
+        assert_eq!(
+            config.notifications_base_url(),
+            Some(url("https://example.com/vault/notifications/"))
+        );
+    }
 }
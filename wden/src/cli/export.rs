@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use zeroize::Zeroizing;
+
+use crate::export::ExportFormat;
+
+use super::vault::{load_vault, UnlockArgs};
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+
+    /// File to write the export to. The file must not exist.
+    #[arg(long, value_name = "PATH")]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub unlock: UnlockArgs,
+}
+
+/// Runs `wden export`
+pub async fn export_vault(
+    profile: &str,
+    args: ExportArgs,
+    pinentry: Option<&str>,
+    accept_invalid_certs: bool,
+    allow_insecure_http: bool,
+) -> anyhow::Result<()> {
+    use crate::bitwarden::keys::resolve_item_keys;
+    use anyhow::Context;
+
+    // Ask for the export password first, so that a mistyped one doesn't
+    // waste a sync
+    let export_password = if args.format == ExportFormat::EncryptedJson {
+        Some(read_export_password()?)
+    } else {
+        None
+    };
+
+    let vault = load_vault(
+        profile,
+        &args.unlock,
+        pinentry,
+        accept_invalid_certs,
+        allow_insecure_http,
+    )
+    .await?;
+    let items: Vec<_> = vault
+        .sync
+        .ciphers
+        .iter()
+        .filter(|ci| ci.organization_id.is_none())
+        .filter_map(|ci| {
+            let keys = resolve_item_keys(ci, (&vault.user_keys).into(), |_, _| None)?;
+            Some((ci, keys))
+        })
+        .collect();
+    let items: Vec<_> = items.iter().map(|(ci, keys)| (*ci, &**keys)).collect();
+    let folders: Vec<_> = vault
+        .sync
+        .folders
+        .iter()
+        .map(|f| (f.id.clone(), f.name.decrypt_to_string(&vault.user_keys)))
+        .collect();
+
+    let export = crate::export::export(
+        args.format,
+        &items,
+        &folders,
+        export_password.as_deref().map(String::as_str),
+    )?;
+
+    crate::export::write_to_new_file(&args.output, &export)
+        .with_context(|| format!("Writing {} failed", args.output.display()))?;
+    println!(
+        "Exported {} items to {}",
+        items.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Asks for the password of an encrypted export twice
+fn read_export_password() -> anyhow::Result<Zeroizing<String>> {
+    use console::style;
+    use std::io::Write;
+
+    let ask = |prompt: &str| -> anyhow::Result<Zeroizing<String>> {
+        print!("{}", style(prompt).bold().bright().white());
+        std::io::stdout().flush()?;
+        Ok(Zeroizing::new(rpassword::read_password()?))
+    };
+    let password = ask(":: Enter a password for the export: ")?;
+    if password.is_empty() {
+        anyhow::bail!("The export password can't be empty");
+    }
+    if *ask(":: Enter the export password again: ")? != *password {
+        anyhow::bail!("The passwords don't match");
+    }
+    Ok(password)
+}
@@ -0,0 +1,60 @@
+use clap::Args;
+
+use crate::generator::{
+    generate_passphrase, generate_password, PassphraseOptions, PasswordOptions,
+};
+
+use super::copy_and_wait;
+
+#[derive(Args)]
+pub struct GenerateArgs {
+    /// Length of the password
+    #[arg(long, default_value_t = PasswordOptions::default().length)]
+    pub length: usize,
+
+    /// Generates a passphrase of this many words instead of a password
+    #[arg(long, conflicts_with_all = ["length", "no_symbols"])]
+    pub words: Option<usize>,
+
+    /// Leaves symbols out of the password
+    #[arg(long)]
+    pub no_symbols: bool,
+
+    /// Copies the value to the clipboard instead of printing it. wden keeps running
+    /// to serve the clipboard, and clears it after 30 seconds or when interrupted.
+    #[arg(long)]
+    pub copy: bool,
+}
+
+/// Runs `wden generate`
+pub async fn generate(args: GenerateArgs) -> anyhow::Result<()> {
+    let mut rng = rand::thread_rng();
+    let value = match args.words {
+        Some(words) => {
+            let options = PassphraseOptions {
+                words,
+                ..Default::default()
+            };
+            generate_passphrase(&options, &mut rng)?
+        }
+        None => {
+            let options = PasswordOptions {
+                length: args.length,
+                symbols: !args.no_symbols,
+                ..Default::default()
+            };
+            generate_password(&options, &mut rng)?
+        }
+    };
+
+    if !args.copy {
+        println!("{}", *value);
+        return Ok(());
+    }
+
+    // Generated values are not in the vault yet, so no item policy applies
+    copy_and_wait(&value, GENERATED_CLIPBOARD_EXPIRY).await;
+    Ok(())
+}
+
+const GENERATED_CLIPBOARD_EXPIRY: u64 = 30;
@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use clap::Args;
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::{
+        api::{CipherData, CipherItem},
+        cipher::EncMacKeys,
+        totp::Totp,
+    },
+    profile::ProfileStore,
+    ui::policy::ItemPolicy,
+};
+
+use super::{
+    copy_and_wait,
+    vault::{load_vault, read_sync_password, UnlockArgs, UnlockedVault},
+};
+
+#[derive(Args)]
+pub struct ItemArgs {
+    /// Name or id of the item. Names are matched case-insensitively.
+    pub item: String,
+
+    /// Allows printing the secret values of items in the collections marked as sensitive
+    #[arg(long)]
+    pub allow_sensitive: bool,
+
+    #[command(flatten)]
+    pub unlock: UnlockArgs,
+}
+
+#[derive(Args)]
+pub struct GetArgs {
+    #[command(flatten)]
+    pub item: ItemArgs,
+
+    /// Name of the field, as listed by `wden show`. With "totp", the current TOTP code is
+    /// given instead of the secret.
+    #[arg(long, default_value = "password")]
+    pub field: String,
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    #[command(flatten)]
+    pub item: ItemArgs,
+
+    /// Shows the secret values unmasked
+    #[arg(long)]
+    pub reveal: bool,
+}
+
+#[derive(Args)]
+pub struct TotpArgs {
+    /// Name or id of the login item. Names are matched case-insensitively.
+    pub item: String,
+
+    /// Keeps printing the code whenever it changes, until interrupted
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Copies each new code to the clipboard. wden has to keep running to
+    /// serve the clipboard, so this needs --watch.
+    #[arg(long, requires = "watch")]
+    pub copy: bool,
+
+    /// Gets the master password from the output of the given command, instead of asking for it
+    #[arg(long, value_name = "COMMAND")]
+    pub password_command: Option<String>,
+}
+
+/// The subcommands that read one item
+pub enum ItemCommand {
+    Get(GetArgs),
+    Show(ShowArgs),
+    Copy(GetArgs),
+}
+
+/// Runs `wden get`, `wden show` or `wden copy`
+pub async fn item_command(
+    profile: &str,
+    command: ItemCommand,
+    pinentry: Option<&str>,
+    accept_invalid_certs: bool,
+    allow_insecure_http: bool,
+) -> anyhow::Result<()> {
+    let (args, field, copy, reveal) = match command {
+        ItemCommand::Get(args) => (args.item, Some(args.field), false, false),
+        ItemCommand::Copy(args) => (args.item, Some(args.field), true, false),
+        ItemCommand::Show(args) => (args.item, None, false, args.reveal),
+    };
+
+    let vault = load_vault(
+        profile,
+        &args.unlock,
+        pinentry,
+        accept_invalid_certs,
+        allow_insecure_http,
+    )
+    .await?;
+    let items = vault.find_items(&args.item);
+    let (item, keys) = match &items[..] {
+        [] => anyhow::bail!("No item found with name or id {}", args.item),
+        [(item, keys)] => (*item, &**keys),
+        _ => anyhow::bail!(
+            "Several items are named {}. Use the item id instead.",
+            args.item
+        ),
+    };
+    let fields = item_fields(item, keys);
+
+    // Secrets of sensitive items are only copied, like in the UI
+    let sensitive_collections = ProfileStore::new(profile).load()?.sensitive_collections;
+    let policy = ItemPolicy::with_sensitive_collections(item, &sensitive_collections);
+    let refuse_sensitive = || {
+        anyhow::anyhow!(
+            "The item is in a sensitive collection. Copy the value with `wden copy`, \
+             or give --allow-sensitive to print it."
+        )
+    };
+
+    let Some(field) = field else {
+        if reveal && !policy.allows_reveal() && !args.allow_sensitive {
+            return Err(refuse_sensitive());
+        }
+        for (name, value, secret) in &fields {
+            if value.is_empty() {
+                continue;
+            }
+            if *secret && !reveal {
+                println!("{name}: ********");
+            } else {
+                println!("{name}: {}", **value);
+            }
+        }
+        return Ok(());
+    };
+
+    let (value, secret) = if field.eq_ignore_ascii_case("totp") {
+        let (_, secret, _) = fields
+            .iter()
+            .find(|(name, _, _)| name == "totp")
+            .filter(|(_, secret, _)| !secret.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("The item has no TOTP secret"))?;
+        (Totp::parse(secret)?.generate_now().0, true)
+    } else {
+        fields
+            .into_iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(&field))
+            .map(|(_, value, secret)| (value, secret))
+            .filter(|(value, _)| !value.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("The item has no {field} field"))?
+    };
+
+    if copy {
+        copy_and_wait(&value, policy.clipboard_expiry()).await;
+    } else if secret && !policy.allows_reveal() && !args.allow_sensitive {
+        return Err(refuse_sensitive());
+    } else {
+        println!("{}", *value);
+    }
+    Ok(())
+}
+
+/// The fields of an item by the names used in `wden get --field`, with
+/// a flag for the secret values that `wden show` masks
+fn item_fields(item: &CipherItem, keys: &EncMacKeys) -> Vec<(String, Zeroizing<String>, bool)> {
+    let d = |c: &crate::bitwarden::cipher::Cipher| Zeroizing::new(c.decrypt_to_string(keys));
+    let mut fields = vec![("name", d(&item.name), false)];
+    match &item.data {
+        CipherData::Login(login) => {
+            fields.extend([
+                ("username", d(&login.username), false),
+                ("password", d(&login.password), true),
+                ("totp", d(&login.totp), true),
+            ]);
+            fields.extend(
+                login
+                    .uris()
+                    .into_iter()
+                    .map(|(uri, _)| ("uri", d(uri), false)),
+            );
+        }
+        CipherData::Card(card) => fields.extend([
+            ("cardholder", d(&card.cardholder_name), false),
+            ("brand", d(&card.brand), false),
+            ("number", d(&card.number), true),
+            ("exp-month", d(&card.exp_month), false),
+            ("exp-year", d(&card.exp_year), false),
+            ("code", d(&card.code), true),
+        ]),
+        CipherData::Identity(identity) => fields.extend([
+            ("title", d(&identity.title), false),
+            ("first-name", d(&identity.first_name), false),
+            ("middle-name", d(&identity.middle_name), false),
+            ("last-name", d(&identity.last_name), false),
+            ("username", d(&identity.username), false),
+            ("company", d(&identity.company), false),
+            ("email", d(&identity.email), false),
+            ("phone", d(&identity.phone), false),
+            ("address-1", d(&identity.address_1), false),
+            ("address-2", d(&identity.address_2), false),
+            ("address-3", d(&identity.address_3), false),
+            ("postal-code", d(&identity.postal_code), false),
+            ("city", d(&identity.city), false),
+            ("state", d(&identity.state), false),
+            ("country", d(&identity.country), false),
+            ("ssn", d(&identity.ssn), true),
+            ("passport-number", d(&identity.passport_number), true),
+            ("license-number", d(&identity.license_number), true),
+        ]),
+        CipherData::SshKey(ssh_key) => fields.extend([
+            ("public-key", d(&ssh_key.public_key), false),
+            ("fingerprint", d(&ssh_key.fingerprint), false),
+            ("private-key", d(&ssh_key.private_key), true),
+        ]),
+        CipherData::SecureNote | CipherData::None => {}
+    }
+    fields.push(("notes", d(&item.notes), false));
+
+    let mut fields: Vec<_> = fields
+        .into_iter()
+        .map(|(name, value, secret)| (name.to_owned(), value, secret))
+        .collect();
+    // Custom fields are given by their own names. Linked fields have no value.
+    fields.extend(
+        item.fields
+            .iter()
+            .filter(|f| !f.is_linked())
+            .map(|f| (f.name.decrypt_to_string(keys), d(&f.value), f.is_hidden())),
+    );
+    fields
+}
+
+/// Runs `wden totp`
+pub async fn print_totp(
+    profile: &str,
+    args: TotpArgs,
+    pinentry: Option<&str>,
+) -> anyhow::Result<()> {
+    let totp = load_totp(
+        profile,
+        &args.item,
+        args.password_command.as_deref(),
+        pinentry,
+    )?;
+    if !args.watch {
+        println!("{}", *totp.generate_now().0);
+        return Ok(());
+    }
+
+    loop {
+        let (code, remaining) = totp.generate_now();
+        println!("{} ({remaining} s)", *code);
+        if args.copy {
+            // Cleared when the code expires, unless the next one has replaced it
+            crate::ui::clipboard::clip_expiring_string(code.to_string(), remaining);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(remaining)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                crate::ui::clipboard::clear_pending_clips();
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Finds the login item in the offline vault cache and parses its TOTP secret
+fn load_totp(
+    profile: &str,
+    item: &str,
+    password_command: Option<&str>,
+    pinentry: Option<&str>,
+) -> anyhow::Result<Totp> {
+    use anyhow::Context;
+
+    let profile_store = ProfileStore::new(profile);
+    let keyfile = profile_store.load()?.keyfile;
+    let cache = profile_store
+        .load_vault_cache(keyfile.as_deref())?
+        .context("The profile has no offline vault cache. Enable it with --offline-cache and sync the vault.")?;
+    let password = read_sync_password(profile, password_command, pinentry)?;
+    let vault = UnlockedVault::unlock(cache, &password)?;
+
+    let secrets: Vec<_> = vault
+        .find_items(item)
+        .into_iter()
+        .filter_map(|(cipher_item, keys)| match &cipher_item.data {
+            CipherData::Login(login) => Some(Zeroizing::new(login.totp.decrypt_to_string(&keys))),
+            _ => None,
+        })
+        .collect();
+
+    match &secrets[..] {
+        [] => anyhow::bail!("No login item found with name or id {item}"),
+        [secret] if secret.is_empty() => anyhow::bail!("The item has no TOTP secret"),
+        [secret] => Ok(Totp::parse(secret)?),
+        _ => anyhow::bail!("Several login items are named {item}. Use the item id instead."),
+    }
+}
@@ -0,0 +1,43 @@
+//! The subcommands that run without the UI, e.g. `wden get` and
+//! `wden sync`. The command line is parsed in the binary, and the
+//! subcommands are run with the arguments parsed into these modules.
+
+use std::time::Duration;
+
+use crate::profile::{ClipboardBackend, ProfileStore};
+
+pub mod export;
+pub mod generate;
+pub mod item;
+pub mod profiles;
+pub mod report;
+pub mod stats;
+pub mod sync;
+pub mod vault;
+
+/// Uses the clipboard backend given on the command line, or else the one
+/// stored in the profile
+pub fn set_clipboard_backend(profile: &str, cli_backend: Option<ClipboardBackend>) {
+    let backend = cli_backend
+        .or_else(|| {
+            ProfileStore::new(profile)
+                .load()
+                .ok()
+                .map(|p| p.clipboard_backend)
+        })
+        .unwrap_or_default();
+    crate::ui::clipboard::set_backend(backend);
+}
+
+/// Copies the value to the clipboard, and keeps running to serve the
+/// clipboard until the value expires (after `expiry` seconds) or Ctrl-C is pressed
+pub(super) async fn copy_and_wait(value: &str, expiry: u64) {
+    crate::ui::clipboard::clip_expiring_string(value.to_owned(), expiry);
+    println!("Copied to the clipboard. It will be cleared in {expiry} seconds.");
+    tokio::select! {
+        // A moment longer, so that the expiry timer gets to clear the clipboard
+        _ = tokio::time::sleep(Duration::from_secs(expiry + 1)) => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    crate::ui::clipboard::clear_pending_clips();
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use indicatif::ProgressBar;
+use tabled::{settings::Style, Table, Tabled};
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::{apikey::ApiKey, server::ServerConfiguration},
+    profile::{ProfileOverrides, ProfileStore},
+};
+
+#[derive(Tabled)]
+struct ProfileListRow<'a> {
+    #[tabled(rename = "NAME")]
+    name: &'a str,
+    #[tabled(rename = "SERVER")]
+    server_config: &'a ServerConfiguration,
+    #[tabled(rename = "SAVED EMAIL")]
+    saved_email: &'a str,
+    #[tabled(rename = "API KEY")]
+    api_key: &'static str,
+    #[tabled(rename = "ENVIRONMENT")]
+    environment: &'static str,
+}
+
+/// Prints the profiles for --list-profiles
+pub fn list_profiles() -> std::io::Result<()> {
+    let profiles = ProfileStore::get_all_profiles()?;
+
+    if profiles.is_empty() {
+        println!("No profiles found.")
+    } else {
+        let rows = profiles.iter().map(|(name, profile)| ProfileListRow {
+            name,
+            server_config: &profile.server_configuration,
+            saved_email: profile.saved_email.as_deref().unwrap_or("None"),
+            api_key: if profile.encrypted_api_key.is_some() {
+                "✓"
+            } else {
+                ""
+            },
+            environment: profile.environment.label(),
+        });
+
+        let mut table = Table::new(rows);
+        table.with(Style::blank());
+
+        println!("{table}");
+    }
+
+    Ok(())
+}
+
+/// The names of all profiles, for the subcommands with --all-profiles
+pub fn all_profile_names() -> std::io::Result<Vec<String>> {
+    let profiles = ProfileStore::get_all_profiles()?;
+    Ok(profiles
+        .into_iter()
+        .map(|(file_name, _)| file_name.trim_end_matches(".json").to_owned())
+        .collect())
+}
+
+/// Asks for confirmation if the profile exists and is configured for a different
+/// server than the given one. Returns true if the change can proceed.
+pub fn confirm_server_change(
+    profile: &str,
+    server_config: &ServerConfiguration,
+) -> std::io::Result<bool> {
+    use console::style;
+    use std::io::Write;
+
+    // New profiles don't need confirmation
+    let Ok(current) = ProfileStore::new(profile).load() else {
+        return Ok(true);
+    };
+    if &current.server_configuration == server_config {
+        return Ok(true);
+    }
+
+    println!("\n{}", style(":: Server change ::").bold().bright().white());
+    println!("Profile `{profile}` was used with a different server.\n");
+    println!("\tCurrent server: {}", current.server_configuration);
+    println!("\tNew server:     {server_config}\n");
+    println!("Check that the new server address is correct before entering any credentials.");
+    println!("The remembered two-factor token, the stored API key and the pinned server keys will be removed from the profile.\n");
+
+    print!(
+        "{}",
+        style(":: Change the server? [y/N] ")
+            .bold()
+            .bright()
+            .white()
+    );
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Stores the API key given with the --api-key-* options in the profile,
+/// encrypted with the master password
+pub async fn store_api_keys(
+    profile: String,
+    overrides: ProfileOverrides,
+    client_id: String,
+    client_secret: String,
+    email: String,
+    accept_invalid_certs: bool,
+) -> anyhow::Result<()> {
+    use crate::bitwarden::cipher;
+    use console::style;
+    use std::io::Write;
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_message("Loading data...");
+    spinner.enable_steady_tick(Duration::from_millis(200));
+
+    let (global_settings, _profile_data, profile_store) =
+        crate::ui::launch::load_profile(profile, overrides, accept_invalid_certs, false, false);
+
+    let client = crate::bitwarden::api::ApiClient::new(
+        &global_settings.server_configuration,
+        &global_settings.device_id,
+        global_settings.device_class,
+        global_settings.accept_invalid_certs,
+        &global_settings.pinned_spki_hashes,
+    )?;
+
+    let api_key = ApiKey::new(email.clone(), client_id, client_secret);
+
+    let token_res = client.get_token_with_api_key(&api_key).await?;
+    let pbkdf_params = token_res
+        .pbkdf_parameters()
+        .expect("Token response did not include Pbkdf parameters");
+    spinner.finish_and_clear();
+
+    println!(
+        "\n{}",
+        style(":: Enter your master password ::")
+            .bold()
+            .bright()
+            .white()
+    );
+    println!("wden will encrypt the API key with an encryption key derived from your master password, and store it in profile `{}`\n", &global_settings.profile);
+
+    let mut password: Zeroizing<String>;
+
+    loop {
+        print!(
+            "{}",
+            style(":: Enter master password: ").bold().bright().white()
+        );
+        std::io::stdout().flush().unwrap();
+
+        password = Zeroizing::new(rpassword::read_password()?);
+
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_message("Validating password");
+        spinner.enable_steady_tick(Duration::from_millis(200));
+
+        let check_res = cipher::create_master_key(&email, &password, &pbkdf_params)
+            .and_then(|mk| cipher::decrypt_symmetric_keys(&token_res.key, &mk));
+
+        spinner.finish_and_clear();
+
+        if check_res.is_ok() {
+            break;
+        } else {
+            println!("Invalid password.")
+        }
+    }
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_message("Encrypting API key");
+    spinner.enable_steady_tick(Duration::from_millis(200));
+
+    let enc_api_key = api_key.encrypt(&global_settings.profile, &email, &password)?;
+    profile_store
+        .edit(|d| {
+            d.saved_email = Some(email);
+            d.encrypted_api_key = Some(enc_api_key);
+        })
+        .unwrap();
+
+    spinner.finish_and_clear();
+
+    println!(
+        "{}",
+        style(":: API key encrypted and stored ::")
+            .bold()
+            .bright()
+            .white()
+    );
+    println!("You can now start wden with this profile without the API key arguments. Example:");
+    println!(
+        "\t{} --profile {}",
+        std::env::args().next().unwrap(),
+        global_settings.profile
+    );
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use clap::Args;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{profile::ProfileStore, report::ReportFormat};
+
+use super::vault::{load_vault, UnlockArgs};
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    #[command(flatten)]
+    pub unlock: UnlockArgs,
+}
+
+#[derive(Tabled)]
+struct ReportRow<'a> {
+    #[tabled(rename = "NAME")]
+    name: &'a str,
+    #[tabled(rename = "ISSUE")]
+    issue: crate::report::Issue,
+    #[tabled(rename = "CHANGED")]
+    changed: String,
+    #[tabled(rename = "AGE (DAYS)")]
+    age_days: String,
+}
+
+/// Runs `wden report`
+pub async fn report(
+    profile: &str,
+    args: ReportArgs,
+    pinentry: Option<&str>,
+    accept_invalid_certs: bool,
+    allow_insecure_http: bool,
+) -> anyhow::Result<()> {
+    use crate::bitwarden::keys::resolve_item_keys;
+
+    let vault = load_vault(
+        profile,
+        &args.unlock,
+        pinentry,
+        accept_invalid_certs,
+        allow_insecure_http,
+    )
+    .await?;
+    let items: Vec<_> = vault
+        .sync
+        .ciphers
+        .iter()
+        .filter_map(|ci| {
+            let keys = resolve_item_keys(ci, (&vault.user_keys).into(), |oid, _| {
+                vault.org_keys.get(oid).map(Into::into)
+            })?;
+            Some((ci, keys))
+        })
+        .collect();
+    let items: Vec<_> = items.iter().map(|(ci, keys)| (*ci, &**keys)).collect();
+    let findings = crate::report::find_issues(&items, jiff::Timestamp::now());
+
+    match args.format {
+        ReportFormat::Csv => print!("{}", crate::report::to_csv(&findings)),
+        ReportFormat::Text if findings.is_empty() => println!("No issues found."),
+        ReportFormat::Text => {
+            let date_display = ProfileStore::new(profile)
+                .load()
+                .map(|d| d.date_display())
+                .unwrap_or_default();
+            let rows = findings.iter().map(|f| ReportRow {
+                name: &f.item_name,
+                issue: f.issue,
+                changed: f
+                    .changed
+                    .as_deref()
+                    .map(|date| date_display.format(date))
+                    .unwrap_or_default(),
+                age_days: f.age_days.map(|a| a.to_string()).unwrap_or_default(),
+            });
+            let mut table = Table::new(rows);
+            table.with(Style::blank());
+            println!("{table}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use clap::Args;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::profile::{metrics_trends, MetricKind, ProfileStore};
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Shows the metrics of all profiles instead of the one set with --profile
+    #[arg(long)]
+    pub all_profiles: bool,
+}
+
+#[derive(Tabled)]
+struct StatsRow {
+    #[tabled(rename = "PROFILE")]
+    profile: String,
+    #[tabled(rename = "OPERATION")]
+    operation: &'static str,
+    #[tabled(rename = "COUNT")]
+    count: usize,
+    #[tabled(rename = "FIRST AVG")]
+    first_avg: String,
+    #[tabled(rename = "RECENT AVG")]
+    last_avg: String,
+    #[tabled(rename = "CHANGE")]
+    change: String,
+    #[tabled(rename = "ITEMS")]
+    items: String,
+}
+
+/// Prints the average durations of the oldest and the newest recorded
+/// operations of each profile
+pub fn print_stats(profiles: Vec<String>) -> std::io::Result<()> {
+    let mut rows = vec![];
+    for profile in profiles {
+        let records = ProfileStore::new(&profile).load_metrics()?;
+        for trend in metrics_trends(&records) {
+            let change = if trend.first_avg_ms > 0 {
+                let percent = (trend.last_avg_ms as f64 / trend.first_avg_ms as f64 - 1.0) * 100.0;
+                format!("{percent:+.0}%")
+            } else {
+                "-".to_owned()
+            };
+            rows.push(StatsRow {
+                profile: profile.clone(),
+                operation: match trend.kind {
+                    MetricKind::Unlock => "unlock",
+                    MetricKind::Sync => "sync",
+                },
+                count: trend.count,
+                first_avg: format!("{} ms", trend.first_avg_ms),
+                last_avg: format!("{} ms", trend.last_avg_ms),
+                change,
+                items: trend
+                    .latest_item_count
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+
+    if rows.is_empty() {
+        println!("No metrics recorded. Enable recording with --record-metrics.");
+    } else {
+        let mut table = Table::new(rows);
+        table.with(Style::blank());
+        println!("{table}");
+    }
+
+    Ok(())
+}
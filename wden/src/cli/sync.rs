@@ -0,0 +1,106 @@
+use clap::Args;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::{
+    bitwarden::{api::SyncResponse, apikey::ApiKey},
+    profile::ProfileStore,
+    scrub::scrub,
+};
+
+use super::vault::{fetch_vault, read_sync_password};
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Syncs all profiles instead of the one set with --profile
+    #[arg(long)]
+    pub all_profiles: bool,
+
+    /// Gets the master password from the output of the given command, instead of asking for it
+    ///
+    /// The command is run with a shell for each synced profile, with the profile name in the
+    /// WDEN_PROFILE environment variable. Example: --password-command 'pass show "wden/$WDEN_PROFILE"'
+    #[arg(long, value_name = "COMMAND")]
+    pub password_command: Option<String>,
+}
+
+#[derive(Tabled)]
+struct SyncResultRow {
+    #[tabled(rename = "PROFILE")]
+    name: String,
+    #[tabled(rename = "RESULT")]
+    result: String,
+}
+
+/// Syncs the offline caches of the given profiles. Returns false if
+/// syncing any of the profiles failed.
+pub async fn sync_profiles(
+    profiles: Vec<String>,
+    password_command: Option<&str>,
+    pinentry: Option<&str>,
+    accept_invalid_certs: bool,
+    allow_insecure_http: bool,
+) -> bool {
+    let mut rows = vec![];
+    let mut all_ok = true;
+
+    for name in profiles {
+        let result = match sync_profile(
+            &name,
+            password_command,
+            pinentry,
+            accept_invalid_certs,
+            allow_insecure_http,
+        )
+        .await
+        {
+            Ok(Some(item_count)) => format!("Synced {item_count} items"),
+            Ok(None) => "Skipped: offline cache or API key not set".to_owned(),
+            Err(e) => {
+                all_ok = false;
+                scrub(&format!("Failed: {e:#}"))
+            }
+        };
+        rows.push(SyncResultRow { name, result });
+    }
+
+    let mut table = Table::new(rows);
+    table.with(Style::blank());
+    println!("{table}");
+
+    all_ok
+}
+
+/// Syncs the offline cache of one profile, using the stored API key.
+/// Returns the number of synced items, or None if the profile was skipped.
+async fn sync_profile(
+    profile: &str,
+    password_command: Option<&str>,
+    pinentry: Option<&str>,
+    accept_invalid_certs: bool,
+    allow_insecure_http: bool,
+) -> anyhow::Result<Option<usize>> {
+    use anyhow::Context;
+
+    let profile_store = ProfileStore::new(profile);
+    let profile_data = profile_store.load()?;
+    if profile_data.server_configuration.uses_insecure_http() && !allow_insecure_http {
+        anyhow::bail!("The server URL uses unencrypted HTTP (allow with --allow-insecure-http)");
+    }
+    let (Some(enc_api_key), Some(email), true) = (
+        &profile_data.encrypted_api_key,
+        &profile_data.saved_email,
+        profile_data.offline_cache,
+    ) else {
+        return Ok(None);
+    };
+
+    let password = read_sync_password(profile, password_command, pinentry)?;
+    let api_key = ApiKey::decrypt(enc_api_key, profile, email, &password)
+        .context("Decrypting the API key failed")?;
+
+    let cache = fetch_vault(&profile_data, email, &api_key, accept_invalid_certs).await?;
+    let item_count = SyncResponse::from_json(&cache.sync_data)?.ciphers.len();
+    profile_store.store_vault_cache(cache, profile_data.keyfile.as_deref())?;
+
+    Ok(Some(item_count))
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use clap::Args;
+use maybe_owned::MaybeOwned;
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::{
+        api::{ApiClient, CipherItem, SyncResponse},
+        apikey::ApiKey,
+        cipher::EncMacKeys,
+    },
+    profile::{ProfileData, ProfileStore, VaultCache},
+};
+
+/// How the subcommands that read the vault get the master password and the vault
+#[derive(Args)]
+pub struct UnlockArgs {
+    /// Reads the master password from the first line of stdin, instead of asking for it
+    #[arg(long, conflicts_with = "password_command")]
+    pub password_stdin: bool,
+
+    /// Gets the master password from the output of the given command, instead of asking for it
+    #[arg(long, value_name = "COMMAND")]
+    pub password_command: Option<String>,
+
+    /// Reads the vault from the offline vault cache without syncing
+    #[arg(long)]
+    pub no_sync: bool,
+}
+
+/// A vault from a sync response, with the user and organization keys decrypted
+pub(super) struct UnlockedVault {
+    pub(super) sync: SyncResponse,
+    pub(super) user_keys: EncMacKeys,
+    pub(super) org_keys: HashMap<String, EncMacKeys>,
+}
+
+impl UnlockedVault {
+    pub(super) fn unlock(cache: VaultCache, password: &str) -> anyhow::Result<Self> {
+        use crate::bitwarden::cipher::{self, DerPrivateKey, PrivateKey};
+        use anyhow::Context;
+
+        let master_key = cipher::create_master_key(&cache.email, password, &cache.pbkdf)?;
+        let user_keys = cipher::decrypt_symmetric_keys(&cache.key, &master_key)
+            .context("Decrypting the vault failed. Check the password.")?;
+        let sync = SyncResponse::from_json(&cache.sync_data)?;
+
+        let mut org_keys = HashMap::new();
+        if !sync.profile.organizations.is_empty() {
+            let der: DerPrivateKey = cache.private_key.decrypt(&user_keys)?.into();
+            let private_key = PrivateKey::from_der(&der)?;
+            for org in &sync.profile.organizations {
+                match cipher::decrypt_org_keys(&private_key, &org.key) {
+                    Ok(keys) => _ = org_keys.insert(org.id.clone(), keys),
+                    Err(e) => log::warn!("Org key decryption failed: {e}"),
+                }
+            }
+        }
+
+        Ok(UnlockedVault {
+            sync,
+            user_keys,
+            org_keys,
+        })
+    }
+
+    /// Items with the given id, or with the given name case-insensitively
+    pub(super) fn find_items(
+        &self,
+        name_or_id: &str,
+    ) -> Vec<(&CipherItem, MaybeOwned<'_, EncMacKeys>)> {
+        use crate::bitwarden::keys::resolve_item_keys;
+
+        self.sync
+            .ciphers
+            .iter()
+            .filter_map(|cipher_item| {
+                let keys = resolve_item_keys(cipher_item, (&self.user_keys).into(), |oid, _| {
+                    self.org_keys.get(oid).map(Into::into)
+                })?;
+                let matches = cipher_item.id == name_or_id
+                    || cipher_item
+                        .name
+                        .decrypt_to_string(&keys)
+                        .eq_ignore_ascii_case(name_or_id);
+                matches.then_some((cipher_item, keys))
+            })
+            .collect()
+    }
+}
+
+/// Syncs the vault with the stored API key, or reads the offline vault
+/// cache if the profile has no API key, and unlocks it
+pub(super) async fn load_vault(
+    profile: &str,
+    args: &UnlockArgs,
+    pinentry: Option<&str>,
+    accept_invalid_certs: bool,
+    allow_insecure_http: bool,
+) -> anyhow::Result<UnlockedVault> {
+    use anyhow::Context;
+
+    let profile_store = ProfileStore::new(profile);
+    let profile_data = profile_store.load()?;
+    let password = if args.password_stdin {
+        let mut line = Zeroizing::new(String::new());
+        std::io::stdin().read_line(&mut line)?;
+        Zeroizing::new(line.trim_end_matches(['\r', '\n']).to_owned())
+    } else {
+        read_sync_password(profile, args.password_command.as_deref(), pinentry)?
+    };
+
+    let cache = match (&profile_data.encrypted_api_key, &profile_data.saved_email) {
+        (Some(enc_api_key), Some(email)) if !args.no_sync => {
+            if profile_data.server_configuration.uses_insecure_http() && !allow_insecure_http {
+                anyhow::bail!(
+                    "The server URL uses unencrypted HTTP (allow with --allow-insecure-http)"
+                );
+            }
+            let api_key = ApiKey::decrypt(enc_api_key, profile, email, &password)
+                .context("Decrypting the API key failed")?;
+            let cache = fetch_vault(&profile_data, email, &api_key, accept_invalid_certs).await?;
+            if profile_data.offline_cache {
+                profile_store
+                    .store_vault_cache(cache.clone(), profile_data.keyfile.as_deref())?;
+            }
+            cache
+        }
+        _ => profile_store.load_vault_cache(profile_data.keyfile.as_deref())?.context(
+            "The profile has no stored API key or offline vault cache. Store an API key with the --api-key-* options, or enable --offline-cache and sync the vault.",
+        )?,
+    };
+
+    UnlockedVault::unlock(cache, &password)
+}
+
+/// Logs in with the API key and syncs the vault
+pub(super) async fn fetch_vault(
+    profile_data: &ProfileData,
+    email: &str,
+    api_key: &ApiKey,
+    accept_invalid_certs: bool,
+) -> anyhow::Result<VaultCache> {
+    use anyhow::Context;
+
+    let client = ApiClient::new(
+        &profile_data.server_configuration,
+        &profile_data.device_id,
+        profile_data.device_class,
+        accept_invalid_certs,
+        &profile_data.pinned_spki_hashes,
+    )?;
+    let token = client.get_token_with_api_key(api_key).await?;
+    let pbkdf = token
+        .pbkdf_parameters()
+        .context("Token response did not include Pbkdf parameters")?;
+
+    let client = ApiClient::with_token(
+        &profile_data.server_configuration,
+        &profile_data.device_id,
+        profile_data.device_class,
+        &token.access_token,
+        accept_invalid_certs,
+        &profile_data.pinned_spki_hashes,
+    )?;
+    let sync_json = client.sync_json().await?;
+
+    Ok(VaultCache {
+        email: email.to_owned(),
+        pbkdf,
+        key: token.key,
+        private_key: token.private_key,
+        sync_data: sync_json,
+        keyfile_wrapped: false,
+    })
+}
+
+/// Gets the master password from the password command, the pinentry
+/// program or the terminal, in that order. The settings stored in the
+/// profile are used if they are not given.
+pub(super) fn read_sync_password(
+    profile: &str,
+    password_command: Option<&str>,
+    pinentry: Option<&str>,
+) -> anyhow::Result<Zeroizing<String>> {
+    use console::style;
+    use std::io::Write;
+    use std::process::Command;
+
+    let profile_data = ProfileStore::new(profile).load()?;
+    let pinentry = pinentry.or(profile_data.pinentry.as_deref());
+    let Some(password_command) = password_command.or(profile_data.password_command.as_deref())
+    else {
+        if let Some(pinentry) = pinentry {
+            return crate::pinentry::get_pin(
+                pinentry,
+                &format!("Enter the master password for the wden profile `{profile}`"),
+                "Master password:",
+            );
+        }
+        let prompt = format!(":: Enter master password for profile `{profile}`: ");
+        print!("{}", style(prompt).bold().bright().white());
+        std::io::stdout().flush()?;
+        return Ok(Zeroizing::new(rpassword::read_password()?));
+    };
+
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C");
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    };
+    let output = command
+        .arg(password_command)
+        .env("WDEN_PROFILE", profile)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("Password command failed ({})", output.status);
+    }
+
+    let output = Zeroizing::new(String::from_utf8(output.stdout)?);
+    Ok(Zeroizing::new(
+        output.lines().next().unwrap_or_default().to_owned(),
+    ))
+}
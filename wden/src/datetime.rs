@@ -0,0 +1,129 @@
+//! Rendering of the timestamps of the API, which are UTC ISO 8601 strings,
+//! in the local time zone or as relative times
+
+use clap::ValueEnum;
+use jiff::{fmt::strtime, tz::TimeZone, Timestamp};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum DateStyle {
+    /// The date and time in the configured format
+    #[default]
+    Absolute,
+    /// E.g. "3 days ago"
+    Relative,
+}
+
+/// How timestamps are shown in the UI and the reports
+#[derive(Clone, Default, Debug)]
+pub struct DateDisplay {
+    pub style: DateStyle,
+    /// A strftime format, `DEFAULT_DATE_FORMAT` if not set
+    pub format: Option<String>,
+    /// An IANA time zone name, e.g. Europe/Helsinki. The system time zone
+    /// is used if not set.
+    pub time_zone: Option<String>,
+}
+
+impl DateDisplay {
+    /// Formats an API timestamp. Unparseable values are returned as is.
+    pub fn format(&self, date: &str) -> String {
+        self.format_at(date, Timestamp::now())
+    }
+
+    fn format_at(&self, date: &str, now: Timestamp) -> String {
+        let Ok(timestamp) = date.parse::<Timestamp>() else {
+            return date.to_string();
+        };
+        match self.style {
+            DateStyle::Relative => relative(timestamp, now),
+            DateStyle::Absolute => {
+                let zoned = timestamp.to_zoned(self.time_zone());
+                let format = self.format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT);
+                strtime::format(format, &zoned).unwrap_or_else(|e| {
+                    log::warn!("Invalid date format {format}: {e}");
+                    zoned.strftime(DEFAULT_DATE_FORMAT).to_string()
+                })
+            }
+        }
+    }
+
+    fn time_zone(&self) -> TimeZone {
+        match &self.time_zone {
+            Some(name) => TimeZone::get(name).unwrap_or_else(|e| {
+                log::warn!("Unknown time zone {name}, using the system time zone: {e}");
+                TimeZone::system()
+            }),
+            None => TimeZone::system(),
+        }
+    }
+}
+
+/// Checks a time zone name given on the command line
+pub fn validate_time_zone(name: &str) -> Result<(), String> {
+    TimeZone::get(name)
+        .map(drop)
+        .map_err(|e| format!("Unknown time zone: {e}"))
+}
+
+/// Checks a strftime format given on the command line
+pub fn validate_date_format(format: &str) -> Result<(), String> {
+    strtime::format(format, &Timestamp::UNIX_EPOCH.to_zoned(TimeZone::UTC))
+        .map(drop)
+        .map_err(|e| format!("Invalid date format: {e}"))
+}
+
+fn relative(timestamp: Timestamp, now: Timestamp) -> String {
+    let seconds = now.as_second() - timestamp.as_second();
+    // E.g. the deletion dates of Sends are in the future
+    let future = seconds < 0;
+    let seconds = seconds.abs();
+    let (count, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86_399 => (seconds / 3600, "hour"),
+        86_400..=2_591_999 => (seconds / 86_400, "day"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    if future {
+        format!("in {count} {unit}{plural}")
+    } else {
+        format!("{count} {unit}{plural} ago")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let now: Timestamp = "2024-03-10T12:00:00Z".parse().unwrap();
+        let mut display = DateDisplay {
+            style: DateStyle::Absolute,
+            format: None,
+            time_zone: Some("Europe/Helsinki".to_string()),
+        };
+        assert_eq!(
+            display.format_at("2024-03-01T10:30:00.1234567Z", now),
+            "2024-03-01 12:30"
+        );
+        display.format = Some("%d.%m.%Y".to_string());
+        assert_eq!(display.format_at("2024-03-01T23:30:00Z", now), "02.03.2024");
+        assert_eq!(display.format_at("not a date", now), "not a date");
+
+        display.style = DateStyle::Relative;
+        assert_eq!(display.format_at("2024-03-10T11:59:30Z", now), "just now");
+        assert_eq!(display.format_at("2024-03-10T11:00:00Z", now), "1 hour ago");
+        assert_eq!(display.format_at("2024-03-07T12:00:00Z", now), "3 days ago");
+        assert_eq!(display.format_at("2024-03-17T12:00:00Z", now), "in 7 days");
+        assert_eq!(
+            display.format_at("2021-03-01T12:00:00Z", now),
+            "3 years ago"
+        );
+    }
+}
@@ -0,0 +1,572 @@
+//! Vault exports in the formats of the official Bitwarden clients:
+//! unencrypted JSON, CSV and password protected JSON. Single items can
+//! also be exported in the `bw get item` format.
+
+use std::{io::Write, path::Path};
+
+use base64::prelude::*;
+use clap::ValueEnum;
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::bitwarden::{
+    api::{CipherData, CipherItem},
+    cipher::{derive_export_keys, Cipher, CipherError, EncMacKeys},
+};
+
+/// Replaces secret values in masked item JSON
+const MASKED_VALUE: &str = "********";
+
+/// PBKDF2 iterations for password protected exports, the default of the
+/// official clients
+pub const EXPORT_KDF_ITERATIONS: u32 = 600_000;
+
+const CSV_HEADER: &str =
+    "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp";
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Json,
+    /// Only login and secure note items, like in the official clients
+    Csv,
+    /// JSON encrypted with a password of its own
+    EncryptedJson,
+}
+
+impl ExportFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json | ExportFormat::EncryptedJson => "json",
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("A password is needed for an encrypted export")]
+    MissingPassword,
+    #[error("Encrypting the export failed")]
+    Encryption(#[from] CipherError),
+    #[error("Creating the JSON failed")]
+    Json(#[from] serde_json::Error),
+}
+
+/// An item with the keys to decrypt it
+pub type ExportItem<'a> = (&'a CipherItem, &'a EncMacKeys);
+
+/// Exports the items. `folders` has the ids and the decrypted names of
+/// the folders. The password is only used for encrypted exports.
+pub fn export(
+    format: ExportFormat,
+    items: &[ExportItem],
+    folders: &[(String, String)],
+    password: Option<&str>,
+) -> Result<Zeroizing<String>, ExportError> {
+    match format {
+        ExportFormat::Json => to_json(items, folders),
+        ExportFormat::Csv => Ok(to_csv(items, folders)),
+        ExportFormat::EncryptedJson => {
+            let password = password.ok_or(ExportError::MissingPassword)?;
+            let json = to_json(items, folders)?;
+            encrypt_json(&json, password)
+        }
+    }
+}
+
+/// Writes the export to a new file that only the current user can read
+pub fn write_to_new_file(path: &Path, export: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(export.as_bytes())
+}
+
+fn to_json(
+    items: &[ExportItem],
+    folders: &[(String, String)],
+) -> Result<Zeroizing<String>, ExportError> {
+    let export = ExportJson {
+        encrypted: false,
+        folders: folders
+            .iter()
+            .map(|(id, name)| FolderJson { id, name })
+            .collect(),
+        items: items
+            .iter()
+            .filter_map(|(item, keys)| item_json(item, keys, false))
+            .collect(),
+    };
+    Ok(to_zeroizing_json(&export)?)
+}
+
+/// The decrypted item like `bw get item` prints it, for debugging and
+/// scripting. With `mask_secrets`, passwords, hidden fields and other
+/// secret values are replaced with asterisks. Returns `None` for items of
+/// unknown types.
+pub fn item_to_json(
+    item: &CipherItem,
+    keys: &EncMacKeys,
+    mask_secrets: bool,
+) -> Result<Option<Zeroizing<String>>, ExportError> {
+    let Some(mut json) = item_json(item, keys, mask_secrets) else {
+        return Ok(None);
+    };
+    json.object = Some("item");
+    json.revision_date = Some(item.revision_date.as_deref());
+    json.collection_ids = Some(&item.collection_ids);
+    Ok(Some(to_zeroizing_json(&json)?))
+}
+
+/// Pretty-printed JSON in a buffer that is zeroized, also when it grows
+fn to_zeroizing_json(value: &impl Serialize) -> Result<Zeroizing<String>, serde_json::Error> {
+    let mut writer = ZeroizingWriter(Zeroizing::new(Vec::new()));
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    let json = std::mem::take(&mut *writer.0);
+    // serde_json only writes valid UTF-8
+    Ok(Zeroizing::new(String::from_utf8(json).unwrap()))
+}
+
+/// Copies the data to a new buffer when it's full, and zeroizes the old one.
+/// A `Vec` would leave the old contents in freed memory.
+struct ZeroizingWriter(Zeroizing<Vec<u8>>);
+
+impl Write for ZeroizingWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let len = self.0.len() + data.len();
+        if len > self.0.capacity() {
+            let mut grown = Vec::with_capacity(len.max(2 * self.0.capacity()));
+            grown.extend_from_slice(&self.0);
+            self.0 = Zeroizing::new(grown);
+        }
+        self.0.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A decrypted value. Empty values are null in the official exports.
+type JsonValue = Option<Zeroizing<String>>;
+
+#[derive(Serialize)]
+struct ExportJson<'a> {
+    encrypted: bool,
+    folders: Vec<FolderJson<'a>>,
+    items: Vec<ItemJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct FolderJson<'a> {
+    id: &'a str,
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemJson<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revision_date: Option<Option<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object: Option<&'static str>,
+    id: &'a str,
+    organization_id: Option<&'a str>,
+    folder_id: Option<&'a str>,
+    #[serde(rename = "type")]
+    item_type: u8,
+    reprompt: u8,
+    name: JsonValue,
+    notes: JsonValue,
+    favorite: bool,
+    #[serde(flatten)]
+    data: ItemDataJson,
+    fields: Vec<FieldJson>,
+    collection_ids: Option<&'a [String]>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum ItemDataJson {
+    Login(LoginJson),
+    SecureNote {
+        #[serde(rename = "type")]
+        note_type: u8,
+    },
+    Card(CardJson),
+    Identity(Box<IdentityJson>),
+    SshKey(SshKeyJson),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FieldJson {
+    name: JsonValue,
+    value: JsonValue,
+    #[serde(rename = "type")]
+    field_type: u8,
+    linked_id: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct LoginJson {
+    uris: Vec<UriJson>,
+    username: JsonValue,
+    password: JsonValue,
+    totp: JsonValue,
+}
+
+#[derive(Serialize)]
+struct UriJson {
+    #[serde(rename = "match")]
+    match_type: Option<u8>,
+    uri: JsonValue,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CardJson {
+    cardholder_name: JsonValue,
+    brand: JsonValue,
+    number: JsonValue,
+    exp_month: JsonValue,
+    exp_year: JsonValue,
+    code: JsonValue,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IdentityJson {
+    title: JsonValue,
+    first_name: JsonValue,
+    middle_name: JsonValue,
+    last_name: JsonValue,
+    address1: JsonValue,
+    address2: JsonValue,
+    address3: JsonValue,
+    city: JsonValue,
+    state: JsonValue,
+    postal_code: JsonValue,
+    country: JsonValue,
+    company: JsonValue,
+    email: JsonValue,
+    phone: JsonValue,
+    ssn: JsonValue,
+    username: JsonValue,
+    passport_number: JsonValue,
+    license_number: JsonValue,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SshKeyJson {
+    private_key: JsonValue,
+    public_key: JsonValue,
+    key_fingerprint: JsonValue,
+}
+
+fn item_json<'a>(
+    item: &'a CipherItem,
+    keys: &EncMacKeys,
+    mask_secrets: bool,
+) -> Option<ItemJson<'a>> {
+    let d = |c: &Cipher| Some(Zeroizing::new(c.decrypt_to_string(keys))).filter(|s| !s.is_empty());
+    let secret = |c: &Cipher| match d(c) {
+        Some(_) if mask_secrets => Some(Zeroizing::new(MASKED_VALUE.to_owned())),
+        v => v,
+    };
+
+    let (item_type, data) = match &item.data {
+        CipherData::Login(login) => (
+            1,
+            ItemDataJson::Login(LoginJson {
+                uris: login
+                    .uris()
+                    .into_iter()
+                    .map(|(uri, match_type)| UriJson {
+                        match_type: match_type.map(|m| m.as_u8()),
+                        uri: d(uri),
+                    })
+                    .collect(),
+                username: d(&login.username),
+                password: secret(&login.password),
+                totp: secret(&login.totp),
+            }),
+        ),
+        CipherData::SecureNote => (2, ItemDataJson::SecureNote { note_type: 0 }),
+        CipherData::Card(card) => (
+            3,
+            ItemDataJson::Card(CardJson {
+                cardholder_name: d(&card.cardholder_name),
+                brand: d(&card.brand),
+                number: secret(&card.number),
+                exp_month: d(&card.exp_month),
+                exp_year: d(&card.exp_year),
+                code: secret(&card.code),
+            }),
+        ),
+        CipherData::Identity(identity) => (
+            4,
+            ItemDataJson::Identity(Box::new(IdentityJson {
+                title: d(&identity.title),
+                first_name: d(&identity.first_name),
+                middle_name: d(&identity.middle_name),
+                last_name: d(&identity.last_name),
+                address1: d(&identity.address_1),
+                address2: d(&identity.address_2),
+                address3: d(&identity.address_3),
+                city: d(&identity.city),
+                state: d(&identity.state),
+                postal_code: d(&identity.postal_code),
+                country: d(&identity.country),
+                company: d(&identity.company),
+                email: d(&identity.email),
+                phone: d(&identity.phone),
+                ssn: secret(&identity.ssn),
+                username: d(&identity.username),
+                passport_number: d(&identity.passport_number),
+                license_number: d(&identity.license_number),
+            })),
+        ),
+        CipherData::SshKey(ssh_key) => (
+            5,
+            ItemDataJson::SshKey(SshKeyJson {
+                private_key: secret(&ssh_key.private_key),
+                public_key: d(&ssh_key.public_key),
+                key_fingerprint: d(&ssh_key.fingerprint),
+            }),
+        ),
+        CipherData::None => return None,
+    };
+
+    Some(ItemJson {
+        revision_date: None,
+        object: None,
+        id: &item.id,
+        organization_id: item.organization_id.as_deref(),
+        folder_id: item.folder_id.as_deref(),
+        item_type,
+        reprompt: u8::from(item.reprompt),
+        name: d(&item.name),
+        notes: d(&item.notes),
+        favorite: item.favorite,
+        data,
+        fields: item
+            .fields
+            .iter()
+            .map(|f| FieldJson {
+                name: d(&f.name),
+                value: if f.is_hidden() {
+                    secret(&f.value)
+                } else {
+                    d(&f.value)
+                },
+                field_type: f.field_type,
+                linked_id: f.linked_id,
+            })
+            .collect(),
+        collection_ids: None,
+    })
+}
+
+fn to_csv(items: &[ExportItem], folders: &[(String, String)]) -> Zeroizing<String> {
+    let mut csv = Zeroizing::new(String::new());
+    csv.push_str(CSV_HEADER);
+    csv.push('\n');
+
+    for (item, keys) in items {
+        let d = |c: &Cipher| Zeroizing::new(c.decrypt_to_string(keys));
+        let (item_type, login) = match &item.data {
+            CipherData::Login(login) => ("login", Some(login)),
+            CipherData::SecureNote => ("note", None),
+            _ => continue,
+        };
+        let folder = item
+            .folder_id
+            .as_ref()
+            .and_then(|id| folders.iter().find(|(fid, _)| fid == id))
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_default();
+        let fields = Zeroizing::new(
+            item.fields
+                .iter()
+                .map(|f| format!("{}: {}", *d(&f.name), *d(&f.value)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+        let (uris, username, password, totp) = match login {
+            Some(login) => (
+                Zeroizing::new(
+                    login
+                        .uris()
+                        .into_iter()
+                        .map(|(uri, _)| d(uri).to_string())
+                        .filter(|uri| !uri.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                d(&login.username),
+                d(&login.password),
+                d(&login.totp),
+            ),
+            None => Default::default(),
+        };
+
+        let (name, notes) = (d(&item.name), d(&item.notes));
+        let row: [&str; 11] = [
+            folder,
+            if item.favorite { "1" } else { "" },
+            item_type,
+            &name,
+            &notes,
+            &fields,
+            if item.reprompt { "1" } else { "0" },
+            &uris,
+            &username,
+            &password,
+            &totp,
+        ];
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                csv.push(',');
+            }
+            csv.push_str(&csv_escape(value));
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+pub(crate) fn csv_escape(value: &str) -> Zeroizing<String> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Zeroizing::new(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Zeroizing::new(value.to_string())
+    }
+}
+
+/// Encrypts the JSON export like the official clients do, with keys
+/// derived from the password and a random salt
+fn encrypt_json(json: &str, password: &str) -> Result<Zeroizing<String>, ExportError> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = BASE64_STANDARD.encode(salt);
+
+    let keys = derive_export_keys(password, &salt, EXPORT_KDF_ITERATIONS);
+    let validation = Cipher::encrypt(Uuid::new_v4().to_string().as_bytes(), &keys)?;
+    let data = Cipher::encrypt(json.as_bytes(), &keys)?;
+
+    let export = json!({
+        "encrypted": true,
+        "passwordProtected": true,
+        "salt": salt,
+        "kdfType": 0,
+        "kdfIterations": EXPORT_KDF_ITERATIONS,
+        "encKeyValidation_DO_NOT_EDIT": validation.encode(),
+        "data": data.encode(),
+    });
+    Ok(Zeroizing::new(serde_json::to_string_pretty(&export)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::bitwarden::api::SyncResponse;
+
+    use super::*;
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(*csv_escape("plain"), "plain");
+        assert_eq!(*csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(*csv_escape("say \"hi\"\n"), "\"say \"\"hi\"\"\n\"");
+    }
+
+    #[test]
+    fn test_encrypt_json() {
+        let encrypted = encrypt_json("{\"items\":[]}", "export password").unwrap();
+        let encrypted: Value = serde_json::from_str(&encrypted).unwrap();
+
+        let keys = derive_export_keys(
+            "export password",
+            encrypted["salt"].as_str().unwrap(),
+            EXPORT_KDF_ITERATIONS,
+        );
+        let data: Cipher = encrypted["data"].as_str().unwrap().parse().unwrap();
+        assert_eq!(data.decrypt(&keys).unwrap(), b"{\"items\":[]}");
+    }
+
+    #[test]
+    fn test_item_to_json() {
+        let keys = derive_export_keys("password", "salt", 1);
+        let enc = |s: &str| Cipher::encrypt(s.as_bytes(), &keys).unwrap().encode();
+        let sync = SyncResponse::from_json(&serde_json::json!({
+            "ciphers": [{
+                "id": "item",
+                "type": 1,
+                "name": enc("Mail"),
+                "favorite": true,
+                "collectionIds": ["collection"],
+                "revisionDate": "2024-03-01T10:00:00.000Z",
+                "login": {
+                    "username": enc("user"),
+                    "password": enc("hunter2"),
+                    "uris": [{ "uri": enc("https://example.com"), "match": 0 }],
+                },
+                "fields": [{ "name": enc("pin"), "value": enc("1234"), "type": 1 }],
+            }],
+            "profile": { "organizations": [] },
+            "collections": [],
+        }))
+        .unwrap();
+        let item = &sync.ciphers[0];
+
+        let json = item_to_json(item, &keys, false).unwrap().unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "revisionDate": "2024-03-01T10:00:00.000Z",
+                "object": "item",
+                "id": "item",
+                "organizationId": null,
+                "folderId": null,
+                "type": 1,
+                "reprompt": 0,
+                "name": "Mail",
+                "notes": null,
+                "favorite": true,
+                "login": {
+                    "uris": [{ "match": 0, "uri": "https://example.com" }],
+                    "username": "user",
+                    "password": "hunter2",
+                    "totp": null,
+                },
+                "fields": [{ "name": "pin", "value": "1234", "type": 1, "linkedId": null }],
+                "collectionIds": ["collection"],
+            })
+        );
+
+        let masked = item_to_json(item, &keys, true).unwrap().unwrap();
+        let value: Value = serde_json::from_str(&masked).unwrap();
+        assert_eq!(value["login"]["password"], MASKED_VALUE);
+        assert_eq!(value["login"]["username"], "user");
+        assert_eq!(value["fields"][0]["value"], MASKED_VALUE);
+
+        let export = to_json(&[(item, &keys)], &[]).unwrap();
+        let value: Value = serde_json::from_str(&export).unwrap();
+        assert_eq!(value["encrypted"], false);
+        assert_eq!(value["items"][0]["collectionIds"], Value::Null);
+        assert!(value["items"][0].get("object").is_none());
+    }
+}
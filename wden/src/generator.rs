@@ -0,0 +1,241 @@
+//! Password and passphrase generation. Passwords are built from character
+//! classes, passphrases from the EFF large wordlist.
+
+use rand::{seq::SliceRandom, Rng};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*";
+/// Characters that are easy to mix up with each other
+const AMBIGUOUS: &str = "lIO01";
+
+/// Length of the longest word in the EFF large wordlist
+const MAX_WORD_LENGTH: usize = 9;
+
+pub const MIN_PASSWORD_LENGTH: usize = 5;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+pub const MIN_PASSPHRASE_WORDS: usize = 3;
+pub const MAX_PASSPHRASE_WORDS: usize = 20;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GeneratorError {
+    #[error("Select at least one character class")]
+    NoCharacterClasses,
+    #[error("The length must be from {MIN_PASSWORD_LENGTH} to {MAX_PASSWORD_LENGTH}")]
+    InvalidLength,
+    #[error("The number of words must be from {MIN_PASSPHRASE_WORDS} to {MAX_PASSPHRASE_WORDS}")]
+    InvalidWordCount,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordOptions {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub avoid_ambiguous: bool,
+}
+
+impl Default for PasswordOptions {
+    fn default() -> Self {
+        PasswordOptions {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            avoid_ambiguous: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: char,
+    pub capitalize: bool,
+    /// Appends a digit to one of the words
+    pub include_number: bool,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        PassphraseOptions {
+            words: 5,
+            separator: '-',
+            capitalize: false,
+            include_number: false,
+        }
+    }
+}
+
+/// Generates a password with at least one character from each selected class
+pub fn generate_password(
+    options: &PasswordOptions,
+    rng: &mut impl Rng,
+) -> Result<Zeroizing<String>, GeneratorError> {
+    let classes: Vec<Vec<char>> = [
+        (options.lowercase, LOWERCASE),
+        (options.uppercase, UPPERCASE),
+        (options.digits, DIGITS),
+        (options.symbols, SYMBOLS),
+    ]
+    .into_iter()
+    .filter(|(selected, _)| *selected)
+    .map(|(_, chars)| {
+        chars
+            .chars()
+            .filter(|c| !options.avoid_ambiguous || !AMBIGUOUS.contains(*c))
+            .collect()
+    })
+    .collect();
+
+    if classes.is_empty() {
+        return Err(GeneratorError::NoCharacterClasses);
+    }
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&options.length) {
+        return Err(GeneratorError::InvalidLength);
+    }
+
+    let all: Vec<char> = classes.concat();
+    let mut chars = Zeroizing::new(Vec::with_capacity(options.length));
+    for class in &classes {
+        chars.push(*class.choose(rng).unwrap());
+    }
+    while chars.len() < options.length {
+        chars.push(*all.choose(rng).unwrap());
+    }
+    chars.shuffle(rng);
+
+    // Allocated up front, so that growing the string doesn't leave copies around
+    let mut password = Zeroizing::new(String::with_capacity(options.length));
+    password.extend(chars.iter());
+    Ok(password)
+}
+
+/// Generates a passphrase of random words from the EFF large wordlist
+pub fn generate_passphrase(
+    options: &PassphraseOptions,
+    rng: &mut impl Rng,
+) -> Result<Zeroizing<String>, GeneratorError> {
+    if !(MIN_PASSPHRASE_WORDS..=MAX_PASSPHRASE_WORDS).contains(&options.words) {
+        return Err(GeneratorError::InvalidWordCount);
+    }
+
+    let number_index = rng.gen_range(0..options.words);
+    let mut passphrase = Zeroizing::new(String::with_capacity(
+        options.words * (MAX_WORD_LENGTH + 1 + options.separator.len_utf8()),
+    ));
+    for i in 0..options.words {
+        if i > 0 {
+            passphrase.push(options.separator);
+        }
+        let (_, word) = eff_wordlist::large::LIST.choose(rng).unwrap();
+        let mut chars = word.chars();
+        if options.capitalize {
+            passphrase.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        }
+        passphrase.extend(chars);
+        if options.include_number && i == number_index {
+            passphrase.push(char::from_digit(rng.gen_range(0..10), 10).unwrap());
+        }
+    }
+
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_generate_password() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let options = PasswordOptions {
+            length: MIN_PASSWORD_LENGTH,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let password = generate_password(&options, &mut rng).unwrap();
+            assert_eq!(password.len(), MIN_PASSWORD_LENGTH);
+            for class in [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS] {
+                assert!(password.chars().any(|c| class.contains(c)));
+            }
+        }
+
+        let options = PasswordOptions {
+            symbols: false,
+            avoid_ambiguous: true,
+            length: MAX_PASSWORD_LENGTH,
+            ..Default::default()
+        };
+        let password = generate_password(&options, &mut rng).unwrap();
+        assert!(password
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !AMBIGUOUS.contains(c)));
+    }
+
+    #[test]
+    fn test_generate_password_invalid_options() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let options = PasswordOptions {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_password(&options, &mut rng),
+            Err(GeneratorError::NoCharacterClasses)
+        );
+
+        let options = PasswordOptions {
+            length: MAX_PASSWORD_LENGTH + 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_password(&options, &mut rng),
+            Err(GeneratorError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn test_generate_passphrase() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let options = PassphraseOptions {
+            words: 4,
+            separator: ' ',
+            capitalize: true,
+            include_number: true,
+        };
+        let passphrase = generate_passphrase(&options, &mut rng).unwrap();
+        let words: Vec<_> = passphrase.split(' ').collect();
+        assert_eq!(words.len(), 4);
+        assert!(words
+            .iter()
+            .all(|w| w.starts_with(|c: char| c.is_ascii_uppercase())));
+        assert_eq!(
+            words
+                .iter()
+                .filter(|w| w.ends_with(|c: char| c.is_ascii_digit()))
+                .count(),
+            1
+        );
+
+        let options = PassphraseOptions {
+            words: MAX_PASSPHRASE_WORDS + 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_passphrase(&options, &mut rng),
+            Err(GeneratorError::InvalidWordCount)
+        );
+    }
+}
@@ -1,3 +1,10 @@
 pub mod bitwarden;
+pub mod cli;
+pub mod datetime;
+pub mod export;
+pub mod generator;
+pub mod pinentry;
 pub mod profile;
+pub mod report;
+pub mod scrub;
 pub mod ui;
@@ -1,18 +1,34 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use clap::{
-    builder::{StringValueParser, TypedValueParser},
-    Parser,
+    builder::{PathBufValueParser, StringValueParser, TypedValueParser},
+    error::ErrorKind,
+    parser::ValueSource,
+    ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand,
 };
-use indicatif::ProgressBar;
 use reqwest::Url;
-use tabled::{settings::Style, Table, Tabled};
 use wden::{
     bitwarden::{
-        apikey::ApiKey,
+        api::DeviceClass,
+        pinning,
         server::{BitwardenCloudRegion, ServerConfiguration},
     },
-    profile::ProfileStore,
+    cli::{
+        self,
+        export::ExportArgs,
+        generate::GenerateArgs,
+        item::{GetArgs, ItemCommand, ShowArgs, TotpArgs},
+        report::ReportArgs,
+        stats::StatsArgs,
+        sync::SyncArgs,
+    },
+    datetime::{validate_date_format, validate_time_zone, DateStyle},
+    profile::{
+        self, AlertStyle, CardNumberFormat, ClipboardBackend, EnterAction, EnvConfig,
+        ProfileEnvironment, ProfileOverrides, ProfileStore, ENV_PROFILE,
+    },
+    scrub::scrub,
+    ui::{launch::Backend, GlyphSet},
 };
 
 fn validate_profile_name(value: String) -> Result<String, &'static str> {
@@ -26,9 +42,80 @@ fn validate_profile_name(value: String) -> Result<String, &'static str> {
     }
 }
 
+fn parse_time_zone(value: String) -> Result<String, String> {
+    validate_time_zone(&value).map(|()| value)
+}
+
+fn parse_date_format(value: String) -> Result<String, String> {
+    validate_date_format(&value).map(|()| value)
+}
+
+fn validate_spki_pin(value: String) -> Result<String, &'static str> {
+    if pinning::is_valid_pin(&value) {
+        Ok(value)
+    } else {
+        Err("Invalid key pin. The pin must be a base64-encoded SHA-256 hash.")
+    }
+}
+
+fn parse_uri_handler(value: String) -> Result<(String, String), &'static str> {
+    match value.split_once('=') {
+        Some((scheme, command))
+            if !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) =>
+        {
+            Ok((scheme.to_ascii_lowercase(), command.trim().to_string()))
+        }
+        _ => Err("Invalid URI handler. The handler must be given as SCHEME=COMMAND."),
+    }
+}
+
+fn validate_keyfile(value: PathBuf) -> Result<PathBuf, &'static str> {
+    match std::fs::canonicalize(value) {
+        Ok(path) if path.is_file() => Ok(path),
+        _ => Err("Keyfile not found."),
+    }
+}
+
+/// Fills in the options that were not given on the command line from the
+/// environment. Returns the server configuration from the environment,
+/// which is only used if no server options were given.
+fn apply_env_config(
+    opts: &mut Opts,
+    matches: &ArgMatches,
+    env_config: EnvConfig,
+) -> Option<ServerConfiguration> {
+    let profile_from_cli = matches.value_source("profile") == Some(ValueSource::CommandLine);
+    match env_config.profile {
+        Some(profile) if !profile_from_cli => {
+            opts.profile = validate_profile_name(profile).unwrap_or_else(|e| {
+                Opts::command()
+                    .error(ErrorKind::InvalidValue, format!("{ENV_PROFILE}: {e}"))
+                    .exit()
+            });
+        }
+        None if opts.profile_from_env => Opts::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                format!("--profile-from-env was given, but {ENV_PROFILE} is not set"),
+            )
+            .exit(),
+        _ => {}
+    }
+
+    opts.accept_invalid_certs |= env_config.accept_invalid_certs;
+
+    env_config.server_configuration
+}
+
 #[derive(Parser)]
 #[command(version)]
 struct Opts {
+    #[command(subcommand)]
+    subcommand: Option<Subcommands>,
+
     /// Sets the profile that will be used.
     /// 
     /// Profile names can only include lowercase alphanumeric characters, dashes (-) and
@@ -39,6 +126,13 @@ struct Opts {
         value_parser=StringValueParser::new().try_map(validate_profile_name))]
     profile: String,
 
+    /// Reads the profile name from the WDEN_PROFILE environment variable, failing if it's not set
+    ///
+    /// WDEN_PROFILE is also used without this option when --profile is not given. This option
+    /// makes sure that the default profile is not used by accident, e.g. in scripts.
+    #[arg(long, conflicts_with = "profile")]
+    profile_from_env: bool,
+
     /// Sets the current profile to use the given Bitwarden
     /// cloud server region.
     ///
@@ -112,20 +206,532 @@ struct Opts {
     #[arg(long, help_heading=Some("Advanced options"))]
     accept_invalid_certs: bool,
 
+    /// Danger: Allows connecting to a server with a plain http:// URL
+    ///
+    /// Without TLS, the master password hash and the access tokens are sent unencrypted,
+    /// and anyone on the network can read them. Only use this for testing.
+    ///
+    /// Note: this option is not stored in the profile settings.
+    /// It must be specified every time when connecting to an HTTP server.
+    #[arg(long, help_heading=Some("Advanced options"))]
+    allow_insecure_http: bool,
+
+    /// Pins the server certificate public key for the current profile
+    ///
+    /// The value is the base64-encoded SHA-256 hash of the server certificate's
+    /// public key (SubjectPublicKeyInfo). The option may be given multiple times,
+    /// e.g. to pin both the current and a backup key. The pins are stored in the
+    /// profile, and wden refuses to connect to the server if it presents a key that is not pinned.
+    ///
+    /// The pin of a server can be computed with:
+    /// openssl s_client -connect example.com:443 </dev/null | openssl x509 -pubkey -noout |
+    /// openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64
+    #[arg(
+        long,
+        value_name = "SHA256_BASE64",
+        value_parser=StringValueParser::new().try_map(validate_spki_pin),
+        help_heading=Some("Advanced options"))]
+    pin_server_key: Vec<String>,
+
+    /// Removes all pinned server keys from the current profile
+    #[arg(long, conflicts_with="pin_server_key", help_heading=Some("Advanced options"))]
+    clear_server_key_pins: bool,
+
+    /// Sets the command for opening login URIs with the given scheme from the item details
+    ///
+    /// For example --uri-handler 'ssh=alacritty -e ssh -p {port} {username}@{host}'. The
+    /// command is split at whitespace, and {uri}, {host}, {port} and {username} are replaced.
+    /// There are built-in handlers for ssh, rdp and vnc. An empty command disables the scheme.
+    /// The handlers are stored in the profile. Can be given multiple times.
+    #[arg(
+        long,
+        value_name = "SCHEME=COMMAND",
+        value_parser=StringValueParser::new().try_map(parse_uri_handler),
+        help_heading=Some("Advanced options"))]
+    uri_handler: Vec<(String, String)>,
+
+    /// Removes the URI handlers of the current profile, leaving the built-in ones
+    #[arg(long, help_heading=Some("Advanced options"))]
+    clear_uri_handlers: bool,
+
+    /// Lock the vault when it has been idle for the given number of minutes. 0 disables autolock.
+    ///
+    /// The setting is stored in the profile. The default is 5 minutes. A vault timeout policy of
+    /// an organization limits the duration, also when autolock is disabled.
+    #[arg(long, value_name = "MINUTES", help_heading=Some("Locking options"))]
+    autolock: Option<u64>,
+
+    /// Lock the vault when the terminal window has been unfocused for the given number of seconds
+    ///
+    /// This complements the idle-based autolock. 0 locks as soon as the focus is lost.
+    /// The setting is stored in the profile.
+    /// Focus loss is detected from the focus reports of the terminal with the crossterm
+    /// backend. Otherwise it can only be detected on X11, with terminals that set the
+    /// WINDOWID environment variable.
+    #[arg(long, value_name = "SECONDS", help_heading=Some("Locking options"))]
+    lock_on_focus_loss: Option<u64>,
+
+    /// Disables locking the vault on terminal focus loss for the current profile
+    #[arg(long, conflicts_with="lock_on_focus_loss", help_heading=Some("Locking options"))]
+    no_lock_on_focus_loss: bool,
+
+    /// Requires the given keyfile, in addition to the master password, for signing in and unlocking
+    ///
+    /// The keyfile contents are mixed into the master key to create a verifier, which is stored
+    /// in the profile on the first successful sign in. The keys stored locally for unlocking
+    /// (the offline vault cache and the keyring unlock key) are encrypted with keys derived from
+    /// the keyfile. The Bitwarden server does not know about the keyfile. The keyfile path is
+    /// stored in the profile.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_parser=PathBufValueParser::new().try_map(validate_keyfile),
+        help_heading=Some("Locking options"))]
+    keyfile: Option<PathBuf>,
+
+    /// Removes the keyfile requirement from the current profile. The current keyfile must be
+    /// given with --keyfile.
+    #[arg(long, requires="keyfile", help_heading=Some("Locking options"))]
+    no_keyfile: bool,
+
+    /// Stores a command whose output is used as the master password by the subcommands
+    ///
+    /// The first line of the output is used, e.g. with --password-command "pass show bw-master".
+    /// The command is run with a shell, with the profile name in the WDEN_PROFILE environment
+    /// variable. It's used by sync, totp, get, show and copy when they are not given their own
+    /// --password-command. The UI still asks for the password. The command is stored in the
+    /// profile.
+    #[arg(long, value_name = "COMMAND", help_heading=Some("Locking options"))]
+    password_command: Option<String>,
+
+    /// Removes the stored password command from the current profile
+    #[arg(long, conflicts_with="password_command", help_heading=Some("Locking options"))]
+    no_password_command: bool,
+
+    /// Asks for the master password with the given pinentry program in the subcommands
+    ///
+    /// E.g. --pinentry pinentry-curses or --pinentry pinentry-gnome3. The pinentry programs of
+    /// GnuPG grab the keyboard and keep the password out of the terminal. A password command
+    /// is used instead if one is set. The UI still asks for the password itself. The program
+    /// is stored in the profile.
+    #[arg(long, value_name = "PROGRAM", help_heading=Some("Locking options"))]
+    pinentry: Option<String>,
+
+    /// Removes the stored pinentry program from the current profile
+    #[arg(long, conflicts_with="pinentry", help_heading=Some("Locking options"))]
+    no_pinentry: bool,
+
+    /// Drops the master key from memory after deriving the vault keys on unlock
+    ///
+    /// By default, the master key is kept in memory (encrypted with a session key) while the
+    /// vault is unlocked, and the vault keys are derived from it when needed. With this option,
+    /// only the vault keys are kept. If the account keys are rotated on the server while wden
+    /// is running, signing in again is required. The setting is stored in the profile.
+    #[arg(long, conflicts_with="keep_master_key", help_heading=Some("Locking options"))]
+    forget_master_key: bool,
+
+    /// Keeps the master key in memory while the vault is unlocked (default)
+    #[arg(long, help_heading=Some("Locking options"))]
+    keep_master_key: bool,
+
+    /// Stores the master key in the platform keystore for unlocking without the password.
+    /// REDUCES SECURITY
+    ///
+    /// The master key is stored in the Secret Service (Linux) or the Credential Manager
+    /// (Windows) when the vault is unlocked with the password. When starting with --offline,
+    /// the cached vault is then unlocked with the stored key, without asking for the password.
+    /// Anyone with access to your user session can read the key and decrypt the vault. Only use
+    /// this in low-threat environments. The setting is stored in the profile.
+    #[arg(long, conflicts_with="no_keyring_unlock", help_heading=Some("Locking options"))]
+    keyring_unlock: bool,
+
+    /// Disables keyring unlock and removes the master key from the platform keystore
+    #[arg(long, help_heading=Some("Locking options"))]
+    no_keyring_unlock: bool,
+
+    /// Remembers the login session in the platform keystore. REDUCES SECURITY
+    ///
+    /// The refresh token and the encrypted user keys are stored in the Secret Service (Linux)
+    /// or the Credential Manager (Windows) after logging in, so that wden starts with the
+    /// vault locked instead of asking to log in again, also after a reboot. Unlocking still
+    /// needs the password (or --keyring-unlock). Anyone with access to your user session can
+    /// use the refresh token to access the account. Not used with API key logins. The setting
+    /// is stored in the profile.
+    #[arg(long, conflicts_with="no_remember_session", help_heading=Some("Locking options"))]
+    remember_session: bool,
+
+    /// Stops remembering the login session and removes it from the platform keystore
+    #[arg(long, help_heading=Some("Locking options"))]
+    no_remember_session: bool,
+
+    /// Sets how card numbers are formatted when they are copied
+    ///
+    /// The setting is stored in the profile.
+    #[arg(long, value_enum, value_name = "FORMAT", help_heading=Some("Advanced options"))]
+    card_number_format: Option<CardNumberFormat>,
+
+    /// Sets what Enter does on the selected item in the vault view
+    ///
+    /// With "copy-password", Enter copies the password of login items, and other items
+    /// are opened. The details can still be opened with a double click. The setting is
+    /// stored in the profile.
+    #[arg(long, value_enum, value_name = "ACTION", help_heading=Some("Advanced options"))]
+    enter_action: Option<EnterAction>,
+
+    /// Sets how failed unlocks, sync errors and clipboard clearing are signaled
+    ///
+    /// With "bell", the terminal bell is rung, and with "visual", the terminal screen is
+    /// flashed. The dialogs are shown either way. Whether the bell makes a sound or the
+    /// screen flash is visible depends on the terminal. The setting is stored in the profile.
+    #[arg(long, value_enum, value_name = "STYLE", help_heading=Some("Advanced options"))]
+    alert_style: Option<AlertStyle>,
+
+    /// Sets how dates, e.g. when a password was changed, are shown
+    ///
+    /// With "relative", dates are shown like "3 days ago". The setting is stored in the profile.
+    #[arg(long, value_enum, value_name = "STYLE", help_heading=Some("Advanced options"))]
+    date_style: Option<DateStyle>,
+
+    /// Sets the strftime format of the shown dates
+    ///
+    /// The default is "%Y-%m-%d %H:%M". The setting is stored in the profile.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser=StringValueParser::new().try_map(parse_date_format),
+        conflicts_with="default_date_format",
+        help_heading=Some("Advanced options"))]
+    date_format: Option<String>,
+
+    /// Restores the default date format in the current profile
+    #[arg(long, help_heading=Some("Advanced options"))]
+    default_date_format: bool,
+
+    /// Shows dates in the given time zone instead of the system time zone
+    ///
+    /// The time zone is given as an IANA name, e.g. Europe/Helsinki or UTC. The setting is stored
+    /// in the profile.
+    #[arg(
+        long,
+        value_name = "TIME_ZONE",
+        value_parser=StringValueParser::new().try_map(parse_time_zone),
+        conflicts_with="system_time_zone",
+        help_heading=Some("Advanced options"))]
+    time_zone: Option<String>,
+
+    /// Shows dates in the system time zone again in the current profile
+    #[arg(long, help_heading=Some("Advanced options"))]
+    system_time_zone: bool,
+
+    /// Marks the profile as a production or a test profile
+    ///
+    /// Deleting items and Sends and confirming organization members in a production profile
+    /// needs the profile name typed in, to tell a real vault apart from a lab server. The
+    /// marking is shown in the profile list. The setting is stored in the profile.
+    #[arg(long, value_enum, value_name = "ENV", help_heading=Some("Advanced options"))]
+    environment: Option<ProfileEnvironment>,
+
+    /// Sets the device type that wden reports to the server when logging in
+    ///
+    /// By default, wden logs in as a command line client, like the official Bitwarden CLI.
+    /// With "desktop", it reports the device type of the official desktop app instead, which
+    /// may help if the server treats CLI logins more strictly. The device is still named
+    /// after the operating system, as in the official clients. This does not hide wden from
+    /// the server in any other way, and it may stop working if the server starts checking
+    /// the client. Use at your own risk. The setting is stored in the profile.
+    #[arg(long, value_enum, value_name = "TYPE", help_heading=Some("Advanced options"))]
+    device_type: Option<DeviceClass>,
+
+    /// Syncs the profile settings (e.g. autolock) via an encrypted secure note in the vault
+    ///
+    /// The settings are stored in a secure note named "wden:settings". The locking settings,
+    /// pinned collections, Enter action, date display, card number format, vault table columns
+    /// and alert style are synced. Machine-specific settings (keyfile, keystore, offline cache,
+    /// clipboard backend, URI handlers, commands) and the theme are not. The setting is stored
+    /// in the profile.
+    #[arg(long, conflicts_with="no_sync_settings", help_heading=Some("Advanced options"))]
+    sync_settings: bool,
+
+    /// Disables syncing the profile settings via the vault
+    #[arg(long, help_heading=Some("Advanced options"))]
+    no_sync_settings: bool,
+
+    /// Records unlock and sync durations and vault item counts to a local file
+    ///
+    /// The metrics are only stored locally, next to the profile settings, and can be shown
+    /// with `wden stats`. They help to tell whether the KDF settings or the vault growth
+    /// make unlocking and syncing slower over time. The setting is stored in the profile.
+    #[arg(long, conflicts_with="no_record_metrics", help_heading=Some("Advanced options"))]
+    record_metrics: bool,
+
+    /// Stops recording metrics and removes the recorded metrics
+    #[arg(long, help_heading=Some("Advanced options"))]
+    no_record_metrics: bool,
+
+    /// Starts without network access, using the offline vault cache
+    ///
+    /// Login and token refresh are skipped, and the vault is unlocked from the cache
+    /// stored by --offline-cache. Syncing is not available in offline mode.
+    #[arg(long, conflicts_with="api_key_client_id", help_heading=Some("Offline options"))]
+    offline: bool,
+
+    /// Stores the encrypted vault locally after each sync, for use with --offline
+    ///
+    /// The vault items and keys are stored encrypted as they are received from the server,
+    /// and can only be unlocked with the master password. The setting is stored in the profile.
+    #[arg(long, conflicts_with="no_offline_cache", help_heading=Some("Offline options"))]
+    offline_cache: bool,
+
+    /// Disables the offline vault cache and removes the cached vault
+    #[arg(long, help_heading=Some("Offline options"))]
+    no_offline_cache: bool,
+
+    /// Sets the terminal backend
+    ///
+    /// Changing the backend can help with terminal-specific rendering issues.
+    /// The ncurses and termion backends are only available if wden was built with
+    /// the ncurses-backend or termion-backend features.
+    #[arg(long, value_enum, default_value_t, help_heading=Some("Terminal options"))]
+    backend: Backend,
+
+    /// Sets the glyphs used in the UI
+    ///
+    /// By default, emoji are used if the locale (LC_ALL, LC_CTYPE or LANG) uses
+    /// UTF-8, and ASCII otherwise. Use ascii if the emoji glyphs are not
+    /// rendered correctly by the terminal.
+    #[arg(long, value_enum, default_value_t, help_heading=Some("Terminal options"))]
+    glyphs: GlyphSet,
+
+    /// Sets where copied values are put
+    ///
+    /// With "osc52", values are copied to the clipboard of the terminal with the OSC 52 escape
+    /// sequence. This works over SSH, where there is no usable system clipboard, if the terminal
+    /// supports it. The setting is stored in the profile.
+    #[arg(long, value_enum, value_name = "BACKEND", help_heading=Some("Terminal options"))]
+    clipboard_backend: Option<ClipboardBackend>,
+
+    /// Does not show the profile and the lock state in the terminal window title
+    #[arg(long, help_heading=Some("Terminal options"))]
+    no_terminal_title: bool,
+
     /// Debug option: always do token refresh when syncing.
     #[arg(long, hide(true))]
     always_refresh_token_on_sync: bool,
+
+    /// Debug option: report the places where decrypted data is converted to
+    /// plain String/Vec values outside Zeroizing wrappers when exiting.
+    #[arg(long, hide(true))]
+    audit_secret_conversions: bool,
+}
+
+#[derive(Subcommand)]
+enum Subcommands {
+    /// Refreshes the offline vault cache without starting the UI
+    ///
+    /// Only profiles with the offline cache enabled and a stored API key are synced.
+    Sync(SyncArgs),
+    /// Shows how the unlock and sync durations have changed over time
+    ///
+    /// The metrics are recorded for profiles with --record-metrics.
+    Stats(StatsArgs),
+    /// Prints the current TOTP code of a login item
+    ///
+    /// The item is read from the offline vault cache, so the profile needs --offline-cache.
+    Totp(TotpArgs),
+    /// Prints a field of an item, the password by default
+    ///
+    /// The vault is synced with the stored API key, or read from the offline vault cache if
+    /// the profile has no API key.
+    Get(GetArgs),
+    /// Prints the fields of an item. Passwords and other secrets are masked.
+    Show(ShowArgs),
+    /// Copies a field of an item to the clipboard, the password by default
+    ///
+    /// wden keeps running to serve the clipboard, and clears it after 30 seconds or when
+    /// interrupted.
+    Copy(GetArgs),
+    /// Exports the vault in the formats of the official Bitwarden clients
+    ///
+    /// Only the items of the personal vault are exported, like in the official clients. The
+    /// JSON and CSV exports are unencrypted. The encrypted JSON export is protected with a
+    /// password that is asked for, and can be imported with the official clients.
+    Export(ExportArgs),
+    /// Reports weak, reused and stale passwords, duplicate items and insecure URIs
+    ///
+    /// The report has only the item names, the issues and the password ages, no secrets, so
+    /// that it can be shared.
+    Report(ReportArgs),
+    /// Generates a password, or a passphrase with --words
+    ///
+    /// No profile is needed, and the network is not accessed.
+    Generate(GenerateArgs),
 }
 
 #[tokio::main]
 async fn main() {
-    let opts: Opts = Opts::parse();
+    let matches = Opts::command().get_matches();
+    let mut opts = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(Subcommands::Generate(args)) = opts.subcommand {
+        cli::set_clipboard_backend(&opts.profile, opts.clipboard_backend);
+        if let Err(e) = cli::generate::generate(args).await {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let env_config = EnvConfig::from_env().unwrap_or_else(|e| {
+        Opts::command()
+            .error(ErrorKind::InvalidValue, format!("{e:#}"))
+            .exit()
+    });
+    let env_server_config = apply_env_config(&mut opts, &matches, env_config);
+    // The UI sets the backend itself when it loads the profile, but the
+    // subcommands that copy to the clipboard need it set here
+    cli::set_clipboard_backend(&opts.profile, opts.clipboard_backend);
+
+    if opts.audit_secret_conversions {
+        wden::bitwarden::cipher::audit::enable();
+    }
 
     if opts.list_profiles {
-        list_profiles().unwrap();
+        if let Err(e) = cli::profiles::list_profiles() {
+            eprintln!("Reading the profiles failed: {e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // A --password-command given before the subcommand applies to it, if
+    // it doesn't have its own. It's only stored in the profile by the UI.
+    let subcommand_password_command = match &mut opts.subcommand {
+        Some(Subcommands::Sync(args)) => Some(&mut args.password_command),
+        Some(Subcommands::Totp(args)) => Some(&mut args.password_command),
+        Some(Subcommands::Get(args) | Subcommands::Copy(args)) => {
+            Some(&mut args.item.unlock.password_command)
+        }
+        Some(Subcommands::Show(args)) => Some(&mut args.item.unlock.password_command),
+        Some(Subcommands::Export(args)) => Some(&mut args.unlock.password_command),
+        Some(Subcommands::Report(args)) => Some(&mut args.unlock.password_command),
+        _ => None,
+    };
+    if let Some(command) = subcommand_password_command {
+        if command.is_none() {
+            command.clone_from(&opts.password_command);
+        }
+    }
+
+    if let Some(Subcommands::Stats(args)) = opts.subcommand {
+        let profiles = if args.all_profiles {
+            all_profile_names()
+        } else {
+            vec![opts.profile]
+        };
+        if let Err(e) = cli::stats::print_stats(profiles) {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
         return;
     }
 
+    if let Some(Subcommands::Totp(args)) = opts.subcommand {
+        if let Err(e) = cli::item::print_totp(&opts.profile, args, opts.pinentry.as_deref()).await {
+            eprintln!("{}", scrub(&format!("{e:#}")));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let item_command = match opts.subcommand.take() {
+        Some(Subcommands::Get(args)) => Some(ItemCommand::Get(args)),
+        Some(Subcommands::Show(args)) => Some(ItemCommand::Show(args)),
+        Some(Subcommands::Copy(args)) => Some(ItemCommand::Copy(args)),
+        other => {
+            opts.subcommand = other;
+            None
+        }
+    };
+    if let Some(command) = item_command {
+        if let Err(e) = cli::item::item_command(
+            &opts.profile,
+            command,
+            opts.pinentry.as_deref(),
+            opts.accept_invalid_certs,
+            opts.allow_insecure_http,
+        )
+        .await
+        {
+            eprintln!("{}", scrub(&format!("{e:#}")));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Subcommands::Export(args)) = opts.subcommand {
+        if let Err(e) = cli::export::export_vault(
+            &opts.profile,
+            args,
+            opts.pinentry.as_deref(),
+            opts.accept_invalid_certs,
+            opts.allow_insecure_http,
+        )
+        .await
+        {
+            eprintln!("{}", scrub(&format!("{e:#}")));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Subcommands::Report(args)) = opts.subcommand {
+        if let Err(e) = cli::report::report(
+            &opts.profile,
+            args,
+            opts.pinentry.as_deref(),
+            opts.accept_invalid_certs,
+            opts.allow_insecure_http,
+        )
+        .await
+        {
+            eprintln!("{}", scrub(&format!("{e:#}")));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Subcommands::Sync(args)) = opts.subcommand {
+        let profiles = if args.all_profiles {
+            all_profile_names()
+        } else {
+            vec![opts.profile]
+        };
+
+        let all_ok = cli::sync::sync_profiles(
+            profiles,
+            args.password_command.as_deref(),
+            opts.pinentry.as_deref(),
+            opts.accept_invalid_certs,
+            opts.allow_insecure_http,
+        )
+        .await;
+        if !all_ok {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if !opts.backend.is_available() {
+        Opts::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "the {:?} backend is not available in this build of wden",
+                    opts.backend
+                ),
+            )
+            .exit();
+    }
+
     let server_config = if let Some(region) = opts.bitwarden_cloud_region {
         Some(ServerConfiguration::cloud(region))
     } else if let Some(url) = opts.server_url {
@@ -133,167 +739,187 @@ async fn main() {
     } else if let Some((api_url, identity_url)) = opts.api_server_url.zip(opts.identity_server_url)
     {
         Some(ServerConfiguration::separate_hosts(api_url, identity_url))
+    } else {
+        env_server_config
+    };
+
+    let pinned_spki_hashes = if opts.clear_server_key_pins {
+        Some(vec![])
+    } else if !opts.pin_server_key.is_empty() {
+        Some(opts.pin_server_key)
     } else {
         None
     };
 
+    let focus_loss_lock_delay = if opts.no_lock_on_focus_loss {
+        Some(None)
+    } else {
+        opts.lock_on_focus_loss
+            .map(|secs| Some(Duration::from_secs(secs)))
+    };
+
+    // With --no-keyfile, --keyfile is the current keyfile
+    let (keyfile, current_keyfile) = if opts.no_keyfile {
+        (Some(None), opts.keyfile)
+    } else {
+        (opts.keyfile.map(Some), None)
+    };
+
+    let overrides = ProfileOverrides {
+        server_configuration: server_config,
+        pinned_spki_hashes,
+        autolock_duration: opts.autolock.map(|mins| Duration::from_secs(mins * 60)),
+        focus_loss_lock_delay,
+        keyfile,
+        settings_sync: match (opts.sync_settings, opts.no_sync_settings) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        offline_cache: match (opts.offline_cache, opts.no_offline_cache) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        forget_master_key: match (opts.forget_master_key, opts.keep_master_key) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        card_number_format: opts.card_number_format,
+        device_class: opts.device_type,
+        enter_action: opts.enter_action,
+        alert_style: opts.alert_style,
+        environment: opts.environment,
+        clear_uri_handlers: opts.clear_uri_handlers,
+        uri_handlers: opts.uri_handler,
+        date_style: opts.date_style,
+        clipboard_backend: opts.clipboard_backend,
+        date_format: match (opts.date_format, opts.default_date_format) {
+            (Some(format), _) => Some(Some(format)),
+            (_, true) => Some(None),
+            _ => None,
+        },
+        time_zone: match (opts.time_zone, opts.system_time_zone) {
+            (Some(time_zone), _) => Some(Some(time_zone)),
+            (_, true) => Some(None),
+            _ => None,
+        },
+        password_command: match (opts.password_command, opts.no_password_command) {
+            (Some(command), _) => Some(Some(command)),
+            (_, true) => Some(None),
+            _ => None,
+        },
+        pinentry: match (opts.pinentry, opts.no_pinentry) {
+            (Some(program), _) => Some(Some(program)),
+            (_, true) => Some(None),
+            _ => None,
+        },
+        keyring_unlock: match (opts.keyring_unlock, opts.no_keyring_unlock) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        remember_session: match (opts.remember_session, opts.no_remember_session) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        record_metrics: match (opts.record_metrics, opts.no_record_metrics) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+    };
+
+    if let Some(keyfile) = &overrides.keyfile {
+        let profile_store = ProfileStore::new(&opts.profile);
+        if let Err(e) = profile::change_keyfile(
+            &opts.profile,
+            &profile_store,
+            current_keyfile.as_deref(),
+            keyfile.as_deref(),
+        ) {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(server_config) = &overrides.server_configuration {
+        match cli::profiles::confirm_server_change(&opts.profile, server_config) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("Aborted, the profile was not changed.");
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let insecure_http = overrides
+        .server_configuration
+        .clone()
+        .or_else(|| {
+            ProfileStore::new(&opts.profile)
+                .load()
+                .ok()
+                .map(|p| p.server_configuration)
+        })
+        .is_some_and(|c| c.uses_insecure_http());
+    if insecure_http && !opts.allow_insecure_http {
+        Opts::command()
+            .error(
+                ErrorKind::InvalidValue,
+                "the server URL uses unencrypted HTTP, which exposes the master password hash \
+                    to the network. Use an https:// URL, or give --allow-insecure-http to connect anyway",
+            )
+            .exit();
+    }
+
     if let Some(((client_id, client_secret), email)) = opts
         .api_key_client_id
         .zip(opts.api_key_client_secret)
         .zip(opts.api_key_login_email)
     {
-        store_api_keys(
+        if let Err(e) = cli::profiles::store_api_keys(
             opts.profile,
-            server_config,
+            overrides,
             client_id,
             client_secret,
             email,
             opts.accept_invalid_certs,
         )
         .await
-        .unwrap();
+        {
+            eprintln!("Storing the API key failed: {}", scrub(&format!("{e:#}")));
+            std::process::exit(1);
+        }
         return;
     }
 
     let _ph = wden::ui::panic_handler::PanicHandler::new();
-    wden::ui::launch(
-        opts.profile,
-        server_config,
-        opts.accept_invalid_certs,
-        opts.always_refresh_token_on_sync,
-    );
-}
-
-#[derive(Tabled)]
-struct ProfileListRow<'a> {
-    #[tabled(rename = "NAME")]
-    name: &'a str,
-    #[tabled(rename = "SERVER")]
-    server_config: &'a ServerConfiguration,
-    #[tabled(rename = "SAVED EMAIL")]
-    saved_email: &'a str,
-    #[tabled(rename = "API KEY")]
-    api_key: &'static str
-}
-
-fn list_profiles() -> std::io::Result<()> {
-    let profiles = ProfileStore::get_all_profiles()?;
-
-    if profiles.is_empty() {
-        println!("No profiles found.")
-    } else {
-        let rows = profiles.iter().map(|(name, profile)| ProfileListRow {
-            name,
-            server_config: &profile.server_configuration,
-            saved_email: profile.saved_email.as_deref().unwrap_or("None"),
-            api_key: if profile.encrypted_api_key.is_some() { "✓" } else { "" },
-        });
-
-        let mut table = Table::new(rows);
-        table.with(Style::blank());
-
-        println!("{table}");
+    wden::ui::Launcher::new(opts.profile)
+        .overrides(overrides)
+        .accept_invalid_certs(opts.accept_invalid_certs)
+        .always_refresh_token_on_sync(opts.always_refresh_token_on_sync)
+        .offline(opts.offline)
+        .backend(opts.backend)
+        .glyphs(opts.glyphs)
+        .terminal_title(!opts.no_terminal_title)
+        .launch();
+
+    if let Some(report) = wden::bitwarden::cipher::audit::report() {
+        eprintln!("{report}");
     }
-
-    Ok(())
 }
 
-async fn store_api_keys(
-    profile: String,
-    server_config: Option<ServerConfiguration>,
-    client_id: String,
-    client_secret: String,
-    email: String,
-    accept_invalid_certs: bool,
-) -> anyhow::Result<()> {
-    use console::style;
-    use std::io::Write;
-    use wden::bitwarden::cipher;
-
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_message("Loading data...");
-    spinner.enable_steady_tick(Duration::from_millis(200));
-
-    let (global_settings, _profile_data, profile_store) =
-        wden::ui::launch::load_profile(profile, server_config, accept_invalid_certs, false);
-
-    let client = wden::bitwarden::api::ApiClient::new(
-        &global_settings.server_configuration,
-        &global_settings.device_id,
-        global_settings.accept_invalid_certs,
-    );
-
-    let api_key = ApiKey::new(email.clone(), client_id, client_secret);
-
-    let token_res = client.get_token_with_api_key(&api_key).await?;
-    let pbkdf_params = token_res
-        .pbkdf_parameters()
-        .expect("Token response did not include Pbkdf parameters");
-    spinner.finish_and_clear();
-
-    println!(
-        "\n{}",
-        style(":: Enter your master password ::")
-            .bold()
-            .bright()
-            .white()
-    );
-    println!("wden will encrypt the API key with an encryption key derived from your master password, and store it in profile `{}`\n", &global_settings.profile);
-
-    let mut password: String;
-
-    loop {
-        print!(
-            "{}",
-            style(":: Enter master password: ").bold().bright().white()
-        );
-        std::io::stdout().flush().unwrap();
-
-        password = rpassword::read_password()?;
-
-        let spinner = ProgressBar::new_spinner();
-        spinner.set_message("Validating password");
-        spinner.enable_steady_tick(Duration::from_millis(200));
-
-        let check_res = cipher::create_master_key(&email, &password, &pbkdf_params)
-            .and_then(|mk| cipher::decrypt_symmetric_keys(&token_res.key, &mk));
-
-        spinner.finish_and_clear();
-
-        if check_res.is_ok() {
-            break;
-        } else {
-            println!("Invalid password.")
-        }
-    }
-
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_message("Encrypting API key");
-    spinner.enable_steady_tick(Duration::from_millis(200));
-
-    let enc_api_key = api_key.encrypt(&global_settings.profile, &email, &password)?;
-    profile_store
-        .edit(|d| {
-            d.saved_email = Some(email);
-            d.encrypted_api_key = Some(enc_api_key);
-        })
-        .unwrap();
-
-    spinner.finish_and_clear();
-
-    println!(
-        "{}",
-        style(":: API key encrypted and stored ::")
-            .bold()
-            .bright()
-            .white()
-    );
-    println!("You can now start wden with this profile without the API key arguments. Example:");
-    println!(
-        "\t{} --profile {}",
-        std::env::args().next().unwrap(),
-        global_settings.profile
-    );
-
-    Ok(())
+/// The names of all profiles, or exits if they can't be read
+fn all_profile_names() -> Vec<String> {
+    cli::profiles::all_profile_names().unwrap_or_else(|e| {
+        eprintln!("Reading the profiles failed: {e:#}");
+        std::process::exit(1);
+    })
 }
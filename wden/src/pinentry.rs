@@ -0,0 +1,144 @@
+//! Password prompts with a pinentry program (as used by GnuPG), over
+//! the Assuan protocol on the program's stdin and stdout
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+use zeroize::Zeroizing;
+
+/// Assuan error code for a prompt cancelled by the user
+const ERR_CANCELED: &str = "83886179";
+
+/// Maximum length of an Assuan line
+const MAX_LINE_LENGTH: usize = 1000;
+
+/// Asks for a password with the given pinentry program
+pub fn get_pin(
+    program: &str,
+    description: &str,
+    prompt: &str,
+) -> anyhow::Result<Zeroizing<String>> {
+    let mut child = Command::new(program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Starting {program} failed"))?;
+    let mut input = child.stdin.take().context("No pinentry stdin")?;
+    let mut output = BufReader::new(child.stdout.take().context("No pinentry stdout")?);
+
+    let pin = converse(&mut input, &mut output, description, prompt);
+    drop(input);
+    _ = child.wait();
+    pin
+}
+
+fn converse(
+    input: &mut impl Write,
+    output: &mut impl BufRead,
+    description: &str,
+    prompt: &str,
+) -> anyhow::Result<Zeroizing<String>> {
+    // Greeting
+    read_response(output)?;
+    for command in [
+        "SETTITLE wden".to_string(),
+        format!("SETDESC {}", escape(description)),
+        format!("SETPROMPT {}", escape(prompt)),
+    ] {
+        writeln!(input, "{command}")?;
+        input.flush()?;
+        read_response(output)?;
+    }
+
+    writeln!(input, "GETPIN")?;
+    input.flush()?;
+    let pin = read_response(output)?;
+    _ = writeln!(input, "BYE");
+    Ok(pin)
+}
+
+/// Reads lines until OK or ERR, and returns the data lines decoded
+fn read_response(output: &mut impl BufRead) -> anyhow::Result<Zeroizing<String>> {
+    let mut data = Zeroizing::new(String::with_capacity(MAX_LINE_LENGTH));
+    loop {
+        // Allocated up front, so that reading the pin doesn't leave copies around
+        let mut line = Zeroizing::new(String::with_capacity(MAX_LINE_LENGTH + 2));
+        if output.read_line(&mut line)? == 0 {
+            anyhow::bail!("pinentry exited unexpectedly");
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        if line == "OK" || line.starts_with("OK ") {
+            return Ok(data);
+        } else if let Some(d) = line.strip_prefix("D ") {
+            data.push_str(&unescape(d)?);
+        } else if let Some(err) = line.strip_prefix("ERR ") {
+            if err.split(' ').next() == Some(ERR_CANCELED) {
+                anyhow::bail!("The password prompt was cancelled");
+            }
+            anyhow::bail!("pinentry failed: {err}");
+        }
+        // Status lines (S) and comments (#) are ignored
+    }
+}
+
+/// Percent-escapes the characters that can't appear in Assuan lines
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            '\n' => escaped.push_str("%0A"),
+            '\r' => escaped.push_str("%0D"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> anyhow::Result<Zeroizing<String>> {
+    let mut bytes = Zeroizing::new(Vec::with_capacity(s.len()));
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2])?;
+            bytes.push(u8::from_str_radix(hex, 16).context("Invalid escape from pinentry")?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    let s =
+        String::from_utf8(std::mem::take(&mut *bytes)).context("Invalid UTF-8 from pinentry")?;
+    Ok(Zeroizing::new(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_converse() {
+        let mut input = vec![];
+        let mut output = Cursor::new(
+            "OK Pleased to meet you\nOK\nOK\nOK\nS PASSWORD_FROM_CACHE\nD p%25ss word\nOK\n",
+        );
+        let pin = converse(&mut input, &mut output, "Profile\n`default`", "Password:").unwrap();
+        assert_eq!(*pin, "p%ss word");
+        assert_eq!(
+            String::from_utf8(input).unwrap(),
+            "SETTITLE wden\nSETDESC Profile%0A`default`\nSETPROMPT Password:\nGETPIN\nBYE\n"
+        );
+
+        let mut output = Cursor::new("OK\nOK\nOK\nOK\nERR 83886179 Operation cancelled\n");
+        let err = converse(&mut vec![], &mut output, "", "").unwrap_err();
+        assert_eq!(err.to_string(), "The password prompt was cancelled");
+    }
+}
@@ -1,13 +1,23 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    time::Duration,
+};
 
 use anyhow::Context;
+use clap::ValueEnum;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::bitwarden::{
-    apikey::EncryptedApiKey,
-    server::{BitwardenCloudRegion, ServerConfiguration},
+use crate::{
+    bitwarden::{
+        api::DeviceClass,
+        apikey::EncryptedApiKey,
+        cipher::Cipher,
+        server::{BitwardenCloudRegion, ServerConfiguration},
+    },
+    datetime::{DateDisplay, DateStyle},
 };
 
 #[derive(Deserialize, Serialize)]
@@ -18,12 +28,186 @@ pub struct ProfileData {
     // Legacy field
     pub server_url: Option<String>,
     pub saved_two_factor_token: Option<String>,
+    /// Lock the vault after it has been idle for this long. Zero disables autolock.
     pub autolock_duration: Duration,
     pub device_id: String,
     #[serde(default)]
     pub server_configuration: ServerConfiguration,
     #[serde(default)]
     pub encrypted_api_key: Option<EncryptedApiKey>,
+    /// Base64-encoded SHA-256 hashes of the server certificate public keys (SPKI)
+    /// that are accepted for this profile. Empty means no pinning.
+    #[serde(default)]
+    pub pinned_spki_hashes: Vec<String>,
+    /// Lock the vault when the terminal window has been unfocused for this long.
+    /// None disables locking on focus loss.
+    #[serde(default)]
+    pub focus_loss_lock_delay: Option<Duration>,
+    /// Local keyfile that is required in addition to the master password
+    #[serde(default)]
+    pub keyfile: Option<PathBuf>,
+    /// Verifier derived from the master key and the keyfile contents.
+    /// Stored after the first successful login or unlock with the keyfile.
+    #[serde(default)]
+    pub keyfile_verifier: Option<String>,
+    /// A known value encrypted with the keys derived from the keyfile, for
+    /// checking the keyfile without the master key, e.g. before removing it
+    #[serde(default)]
+    pub keyfile_check: Option<Cipher>,
+    /// Sync the settings via a secure note in the vault
+    #[serde(default)]
+    pub settings_sync: bool,
+    /// Time of the latest local change to the synced settings
+    #[serde(default)]
+    pub settings_revision: u64,
+    /// Revision of the settings last pulled from or pushed to the vault
+    #[serde(default)]
+    pub settings_synced_revision: u64,
+    /// Store the encrypted vault locally after syncing, for the offline mode
+    #[serde(default)]
+    pub offline_cache: bool,
+    /// Collection ids pinned to the number keys 1-9 in the vault view
+    #[serde(default)]
+    pub pinned_collections: BTreeMap<u8, String>,
+    /// Drop the master key after deriving the symmetric keys on unlock,
+    /// instead of keeping it (encrypted) in memory while unlocked
+    #[serde(default)]
+    pub forget_master_key: bool,
+    #[serde(default)]
+    pub card_number_format: CardNumberFormat,
+    /// Store the master key in the platform keystore, for unlocking the
+    /// offline vault without the password. Reduced security.
+    #[serde(default)]
+    pub keyring_unlock: bool,
+    /// Store the refresh token and the encrypted user keys in the platform
+    /// keystore, so that logging in is not needed on the next start
+    #[serde(default)]
+    pub remember_session: bool,
+    /// Record unlock and sync durations to a local metrics file
+    #[serde(default)]
+    pub record_metrics: bool,
+    /// The device type reported to the server when logging in
+    #[serde(default)]
+    pub device_class: DeviceClass,
+    #[serde(default)]
+    pub enter_action: EnterAction,
+    /// The warning about copying over a forwarded or remote display
+    /// has been dismissed with "Don't show again"
+    #[serde(default)]
+    pub hide_remote_clipboard_warning: bool,
+    /// Collection ids marked as sensitive, see `ui::policy`
+    #[serde(default)]
+    pub sensitive_collections: BTreeSet<String>,
+    /// Show the folder of each item in the vault table
+    #[serde(default)]
+    pub show_folder_column: bool,
+    /// Show when the password of each login was last changed
+    #[serde(default)]
+    pub show_password_changed_column: bool,
+    #[serde(default)]
+    pub alert_style: AlertStyle,
+    /// Command whose output is used as the master password by the
+    /// command line subcommands. Not used by the UI.
+    #[serde(default)]
+    pub password_command: Option<String>,
+    /// pinentry program used for the password prompts of the command
+    /// line subcommands, instead of reading from the terminal
+    #[serde(default)]
+    pub pinentry: Option<String>,
+    /// Whether the profile is for a production or a test vault. Not synced.
+    #[serde(default)]
+    pub environment: ProfileEnvironment,
+    /// Commands for opening login URIs by scheme, e.g. "ssh". These replace
+    /// the built-in handlers, and an empty command disables the scheme.
+    #[serde(default)]
+    pub uri_handlers: BTreeMap<String, String>,
+    /// Ids of the items pinned to the top of the vault table, see
+    /// `ui::pinned_items`. Encrypted with the user key.
+    #[serde(default)]
+    pub pinned_items: Cipher,
+    /// Templates for new items, see `ui::item_templates`. Encrypted with
+    /// the user key.
+    #[serde(default)]
+    pub item_templates: Cipher,
+    #[serde(default)]
+    pub date_style: DateStyle,
+    /// strftime format of the shown dates, the default format if not set
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// Time zone of the shown dates, the system time zone if not set
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    #[serde(default)]
+    pub clipboard_backend: ClipboardBackend,
+}
+
+/// How card numbers are formatted when they are copied
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum CardNumberFormat {
+    /// As stored in the vault
+    #[default]
+    AsStored,
+    /// Digits only, spaces removed
+    Digits,
+    /// Digits in groups of four
+    Grouped,
+}
+
+/// Where copied values are put
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum ClipboardBackend {
+    /// The clipboard of the operating system or the display server
+    #[default]
+    System,
+    /// The clipboard of the terminal, set with the OSC 52 escape sequence.
+    /// Works over SSH.
+    Osc52,
+}
+
+/// What Enter does on the selected item in the vault table
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum EnterAction {
+    /// Opens the item details
+    #[default]
+    Details,
+    /// Copies the password of login items. Other items are opened.
+    CopyPassword,
+}
+
+/// How failed unlocks, sync errors and clipboard clearing are signaled,
+/// in addition to the dialogs
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum AlertStyle {
+    /// Only the dialogs
+    #[default]
+    None,
+    /// Rings the terminal bell
+    Bell,
+    /// Flashes the terminal screen
+    Visual,
+}
+
+/// Marks profiles of production and test vaults apart. Destructive
+/// operations on production profiles need the profile name typed in.
+#[derive(Clone, Copy, Default, Serialize, Deserialize, ValueEnum, PartialEq, Eq, Debug)]
+pub enum ProfileEnvironment {
+    /// Not marked, destructive operations are confirmed as usual
+    #[default]
+    Unmarked,
+    /// Destructive operations need the profile name typed in
+    Production,
+    /// A lab or test vault, destructive operations are confirmed as usual
+    Test,
+}
+
+impl ProfileEnvironment {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProfileEnvironment::Unmarked => "",
+            ProfileEnvironment::Production => "production",
+            ProfileEnvironment::Test => "test",
+        }
+    }
 }
 
 impl Default for ProfileData {
@@ -37,11 +221,60 @@ impl Default for ProfileData {
             device_id: format!("{}", Uuid::new_v4()),
             server_configuration: Default::default(),
             encrypted_api_key: None,
+            pinned_spki_hashes: vec![],
+            focus_loss_lock_delay: None,
+            keyfile: None,
+            keyfile_verifier: None,
+            keyfile_check: None,
+            settings_sync: false,
+            settings_revision: 0,
+            settings_synced_revision: 0,
+            offline_cache: false,
+            pinned_collections: BTreeMap::new(),
+            forget_master_key: false,
+            keyring_unlock: false,
+            remember_session: false,
+            record_metrics: false,
+            device_class: DeviceClass::default(),
+            card_number_format: CardNumberFormat::default(),
+            enter_action: EnterAction::default(),
+            hide_remote_clipboard_warning: false,
+            sensitive_collections: BTreeSet::new(),
+            show_folder_column: false,
+            show_password_changed_column: false,
+            alert_style: AlertStyle::default(),
+            password_command: None,
+            pinentry: None,
+            environment: ProfileEnvironment::default(),
+            uri_handlers: BTreeMap::new(),
+            pinned_items: Cipher::Empty,
+            item_templates: Cipher::Empty,
+            date_style: DateStyle::default(),
+            date_format: None,
+            time_zone: None,
+            clipboard_backend: ClipboardBackend::default(),
         }
     }
 }
 
 impl ProfileData {
+    pub fn date_display(&self) -> DateDisplay {
+        DateDisplay {
+            style: self.date_style,
+            format: self.date_format.clone(),
+            time_zone: self.time_zone.clone(),
+        }
+    }
+
+    /// Removes the stored data that is tied to the current server:
+    /// the remembered two-factor token, the API key and the key pins.
+    pub fn clear_server_credentials(&mut self) {
+        self.saved_two_factor_token = None;
+        self.encrypted_api_key = None;
+        self.pinned_spki_hashes.clear();
+        self.settings_synced_revision = 0;
+    }
+
     pub fn run_migrations(mut self) -> Result<Self, anyhow::Error> {
         // Version 0: initial. Default if the version field is missing.
         // Version 1: server_url -> server_configuration
@@ -0,0 +1,128 @@
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use reqwest::Url;
+
+use crate::bitwarden::server::{BitwardenCloudRegion, ServerConfiguration};
+
+pub const ENV_PROFILE: &str = "WDEN_PROFILE";
+const ENV_BITWARDEN_CLOUD_REGION: &str = "WDEN_BITWARDEN_CLOUD_REGION";
+const ENV_SERVER_URL: &str = "WDEN_SERVER_URL";
+const ENV_API_SERVER_URL: &str = "WDEN_API_SERVER_URL";
+const ENV_IDENTITY_SERVER_URL: &str = "WDEN_IDENTITY_SERVER_URL";
+const ENV_ACCEPT_INVALID_CERTS: &str = "WDEN_ACCEPT_INVALID_CERTS";
+
+/// Options read from the WDEN_* environment variables. These are
+/// only used for the options that are not given on the command line.
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct EnvConfig {
+    pub profile: Option<String>,
+    pub server_configuration: Option<ServerConfiguration>,
+    pub accept_invalid_certs: bool,
+}
+
+impl EnvConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        // Treat empty variables as unset
+        let var = |name: &str| lookup(name).filter(|v| !v.is_empty());
+        let url = |name: &str| -> anyhow::Result<Option<Url>> {
+            var(name)
+                .map(|v| Url::parse(&v).with_context(|| format!("Invalid URL in {name}")))
+                .transpose()
+        };
+
+        let region = var(ENV_BITWARDEN_CLOUD_REGION)
+            .map(|v| {
+                BitwardenCloudRegion::from_str(&v, true).map_err(|_| {
+                    anyhow::anyhow!("Invalid region in {ENV_BITWARDEN_CLOUD_REGION}: {v}")
+                })
+            })
+            .transpose()?;
+        let server_url = url(ENV_SERVER_URL)?;
+        let api_server_url = url(ENV_API_SERVER_URL)?;
+        let identity_server_url = url(ENV_IDENTITY_SERVER_URL)?;
+
+        let server_configuration = match (region, server_url, api_server_url, identity_server_url) {
+            (None, None, None, None) => None,
+            (Some(region), None, None, None) => Some(ServerConfiguration::cloud(region)),
+            (None, Some(url), None, None) => Some(ServerConfiguration::single_host(url)),
+            (None, None, Some(api), Some(identity)) => {
+                Some(ServerConfiguration::separate_hosts(api, identity))
+            }
+            (None, None, Some(_), None) | (None, None, None, Some(_)) => {
+                bail!("{ENV_API_SERVER_URL} and {ENV_IDENTITY_SERVER_URL} must be set together")
+            }
+            _ => bail!(
+                "Only one of {ENV_BITWARDEN_CLOUD_REGION}, {ENV_SERVER_URL} or \
+                {ENV_API_SERVER_URL} may be set"
+            ),
+        };
+
+        let accept_invalid_certs = match var(ENV_ACCEPT_INVALID_CERTS).as_deref() {
+            None | Some("0" | "false" | "no") => false,
+            Some("1" | "true" | "yes") => true,
+            Some(v) => bail!("Invalid value in {ENV_ACCEPT_INVALID_CERTS}: {v}"),
+        };
+
+        Ok(EnvConfig {
+            profile: var(ENV_PROFILE),
+            server_configuration,
+            accept_invalid_certs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(vars: &[(&str, &str)]) -> anyhow::Result<EnvConfig> {
+        EnvConfig::from_lookup(|name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn test_env_config() {
+        assert_eq!(parse(&[]).unwrap(), EnvConfig::default());
+
+        let config = parse(&[
+            ("WDEN_PROFILE", "work"),
+            ("WDEN_SERVER_URL", "https://bitwarden.example.com"),
+            ("WDEN_ACCEPT_INVALID_CERTS", "1"),
+        ])
+        .unwrap();
+        assert_eq!(config.profile.as_deref(), Some("work"));
+        assert_eq!(
+            config.server_configuration,
+            Some(ServerConfiguration::single_host(
+                Url::parse("https://bitwarden.example.com").unwrap()
+            ))
+        );
+        assert!(config.accept_invalid_certs);
+
+        let config = parse(&[("WDEN_BITWARDEN_CLOUD_REGION", "eu"), ("WDEN_PROFILE", "")]).unwrap();
+        assert_eq!(config.profile, None);
+        assert_eq!(
+            config.server_configuration,
+            Some(ServerConfiguration::cloud(BitwardenCloudRegion::EU))
+        );
+    }
+
+    #[test]
+    fn test_env_config_invalid() {
+        assert!(parse(&[("WDEN_SERVER_URL", "not a url")]).is_err());
+        assert!(parse(&[("WDEN_API_SERVER_URL", "https://api.example.com")]).is_err());
+        assert!(parse(&[
+            ("WDEN_BITWARDEN_CLOUD_REGION", "us"),
+            ("WDEN_SERVER_URL", "https://bitwarden.example.com"),
+        ])
+        .is_err());
+        assert!(parse(&[("WDEN_ACCEPT_INVALID_CERTS", "maybe")]).is_err());
+    }
+}
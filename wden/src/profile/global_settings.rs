@@ -1,13 +1,37 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
 
-use crate::bitwarden::{apikey::EncryptedApiKey, server::ServerConfiguration};
+use crate::{
+    bitwarden::{api::DeviceClass, apikey::EncryptedApiKey, server::ServerConfiguration},
+    datetime::DateDisplay,
+};
+
+use super::{AlertStyle, CardNumberFormat, ClipboardBackend, EnterAction, ProfileEnvironment};
 
 pub struct GlobalSettings {
     pub server_configuration: ServerConfiguration,
     pub profile: String,
     pub autolock_duration: Duration,
     pub device_id: String,
+    pub device_class: DeviceClass,
     pub accept_invalid_certs: bool,
     pub always_refresh_token_on_sync: bool,
     pub encrypted_api_key: Option<EncryptedApiKey>,
+    pub pinned_spki_hashes: Vec<String>,
+    pub focus_loss_lock_delay: Option<Duration>,
+    pub keyfile: Option<PathBuf>,
+    pub settings_sync: bool,
+    pub offline_cache: bool,
+    pub forget_master_key: bool,
+    pub card_number_format: CardNumberFormat,
+    pub enter_action: EnterAction,
+    pub alert_style: AlertStyle,
+    pub environment: ProfileEnvironment,
+    pub keyring_unlock: bool,
+    pub remember_session: bool,
+    pub record_metrics: bool,
+    pub uri_handlers: BTreeMap<String, String>,
+    pub date_display: DateDisplay,
+    pub clipboard_backend: ClipboardBackend,
+    /// Started with --offline: no network access, the vault is read from the cache
+    pub offline: bool,
 }
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::cipher::{self, constant_time, Cipher, EncMacKeys, MasterKey},
+    ui::util::keystore,
+};
+
+use super::ProfileStore;
+
+/// Encrypted with the keyfile keys and stored in the profile, for checking
+/// the keyfile when the master key is not known
+const KEYFILE_CHECK_VALUE: &[u8] = b"wden-keyfile-check";
+
+fn read_keyfile(keyfile: &Path) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let contents = Zeroizing::new(
+        std::fs::read(keyfile)
+            .with_context(|| format!("Reading keyfile {} failed", keyfile.display()))?,
+    );
+    if contents.is_empty() {
+        bail!("The keyfile {} is empty", keyfile.display());
+    }
+    Ok(contents)
+}
+
+/// Reads the keyfile and derives the keys that protect the locally
+/// stored unlock keys with it
+pub fn keyfile_keys(keyfile: &Path) -> anyhow::Result<EncMacKeys> {
+    Ok(cipher::create_keyfile_keys(&read_keyfile(keyfile)?))
+}
+
+/// Checks the keyfile against the verifier stored in the profile.
+///
+/// The master key must already have been verified to be correct: if the
+/// profile does not have a verifier yet, one is created from the keyfile
+/// and stored.
+pub fn verify_keyfile(
+    keyfile: &Path,
+    master_key: &MasterKey,
+    profile_store: &ProfileStore,
+) -> anyhow::Result<()> {
+    let contents = read_keyfile(keyfile)?;
+    let verifier = cipher::create_keyfile_verifier(master_key, &contents);
+
+    let profile_data = profile_store.load()?;
+    match profile_data.keyfile_verifier {
+        Some(stored) if constant_time::eq(stored.as_bytes(), verifier.as_bytes()) => {
+            if profile_data.keyfile_check.is_none() {
+                store_keyfile_check(&contents, profile_store)?;
+            }
+            Ok(())
+        }
+        Some(_) => bail!(
+            "The keyfile {} does not match the keyfile configured for this profile",
+            keyfile.display()
+        ),
+        None => {
+            log::info!("Storing keyfile verifier");
+            profile_store.edit(|d| d.keyfile_verifier = Some(verifier))?;
+            store_keyfile_check(&contents, profile_store)
+        }
+    }
+}
+
+fn store_keyfile_check(contents: &[u8], profile_store: &ProfileStore) -> anyhow::Result<()> {
+    let keys = cipher::create_keyfile_keys(contents);
+    let check = Cipher::encrypt(KEYFILE_CHECK_VALUE, &keys)?;
+    profile_store.edit(|d| d.keyfile_check = Some(check))
+}
+
+/// Checks that the keyfile of the profile can be replaced with `new`
+/// (`None` removes the requirement). If the profile has a keyfile, the
+/// current keyfile has to be given in `current`. The offline vault cache
+/// is then stored again without the keyfile protection, and the master key
+/// in the keystore is encrypted again with the new keyfile.
+pub fn change_keyfile(
+    profile: &str,
+    profile_store: &ProfileStore,
+    current: Option<&Path>,
+    new: Option<&Path>,
+) -> anyhow::Result<()> {
+    let Ok(profile_data) = profile_store.load() else {
+        // A new profile
+        return Ok(());
+    };
+    if profile_data.keyfile.as_deref() == new {
+        return Ok(());
+    }
+    let Some(configured) = &profile_data.keyfile else {
+        // Nothing is protected with a keyfile yet
+        if profile_data.keyring_unlock {
+            rewrap_keystore_key(profile, None, new);
+        }
+        return Ok(());
+    };
+    let Some(current) = current else {
+        bail!(
+            "The profile requires a keyfile. Remove the requirement first by giving the \
+             current keyfile: --no-keyfile --keyfile <PATH>"
+        );
+    };
+    let mismatch = || {
+        anyhow::anyhow!(
+            "The keyfile {} does not match the keyfile configured for this profile",
+            current.display()
+        )
+    };
+
+    match &profile_data.keyfile_check {
+        Some(check) => {
+            let keys = keyfile_keys(current)?;
+            let value = check.decrypt(&keys).map_err(|_| mismatch())?;
+            if !constant_time::eq(&value, KEYFILE_CHECK_VALUE) {
+                bail!("The keyfile check value stored in the profile is not valid");
+            }
+        }
+        None => {
+            // The keyfile has not been used yet, so it is compared with
+            // the configured file directly
+            let contents = read_keyfile(current)?;
+            let configured_contents = read_keyfile(configured)?;
+            if !constant_time::eq(&contents, &configured_contents) {
+                return Err(mismatch());
+            }
+        }
+    }
+
+    if let Some(cache) = profile_store.load_vault_cache(Some(current))? {
+        log::info!("Storing the offline vault cache without the keyfile");
+        profile_store.store_vault_cache(cache, None)?;
+    }
+    if profile_data.keyring_unlock {
+        rewrap_keystore_key(profile, Some(current), new);
+    }
+    Ok(())
+}
+
+/// Encrypts the master key stored for keyring unlock again with the new
+/// keyfile. If that fails, the key is removed, so that the old keyfile
+/// can't be used for unlocking anymore.
+fn rewrap_keystore_key(profile: &str, current: Option<&Path>, new: Option<&Path>) {
+    let keystore = keystore::get_platform_keystore();
+    let res = keystore
+        .load_master_key(profile, current)
+        .and_then(|master_key| match master_key {
+            Some(master_key) => keystore.store_master_key(profile, &master_key, new),
+            None => Ok(()),
+        });
+    if let Err(e) = res {
+        log::warn!("Storing the master key with the new keyfile failed: {e:#}");
+        if let Err(e) = keystore.delete_master_key(profile) {
+            log::warn!("Removing the master key from the keystore failed: {e:#}");
+        }
+    }
+}
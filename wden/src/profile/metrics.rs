@@ -0,0 +1,134 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::ProfileStore;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    /// Deriving the master key and unlocking the vault
+    Unlock,
+    Sync,
+}
+
+/// One timed operation, stored as a line in the metrics file
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct MetricsRecord {
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+    pub kind: MetricKind,
+    pub duration_ms: u64,
+    pub item_count: Option<usize>,
+}
+
+impl MetricsRecord {
+    pub fn now(kind: MetricKind, duration: Duration, item_count: Option<usize>) -> Self {
+        MetricsRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            kind,
+            duration_ms: duration.as_millis() as u64,
+            item_count,
+        }
+    }
+}
+
+/// Appends a record to the metrics file of the profile. Failures are
+/// only logged, the metrics are not important enough to interrupt anything.
+pub fn record_metric(
+    store: &ProfileStore,
+    kind: MetricKind,
+    duration: Duration,
+    item_count: Option<usize>,
+) {
+    let record = MetricsRecord::now(kind, duration, item_count);
+    if let Err(e) = store.append_metrics_record(&record) {
+        log::warn!("Recording metrics failed: {e}");
+    }
+}
+
+/// How the duration of an operation has changed over the recorded history
+#[derive(PartialEq, Eq, Debug)]
+pub struct MetricsTrend {
+    pub kind: MetricKind,
+    pub count: usize,
+    /// Average duration of the oldest records
+    pub first_avg_ms: u64,
+    /// Average duration of the newest records
+    pub last_avg_ms: u64,
+    pub latest_item_count: Option<usize>,
+}
+
+/// Number of records averaged at both ends of the history
+const TREND_WINDOW: usize = 5;
+
+pub fn metrics_trends(records: &[MetricsRecord]) -> Vec<MetricsTrend> {
+    let average = |records: &[&MetricsRecord]| {
+        records.iter().map(|r| r.duration_ms).sum::<u64>() / records.len().max(1) as u64
+    };
+
+    [MetricKind::Unlock, MetricKind::Sync]
+        .into_iter()
+        .filter_map(|kind| {
+            let mut of_kind: Vec<_> = records.iter().filter(|r| r.kind == kind).collect();
+            if of_kind.is_empty() {
+                return None;
+            }
+            of_kind.sort_by_key(|r| r.timestamp);
+            let window = TREND_WINDOW.min(of_kind.len());
+            Some(MetricsTrend {
+                kind,
+                count: of_kind.len(),
+                first_avg_ms: average(&of_kind[..window]),
+                last_avg_ms: average(&of_kind[of_kind.len() - window..]),
+                latest_item_count: of_kind.iter().rev().find_map(|r| r.item_count),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: u64, kind: MetricKind, duration_ms: u64) -> MetricsRecord {
+        MetricsRecord {
+            timestamp,
+            kind,
+            duration_ms,
+            item_count: Some(timestamp as usize),
+        }
+    }
+
+    #[test]
+    fn test_metrics_trends() {
+        let mut records: Vec<_> = (0..10)
+            .map(|i| record(i, MetricKind::Sync, 100 + i * 10))
+            .collect();
+        records.push(record(3, MetricKind::Unlock, 500));
+
+        let trends = metrics_trends(&records);
+        assert_eq!(
+            trends,
+            vec![
+                MetricsTrend {
+                    kind: MetricKind::Unlock,
+                    count: 1,
+                    first_avg_ms: 500,
+                    last_avg_ms: 500,
+                    latest_item_count: Some(3),
+                },
+                MetricsTrend {
+                    kind: MetricKind::Sync,
+                    count: 10,
+                    first_avg_ms: 120,
+                    last_avg_ms: 170,
+                    latest_item_count: Some(9),
+                },
+            ]
+        );
+    }
+}
@@ -1,7 +1,21 @@
 mod data;
+mod env_config;
 mod global_settings;
+mod keyfile;
+mod metrics;
+mod overrides;
+mod pending_items;
+mod settings_sync;
 mod store;
+mod vault_cache;
 
 pub use data::*;
+pub use env_config::*;
 pub use global_settings::*;
+pub use keyfile::*;
+pub use metrics::*;
+pub use overrides::*;
+pub use pending_items::*;
+pub use settings_sync::*;
 pub use store::*;
+pub use vault_cache::*;
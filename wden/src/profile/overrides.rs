@@ -0,0 +1,139 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    bitwarden::{api::DeviceClass, server::ServerConfiguration},
+    datetime::DateStyle,
+};
+
+use super::{
+    settings_revision_now, AlertStyle, CardNumberFormat, ClipboardBackend, EnterAction,
+    ProfileData, ProfileEnvironment, SyncedSettings,
+};
+
+/// Profile settings given as command line options. Set values replace
+/// the ones stored in the profile.
+#[derive(Default)]
+pub struct ProfileOverrides {
+    pub server_configuration: Option<ServerConfiguration>,
+    pub pinned_spki_hashes: Option<Vec<String>>,
+    /// Zero disables autolock
+    pub autolock_duration: Option<Duration>,
+    /// `Some(None)` disables locking on focus loss
+    pub focus_loss_lock_delay: Option<Option<Duration>>,
+    /// `Some(None)` removes the keyfile requirement
+    pub keyfile: Option<Option<PathBuf>>,
+    pub settings_sync: Option<bool>,
+    pub offline_cache: Option<bool>,
+    pub forget_master_key: Option<bool>,
+    pub card_number_format: Option<CardNumberFormat>,
+    pub keyring_unlock: Option<bool>,
+    pub remember_session: Option<bool>,
+    pub record_metrics: Option<bool>,
+    pub device_class: Option<DeviceClass>,
+    pub enter_action: Option<EnterAction>,
+    pub alert_style: Option<AlertStyle>,
+    /// `Some(None)` removes the stored command
+    pub password_command: Option<Option<String>>,
+    /// `Some(None)` removes the stored program
+    pub pinentry: Option<Option<String>>,
+    pub environment: Option<ProfileEnvironment>,
+    /// Removes the URI handlers of the profile before adding `uri_handlers`
+    pub clear_uri_handlers: bool,
+    /// Scheme and command pairs. An empty command disables the scheme.
+    pub uri_handlers: Vec<(String, String)>,
+    pub date_style: Option<DateStyle>,
+    /// `Some(None)` restores the default format
+    pub date_format: Option<Option<String>>,
+    /// `Some(None)` restores the system time zone
+    pub time_zone: Option<Option<String>>,
+    pub clipboard_backend: Option<ClipboardBackend>,
+}
+
+impl ProfileOverrides {
+    pub fn apply(self, profile_data: &mut ProfileData) {
+        let synced_settings = SyncedSettings::from_profile(profile_data);
+
+        if let Some(server_configuration) = self.server_configuration {
+            if server_configuration != profile_data.server_configuration {
+                profile_data.clear_server_credentials();
+                profile_data.server_configuration = server_configuration;
+            }
+        }
+        if let Some(pins) = self.pinned_spki_hashes {
+            profile_data.pinned_spki_hashes = pins;
+        }
+        if let Some(duration) = self.autolock_duration {
+            profile_data.autolock_duration = duration;
+        }
+        if let Some(delay) = self.focus_loss_lock_delay {
+            profile_data.focus_loss_lock_delay = delay;
+        }
+        if let Some(keyfile) = self.keyfile {
+            if keyfile != profile_data.keyfile {
+                // The verifier and the check are only valid for the old keyfile
+                profile_data.keyfile_verifier = None;
+                profile_data.keyfile_check = None;
+                profile_data.keyfile = keyfile;
+            }
+        }
+        if let Some(settings_sync) = self.settings_sync {
+            profile_data.settings_sync = settings_sync;
+        }
+        if let Some(offline_cache) = self.offline_cache {
+            profile_data.offline_cache = offline_cache;
+        }
+        if let Some(forget_master_key) = self.forget_master_key {
+            profile_data.forget_master_key = forget_master_key;
+        }
+        if let Some(card_number_format) = self.card_number_format {
+            profile_data.card_number_format = card_number_format;
+        }
+        if let Some(keyring_unlock) = self.keyring_unlock {
+            profile_data.keyring_unlock = keyring_unlock;
+        }
+        if let Some(remember_session) = self.remember_session {
+            profile_data.remember_session = remember_session;
+        }
+        if let Some(record_metrics) = self.record_metrics {
+            profile_data.record_metrics = record_metrics;
+        }
+        if let Some(device_class) = self.device_class {
+            profile_data.device_class = device_class;
+        }
+        if let Some(enter_action) = self.enter_action {
+            profile_data.enter_action = enter_action;
+        }
+        if let Some(alert_style) = self.alert_style {
+            profile_data.alert_style = alert_style;
+        }
+        if let Some(password_command) = self.password_command {
+            profile_data.password_command = password_command;
+        }
+        if let Some(pinentry) = self.pinentry {
+            profile_data.pinentry = pinentry;
+        }
+        if let Some(environment) = self.environment {
+            profile_data.environment = environment;
+        }
+        if self.clear_uri_handlers {
+            profile_data.uri_handlers.clear();
+        }
+        profile_data.uri_handlers.extend(self.uri_handlers);
+        if let Some(date_style) = self.date_style {
+            profile_data.date_style = date_style;
+        }
+        if let Some(date_format) = self.date_format {
+            profile_data.date_format = date_format;
+        }
+        if let Some(time_zone) = self.time_zone {
+            profile_data.time_zone = time_zone;
+        }
+        if let Some(clipboard_backend) = self.clipboard_backend {
+            profile_data.clipboard_backend = clipboard_backend;
+        }
+
+        if SyncedSettings::from_profile(profile_data) != synced_settings {
+            profile_data.settings_revision = settings_revision_now();
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use crate::bitwarden::api::NewCipher;
+
+/// An item created in offline mode, waiting to be pushed to the server.
+/// The fields are encrypted with the user or organization keys, exactly
+/// as they will be sent, so the queue is stored encrypted.
+#[derive(Serialize, Deserialize)]
+pub struct PendingItem {
+    /// Local id for the queue, the server assigns the item id
+    pub id: String,
+    pub item: NewCipher,
+    /// Organization and collection ids for organization items
+    pub collection: Option<(String, String)>,
+    /// When the item was queued, formatted like the API timestamps
+    pub created: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitwarden::{
+        api::NewCipherData,
+        cipher::{extract_enc_mac_keys, Cipher},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_pending_item_roundtrip() {
+        let keys = extract_enc_mac_keys(&[7; 64]).unwrap();
+        let item = PendingItem {
+            id: "local-1".to_string(),
+            item: NewCipher {
+                name: Cipher::encrypt(b"Server", &keys).unwrap(),
+                notes: None,
+                data: NewCipherData::Login {
+                    username: Some(Cipher::encrypt(b"admin", &keys).unwrap()),
+                    password: None,
+                    uri: None,
+                },
+                fields: vec![],
+            },
+            collection: Some(("org".to_string(), "coll".to_string())),
+            created: "2024-05-01T12:00:00Z".to_string(),
+        };
+
+        let json = serde_json::to_string(&item).unwrap();
+        assert!(!json.contains("admin"));
+        let parsed: PendingItem = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.id, "local-1");
+        assert_eq!(parsed.collection, item.collection);
+        assert_eq!(parsed.item.name.decrypt_to_string(&keys), "Server");
+        let NewCipherData::Login { username, .. } = parsed.item.data else {
+            panic!("not a login");
+        };
+        assert_eq!(username.unwrap().decrypt_to_string(&keys), "admin");
+    }
+}
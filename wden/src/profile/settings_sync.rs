@@ -0,0 +1,215 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::datetime::DateStyle;
+
+use super::{AlertStyle, CardNumberFormat, EnterAction, ProfileData};
+
+/// Name of the secure note that holds the synced settings
+pub const SETTINGS_ITEM_NAME: &str = "wden:settings";
+
+/// Profile settings that follow the user across machines.
+///
+/// Settings that depend on the machine (keyfile, keystore, offline cache,
+/// clipboard backend, URI handlers, commands) are not synced. The theme is
+/// not a profile setting, so it is not synced either.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SyncedSettings {
+    pub autolock_duration: Duration,
+    #[serde(default)]
+    pub focus_loss_lock_delay: Option<Duration>,
+    // The rest were added later, notes without them keep the local values
+    #[serde(default)]
+    pub pinned_collections: Option<BTreeMap<u8, String>>,
+    #[serde(default)]
+    pub enter_action: Option<EnterAction>,
+    #[serde(default)]
+    pub date_style: Option<DateStyle>,
+    // null is the default format or time zone, a missing value is not synced
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub date_format: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub time_zone: Option<Option<String>>,
+    #[serde(default)]
+    pub card_number_format: Option<CardNumberFormat>,
+    #[serde(default)]
+    pub show_folder_column: Option<bool>,
+    #[serde(default)]
+    pub show_password_changed_column: Option<bool>,
+    #[serde(default)]
+    pub alert_style: Option<AlertStyle>,
+}
+
+impl SyncedSettings {
+    pub fn from_profile(profile_data: &ProfileData) -> Self {
+        SyncedSettings {
+            autolock_duration: profile_data.autolock_duration,
+            focus_loss_lock_delay: profile_data.focus_loss_lock_delay,
+            pinned_collections: Some(profile_data.pinned_collections.clone()),
+            enter_action: Some(profile_data.enter_action),
+            date_style: Some(profile_data.date_style),
+            date_format: Some(profile_data.date_format.clone()),
+            time_zone: Some(profile_data.time_zone.clone()),
+            card_number_format: Some(profile_data.card_number_format),
+            show_folder_column: Some(profile_data.show_folder_column),
+            show_password_changed_column: Some(profile_data.show_password_changed_column),
+            alert_style: Some(profile_data.alert_style),
+        }
+    }
+
+    pub fn apply(&self, profile_data: &mut ProfileData) {
+        profile_data.autolock_duration = self.autolock_duration;
+        profile_data.focus_loss_lock_delay = self.focus_loss_lock_delay;
+        if let Some(pinned_collections) = &self.pinned_collections {
+            profile_data.pinned_collections = pinned_collections.clone();
+        }
+        if let Some(enter_action) = self.enter_action {
+            profile_data.enter_action = enter_action;
+        }
+        if let Some(date_style) = self.date_style {
+            profile_data.date_style = date_style;
+        }
+        if let Some(date_format) = &self.date_format {
+            profile_data.date_format = date_format.clone();
+        }
+        if let Some(time_zone) = &self.time_zone {
+            profile_data.time_zone = time_zone.clone();
+        }
+        if let Some(card_number_format) = self.card_number_format {
+            profile_data.card_number_format = card_number_format;
+        }
+        if let Some(show_folder_column) = self.show_folder_column {
+            profile_data.show_folder_column = show_folder_column;
+        }
+        if let Some(show_password_changed_column) = self.show_password_changed_column {
+            profile_data.show_password_changed_column = show_password_changed_column;
+        }
+        if let Some(alert_style) = self.alert_style {
+            profile_data.alert_style = alert_style;
+        }
+    }
+}
+
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Contents of the settings secure note
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SettingsNote {
+    /// Unix timestamp (seconds) of the settings change
+    pub revision: u64,
+    pub settings: SyncedSettings,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum SettingsSyncAction {
+    UpToDate,
+    /// Apply the settings from the vault to the profile
+    Pull,
+    /// Store the profile settings to the vault
+    Push,
+    /// The settings have changed both locally and in the vault since the last sync
+    Conflict,
+}
+
+/// Decides what to do, based on the revision of the local settings, the
+/// revision that was last pulled or pushed, and the note in the vault.
+pub fn resolve_settings_sync(
+    local_revision: u64,
+    synced_revision: u64,
+    remote: Option<&SettingsNote>,
+) -> SettingsSyncAction {
+    let local_changed = local_revision > synced_revision;
+    match remote {
+        None => SettingsSyncAction::Push,
+        Some(note) if note.revision == synced_revision => {
+            if local_changed {
+                SettingsSyncAction::Push
+            } else {
+                SettingsSyncAction::UpToDate
+            }
+        }
+        Some(_) if local_changed => SettingsSyncAction::Conflict,
+        Some(_) => SettingsSyncAction::Pull,
+    }
+}
+
+pub fn settings_revision_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(revision: u64) -> SettingsNote {
+        SettingsNote {
+            revision,
+            settings: SyncedSettings {
+                autolock_duration: Duration::from_secs(60),
+                focus_loss_lock_delay: None,
+                pinned_collections: Some(BTreeMap::from([(1, "collection".to_owned())])),
+                enter_action: Some(EnterAction::CopyPassword),
+                date_style: None,
+                date_format: Some(None),
+                time_zone: Some(Some("Europe/Helsinki".to_owned())),
+                card_number_format: None,
+                show_folder_column: Some(true),
+                show_password_changed_column: None,
+                alert_style: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_resolve_settings_sync() {
+        use SettingsSyncAction::*;
+
+        // Nothing in the vault yet
+        assert_eq!(resolve_settings_sync(0, 0, None), Push);
+        // Vault unchanged since the last sync
+        assert_eq!(resolve_settings_sync(10, 10, Some(&note(10))), UpToDate);
+        assert_eq!(resolve_settings_sync(20, 10, Some(&note(10))), Push);
+        // Vault changed since the last sync
+        assert_eq!(resolve_settings_sync(10, 10, Some(&note(30))), Pull);
+        assert_eq!(resolve_settings_sync(0, 0, Some(&note(30))), Pull);
+        assert_eq!(resolve_settings_sync(20, 10, Some(&note(30))), Conflict);
+    }
+
+    #[test]
+    fn test_settings_note_roundtrip() {
+        let n = note(1234);
+        let json = serde_json::to_string(&n).unwrap();
+        assert_eq!(serde_json::from_str::<SettingsNote>(&json).unwrap(), n);
+    }
+
+    #[test]
+    fn test_settings_note_without_ui_settings() {
+        // Notes stored before the UI settings were synced
+        let json = r#"{"revision":1,"settings":{"autolock_duration":{"secs":60,"nanos":0}}}"#;
+        let settings = serde_json::from_str::<SettingsNote>(json).unwrap().settings;
+        assert_eq!(settings.autolock_duration, Duration::from_secs(60));
+        assert_eq!(settings.pinned_collections, None);
+        assert_eq!(settings.date_format, None);
+    }
+}
@@ -7,22 +7,33 @@ use std::{
 use anyhow::Context;
 use directories_next::ProjectDirs;
 
-use super::data::ProfileData;
+use super::{
+    data::ProfileData, metrics::MetricsRecord, pending_items::PendingItem, vault_cache::VaultCache,
+};
 
 #[derive(Clone)]
 pub struct ProfileStore {
     config_dir: PathBuf,
     profile_config_file: PathBuf,
+    vault_cache_file: PathBuf,
+    pending_items_file: PathBuf,
+    metrics_file: PathBuf,
 }
 
 impl ProfileStore {
     pub fn new(profile_name: &str) -> ProfileStore {
         let config_dir = get_config_dir();
         let profile_config_file = config_dir.join(format!("{profile_name}.json"));
+        let vault_cache_file = config_dir.join(format!("{profile_name}.vault_cache"));
+        let pending_items_file = config_dir.join(format!("{profile_name}.pending_items"));
+        let metrics_file = config_dir.join(format!("{profile_name}.metrics"));
 
         ProfileStore {
             config_dir,
             profile_config_file,
+            vault_cache_file,
+            pending_items_file,
+            metrics_file,
         }
     }
 
@@ -80,6 +91,137 @@ impl ProfileStore {
         // Store the edited data
         self.store(&data).context("Rewriting profile file failed")
     }
+
+    /// Loads the offline vault cache. Returns None if there is no cache.
+    /// `keyfile` is the keyfile of the profile, if it has one.
+    pub fn load_vault_cache(
+        &self,
+        keyfile: Option<&Path>,
+    ) -> Result<Option<VaultCache>, anyhow::Error> {
+        let contents = match std::fs::read(&self.vault_cache_file) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut cache: VaultCache =
+            serde_json::from_slice(&contents).context("Parsing vault cache failed")?;
+        cache.unwrap_key(keyfile)?;
+        Ok(Some(cache))
+    }
+
+    /// Stores the offline vault cache. With a keyfile, the user key in
+    /// the cache is encrypted with it.
+    pub fn store_vault_cache(
+        &self,
+        mut cache: VaultCache,
+        keyfile: Option<&Path>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(keyfile) = keyfile {
+            cache.wrap_key(keyfile)?;
+        }
+        std::fs::create_dir_all(&self.config_dir)?;
+        let serialized = serde_json::to_vec(&cache)?;
+
+        Ok(write_private_file(&self.vault_cache_file, &serialized)?)
+    }
+
+    pub fn remove_vault_cache(&self) -> std::io::Result<()> {
+        match std::fs::remove_file(&self.vault_cache_file) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Loads the items created in offline mode that have not been pushed
+    /// to the server yet
+    pub fn load_pending_items(&self) -> Result<Vec<PendingItem>, anyhow::Error> {
+        let contents = match std::fs::read(&self.pending_items_file) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&contents).context("Parsing pending items failed")
+    }
+
+    /// Replaces the pending items. The file is removed when there are none left.
+    pub fn store_pending_items(&self, items: &[PendingItem]) -> Result<(), anyhow::Error> {
+        if items.is_empty() {
+            return match std::fs::remove_file(&self.pending_items_file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        std::fs::create_dir_all(&self.config_dir)?;
+        let serialized = serde_json::to_vec(items)?;
+
+        Ok(write_private_file(&self.pending_items_file, &serialized)?)
+    }
+
+    /// The metrics file has one JSON record per line, so records can be
+    /// appended without reading the file
+    pub fn append_metrics_record(&self, record: &MetricsRecord) -> std::io::Result<()> {
+        use std::io::Write;
+
+        std::fs::create_dir_all(&self.config_dir)?;
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.metrics_file)?
+            .write_all(&line)
+    }
+
+    /// Loads the recorded metrics. Lines that can't be parsed are skipped.
+    pub fn load_metrics(&self) -> std::io::Result<Vec<MetricsRecord>> {
+        let contents = match std::fs::read_to_string(&self.metrics_file) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    pub fn remove_metrics(&self) -> std::io::Result<()> {
+        match std::fs::remove_file(&self.metrics_file) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    }
+}
+
+/// Writes the contents to a new file that only the current user can read,
+/// and renames it over the old file once it's on disk. This keeps the old
+/// file if writing fails, though not on every file system after a crash.
+fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut tmp_file = path.to_path_buf().into_os_string();
+    tmp_file.push(".tmp");
+    match std::fs::remove_file(&tmp_file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp_file)?;
+    let res = file
+        .write_all(contents)
+        .and_then(|_| file.sync_all())
+        .and_then(|_| std::fs::rename(&tmp_file, path));
+    if res.is_err() {
+        _ = std::fs::remove_file(&tmp_file);
+    }
+    res
 }
 
 fn get_config_dir() -> PathBuf {
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::bitwarden::cipher::{Cipher, PbkdfParameters};
+
+use super::keyfile_keys;
+
+/// Vault data cached for the offline mode. The user keys and the vault
+/// items are stored encrypted, as they were received from the server,
+/// so the cache can only be used with the master password.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultCache {
+    pub email: String,
+    pub pbkdf: PbkdfParameters,
+    /// User symmetric key, encrypted with the master key
+    pub key: Cipher,
+    /// User private key, encrypted with the user symmetric key
+    pub private_key: Cipher,
+    /// The sync response as received from the server
+    pub sync_data: serde_json::Value,
+    /// `key` is encrypted again with the keys derived from the profile keyfile
+    #[serde(default)]
+    pub keyfile_wrapped: bool,
+}
+
+impl VaultCache {
+    /// Encrypts the user key with the keys derived from the keyfile
+    pub(super) fn wrap_key(&mut self, keyfile: &Path) -> anyhow::Result<()> {
+        let keys = keyfile_keys(keyfile)?;
+        self.key = Cipher::encrypt(self.key.encode().as_bytes(), &keys)?;
+        self.keyfile_wrapped = true;
+        Ok(())
+    }
+
+    /// Reverses [`VaultCache::wrap_key`]. A cache stored without the keyfile
+    /// is not accepted when the profile has one.
+    pub(super) fn unwrap_key(&mut self, keyfile: Option<&Path>) -> anyhow::Result<()> {
+        match (keyfile, self.keyfile_wrapped) {
+            (None, false) => Ok(()),
+            (None, true) => bail!(
+                "The offline vault cache is protected with a keyfile, but the profile has \
+                 no keyfile configured"
+            ),
+            (Some(_), false) => bail!(
+                "The offline vault cache was stored without the keyfile. Sync the vault \
+                 while online to replace it."
+            ),
+            (Some(keyfile), true) => {
+                let keys = keyfile_keys(keyfile)?;
+                let key = self.key.decrypt(&keys).map_err(|_| {
+                    anyhow::anyhow!(
+                        "The offline vault cache is protected with another keyfile than {}",
+                        keyfile.display()
+                    )
+                })?;
+                self.key = String::from_utf8(key)
+                    .context("The wrapped vault cache key is not valid")?
+                    .parse()?;
+                self.keyfile_wrapped = false;
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Vault health report: weak, reused and old passwords, duplicate items
+//! and insecure URIs. The findings only have item names and password
+//! ages, no secrets, so that the report can be shared.
+
+use std::{collections::HashMap, fmt::Display};
+
+use clap::ValueEnum;
+use jiff::{tz::TimeZone, Timestamp};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::{api::CipherData, cipher::Cipher},
+    export::{csv_escape, ExportItem},
+};
+
+/// Passwords that haven't been changed in this many days are reported
+pub const STALE_PASSWORD_DAYS: i64 = 365;
+
+/// Passwords with less estimated entropy are reported as weak
+const WEAK_PASSWORD_BITS: f64 = 60.0;
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Issue {
+    WeakPassword,
+    ReusedPassword,
+    StalePassword,
+    DuplicateItem,
+    InsecureUri,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Issue::WeakPassword => "weak password",
+            Issue::ReusedPassword => "reused password",
+            Issue::StalePassword => "stale password",
+            Issue::DuplicateItem => "duplicate item",
+            Issue::InsecureUri => "insecure URI",
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Finding {
+    pub item_name: String,
+    pub issue: Issue,
+    /// When the password (or the item, if not known) was changed, as
+    /// given by the API
+    pub changed: Option<String>,
+    /// Days since `changed`
+    pub age_days: Option<i64>,
+}
+
+/// Checks the login items
+pub fn find_issues(items: &[ExportItem], now: Timestamp) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut by_password: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    let mut logins = vec![];
+
+    for (item, keys) in items {
+        let CipherData::Login(login) = &item.data else {
+            continue;
+        };
+        let d = |c: &Cipher| Zeroizing::new(c.decrypt_to_string(keys));
+        let name = item.name.decrypt_to_string(keys);
+        let changed = item
+            .password_changed_date()
+            .or(item.revision_date.as_deref());
+        let age_days = changed.and_then(|date| days_since(date, now));
+        let index = logins.len();
+
+        let password = d(&login.password);
+        let mut issues = vec![];
+        if !password.is_empty() {
+            if estimated_bits(&password) < WEAK_PASSWORD_BITS {
+                issues.push(Issue::WeakPassword);
+            }
+            if age_days.is_some_and(|age| age > STALE_PASSWORD_DAYS) {
+                issues.push(Issue::StalePassword);
+            }
+            by_password
+                .entry(Sha256::digest(password.as_bytes()).into())
+                .or_default()
+                .push(index);
+        }
+        if login
+            .uris()
+            .into_iter()
+            .any(|(uri, _)| d(uri).to_ascii_lowercase().starts_with("http://"))
+        {
+            issues.push(Issue::InsecureUri);
+        }
+        by_name
+            .entry((name.to_lowercase(), d(&login.username).to_lowercase()))
+            .or_default()
+            .push(index);
+
+        logins.push((name, changed, age_days, issues));
+    }
+
+    for indices in by_password.values().filter(|i| i.len() > 1) {
+        for &i in indices {
+            logins[i].3.push(Issue::ReusedPassword);
+        }
+    }
+    for indices in by_name.values().filter(|i| i.len() > 1) {
+        for &i in indices {
+            logins[i].3.push(Issue::DuplicateItem);
+        }
+    }
+
+    for (name, changed, age_days, issues) in logins {
+        findings.extend(issues.into_iter().map(|issue| Finding {
+            item_name: name.clone(),
+            issue,
+            changed: changed.map(str::to_string),
+            age_days,
+        }));
+    }
+    findings.sort_by(|a, b| (a.issue, &a.item_name).cmp(&(b.issue, &b.item_name)));
+    findings
+}
+
+/// Formats the findings as CSV with the columns name, issue and age_days
+pub fn to_csv(findings: &[Finding]) -> String {
+    let mut csv = String::from("name,issue,age_days\n");
+    for finding in findings {
+        let age = finding.age_days.map(|a| a.to_string()).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{age}\n",
+            *csv_escape(&finding.item_name),
+            finding.issue
+        ));
+    }
+    csv
+}
+
+/// A rough entropy estimate from the length and the character classes
+fn estimated_bits(password: &str) -> f64 {
+    let mut charset = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        charset += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        charset += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        charset += 10;
+    }
+    if password.chars().any(|c| !c.is_ascii_alphanumeric()) {
+        charset += 33;
+    }
+    password.chars().count() as f64 * f64::from(charset).log2()
+}
+
+/// Calendar days (in UTC) from an ISO 8601 timestamp, e.g.
+/// `2024-01-31T12:00:00Z`, to `now`
+fn days_since(date: &str, now: Timestamp) -> Option<i64> {
+    let date = date
+        .parse::<Timestamp>()
+        .ok()?
+        .to_zoned(TimeZone::UTC)
+        .date();
+    let span = date.until(now.to_zoned(TimeZone::UTC).date()).ok()?;
+    Some(span.get_days().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_since() {
+        let now: Timestamp = "2023-11-14T22:13:20Z".parse().unwrap();
+        assert_eq!(days_since("2023-11-14T10:00:00.000Z", now), Some(0));
+        assert_eq!(days_since("2022-11-14T10:00:00Z", now), Some(365));
+        assert_eq!(days_since("1970-01-01T00:00:00Z", now), Some(19675));
+        assert_eq!(days_since("not a date", now), None);
+    }
+
+    #[test]
+    fn test_estimated_bits() {
+        assert!(estimated_bits("hunter2") < WEAK_PASSWORD_BITS);
+        assert!(estimated_bits("Passw0rd!") < WEAK_PASSWORD_BITS);
+        assert!(estimated_bits("correct horse battery staple") > WEAK_PASSWORD_BITS);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let findings = [Finding {
+            item_name: "Mail, work".to_string(),
+            issue: Issue::ReusedPassword,
+            changed: Some("2023-11-02T10:00:00Z".to_string()),
+            age_days: Some(12),
+        }];
+        assert_eq!(
+            to_csv(&findings),
+            "name,issue,age_days\n\"Mail, work\",reused password,12\n"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Output that goes to the normal terminal screen (not the alternate
+//! screen used by the UI) stays in the scrollback. Text printed there
+//! from error and panic paths is passed through [`scrub`] first.
+
+const REDACTED: &str = "[redacted]";
+
+/// Shortest run of base64 or hex characters that is treated as key material
+const MIN_SECRET_LEN: usize = 32;
+
+/// Replaces values that look like secrets: encrypted cipher strings
+/// ("2.iv|ct|mac"), JWT access tokens and long base64 or hex values.
+pub fn scrub(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word_start = None;
+
+    for (i, c) in text.char_indices() {
+        match (is_word_char(c), word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                out.push_str(scrub_word(&text[start..i]));
+                out.push(c);
+                word_start = None;
+            }
+            (false, None) => out.push(c),
+            (true, Some(_)) => {}
+        }
+    }
+    if let Some(start) = word_start {
+        out.push_str(scrub_word(&text[start..]));
+    }
+
+    out
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '_' | '-' | '.' | '|')
+}
+
+fn scrub_word(word: &str) -> &str {
+    let is_cipher_string = word.len() > 2
+        && word.as_bytes()[0].is_ascii_digit()
+        && word.as_bytes()[1] == b'.'
+        && word.contains('|');
+    let is_jwt = word.starts_with("eyJ") && word.matches('.').count() == 2;
+    // Paths can be long too, but rarely have digits
+    let is_long_value = word.split(['.', '|']).any(|part| {
+        part.len() >= MIN_SECRET_LEN
+            && part.contains(|c: char| c.is_ascii_digit())
+            && part.contains(|c: char| c.is_ascii_alphabetic())
+    });
+
+    if is_cipher_string || is_jwt || is_long_value {
+        REDACTED
+    } else {
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scrub;
+
+    #[test]
+    fn test_scrub() {
+        assert_eq!(
+            scrub("Error: invalid cipher 2.aGVsbG8=|d29ybGQ=|Zm9v in item"),
+            "Error: invalid cipher [redacted] in item"
+        );
+        assert_eq!(
+            scrub("token eyJhbGciOi.eyJzdWIiOi.c2lnbmF0dXJl, expired"),
+            "token [redacted], expired"
+        );
+        assert_eq!(
+            scrub("key: 0123456789abcdef0123456789abcdef"),
+            "key: [redacted]"
+        );
+        assert_eq!(
+            scrub("Failed to read /home/user/.config/wden/default.json"),
+            "Failed to read /home/user/.config/wden/default.json"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use std::{io::Write, time::Duration};
+
+use cursive::Cursive;
+
+use crate::profile::AlertStyle;
+
+use super::util::cursive_ext::CursiveExt;
+
+/// How long the screen stays in reverse video for a visual alert
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+/// Signals an event that should not be missed, with the alert style of
+/// the profile. Shown dialogs are not affected.
+pub fn alert(siv: &mut Cursive) {
+    match siv.get_user_data().global_settings().alert_style {
+        AlertStyle::None => {}
+        AlertStyle::Bell => write_escape("\x07"),
+        AlertStyle::Visual => {
+            // Reverse video mode (DECSCNM), like the terminfo flash capability
+            write_escape("\x1b[?5h");
+            tokio::spawn(async {
+                tokio::time::sleep(FLASH_DURATION).await;
+                write_escape("\x1b[?5l");
+            });
+        }
+    }
+}
+
+/// Alerts when an expiring clipboard value is cleared
+pub fn alert_on_clipboard_clear(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    super::clipboard::set_clear_notifier(move || {
+        _ = cb_sink.send(Box::new(alert));
+    });
+}
+
+fn write_escape(seq: &str) {
+    let mut stdout = std::io::stdout();
+    if let Err(e) = stdout
+        .write_all(seq.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        log::warn!("Writing the alert failed: {e}");
+    }
+}
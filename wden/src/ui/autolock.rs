@@ -8,13 +8,17 @@ use super::util::cursive_ext::CursiveCallbackExt;
 
 pub struct Autolocker {
     next_lock_time: Option<Instant>,
+    // Zero disables autolock
     autolock_time: Duration,
+    // Set by an organization vault timeout policy
+    max_autolock_time: Option<Duration>,
 }
 
 pub fn start_autolocker(cb_sink: CbSink, autolock_time: Duration) -> Arc<Mutex<Autolocker>> {
     let next_autolock_time = Arc::new(Mutex::new(Autolocker {
         next_lock_time: None,
         autolock_time,
+        max_autolock_time: None,
     }));
 
     tokio::spawn(autolock_loop(cb_sink, Arc::clone(&next_autolock_time)));
@@ -25,7 +29,29 @@ pub fn start_autolocker(cb_sink: CbSink, autolock_time: Duration) -> Arc<Mutex<A
 impl Autolocker {
     pub fn update_next_autolock_time(&mut self, enable_lock: bool) {
         if self.next_lock_time.is_some() || enable_lock {
-            self.next_lock_time = Some(Instant::now() + self.autolock_time);
+            self.next_lock_time = self
+                .effective_autolock_time()
+                .map(|time| Instant::now() + time);
+        }
+    }
+
+    pub fn set_autolock_duration(&mut self, autolock_time: Duration) {
+        self.autolock_time = autolock_time;
+        self.update_next_autolock_time(false);
+    }
+
+    /// Limits the autolock duration, whatever the profile setting is
+    pub fn set_max_autolock_duration(&mut self, max_autolock_time: Option<Duration>) {
+        self.max_autolock_time = max_autolock_time;
+        self.update_next_autolock_time(false);
+    }
+
+    fn effective_autolock_time(&self) -> Option<Duration> {
+        match (self.autolock_time.is_zero(), self.max_autolock_time) {
+            (false, Some(max)) => Some(self.autolock_time.min(max)),
+            (false, None) => Some(self.autolock_time),
+            // The policy applies even if autolock is disabled
+            (true, max) => max,
         }
     }
 
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use anyhow::{bail, Context};
+use objc2_app_kit::{NSPasteboard, NSPasteboardTypeString};
+use objc2_foundation::NSString;
+
+use super::{PlatformClipboard, PlatformClipboardResult};
+
+/// Marks the contents as sensitive, so that clipboard managers that follow
+/// the nspasteboard.org conventions don't store them in their history
+const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+
+/// The change count of the pasteboard after wden last wrote to it. The
+/// count increases whenever any application changes the contents, so
+/// comparing it tells whether the clipboard still has our value.
+static OWN_CHANGE_COUNT: AtomicIsize = AtomicIsize::new(-1);
+
+pub struct MacosClipboard;
+
+impl PlatformClipboard for MacosClipboard {
+    fn clip_string(s: String) -> PlatformClipboardResult<()> {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard();
+            pasteboard.clearContents();
+            let value = NSString::from_str(&s);
+            if !pasteboard.setString_forType(&value, NSPasteboardTypeString) {
+                bail!("Writing to the pasteboard failed");
+            }
+            let concealed = NSString::from_str(CONCEALED_TYPE);
+            pasteboard.setString_forType(&NSString::from_str(""), &concealed);
+            OWN_CHANGE_COUNT.store(pasteboard.changeCount(), Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn get_string_contents() -> PlatformClipboardResult<String> {
+        let contents = unsafe {
+            NSPasteboard::generalPasteboard()
+                .stringForType(NSPasteboardTypeString)
+                .context("The pasteboard has no text")?
+        };
+        Ok(contents.to_string())
+    }
+
+    fn clear() -> PlatformClipboardResult<()> {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard();
+            // Another application has copied something since, even if the
+            // text happens to be the same
+            if pasteboard.changeCount() != OWN_CHANGE_COUNT.load(Ordering::Relaxed) {
+                log::info!("The pasteboard has changed, not clearing");
+                return Ok(());
+            }
+            pasteboard.clearContents();
+        }
+        Ok(())
+    }
+}
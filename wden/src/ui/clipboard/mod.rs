@@ -1,4 +1,14 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use zeroize::Zeroizing;
+
+use crate::profile::ClipboardBackend;
 
 #[cfg(windows)]
 mod windows_clipboard;
@@ -10,21 +20,88 @@ mod linux_clipboard;
 #[cfg(target_os = "linux")]
 type PlatformCbImpl = linux_clipboard::LinuxClipboard;
 
+#[cfg(target_os = "macos")]
+mod macos_clipboard;
+#[cfg(target_os = "macos")]
+type PlatformCbImpl = macos_clipboard::MacosClipboard;
+
+mod osc52_clipboard;
+use osc52_clipboard::Osc52Clipboard;
+
+/// Whether the OSC 52 backend is used instead of the system clipboard
+static USE_OSC52: AtomicBool = AtomicBool::new(false);
+
+pub fn set_backend(backend: ClipboardBackend) {
+    USE_OSC52.store(backend == ClipboardBackend::Osc52, Ordering::Relaxed);
+}
+
+fn backend_clip_string(s: String) -> PlatformClipboardResult<()> {
+    if USE_OSC52.load(Ordering::Relaxed) {
+        Osc52Clipboard::clip_string(s)
+    } else {
+        PlatformCbImpl::clip_string(s)
+    }
+}
+
+fn backend_contents() -> PlatformClipboardResult<String> {
+    if USE_OSC52.load(Ordering::Relaxed) {
+        Osc52Clipboard::get_string_contents()
+    } else {
+        PlatformCbImpl::get_string_contents()
+    }
+}
+
+fn backend_clear() -> PlatformClipboardResult<()> {
+    if USE_OSC52.load(Ordering::Relaxed) {
+        Osc52Clipboard::clear()
+    } else {
+        PlatformCbImpl::clear()
+    }
+}
+
 pub fn clip_string(s: String) {
     log::info!("Clipping...");
-    if let Err(e) = PlatformCbImpl::clip_string(s) {
+    if let Err(e) = backend_clip_string(s) {
         log::warn!("Clipping string failed: {}", e)
     };
 }
 
+/// Values clipped with [`clip_expiring_string`] whose expiry timer has not
+/// run yet. The timers don't run when wden exits, so these are cleared
+/// on shutdown with [`clear_pending_clips`].
+static PENDING_CLIPS: Mutex<Vec<Zeroizing<String>>> = Mutex::new(Vec::new());
+
+type ClearNotifier = Box<dyn Fn() + Send>;
+
+/// Called when an expiring value is cleared from the clipboard
+static CLEAR_NOTIFIER: Mutex<Option<ClearNotifier>> = Mutex::new(None);
+
+pub fn set_clear_notifier(notifier: impl Fn() + Send + 'static) {
+    if let Ok(mut n) = CLEAR_NOTIFIER.lock() {
+        *n = Some(Box::new(notifier));
+    }
+}
+
 pub fn clip_expiring_string(s: String, expiry_seconds: u64) {
     tokio::spawn(async move {
         clip_string(s.clone());
+        if let Ok(mut pending) = PENDING_CLIPS.lock() {
+            pending.push(Zeroizing::new(s.clone()));
+        }
         tokio::time::sleep(Duration::from_secs(expiry_seconds)).await;
-        let res = PlatformCbImpl::get_string_contents().and_then(|curr_contents| {
+        if let Ok(mut pending) = PENDING_CLIPS.lock() {
+            if let Some(i) = pending.iter().position(|p| **p == s) {
+                pending.swap_remove(i);
+            }
+        }
+        let res = backend_contents().and_then(|curr_contents| {
             if curr_contents == s {
                 log::info!("Clearing clipboard...");
-                PlatformCbImpl::clear()
+                backend_clear()?;
+                if let Ok(Some(notifier)) = CLEAR_NOTIFIER.lock().as_deref() {
+                    notifier();
+                }
+                Ok(())
             } else {
                 Ok(())
             }
@@ -36,6 +113,89 @@ pub fn clip_expiring_string(s: String, expiry_seconds: u64) {
     });
 }
 
+/// Clears the clipboard if it still contains a value whose expiry timer
+/// has not run. Also called from the panic hook, so the registry is not
+/// waited for if it's locked.
+pub fn clear_pending_clips() {
+    let Ok(mut pending) = PENDING_CLIPS.try_lock() else {
+        return;
+    };
+    if pending.is_empty() {
+        return;
+    }
+
+    let res = backend_contents().and_then(|curr_contents| {
+        if pending.iter().any(|p| **p == curr_contents) {
+            log::info!("Clearing clipboard on exit...");
+            backend_clear()
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(e) = res {
+        log::warn!("Clearing clipboard failed: {}", e);
+    }
+    pending.clear();
+}
+
+/// Describes why the clipboard may be shared over a remote connection:
+/// X11 or Wayland forwarded over SSH, a display on another host, or a
+/// Remote Desktop session. `None` for a local display.
+pub fn remote_display_reason() -> Option<String> {
+    remote_display_reason_from(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+fn remote_display_reason_from(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let over_ssh = ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|name| var(name).is_some());
+
+    if let Some(display) = var("DISPLAY") {
+        // Local displays are ":0" or "unix:0", forwarded ones e.g. "localhost:10.0"
+        let host = display.split(':').next().unwrap_or_default();
+        if over_ssh {
+            return Some(format!(
+                "wden is running over SSH with X11 forwarding (DISPLAY={display})"
+            ));
+        }
+        if !host.is_empty() && host != "unix" && !host.starts_with('/') {
+            return Some(format!(
+                "The X display is on another host (DISPLAY={display})"
+            ));
+        }
+    }
+    if over_ssh && var("WAYLAND_DISPLAY").is_some() {
+        return Some("wden is running over SSH with a forwarded Wayland display".to_string());
+    }
+    if var("SESSIONNAME").is_some_and(|s| s.starts_with("RDP-")) {
+        return Some("wden is running in a Remote Desktop session".to_string());
+    }
+    None
+}
+
+/// Describes why the terminal may not handle the OSC 52 clipboard
+/// sequence. OSC 52 can't be detected, so this only knows about the
+/// common terminals and multiplexers that drop it.
+pub fn osc52_support_warning() -> Option<&'static str> {
+    osc52_support_warning_from(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+fn osc52_support_warning_from(var: impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    if var("STY").is_some() {
+        return Some("GNU screen does not pass the OSC 52 clipboard sequence to the terminal");
+    }
+    if var("TMUX").is_some() {
+        return Some("In tmux, the set-clipboard option must be on or external for OSC 52 to work");
+    }
+    if var("VTE_VERSION").is_some() {
+        return Some("VTE based terminals, e.g. GNOME Terminal, do not support OSC 52");
+    }
+    if var("TERM_PROGRAM").is_some_and(|t| t == "Apple_Terminal") {
+        return Some("The macOS Terminal app does not support OSC 52");
+    }
+    None
+}
+
 type PlatformClipboardResult<T> = Result<T, anyhow::Error>;
 
 trait PlatformClipboard {
@@ -45,3 +205,40 @@ trait PlatformClipboard {
 
     fn clear() -> PlatformClipboardResult<()>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Environment lookup from a fixed list of variables
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_remote_display_reason() {
+        assert!(remote_display_reason_from(env(&[("DISPLAY", ":0")])).is_none());
+        assert!(remote_display_reason_from(env(&[("DISPLAY", "unix:1")])).is_none());
+        assert!(remote_display_reason_from(env(&[("WAYLAND_DISPLAY", "wayland-0")])).is_none());
+        assert!(remote_display_reason_from(env(&[("DISPLAY", "remote:0")])).is_some());
+        assert!(remote_display_reason_from(env(&[
+            ("SSH_CONNECTION", "10.0.0.2 5555 10.0.0.1 22"),
+            ("DISPLAY", "localhost:10.0")
+        ]))
+        .is_some());
+        // SSH without a display: nothing is forwarded
+        assert!(remote_display_reason_from(env(&[("SSH_TTY", "/dev/pts/1")])).is_none());
+    }
+
+    #[test]
+    fn test_osc52_support_warning() {
+        assert!(osc52_support_warning_from(env(&[("TERM", "xterm-kitty")])).is_none());
+        assert!(osc52_support_warning_from(env(&[("TERM_PROGRAM", "iTerm.app")])).is_none());
+        assert!(osc52_support_warning_from(env(&[("TMUX", "/tmp/tmux-1000/default")])).is_some());
+        assert!(osc52_support_warning_from(env(&[("VTE_VERSION", "7600")])).is_some());
+    }
+}
@@ -0,0 +1,60 @@
+use std::{io::Write, sync::Mutex};
+
+use anyhow::{bail, Context};
+use base64::prelude::*;
+use zeroize::Zeroizing;
+
+use super::{PlatformClipboard, PlatformClipboardResult};
+
+/// Terminals limit the length of the escape sequence, e.g. hterm and
+/// many others to 100 000 bytes. Longer values are refused instead of
+/// being cut off by the terminal.
+const MAX_PAYLOAD_BYTES: usize = 100_000;
+
+/// The terminal clipboard can't be read back (most terminals don't allow
+/// it), so the last value wden wrote stands in for the contents.
+static LAST_CLIP: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+/// Sets the clipboard of the terminal with the OSC 52 escape sequence.
+/// Works over SSH, as the terminal on the local machine handles it.
+pub struct Osc52Clipboard;
+
+impl PlatformClipboard for Osc52Clipboard {
+    fn clip_string(s: String) -> PlatformClipboardResult<()> {
+        let payload = Zeroizing::new(BASE64_STANDARD.encode(s.as_bytes()));
+        if payload.len() > MAX_PAYLOAD_BYTES {
+            bail!(
+                "The value is too long for the terminal clipboard ({} bytes encoded)",
+                payload.len()
+            );
+        }
+        write_osc52(&payload)?;
+        *LAST_CLIP.lock().unwrap() = Some(Zeroizing::new(s));
+        Ok(())
+    }
+
+    fn get_string_contents() -> PlatformClipboardResult<String> {
+        LAST_CLIP
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|s| s.to_string())
+            .context("Nothing has been copied")
+    }
+
+    fn clear() -> PlatformClipboardResult<()> {
+        // An empty payload empties the clipboard
+        write_osc52("")?;
+        *LAST_CLIP.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+fn write_osc52(payload: &str) -> PlatformClipboardResult<()> {
+    let seq = Zeroizing::new(format!("\x1b]52;c;{payload}\x07"));
+    let mut stdout = std::io::stdout();
+    stdout
+        .write_all(seq.as_bytes())
+        .and_then(|_| stdout.flush())
+        .context("Writing to the terminal failed")
+}
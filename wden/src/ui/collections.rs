@@ -1,20 +1,37 @@
+use std::sync::Arc;
+
 use cursive::{
-    view::{Scrollable, ViewWrapper},
-    views::{Dialog, SelectView},
+    traits::Resizable,
+    view::{Nameable, Scrollable, ViewWrapper},
+    views::{Dialog, DummyView, EditView, LinearLayout, SelectView, TextView},
     wrap_impl, Cursive,
 };
 use serde::{Deserialize, Serialize};
 
-use super::util::cursive_ext::CursiveExt;
+use crate::bitwarden::{api::ApiClient, cipher::Cipher};
+
+use super::{
+    data::{StatefulUserData, Unlocked},
+    sync::do_sync,
+    util::cursive_ext::CursiveExt,
+};
 
+/// The filter of the vault table. Like in the Bitwarden clients, a folder
+/// or a collection is shown at a time.
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub enum CollectionSelection {
     #[default]
     All,
     Unassigned,
     Collection(String),
+    NoFolder,
+    Folder(String),
 }
 
+const VIEW_NAME_COLLECTION_SELECT: &str = "collection_select";
+const VIEW_NAME_NEW_COLLECTION_ORG: &str = "new_collection_org";
+const VIEW_NAME_NEW_COLLECTION_NAME: &str = "new_collection_name";
+
 struct CollectionFilterDialog {
     dialog: Dialog,
 }
@@ -26,16 +43,12 @@ impl ViewWrapper for CollectionFilterDialog {
 impl CollectionFilterDialog {
     fn new<S: Fn(&mut Cursive, CollectionSelection) + 'static + Clone + Send + Sync>(
         collections: Vec<(String, String)>,
+        managed_organizations: Vec<(String, String)>,
         selection_callback: S,
     ) -> Self {
+        let collections = Arc::new(collections);
         let mut sel = SelectView::new();
-
-        sel.add_item("All", CollectionSelection::All);
-        sel.add_item("Unassigned", CollectionSelection::Unassigned);
-
-        for (name, id) in collections {
-            sel.add_item(name, CollectionSelection::Collection(id));
-        }
+        add_collection_items(&mut sel, &collections, "");
 
         let cb2 = selection_callback.clone();
         sel.set_on_submit(move |siv, sel| {
@@ -43,7 +56,37 @@ impl CollectionFilterDialog {
             cb2(siv, sel.clone());
         });
 
-        let dialog = Dialog::around(sel.scrollable())
+        let cb3 = selection_callback.clone();
+        let filter_edit = EditView::new()
+            .on_edit(move |siv, filter, _| {
+                siv.call_on_name(
+                    VIEW_NAME_COLLECTION_SELECT,
+                    |sel: &mut SelectView<CollectionSelection>| {
+                        sel.clear();
+                        add_collection_items(sel, &collections, filter);
+                    },
+                );
+            })
+            .on_submit(move |siv, _| {
+                // Select the first (or highlighted) match
+                let selection = siv
+                    .call_on_name(
+                        VIEW_NAME_COLLECTION_SELECT,
+                        |sel: &mut SelectView<CollectionSelection>| sel.selection(),
+                    )
+                    .flatten();
+                if let Some(selection) = selection {
+                    siv.pop_layer();
+                    cb3(siv, (*selection).clone());
+                }
+            });
+
+        let layout = LinearLayout::vertical()
+            .child(filter_edit)
+            .child(DummyView)
+            .child(sel.with_name(VIEW_NAME_COLLECTION_SELECT).scrollable());
+
+        let mut dialog = Dialog::around(layout)
             .title("Collections")
             .dismiss_button("Cancel")
             .button("Reset", move |siv| {
@@ -51,29 +94,217 @@ impl CollectionFilterDialog {
                 selection_callback(siv, CollectionSelection::All)
             });
 
+        if !managed_organizations.is_empty() {
+            let managed_organizations = Arc::new(managed_organizations);
+            dialog.add_button("New", move |siv| {
+                siv.pop_layer();
+                show_create_collection_dialog(siv, &managed_organizations);
+            });
+        }
+
         CollectionFilterDialog { dialog }
     }
 }
 
+fn add_collection_items(
+    sel: &mut SelectView<CollectionSelection>,
+    collections: &[(String, String)],
+    filter: &str,
+) {
+    if filter.trim().is_empty() {
+        sel.add_item("All", CollectionSelection::All);
+        sel.add_item("Unassigned", CollectionSelection::Unassigned);
+    }
+
+    for (name, id) in collections {
+        if collection_name_matches(name, filter) {
+            sel.add_item(name.clone(), CollectionSelection::Collection(id.clone()));
+        }
+    }
+}
+
+/// Case-insensitive match of a collection name against the filter. Nested
+/// collection names are paths separated with slashes, and each segment of a
+/// path filter must match a segment of the name in order: "infra/prod"
+/// matches "Infrastructure/Production".
+pub fn collection_name_matches(name: &str, filter: &str) -> bool {
+    let name = name.to_lowercase();
+    let filter = filter.trim().to_lowercase();
+
+    let mut name_segments = name.split('/');
+    filter
+        .split('/')
+        .all(|f| name_segments.any(|segment| segment.contains(f)))
+}
+
 pub fn show_collection_filter<S>(cursive: &mut Cursive, selection_callback: S)
 where
     S: Fn(&mut Cursive, CollectionSelection) + Clone + 'static + Send + Sync,
 {
     let ud = cursive.get_user_data().with_unlocked_state().unwrap();
 
+    let collection_items = collection_names(&ud, ud.collections().keys());
+    let org_keys = ud.get_org_keys_for_vault();
+
+    // Creating collections needs the server
+    let mut managed_organizations: Vec<_> = if ud.global_settings().offline {
+        Vec::new()
+    } else {
+        ud.organizations()
+            .values()
+            .filter(|o| o.can_manage_collections() && org_keys.contains_key(&o.id))
+            .map(|o| (o.name.clone(), o.id.clone()))
+            .collect()
+    };
+    managed_organizations.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let dialog =
+        CollectionFilterDialog::new(collection_items, managed_organizations, selection_callback);
+    cursive.add_layer(dialog);
+}
+
+/// Decrypts the names of the collections with the given ids. Returns
+/// (name, id) pairs sorted by the name.
+pub fn collection_names<'a>(
+    ud: &StatefulUserData<Unlocked>,
+    ids: impl Iterator<Item = &'a String>,
+) -> Vec<(String, String)> {
     let collections = ud.collections();
     let org_keys = ud.get_org_keys_for_vault();
 
-    let mut collection_items: Vec<_> = collections
-        .values()
+    let mut names: Vec<_> = ids
+        .filter_map(|id| collections.get(id))
         .filter_map(|c| {
             org_keys
                 .get(&c.organization_id)
                 .map(|keys| (c.name.decrypt_to_string(keys), c.id.clone()))
         })
         .collect();
-    collection_items.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    names.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    names
+}
+
+/// Shows a dialog for creating a collection in one of the given
+/// (name, id) organizations
+fn show_create_collection_dialog(cursive: &mut Cursive, organizations: &[(String, String)]) {
+    let mut org_select = SelectView::new().popup();
+    for (name, id) in organizations {
+        org_select.add_item(name.clone(), id.clone());
+    }
 
-    let dialog = CollectionFilterDialog::new(collection_items, selection_callback);
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Organization"))
+        .child(org_select.with_name(VIEW_NAME_NEW_COLLECTION_ORG))
+        .child(DummyView)
+        .child(TextView::new("Name"))
+        .child(
+            EditView::new()
+                .on_submit(|siv, _| create_collection(siv))
+                .with_name(VIEW_NAME_NEW_COLLECTION_NAME)
+                .min_width(40),
+        );
+
+    let dialog = Dialog::around(layout)
+        .title("New collection")
+        .dismiss_button("Cancel")
+        .button("Create", create_collection);
     cursive.add_layer(dialog);
 }
+
+fn create_collection(cursive: &mut Cursive) {
+    let name = cursive
+        .call_on_name(VIEW_NAME_NEW_COLLECTION_NAME, |e: &mut EditView| {
+            e.get_content()
+        })
+        .unwrap();
+    let name = name.trim();
+    if name.is_empty() {
+        cursive.add_layer(Dialog::info("The collection name can't be empty"));
+        return;
+    }
+    let Some(org_id) = cursive
+        .call_on_name(
+            VIEW_NAME_NEW_COLLECTION_ORG,
+            |s: &mut SelectView<String>| s.selection(),
+        )
+        .flatten()
+    else {
+        return;
+    };
+
+    let ud = cursive.get_user_data().with_unlocked_state().unwrap();
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+    let encrypted_name = ud
+        .get_org_keys_for_vault()
+        .get(&*org_id)
+        .map(|keys| Cipher::encrypt(name.as_bytes(), keys));
+    let encrypted_name = match encrypted_name {
+        Some(Ok(n)) => n,
+        Some(Err(e)) => {
+            cursive.add_layer(Dialog::info(format!("Encrypting the name failed: {e}")));
+            return;
+        }
+        None => {
+            cursive.add_layer(Dialog::info("The organization key is not available"));
+            return;
+        }
+    };
+
+    cursive.pop_layer();
+    cursive.add_layer(Dialog::text("Creating collection..."));
+    cursive.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            client.create_collection(&org_id, &encrypted_name).await
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok(id) => {
+                    log::info!("Created collection {id}");
+                    // Sync to get the new collection to the filter
+                    do_sync(siv, false);
+                }
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Creating the collection failed: {e}")));
+                }
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collection_name_matches;
+
+    #[test]
+    fn test_collection_name_matches() {
+        assert!(collection_name_matches("Infrastructure/Production", ""));
+        assert!(collection_name_matches("Infrastructure/Production", "PROD"));
+        assert!(collection_name_matches(
+            "Infrastructure/Production",
+            "infra/prod"
+        ));
+        assert!(collection_name_matches(
+            "Infrastructure/Production/Databases",
+            "infra/data"
+        ));
+        assert!(!collection_name_matches(
+            "Infrastructure/Production",
+            "prod/infra"
+        ));
+        assert!(!collection_name_matches(
+            "Infrastructure/Staging",
+            "infra/prod"
+        ));
+        assert!(!collection_name_matches("Marketing", "infra"));
+    }
+}
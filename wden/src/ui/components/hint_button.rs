@@ -0,0 +1,60 @@
+use cursive::{
+    direction::Direction,
+    event::{Callback, Event, EventResult, MouseButton, MouseEvent},
+    theme::PaletteColor,
+    view::{CannotFocus, ViewWrapper},
+    views::TextView,
+    wrap_impl, Cursive, Vec2, View,
+};
+
+/// Key hint text that can also be clicked with the mouse. It only takes
+/// focus from mouse clicks, so keyboard navigation skips it.
+pub struct HintButton {
+    view: TextView,
+    callback: Callback,
+    size: Vec2,
+}
+
+impl HintButton {
+    pub fn new<F>(content: &str, callback: F) -> Self
+    where
+        F: Fn(&mut Cursive) + Send + Sync + 'static,
+    {
+        HintButton {
+            view: TextView::new(content).style(PaletteColor::Tertiary),
+            callback: Callback::from_fn(callback),
+            size: Vec2::zero(),
+        }
+    }
+}
+
+impl ViewWrapper for HintButton {
+    wrap_impl!(self.view: TextView);
+
+    fn wrap_layout(&mut self, size: Vec2) {
+        self.size = size;
+        self.view.layout(size);
+    }
+
+    fn wrap_take_focus(&mut self, source: Direction) -> Result<EventResult, CannotFocus> {
+        // Mouse clicks focus views with no direction
+        if source == Direction::none() {
+            Ok(EventResult::Consumed(None))
+        } else {
+            Err(CannotFocus)
+        }
+    }
+
+    fn wrap_on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Release(MouseButton::Left),
+            } if position.fits_in_rect(offset, self.size) => {
+                EventResult::Consumed(Some(self.callback.clone()))
+            }
+            _ => EventResult::Ignored,
+        }
+    }
+}
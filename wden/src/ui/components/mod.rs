@@ -1 +1,3 @@
+pub mod hint_button;
 pub mod secret_text_view;
+pub mod warning_banner;
@@ -0,0 +1,16 @@
+use cursive::{
+    theme::{ColorStyle, Effect, Style},
+    traits::Resizable,
+    views::TextView,
+    View,
+};
+
+/// A full-width, highlighted warning line
+pub fn warning_banner(text: &str) -> impl View {
+    let style = Style::from(ColorStyle::highlight()).combine(Effect::Bold);
+    TextView::new(text).style(style).center().full_width()
+}
+
+pub fn insecure_http_banner() -> impl View {
+    warning_banner("Warning: the server is accessed over unencrypted HTTP")
+}
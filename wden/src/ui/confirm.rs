@@ -0,0 +1,53 @@
+use cursive::{
+    traits::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, TextView},
+    Cursive,
+};
+
+use crate::profile::ProfileEnvironment;
+
+use super::util::cursive_ext::CursiveExt;
+
+const VIEW_NAME_PROFILE_NAME: &str = "confirm_profile_name";
+
+/// Asks for a confirmation before a destructive operation. In production
+/// profiles, the profile name has to be typed in too.
+pub fn confirm_destructive(
+    siv: &mut Cursive,
+    title: &str,
+    text: String,
+    button: &str,
+    on_confirm: impl Fn(&mut Cursive) + Send + Sync + 'static,
+) {
+    let global_settings = siv.get_user_data().global_settings();
+    let production = global_settings.environment == ProfileEnvironment::Production;
+    let profile = global_settings.profile.clone();
+
+    let mut content = LinearLayout::vertical().child(TextView::new(text));
+    if production {
+        content.add_child(TextView::new(format!(
+            "\nThis is a production profile. Type the profile name ({profile}) to confirm."
+        )));
+        content.add_child(EditView::new().with_name(VIEW_NAME_PROFILE_NAME));
+    }
+
+    siv.add_layer(
+        Dialog::around(content)
+            .title(title)
+            .dismiss_button("Cancel")
+            .button(button, move |siv| {
+                if production {
+                    let typed = siv
+                        .call_on_name(VIEW_NAME_PROFILE_NAME, |v: &mut EditView| v.get_content())
+                        .unwrap();
+                    if typed.trim() != profile {
+                        siv.add_layer(Dialog::info("The profile name doesn't match"));
+                        return;
+                    }
+                }
+                siv.pop_layer();
+                on_confirm(siv);
+            })
+            .max_width(80),
+    );
+}
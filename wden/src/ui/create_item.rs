@@ -0,0 +1,567 @@
+use cursive::{
+    traits::{Nameable, Resizable, Scrollable},
+    views::{Button, Dialog, EditView, LinearLayout, SelectView, TextArea, TextView},
+    Cursive,
+};
+use cursive_secret_edit_view::SecretEditView;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::{
+        api::{ApiClient, NewCipher, NewCipherData, NewCustomField},
+        cipher::{Cipher, EncMacKeys},
+    },
+    profile::PendingItem,
+};
+
+use super::{
+    confirm::confirm_destructive,
+    generator::show_generator_for_field,
+    item_templates::{
+        item_templates, remove_item_template, save_item_template, ItemTemplate, TemplateField,
+    },
+    sync::do_sync,
+    util::cursive_ext::CursiveExt,
+};
+
+const VIEW_NAME_TYPE: &str = "new_item_type";
+const VIEW_NAME_NAME: &str = "new_item_name";
+const VIEW_NAME_NOTES: &str = "new_item_notes";
+const VIEW_NAME_OWNER: &str = "new_item_owner";
+const VIEW_NAME_PASSWORD: &str = "new_item_password";
+const VIEW_NAME_CUSTOM_FIELDS: &str = "new_item_custom_fields";
+// Followed by the index of the custom field
+const VIEW_NAME_FIELD_NAME: &str = "new_item_field_name_";
+const VIEW_NAME_FIELD_VALUE: &str = "new_item_field_value_";
+const VIEW_NAME_TEMPLATE_NAME: &str = "new_item_template_name";
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum NewItemType {
+    Login,
+    SecureNote,
+    Card,
+}
+
+impl NewItemType {
+    pub fn label(self) -> &'static str {
+        match self {
+            NewItemType::Login => "Login",
+            NewItemType::SecureNote => "Secure note",
+            NewItemType::Card => "Card",
+        }
+    }
+
+    /// View name, label and whether the field is hidden
+    fn fields(self) -> &'static [(&'static str, &'static str, bool)] {
+        match self {
+            NewItemType::Login => &[
+                ("new_item_username", "Username", false),
+                (VIEW_NAME_PASSWORD, "Password", true),
+                ("new_item_uri", "URI", false),
+            ],
+            NewItemType::SecureNote => &[],
+            NewItemType::Card => &[
+                ("new_item_cardholder_name", "Cardholder name", false),
+                ("new_item_number", "Number", true),
+                ("new_item_exp_month", "Expiration month", false),
+                ("new_item_exp_year", "Expiration year", false),
+                ("new_item_code", "Security code", true),
+            ],
+        }
+    }
+}
+
+/// The new item is either personal or created in an organization collection
+#[derive(Clone)]
+enum Owner {
+    Personal,
+    Collection {
+        organization_id: String,
+        collection_id: String,
+    },
+}
+
+/// A custom field as entered in the form
+struct CustomFieldValue {
+    name: Zeroizing<String>,
+    value: Zeroizing<String>,
+    hidden: bool,
+}
+
+impl Owner {
+    /// Organization and collection ids, as stored for pending items
+    fn into_collection(self) -> Option<(String, String)> {
+        match self {
+            Owner::Personal => None,
+            Owner::Collection {
+                organization_id,
+                collection_id,
+            } => Some((organization_id, collection_id)),
+        }
+    }
+}
+
+/// The item type, or a template of the profile
+type NewItemChoice = (NewItemType, Option<ItemTemplate>);
+
+pub fn show_create_item_dialog(siv: &mut Cursive) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let templates = ud
+        .decrypt_keys()
+        .map(|user_keys| item_templates(&ud, &user_keys))
+        .unwrap_or_default();
+    let has_templates = !templates.is_empty();
+
+    let mut select = SelectView::<NewItemChoice>::new()
+        .with_all(
+            [
+                NewItemType::Login,
+                NewItemType::SecureNote,
+                NewItemType::Card,
+            ]
+            .map(|t| (t.label().to_string(), (t, None))),
+        )
+        .on_submit(|siv, (item_type, template): &NewItemChoice| {
+            siv.pop_layer();
+            show_item_form(siv, *item_type, template.clone());
+        });
+    for t in templates {
+        let label = format!("{} ({})", t.name, t.item_type.label().to_lowercase());
+        select.add_item(label, (t.item_type, Some(t)));
+    }
+
+    let mut dialog = Dialog::around(select.with_name(VIEW_NAME_TYPE).scrollable())
+        .title("New item")
+        .dismiss_button("Cancel");
+    if has_templates {
+        dialog.add_button("Remove template", remove_selected_template);
+    }
+    siv.add_layer(dialog);
+}
+
+fn remove_selected_template(siv: &mut Cursive) {
+    let selection = siv
+        .call_on_name(VIEW_NAME_TYPE, |v: &mut SelectView<NewItemChoice>| {
+            v.selection()
+        })
+        .flatten();
+    let Some(template) = selection.and_then(|s| s.1.clone()) else {
+        siv.add_layer(Dialog::info("Select a template to remove"));
+        return;
+    };
+
+    confirm_destructive(
+        siv,
+        "Remove template",
+        format!("Remove the template \"{}\"?", template.name),
+        "Remove",
+        move |siv| {
+            // Close the list, and show it again without the template
+            siv.pop_layer();
+            match remove_item_template(siv, &template.name) {
+                Ok(()) => show_create_item_dialog(siv),
+                Err(e) => {
+                    log::warn!("Storing the item templates failed: {e}");
+                    siv.add_layer(Dialog::info(format!("Removing the template failed: {e}")));
+                }
+            }
+        },
+    );
+}
+
+fn show_item_form(siv: &mut Cursive, item_type: NewItemType, template: Option<ItemTemplate>) {
+    let owners = owner_choices(siv);
+    let (notes, fields) = template.map(|t| (t.notes, t.fields)).unwrap_or_default();
+
+    let mut layout = LinearLayout::vertical()
+        .child(TextView::new("Name"))
+        .child(EditView::new().with_name(VIEW_NAME_NAME).fixed_width(50));
+    for &(view_name, label, hidden) in item_type.fields() {
+        layout.add_child(TextView::new(label));
+        if hidden {
+            layout.add_child(SecretEditView::new().with_name(view_name).fixed_width(50));
+        } else {
+            layout.add_child(EditView::new().with_name(view_name).fixed_width(50));
+        }
+    }
+    layout.add_child(TextView::new("Notes"));
+    layout.add_child(
+        TextArea::new()
+            .content(notes)
+            .with_name(VIEW_NAME_NOTES)
+            .fixed_width(50)
+            .min_height(3),
+    );
+    layout.add_child(TextView::new("Custom fields (name, value)"));
+    let mut custom_fields = LinearLayout::vertical();
+    for (index, f) in fields.iter().enumerate() {
+        custom_fields.add_child(custom_field_row(index, &f.name, &f.value, f.hidden));
+    }
+    layout.add_child(custom_fields.with_name(VIEW_NAME_CUSTOM_FIELDS));
+    layout.add_child(
+        LinearLayout::horizontal()
+            .child(Button::new("Add field", |siv| add_custom_field(siv, false)))
+            .child(Button::new("Add hidden field", |siv| {
+                add_custom_field(siv, true)
+            })),
+    );
+    if owners.len() > 1 {
+        layout.add_child(TextView::new("Owner"));
+        layout.add_child(
+            SelectView::new()
+                .popup()
+                .with_all(owners)
+                .with_name(VIEW_NAME_OWNER),
+        );
+    }
+
+    let mut dialog = Dialog::around(layout.scrollable())
+        .title(format!("New {}", item_type.label().to_lowercase()))
+        .dismiss_button("Cancel");
+    if matches!(item_type, NewItemType::Login) {
+        dialog.add_button("Generate password", |siv| {
+            show_generator_for_field(siv, insert_generated_password)
+        });
+    }
+    dialog.add_button("Save as template", move |siv| {
+        show_save_template_dialog(siv, item_type)
+    });
+    siv.add_layer(dialog.button("Save", move |siv| save_item(siv, item_type)));
+}
+
+fn custom_field_row(index: usize, name: &str, value: &str, hidden: bool) -> LinearLayout {
+    let mut row = LinearLayout::horizontal()
+        .child(
+            EditView::new()
+                .content(name)
+                .with_name(format!("{VIEW_NAME_FIELD_NAME}{index}"))
+                .fixed_width(20),
+        )
+        .child(TextView::new(" "));
+    let value_name = format!("{VIEW_NAME_FIELD_VALUE}{index}");
+    if hidden {
+        row.add_child(
+            SecretEditView::new()
+                .content(value.to_string())
+                .with_name(value_name)
+                .fixed_width(29),
+        );
+    } else {
+        row.add_child(
+            EditView::new()
+                .content(value)
+                .with_name(value_name)
+                .fixed_width(29),
+        );
+    }
+    row
+}
+
+fn add_custom_field(siv: &mut Cursive, hidden: bool) {
+    siv.call_on_name(VIEW_NAME_CUSTOM_FIELDS, |v: &mut LinearLayout| {
+        let index = v.len();
+        v.add_child(custom_field_row(index, "", "", hidden));
+    });
+}
+
+/// The custom fields of the form that have a name
+fn custom_field_values(siv: &mut Cursive) -> Vec<CustomFieldValue> {
+    let count = siv
+        .call_on_name(VIEW_NAME_CUSTOM_FIELDS, |v: &mut LinearLayout| v.len())
+        .unwrap_or_default();
+    (0..count)
+        .filter_map(|index| {
+            let name = field_value(siv, &format!("{VIEW_NAME_FIELD_NAME}{index}"));
+            let value_name = format!("{VIEW_NAME_FIELD_VALUE}{index}");
+            let hidden = siv
+                .call_on_name(&value_name, |_: &mut SecretEditView| ())
+                .is_some();
+            let value = field_value(siv, &value_name);
+            (!name.trim().is_empty()).then_some(CustomFieldValue {
+                name,
+                value,
+                hidden,
+            })
+        })
+        .collect()
+}
+
+fn notes_value(siv: &mut Cursive) -> Zeroizing<String> {
+    siv.call_on_name(VIEW_NAME_NOTES, |v: &mut TextArea| {
+        Zeroizing::new(v.get_content().to_string())
+    })
+    .unwrap_or_default()
+}
+
+/// Saves the notes and the custom fields of the form as a template.
+/// The values of hidden fields are left out.
+fn show_save_template_dialog(siv: &mut Cursive, item_type: NewItemType) {
+    let content = LinearLayout::vertical()
+        .child(TextView::new(
+            "The notes and the custom fields are saved in the template. \
+             The values of hidden fields are left out.\n\nTemplate name",
+        ))
+        .child(
+            EditView::new()
+                .with_name(VIEW_NAME_TEMPLATE_NAME)
+                .fixed_width(40),
+        );
+
+    siv.add_layer(
+        Dialog::around(content)
+            .title("Save as template")
+            .dismiss_button("Cancel")
+            .button("Save", move |siv| {
+                let name = siv
+                    .call_on_name(VIEW_NAME_TEMPLATE_NAME, |v: &mut EditView| v.get_content())
+                    .unwrap();
+                if name.trim().is_empty() {
+                    siv.add_layer(Dialog::info("The template name is required"));
+                    return;
+                }
+                let fields = custom_field_values(siv)
+                    .into_iter()
+                    .map(|f| TemplateField {
+                        name: f.name.to_string(),
+                        value: if f.hidden {
+                            String::new()
+                        } else {
+                            f.value.to_string()
+                        },
+                        hidden: f.hidden,
+                    })
+                    .collect();
+                let template = ItemTemplate {
+                    name: name.trim().to_string(),
+                    item_type,
+                    notes: notes_value(siv).to_string(),
+                    fields,
+                };
+                siv.pop_layer();
+                save_item_template(siv, template);
+            })
+            .max_width(60),
+    );
+}
+
+/// The personal vault, and the collections of the organizations
+fn owner_choices(siv: &mut Cursive) -> Vec<(String, Owner)> {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let organizations = ud.organizations();
+
+    let mut collections: Vec<_> = ud
+        .collections()
+        .values()
+        .filter_map(|c| {
+            let keys = ud.get_keys_for_collection(c)?;
+            let org_name = organizations
+                .get(&c.organization_id)
+                .map(|o| o.name.as_str())
+                .unwrap_or_default();
+            let label = format!("{org_name} / {}", c.name.decrypt_to_string(&keys));
+            let owner = Owner::Collection {
+                organization_id: c.organization_id.clone(),
+                collection_id: c.id.clone(),
+            };
+            Some((label, owner))
+        })
+        .collect();
+    collections.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut owners = vec![("My vault".to_string(), Owner::Personal)];
+    owners.extend(collections);
+    owners
+}
+
+fn insert_generated_password(siv: &mut Cursive, password: Zeroizing<String>) {
+    let cb = siv.call_on_name(VIEW_NAME_PASSWORD, |v: &mut SecretEditView| {
+        v.set_content(password)
+    });
+    if let Some(cb) = cb {
+        cb(siv);
+    }
+}
+
+fn field_value(siv: &mut Cursive, view_name: &str) -> Zeroizing<String> {
+    siv.call_on_name(view_name, |v: &mut EditView| {
+        Zeroizing::new(String::clone(&v.get_content()))
+    })
+    .or_else(|| {
+        siv.call_on_name(view_name, |v: &mut SecretEditView| {
+            Zeroizing::new(v.get_content().to_string())
+        })
+    })
+    .unwrap_or_default()
+}
+
+fn save_item(siv: &mut Cursive, item_type: NewItemType) {
+    let name = field_value(siv, VIEW_NAME_NAME);
+    if name.trim().is_empty() {
+        siv.add_layer(Dialog::info("The name is required"));
+        return;
+    }
+    let values: Vec<_> = item_type
+        .fields()
+        .iter()
+        .map(|(view_name, _, _)| field_value(siv, view_name))
+        .collect();
+    let notes = notes_value(siv);
+    let fields = custom_field_values(siv);
+    let owner = siv
+        .call_on_name(VIEW_NAME_OWNER, |v: &mut SelectView<Owner>| v.selection())
+        .flatten()
+        .map(|o| (*o).clone())
+        .unwrap_or(Owner::Personal);
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let keys = match &owner {
+        Owner::Personal => ud.decrypt_keys(),
+        Owner::Collection {
+            organization_id, ..
+        } => ud.get_org_keys_for_vault().remove(organization_id),
+    };
+    let Some(keys) = keys else {
+        siv.add_layer(Dialog::info("The encryption keys are not available"));
+        return;
+    };
+    let item = match encrypt_item(item_type, &name, &notes, &values, &fields, &keys) {
+        Ok(item) => item,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Encrypting the item failed: {e}")));
+            return;
+        }
+    };
+
+    let global_settings = ud.global_settings();
+    if global_settings.offline {
+        queue_item(siv, item, owner);
+        return;
+    }
+
+    let token = ud.token();
+    let collection = owner.into_collection();
+    siv.add_layer(Dialog::text("Saving..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            push_item(&client, &item, collection.as_ref()).await
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok(_) => {
+                    // Close the form and sync to show the new item
+                    siv.pop_layer();
+                    do_sync(siv, false);
+                }
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Saving the item failed: {e}")));
+                }
+            }
+        },
+    );
+}
+
+/// Creates the item on the server, in the organization collection if one
+/// is given. Returns the id of the created item.
+pub async fn push_item(
+    client: &ApiClient,
+    item: &NewCipher,
+    collection: Option<&(String, String)>,
+) -> anyhow::Result<String> {
+    let organization = collection.map(|(organization_id, collection_id)| {
+        (
+            organization_id.as_str(),
+            std::slice::from_ref(collection_id),
+        )
+    });
+    client.create_cipher(item, organization).await
+}
+
+/// In offline mode, the item is stored in the profile, and pushed to the
+/// server after the next online sync
+fn queue_item(siv: &mut Cursive, item: NewCipher, owner: Owner) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let profile_store = ud.profile_store();
+    let pending = PendingItem {
+        id: uuid::Uuid::new_v4().to_string(),
+        item,
+        collection: owner.into_collection(),
+        created: jiff::Timestamp::now().to_string(),
+    };
+    let res = profile_store.load_pending_items().and_then(|mut items| {
+        items.push(pending);
+        profile_store.store_pending_items(&items)
+    });
+
+    match res {
+        Ok(()) => {
+            siv.pop_layer();
+            siv.add_layer(Dialog::info(
+                "The item was saved locally. It's pushed to the server after the next online sync.",
+            ));
+        }
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Saving the item failed: {e:#}")));
+        }
+    }
+}
+
+fn encrypt_item(
+    item_type: NewItemType,
+    name: &str,
+    notes: &str,
+    values: &[Zeroizing<String>],
+    fields: &[CustomFieldValue],
+    keys: &EncMacKeys,
+) -> anyhow::Result<NewCipher> {
+    let encrypt = |value: &str| -> anyhow::Result<Option<Cipher>> {
+        if value.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Cipher::encrypt(value.as_bytes(), keys)?))
+        }
+    };
+
+    let data = match item_type {
+        NewItemType::Login => NewCipherData::Login {
+            username: encrypt(&values[0])?,
+            password: encrypt(&values[1])?,
+            uri: encrypt(&values[2])?,
+        },
+        NewItemType::SecureNote => NewCipherData::SecureNote,
+        NewItemType::Card => NewCipherData::Card {
+            cardholder_name: encrypt(&values[0])?,
+            number: encrypt(&values[1])?,
+            exp_month: encrypt(&values[2])?,
+            exp_year: encrypt(&values[3])?,
+            code: encrypt(&values[4])?,
+        },
+    };
+
+    let fields = fields
+        .iter()
+        .map(|f| -> anyhow::Result<_> {
+            Ok(NewCustomField {
+                name: Cipher::encrypt(f.name.as_bytes(), keys)?,
+                value: encrypt(&f.value)?,
+                hidden: f.hidden,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(NewCipher {
+        name: Cipher::encrypt(name.as_bytes(), keys)?,
+        notes: encrypt(notes)?,
+        data,
+        fields,
+    })
+}
@@ -1,10 +1,16 @@
 use crate::{
     bitwarden::{
-        api::{self, CipherItem, Collection, Organization, TokenResponseSuccess},
+        api::{
+            self, CipherItem, Collection, Folder, Organization, Policy, SyncResponse,
+            TokenResponseSuccess,
+        },
         apikey::ApiKey,
-        cipher::{self, EncMacKeys, MasterKey, MasterPasswordHash, PbkdfParameters},
+        cipher::{
+            self, DerPrivateKey, EncMacKeys, MasterKey, MasterPasswordHash, PbkdfParameters,
+            PrivateKey, WrappedMasterKey, WrappedSymmetricKeys,
+        },
     },
-    profile::{GlobalSettings, ProfileStore},
+    profile::{self, GlobalSettings, ProfileStore},
 };
 use anyhow::Context;
 use cipher::decrypt_symmetric_keys;
@@ -12,13 +18,17 @@ use maybe_owned::MaybeOwned;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
     marker::PhantomData,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
 };
 
-use super::{autolock::Autolocker, collections::CollectionSelection};
+use super::{
+    autolock::Autolocker, collections::CollectionSelection, undo::UndoStack,
+    vault_table::VaultPosition,
+};
 
 macro_rules! get_state_data {
     ($app_state_data: expr, $state: path) => {{
@@ -39,16 +49,26 @@ pub struct LoggedOut;
 pub struct LoggingIn {
     email: Arc<String>,
     pbkdf: Arc<PbkdfParameters>,
-    master_key: Arc<cipher::MasterKey>,
+    master_key: Arc<WrappedMasterKey>,
     master_password_hash: Arc<cipher::MasterPasswordHash>,
     api_key: Option<Arc<ApiKey>>,
 }
 
+/// Key material held for decrypting the vault while logged in
+enum UserKeys {
+    /// The symmetric keys are decrypted from the token key when needed
+    MasterKey(Arc<WrappedMasterKey>),
+    /// The master key has been dropped, only the symmetric keys are kept
+    SymmetricKeys(Arc<WrappedSymmetricKeys>),
+}
+
 pub struct Refreshing {
     email: Arc<String>,
     pbkdf: Arc<PbkdfParameters>,
-    master_key: Arc<cipher::MasterKey>,
+    user_keys: UserKeys,
     api_key: Option<Arc<ApiKey>>,
+    // Set when syncing an unlocked vault
+    previous_sync: Option<PreviousSync>,
 }
 
 impl From<LoggingIn> for Refreshing {
@@ -56,12 +76,35 @@ impl From<LoggingIn> for Refreshing {
         Self {
             email: logging_in.email,
             pbkdf: logging_in.pbkdf,
-            master_key: logging_in.master_key,
+            user_keys: UserKeys::MasterKey(logging_in.master_key),
             api_key: logging_in.api_key,
+            previous_sync: None,
         }
     }
 }
 
+/// The vault data before a sync, for finding the items that the sync
+/// added, modified or removed
+struct PreviousSync {
+    vault_data: Arc<HashMap<String, CipherItem>>,
+    // Changes from earlier syncs that have not been viewed yet
+    unseen_changes: HashSet<String>,
+}
+
+impl PreviousSync {
+    fn changed_items(&self, vault_data: &HashMap<String, CipherItem>) -> HashSet<String> {
+        vault_data
+            .iter()
+            .filter(|(id, item)| {
+                self.unseen_changes.contains(*id)
+                    || self.vault_data.get(*id).map(|i| &i.revision_date)
+                        != Some(&item.revision_date)
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
 pub struct LoggedIn {
     refreshing_data: Refreshing,
     token: Arc<TokenResponseSuccess>,
@@ -69,9 +112,28 @@ pub struct LoggedIn {
 
 impl LoggedIn {
     fn decrypt_keys(&self) -> Option<EncMacKeys> {
-        let token_key = &self.token.key;
-        let master_key = &self.refreshing_data.master_key;
-        decrypt_symmetric_keys(token_key, master_key).ok()
+        match &self.refreshing_data.user_keys {
+            UserKeys::MasterKey(master_key) => {
+                let token_key = &self.token.key;
+                // The master key is unwrapped only for the duration of this call
+                let master_key = master_key.unwrap_key().ok()?;
+                decrypt_symmetric_keys(token_key, &master_key).ok()
+            }
+            UserKeys::SymmetricKeys(keys) => keys.unwrap_keys().ok(),
+        }
+    }
+
+    /// Replaces the master key with the symmetric keys derived from it
+    fn forget_master_key(&mut self) {
+        if let UserKeys::MasterKey(_) = self.refreshing_data.user_keys {
+            match self.decrypt_keys().map(|k| WrappedSymmetricKeys::wrap(&k)) {
+                Some(Ok(keys)) => {
+                    self.refreshing_data.user_keys = UserKeys::SymmetricKeys(Arc::new(keys));
+                    log::info!("Dropped the master key");
+                }
+                _ => log::warn!("Deriving the symmetric keys failed, keeping the master key"),
+            }
+        }
     }
 }
 
@@ -80,9 +142,27 @@ pub struct Unlocked {
     vault_data: Arc<HashMap<String, CipherItem>>,
     organizations: Arc<HashMap<String, Organization>>,
     collections: Arc<HashMap<String, Collection>>,
+    folders: Arc<HashMap<String, Folder>>,
+    // Parsed on first use, dropped (and zeroized) when locking
+    private_key: OnceLock<PrivateKey>,
+    // Items added or modified in the last sync, until viewed
+    changed_item_ids: HashSet<String>,
+    // The still encrypted vault data before the last sync
+    previous_vault_data: Option<Arc<HashMap<String, CipherItem>>>,
 }
 
 impl Unlocked {
+    fn private_key(&self, user_keys: &EncMacKeys) -> anyhow::Result<&PrivateKey> {
+        if let Some(key) = self.private_key.get() {
+            return Ok(key);
+        }
+
+        let user_private_key = &self.logged_in_data.token.private_key;
+        let der: DerPrivateKey = user_private_key.decrypt(user_keys)?.into();
+        let key = PrivateKey::from_der(&der)?;
+        Ok(self.private_key.get_or_init(|| key))
+    }
+
     fn decrypt_organization_keys(
         &self,
         organization_id: &str,
@@ -93,13 +173,9 @@ impl Unlocked {
             .get(organization_id)
             .with_context(|| format!("Org not found with id {organization_id}"))?;
 
-        // Organization.key is encrypted with the user private (RSA) key,
-        // get that first
-        let user_private_key = &self.logged_in_data.token.private_key;
-        let decrypted_private_key = user_private_key.decrypt(user_keys)?.into();
-
-        // Then use the private key to decrypt the organization key
-        let org_key = cipher::decrypt_org_keys(&decrypted_private_key, &organization.key)?;
+        // Organization.key is encrypted with the user private (RSA) key
+        let private_key = self.private_key(user_keys)?;
+        let org_key = cipher::decrypt_org_keys(private_key, &organization.key)?;
         Ok(org_key)
     }
 
@@ -132,6 +208,12 @@ impl Unlocked {
         self.logged_in_data
             .decrypt_keys()
             .map(|uk| {
+                // Parse the private key once before decrypting the org keys in parallel
+                if !self.organizations.is_empty() {
+                    let _ = self
+                        .private_key(&uk)
+                        .inspect_err(|e| log::warn!("Private key decryption failed: {e}"));
+                }
                 self.organizations
                     .keys()
                     .par_bridge()
@@ -153,9 +235,15 @@ pub struct Locked {
     vault_data: Arc<HashMap<String, CipherItem>>,
     organizations: Arc<HashMap<String, Organization>>,
     collections: Arc<HashMap<String, Collection>>,
+    folders: Arc<HashMap<String, Folder>>,
     encrypted_search_term: cipher::Cipher,
     collection_selection: CollectionSelection,
+    changed_item_ids: HashSet<String>,
+    previous_vault_data: Option<Arc<HashMap<String, CipherItem>>>,
     api_key: Option<Arc<ApiKey>>,
+    /// Restored from a remembered session: the vault data is fetched
+    /// after unlocking
+    sync_on_unlock: bool,
 }
 
 pub struct Unlocking {
@@ -163,8 +251,11 @@ pub struct Unlocking {
     vault_data: Arc<HashMap<String, CipherItem>>,
     organizations: Arc<HashMap<String, Organization>>,
     collections: Arc<HashMap<String, Collection>>,
+    folders: Arc<HashMap<String, Folder>>,
     encrypted_search_term: cipher::Cipher,
     collection_selection: CollectionSelection,
+    changed_item_ids: HashSet<String>,
+    previous_vault_data: Option<Arc<HashMap<String, CipherItem>>>,
 }
 
 enum AppStateData {
@@ -201,6 +292,11 @@ pub struct UserData {
     profile_store: Arc<ProfileStore>,
     autolocker: Arc<Mutex<Autolocker>>,
     state_data: AppStateData,
+    vault_position: Option<VaultPosition>,
+    last_sync_duration: Option<Duration>,
+    slow_response_hint: Option<String>,
+    org_policies: Arc<Vec<Policy>>,
+    undo_stack: UndoStack,
 }
 
 /// A pseudo-state: either LoggingIn or Refreshing
@@ -244,57 +340,131 @@ impl UserData {
             profile_store,
             global_settings,
             state_data: state,
+            vault_position: None,
+            last_sync_duration: None,
+            slow_response_hint: None,
+            org_policies: Default::default(),
+            undo_stack: Default::default(),
         }
     }
 
-    pub fn with_logged_out_state(&mut self) -> Option<StatefulUserData<LoggedOut>> {
+    /// Name of the active profile, in any state
+    pub fn profile(&self) -> &str {
+        &self.global_settings.profile
+    }
+
+    /// Settings of the active profile, in any state
+    pub fn global_settings(&self) -> Arc<GlobalSettings> {
+        self.global_settings.clone()
+    }
+
+    pub fn with_logged_out_state(&mut self) -> Option<StatefulUserData<'_, LoggedOut>> {
         match &self.state_data {
             &AppStateData::LoggedOut(_) => Some(StatefulUserData::new(self)),
             _ => None,
         }
     }
 
-    pub fn with_logging_in_state(&mut self) -> Option<StatefulUserData<LoggingIn>> {
+    pub fn with_logging_in_state(&mut self) -> Option<StatefulUserData<'_, LoggingIn>> {
         match &self.state_data {
             &AppStateData::LoggingIn(_) => Some(StatefulUserData::new(self)),
             _ => None,
         }
     }
 
-    pub fn with_logged_in_state(&mut self) -> Option<StatefulUserData<LoggedIn>> {
+    pub fn with_logged_in_state(&mut self) -> Option<StatefulUserData<'_, LoggedIn>> {
         match &self.state_data {
             &AppStateData::LoggedIn(_) => Some(StatefulUserData::new(self)),
             _ => None,
         }
     }
 
-    pub fn with_unlocked_state(&mut self) -> Option<StatefulUserData<Unlocked>> {
+    pub fn with_unlocked_state(&mut self) -> Option<StatefulUserData<'_, Unlocked>> {
         match &self.state_data {
             &AppStateData::Unlocked(_) => Some(StatefulUserData::new(self)),
             _ => None,
         }
     }
 
-    pub fn with_locked_state(&mut self) -> Option<StatefulUserData<Locked>> {
+    pub fn with_locked_state(&mut self) -> Option<StatefulUserData<'_, Locked>> {
         match &self.state_data {
             &AppStateData::Locked(_) => Some(StatefulUserData::new(self)),
             _ => None,
         }
     }
 
-    pub fn with_logging_in_like_state(&mut self) -> Option<StatefulUserData<LoggingInLikeState>> {
+    pub fn with_logging_in_like_state(&mut self) -> Option<StatefulUserData<'_, LoggingInLikeState>> {
         match &self.state_data {
             AppStateData::Refreshing(_) => Some(StatefulUserData::new(self)),
             AppStateData::LoggingIn(_) => Some(StatefulUserData::new(self)),
             _ => None,
         }
     }
+
+    /// Stores the vault table position while the vault view is recreated,
+    /// e.g. during a sync
+    pub fn store_vault_position(&mut self, position: VaultPosition) {
+        self.vault_position = Some(position);
+    }
+
+    pub fn take_vault_position(&mut self) -> Option<VaultPosition> {
+        self.vault_position.take()
+    }
+
+    /// Destructive actions of this session, in any state
+    pub fn undo_stack(&mut self) -> &mut UndoStack {
+        &mut self.undo_stack
+    }
+
+    pub fn set_last_sync_duration(&mut self, duration: Duration) {
+        self.last_sync_duration = Some(duration);
+    }
+
+    /// `None` if the vault has not been synced, e.g. in offline mode
+    pub fn last_sync_duration(&self) -> Option<Duration> {
+        self.last_sync_duration
+    }
+
+    /// Stores a hint about a slow server response, to be shown when the
+    /// running operation has finished. Only the latest hint is kept.
+    pub fn set_slow_response_hint(&mut self, hint: String) {
+        self.slow_response_hint = Some(hint);
+    }
+
+    pub fn take_slow_response_hint(&mut self) -> Option<String> {
+        self.slow_response_hint.take()
+    }
+
+    /// Stores the organization policies from the sync response, and
+    /// limits the autolock duration to the strictest vault timeout policy
+    pub fn set_org_policies(&mut self, policies: Vec<Policy>) {
+        for policy in policies.iter().filter(|p| p.enabled) {
+            log::info!(
+                "Active organization policy: {} ({})",
+                policy.name(),
+                policy.organization_id
+            );
+        }
+        let max_timeout = policies
+            .iter()
+            .filter_map(Policy::maximum_vault_timeout)
+            .min();
+        self.autolocker
+            .lock()
+            .unwrap()
+            .set_max_autolock_duration(max_timeout);
+        self.org_policies = Arc::new(policies);
+    }
+
+    pub fn org_policies(&self) -> Arc<Vec<Policy>> {
+        self.org_policies.clone()
+    }
 }
 
 impl<'a> StatefulUserData<'a, LoggedOut> {
     pub fn into_logging_in(
         self,
-        master_key: Arc<MasterKey>,
+        master_key: Arc<WrappedMasterKey>,
         master_password_hash: Arc<MasterPasswordHash>,
         pbkdf: Arc<PbkdfParameters>,
         email: Arc<String>,
@@ -310,6 +480,65 @@ impl<'a> StatefulUserData<'a, LoggedOut> {
 
         StatefulUserData::new(self.user_data)
     }
+
+    /// Moves straight to the locked state with vault data from the offline cache
+    pub fn into_offline_locked(
+        self,
+        email: Arc<String>,
+        pbkdf: Arc<PbkdfParameters>,
+        token: Arc<TokenResponseSuccess>,
+        sync_res: SyncResponse,
+    ) -> StatefulUserData<'a, Locked> {
+        self.user_data.set_org_policies(sync_res.policies);
+        self.user_data.state_data = AppStateData::Locked(Locked {
+            email,
+            pbkdf,
+            token,
+            vault_data: index_by_id(sync_res.ciphers, |ci| &ci.id),
+            organizations: index_by_id(sync_res.profile.organizations, |o| &o.id),
+            collections: index_by_id(sync_res.collections, |c| &c.id),
+            folders: index_by_id(sync_res.folders, |f| &f.id),
+            encrypted_search_term: Default::default(),
+            collection_selection: Default::default(),
+            changed_item_ids: Default::default(),
+            previous_vault_data: None,
+            api_key: None,
+            sync_on_unlock: false,
+        });
+
+        StatefulUserData::new(self.user_data)
+    }
+
+    /// Moves straight to the locked state with a remembered session. There
+    /// is no vault data until it's synced after unlocking.
+    pub fn into_remembered_locked(
+        self,
+        email: Arc<String>,
+        pbkdf: Arc<PbkdfParameters>,
+        token: Arc<TokenResponseSuccess>,
+    ) -> StatefulUserData<'a, Locked> {
+        self.user_data.state_data = AppStateData::Locked(Locked {
+            email,
+            pbkdf,
+            token,
+            vault_data: Default::default(),
+            organizations: Default::default(),
+            collections: Default::default(),
+            folders: Default::default(),
+            encrypted_search_term: Default::default(),
+            collection_selection: Default::default(),
+            changed_item_ids: Default::default(),
+            previous_vault_data: None,
+            api_key: None,
+            sync_on_unlock: true,
+        });
+
+        StatefulUserData::new(self.user_data)
+    }
+}
+
+pub fn index_by_id<T>(items: Vec<T>, id: impl Fn(&T) -> &String) -> Arc<HashMap<String, T>> {
+    Arc::new(items.into_iter().map(|i| (id(&i).clone(), i)).collect())
 }
 
 impl<'a> StatefulUserData<'a, LoggingIn> {
@@ -382,20 +611,40 @@ impl<'a> StatefulUserData<'a, LoggedIn> {
             .clone()
     }
 
+    pub fn pbkdf(&self) -> Arc<PbkdfParameters> {
+        get_state_data!(&self.user_data.state_data, AppStateData::LoggedIn)
+            .refreshing_data
+            .pbkdf
+            .clone()
+    }
+
     pub fn into_unlocked(
         self,
         vault_data: Arc<HashMap<String, CipherItem>>,
         organizations: Arc<HashMap<String, Organization>>,
         collections: Arc<HashMap<String, Collection>>,
+        folders: Arc<HashMap<String, Folder>>,
     ) -> StatefulUserData<'a, Unlocked> {
         let state_data =
             std::mem::replace(&mut self.user_data.state_data, AppStateData::Intermediate);
-        let logged_in_data = get_state_data!(state_data, AppStateData::LoggedIn);
+        let mut logged_in_data = get_state_data!(state_data, AppStateData::LoggedIn);
+        if self.global_settings().forget_master_key {
+            logged_in_data.forget_master_key();
+        }
+        let previous_sync = logged_in_data.refreshing_data.previous_sync.take();
+        let changed_item_ids = previous_sync
+            .as_ref()
+            .map(|previous| previous.changed_items(&vault_data))
+            .unwrap_or_default();
         let unlocked_data = Unlocked {
             logged_in_data,
             vault_data,
             organizations,
             collections,
+            folders,
+            private_key: OnceLock::new(),
+            changed_item_ids,
+            previous_vault_data: previous_sync.map(|previous| previous.vault_data),
         };
 
         self.user_data.state_data = AppStateData::Unlocked(unlocked_data);
@@ -403,6 +652,38 @@ impl<'a> StatefulUserData<'a, LoggedIn> {
         StatefulUserData::new(self.user_data)
     }
 
+    /// The master key, unless it has already been dropped
+    pub fn master_key(&self) -> Option<MasterKey> {
+        let logged_in_data = get_state_data!(&self.user_data.state_data, AppStateData::LoggedIn);
+        match &logged_in_data.refreshing_data.user_keys {
+            UserKeys::MasterKey(master_key) => master_key.unwrap_key().ok(),
+            UserKeys::SymmetricKeys(_) => None,
+        }
+    }
+
+    /// Checks the profile keyfile, if one is configured. The token key
+    /// is decrypted first to make sure that the master key is correct.
+    pub fn verify_keyfile(&self) -> anyhow::Result<()> {
+        let global_settings = self.global_settings();
+        let Some(keyfile) = &global_settings.keyfile else {
+            return Ok(());
+        };
+
+        let logged_in_data = get_state_data!(&self.user_data.state_data, AppStateData::LoggedIn);
+        let UserKeys::MasterKey(master_key) = &logged_in_data.refreshing_data.user_keys else {
+            // The keyfile was already checked before the master key was dropped
+            return Ok(());
+        };
+        let master_key = master_key.unwrap_key()?;
+        decrypt_symmetric_keys(&logged_in_data.token.key, &master_key)?;
+
+        profile::verify_keyfile(keyfile, &master_key, &self.profile_store())
+    }
+
+    pub fn into_logged_out(self) -> StatefulUserData<'a, LoggedOut> {
+        into_logged_out_impl(self.user_data)
+    }
+
     pub fn into_refreshing(self) -> StatefulUserData<'a, Refreshing> {
         let state_data =
             std::mem::replace(&mut self.user_data.state_data, AppStateData::Intermediate);
@@ -443,9 +724,13 @@ impl<'a> StatefulUserData<'a, Unlocked> {
             vault_data: unlocked_data.vault_data,
             organizations: unlocked_data.organizations,
             collections: unlocked_data.collections,
+            folders: unlocked_data.folders,
             encrypted_search_term: enc_search_term.unwrap_or_default(),
             collection_selection,
+            changed_item_ids: unlocked_data.changed_item_ids,
+            previous_vault_data: unlocked_data.previous_vault_data,
             api_key: unlocked_data.logged_in_data.refreshing_data.api_key,
+            sync_on_unlock: false,
         };
 
         self.user_data.state_data = AppStateData::Locked(locked_data);
@@ -456,17 +741,45 @@ impl<'a> StatefulUserData<'a, Unlocked> {
     pub fn into_logged_in(self) -> StatefulUserData<'a, LoggedIn> {
         let state_data =
             std::mem::replace(&mut self.user_data.state_data, AppStateData::Intermediate);
-        let unlocked_data = get_state_data!(state_data, AppStateData::Unlocked);
+        let mut unlocked_data = get_state_data!(state_data, AppStateData::Unlocked);
         self.user_data
             .autolocker
             .lock()
             .unwrap()
             .clear_autolock_time();
+        // Kept for finding the changed items if this is followed by a sync
+        unlocked_data.logged_in_data.refreshing_data.previous_sync = Some(PreviousSync {
+            vault_data: unlocked_data.vault_data,
+            unseen_changes: unlocked_data.changed_item_ids,
+        });
         self.user_data.state_data = AppStateData::LoggedIn(unlocked_data.logged_in_data);
 
         StatefulUserData::new(self.user_data)
     }
 
+    pub fn token(&self) -> Arc<TokenResponseSuccess> {
+        get_state_data!(&self.user_data.state_data, AppStateData::Unlocked)
+            .logged_in_data
+            .token
+            .clone()
+    }
+
+    pub fn email(&self) -> Arc<String> {
+        get_state_data!(&self.user_data.state_data, AppStateData::Unlocked)
+            .logged_in_data
+            .refreshing_data
+            .email
+            .clone()
+    }
+
+    pub fn pbkdf(&self) -> Arc<PbkdfParameters> {
+        get_state_data!(&self.user_data.state_data, AppStateData::Unlocked)
+            .logged_in_data
+            .refreshing_data
+            .pbkdf
+            .clone()
+    }
+
     pub fn decrypt_keys(&self) -> Option<EncMacKeys> {
         let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
         d.logged_in_data.decrypt_keys()
@@ -477,11 +790,57 @@ impl<'a> StatefulUserData<'a, Unlocked> {
         d.vault_data.clone()
     }
 
+    /// Items that were added or modified in the last sync and have not
+    /// been viewed since
+    pub fn changed_item_ids(&self) -> &HashSet<String> {
+        let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
+        &d.changed_item_ids
+    }
+
+    /// The vault data before the last sync, still encrypted. `None` if
+    /// the vault has not been synced while unlocked.
+    pub fn previous_vault_data(&self) -> Option<Arc<HashMap<String, CipherItem>>> {
+        let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
+        d.previous_vault_data.clone()
+    }
+
+    pub fn mark_item_seen(&mut self, item_id: &str) {
+        let d = get_state_data!(&mut self.user_data.state_data, AppStateData::Unlocked);
+        d.changed_item_ids.remove(item_id);
+    }
+
+    /// Removes a deleted item from the vault data, without syncing.
+    /// The vault data is copied if it is in use, e.g. by a running search.
+    pub fn remove_item(&mut self, item_id: &str) -> Option<CipherItem> {
+        let d = get_state_data!(&mut self.user_data.state_data, AppStateData::Unlocked);
+        let removed = Arc::make_mut(&mut d.vault_data).remove(item_id);
+        d.changed_item_ids.remove(item_id);
+        removed
+    }
+
+    /// Adds a restored item back to the vault data, without syncing
+    pub fn insert_item(&mut self, item: CipherItem) {
+        let d = get_state_data!(&mut self.user_data.state_data, AppStateData::Unlocked);
+        Arc::make_mut(&mut d.vault_data)
+            .entry(item.id.clone())
+            .or_insert(item);
+    }
+
     pub fn collections(&self) -> Arc<HashMap<String, Collection>> {
         let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
         d.collections.clone()
     }
 
+    pub fn folders(&self) -> Arc<HashMap<String, Folder>> {
+        let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
+        d.folders.clone()
+    }
+
+    pub fn organizations(&self) -> Arc<HashMap<String, Organization>> {
+        let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
+        d.organizations.clone()
+    }
+
     pub fn get_keys_for_item(&self, item: &CipherItem) -> Option<EncMacKeys> {
         let d = get_state_data!(&self.user_data.state_data, AppStateData::Unlocked);
         d.get_keys_for_item(item)
@@ -514,19 +873,38 @@ impl<'a> StatefulUserData<'a, Unlocking> {
     pub fn into_unlocked(self) -> StatefulUserData<'a, Unlocked> {
         let state_data =
             std::mem::replace(&mut self.user_data.state_data, AppStateData::Intermediate);
-        let unlocking_data = get_state_data!(state_data, AppStateData::Unlocking);
+        let mut unlocking_data = get_state_data!(state_data, AppStateData::Unlocking);
+        if self.global_settings().forget_master_key {
+            unlocking_data.logged_in_data.forget_master_key();
+        }
 
         let unlocked_data = Unlocked {
             logged_in_data: unlocking_data.logged_in_data,
             organizations: unlocking_data.organizations,
             vault_data: unlocking_data.vault_data,
             collections: unlocking_data.collections,
+            folders: unlocking_data.folders,
+            private_key: OnceLock::new(),
+            changed_item_ids: unlocking_data.changed_item_ids,
+            previous_vault_data: unlocking_data.previous_vault_data,
         };
 
         self.user_data.state_data = AppStateData::Unlocked(unlocked_data);
 
         StatefulUserData::new(self.user_data)
     }
+
+    /// For syncing right after unlocking. There is no earlier vault data
+    /// to find the changed items from.
+    pub fn into_logged_in(self) -> StatefulUserData<'a, LoggedIn> {
+        let state_data =
+            std::mem::replace(&mut self.user_data.state_data, AppStateData::Intermediate);
+        let unlocking_data = get_state_data!(state_data, AppStateData::Unlocking);
+
+        self.user_data.state_data = AppStateData::LoggedIn(unlocking_data.logged_in_data);
+
+        StatefulUserData::new(self.user_data)
+    }
 }
 
 impl<'a> StatefulUserData<'a, Locked> {
@@ -554,9 +932,19 @@ impl<'a> StatefulUserData<'a, Locked> {
             .clone()
     }
 
+    pub fn item_count(&self) -> usize {
+        get_state_data!(&self.user_data.state_data, AppStateData::Locked)
+            .vault_data
+            .len()
+    }
+
+    pub fn sync_on_unlock(&self) -> bool {
+        get_state_data!(&self.user_data.state_data, AppStateData::Locked).sync_on_unlock
+    }
+
     pub fn into_unlocking(
         self,
-        master_key: Arc<MasterKey>,
+        master_key: Arc<WrappedMasterKey>,
         api_key: Option<Arc<ApiKey>>,
     ) -> StatefulUserData<'a, Unlocking> {
         let state_data =
@@ -568,16 +956,20 @@ impl<'a> StatefulUserData<'a, Locked> {
                 refreshing_data: Refreshing {
                     email: locked_data.email,
                     pbkdf: locked_data.pbkdf,
-                    master_key,
+                    user_keys: UserKeys::MasterKey(master_key),
                     api_key,
+                    previous_sync: None,
                 },
                 token: locked_data.token,
             },
             organizations: locked_data.organizations,
             vault_data: locked_data.vault_data,
             collections: locked_data.collections,
+            folders: locked_data.folders,
             encrypted_search_term: locked_data.encrypted_search_term,
             collection_selection: locked_data.collection_selection,
+            changed_item_ids: locked_data.changed_item_ids,
+            previous_vault_data: locked_data.previous_vault_data,
         };
 
         self.user_data.state_data = AppStateData::Unlocking(unlocking_data);
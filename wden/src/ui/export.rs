@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use cursive::{
+    traits::{Nameable, Resizable},
+    views::{Checkbox, Dialog, EditView, LinearLayout, SelectView, TextView},
+    Cursive,
+};
+use cursive_secret_edit_view::SecretEditView;
+use zeroize::Zeroizing;
+
+use crate::export::{export, item_to_json, write_to_new_file, ExportFormat};
+
+use super::{clipboard, util::cursive_ext::CursiveExt, vault_table::show_copy_notification};
+
+const VIEW_NAME_FORMAT: &str = "export_format";
+const VIEW_NAME_PATH: &str = "export_path";
+const VIEW_NAME_PASSWORD: &str = "export_password";
+const VIEW_NAME_PASSWORD_AGAIN: &str = "export_password_again";
+const VIEW_NAME_ITEM_JSON_PATH: &str = "item_json_path";
+const VIEW_NAME_ITEM_JSON_SECRETS: &str = "item_json_secrets";
+
+/// Shows a dialog for exporting the personal vault to a file
+pub fn show_export_dialog(siv: &mut Cursive) {
+    let format = SelectView::new()
+        .popup()
+        .item("JSON", ExportFormat::Json)
+        .item("CSV", ExportFormat::Csv)
+        .item("JSON (password protected)", ExportFormat::EncryptedJson)
+        .on_submit(|siv, format: &ExportFormat| {
+            let path = format!("wden_export.{}", format.file_extension());
+            siv.call_on_name(VIEW_NAME_PATH, |v: &mut EditView| v.set_content(path));
+        })
+        .with_name(VIEW_NAME_FORMAT);
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "The items of the personal vault are exported. \
+                    The JSON and CSV exports are not encrypted.",
+                ))
+                .child(TextView::new("\nFormat"))
+                .child(format)
+                .child(TextView::new("Path of the file"))
+                .child(
+                    EditView::new()
+                        .content("wden_export.json")
+                        .with_name(VIEW_NAME_PATH),
+                )
+                .child(TextView::new("Export password (password protected JSON)"))
+                .child(SecretEditView::new().with_name(VIEW_NAME_PASSWORD))
+                .child(TextView::new("Export password again"))
+                .child(SecretEditView::new().with_name(VIEW_NAME_PASSWORD_AGAIN))
+                .fixed_width(60),
+        )
+        .title("Export vault")
+        .dismiss_button("Cancel")
+        .button("Export", export_vault),
+    );
+}
+
+fn export_vault(siv: &mut Cursive) {
+    let format = siv
+        .call_on_name(VIEW_NAME_FORMAT, |v: &mut SelectView<ExportFormat>| {
+            v.selection().map(|f| *f)
+        })
+        .flatten()
+        .unwrap_or(ExportFormat::Json);
+    let path = siv
+        .call_on_name(VIEW_NAME_PATH, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let path = PathBuf::from(path.trim());
+    if path.as_os_str().is_empty() {
+        siv.add_layer(Dialog::info("Enter the path of the file"));
+        return;
+    }
+
+    let password = if format == ExportFormat::EncryptedJson {
+        let [password, again] = [VIEW_NAME_PASSWORD, VIEW_NAME_PASSWORD_AGAIN].map(|name| {
+            siv.call_on_name(name, |v: &mut SecretEditView| {
+                Zeroizing::new(v.get_content().to_string())
+            })
+            .unwrap_or_default()
+        });
+        if password.is_empty() {
+            siv.add_layer(Dialog::info("Enter a password for the export"));
+            return;
+        }
+        if password != again {
+            siv.add_layer(Dialog::info("The passwords don't match"));
+            return;
+        }
+        Some(password)
+    } else {
+        None
+    };
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let Some(user_keys) = ud.decrypt_keys() else {
+        siv.add_layer(Dialog::info("The encryption keys are not available"));
+        return;
+    };
+    let vault_data = ud.vault_data();
+    let items: Vec<_> = vault_data
+        .values()
+        .filter(|item| item.organization_id.is_none())
+        .filter_map(|item| Some((item.id.clone(), ud.get_keys_for_item(item)?)))
+        .collect();
+    let folders: Vec<_> = ud
+        .folders()
+        .values()
+        .map(|f| (f.id.clone(), f.name.decrypt_to_string(&user_keys)))
+        .collect();
+
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Exporting..."));
+    siv.async_op(
+        async move {
+            // Deriving the keys of a password protected export takes a while
+            tokio::task::spawn_blocking(move || {
+                let items: Vec<_> = items
+                    .iter()
+                    .filter_map(|(id, keys)| Some((vault_data.get(id)?, keys)))
+                    .collect();
+                let export = export(
+                    format,
+                    &items,
+                    &folders,
+                    password.as_deref().map(String::as_str),
+                )?;
+                write_to_new_file(&path, &export)
+                    .with_context(|| format!("Writing {} failed", path.display()))?;
+                anyhow::Ok((items.len(), path))
+            })
+            .await
+            .context("The export task failed")?
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok((count, path)) => siv.add_layer(Dialog::info(format!(
+                    "Exported {count} items to {}",
+                    path.display()
+                ))),
+                Err(e) => siv.add_layer(Dialog::info(format!("Exporting failed: {e:#}"))),
+            }
+        },
+    );
+}
+
+/// Shows a dialog for copying the item as JSON, or writing it to a file.
+/// The secret values are masked unless the user checks the box, which is
+/// not offered if the policy doesn't allow revealing them.
+pub fn show_item_json_dialog(siv: &mut Cursive, item_id: &str, allow_secrets: bool, expiry: u64) {
+    let mut content = LinearLayout::vertical().child(TextView::new(
+        "The item is exported like in the output of \"bw get item\". \
+        Passwords and hidden fields are masked unless included below.\n",
+    ));
+    if allow_secrets {
+        content.add_child(
+            LinearLayout::horizontal()
+                .child(Checkbox::new().with_name(VIEW_NAME_ITEM_JSON_SECRETS))
+                .child(TextView::new(" Include passwords and hidden fields")),
+        );
+    }
+    content.add_child(TextView::new("\nPath of the file (for writing to a file)"));
+    content.add_child(
+        EditView::new()
+            .content("wden_item.json")
+            .with_name(VIEW_NAME_ITEM_JSON_PATH),
+    );
+
+    let copy_item_id = item_id.to_string();
+    let write_item_id = item_id.to_string();
+    siv.add_layer(
+        Dialog::around(content.fixed_width(60))
+            .title("Item as JSON")
+            .dismiss_button("Cancel")
+            .button("Copy", move |siv| {
+                copy_item_json(siv, &copy_item_id, expiry)
+            })
+            .button("Write to file", move |siv| {
+                write_item_json(siv, &write_item_id)
+            }),
+    );
+}
+
+/// Returns the JSON and whether the secrets are included
+fn item_json(siv: &mut Cursive, item_id: &str) -> anyhow::Result<(Zeroizing<String>, bool)> {
+    let include_secrets = siv
+        .call_on_name(VIEW_NAME_ITEM_JSON_SECRETS, |v: &mut Checkbox| {
+            v.is_checked()
+        })
+        .unwrap_or(false);
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let vault_data = ud.vault_data();
+    let item = vault_data.get(item_id).context("The item was not found")?;
+    let keys = ud
+        .get_keys_for_item(item)
+        .context("The encryption keys are not available")?;
+    let json = item_to_json(item, &keys, !include_secrets)?
+        .context("Items of this type can't be exported")?;
+    Ok((json, include_secrets))
+}
+
+fn copy_item_json(siv: &mut Cursive, item_id: &str, expiry: u64) {
+    match item_json(siv, item_id) {
+        Ok((json, include_secrets)) => {
+            siv.pop_layer();
+            if include_secrets {
+                clipboard::clip_expiring_string(json.to_string(), expiry);
+            } else {
+                clipboard::clip_string(json.to_string());
+            }
+            show_copy_notification(siv, "Item JSON copied");
+        }
+        Err(e) => siv.add_layer(Dialog::info(format!("Copying failed: {e:#}"))),
+    }
+}
+
+fn write_item_json(siv: &mut Cursive, item_id: &str) {
+    let path = siv
+        .call_on_name(VIEW_NAME_ITEM_JSON_PATH, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let path = PathBuf::from(path.trim());
+    if path.as_os_str().is_empty() {
+        siv.add_layer(Dialog::info("Enter the path of the file"));
+        return;
+    }
+
+    let res = item_json(siv, item_id).and_then(|(json, _)| {
+        write_to_new_file(&path, &json)
+            .with_context(|| format!("Writing {} failed", path.display()))
+    });
+    match res {
+        Ok(()) => {
+            siv.pop_layer();
+            siv.add_layer(Dialog::info(format!(
+                "Wrote the item to {}",
+                path.display()
+            )));
+        }
+        Err(e) => siv.add_layer(Dialog::info(format!("Writing failed: {e:#}"))),
+    }
+}
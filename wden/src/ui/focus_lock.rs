@@ -0,0 +1,485 @@
+use std::{
+    sync::atomic::{AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+use cursive::{CbSink, Cursive};
+
+use super::{lock::lock_vault, util::cursive_ext::CursiveExt};
+
+pub use reporting::crossterm_with_focus_reports;
+
+const FOCUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const FOCUS_UNKNOWN: u8 = 0;
+const FOCUS_GAINED: u8 = 1;
+const FOCUS_LOST: u8 = 2;
+
+/// The focus state last reported by the terminal (xterm focus event mode)
+static REPORTED_FOCUS: AtomicU8 = AtomicU8::new(FOCUS_UNKNOWN);
+
+fn reported_focus() -> Option<bool> {
+    match REPORTED_FOCUS.load(Ordering::Relaxed) {
+        FOCUS_GAINED => Some(true),
+        FOCUS_LOST => Some(false),
+        _ => None,
+    }
+}
+
+/// Starts monitoring the focus state of the terminal window. When the window
+/// has been unfocused for `lock_delay`, the vault of `profile` gets locked.
+///
+/// The focus is tracked with the focus reports of the terminal, which the
+/// crossterm backend enables. If the terminal does not send them (or another
+/// backend is used), the active window is checked on X11 with terminals that
+/// set the WINDOWID environment variable.
+pub fn start_focus_lock_monitor(cb_sink: CbSink, profile: String, lock_delay: Duration) {
+    let detector = FocusDetector::new();
+    if detector.is_none() {
+        log::info!("Locking on focus loss relies on the focus reports of the terminal");
+    }
+
+    std::thread::spawn(move || focus_lock_loop(detector, cb_sink, profile, lock_delay));
+}
+
+fn focus_lock_loop(
+    detector: Option<FocusDetector>,
+    cb_sink: CbSink,
+    profile: String,
+    lock_delay: Duration,
+) {
+    let mut unfocused_since: Option<Instant> = None;
+    let mut lock_sent = false;
+
+    loop {
+        std::thread::sleep(FOCUS_POLL_INTERVAL);
+
+        let focused = reported_focus().or_else(|| detector.as_ref()?.is_focused());
+        match focused {
+            Some(false) => {
+                let since = *unfocused_since.get_or_insert_with(Instant::now);
+                if !lock_sent && since.elapsed() >= lock_delay {
+                    let profile = profile.clone();
+                    let lock = move |siv: &mut Cursive| lock_if_unlocked(siv, &profile);
+                    if cb_sink.send(Box::new(lock)).is_err() {
+                        // Cursive has stopped
+                        return;
+                    }
+                    lock_sent = true;
+                }
+            }
+            Some(true) => {
+                unfocused_since = None;
+                lock_sent = false;
+            }
+            // Unknown state, do nothing
+            None => {}
+        }
+    }
+}
+
+fn lock_if_unlocked(siv: &mut Cursive, profile: &str) {
+    // The monitor keeps running after switching to another profile
+    let is_unlocked = siv
+        .get_user_data()
+        .with_unlocked_state()
+        .is_some_and(|ud| ud.global_settings().profile == profile);
+    if is_unlocked {
+        log::info!("Terminal lost focus, locking");
+        lock_vault(siv);
+    }
+}
+
+/// Crossterm backend that enables the focus reports of the terminal
+/// (`CSI ? 1004 h`). The terminal then sends `CSI I` when it gains the
+/// focus and `CSI O` when it loses it.
+///
+/// The cursive crossterm backend treats focus events as unreachable, and
+/// crossterm has no way to peek at the next event or to put one back. So
+/// the wrapper has to read the events itself: focus events are recorded
+/// and all others are translated as the inner backend would do it. The
+/// tests pin the translation to the cursive one.
+mod reporting {
+    use std::sync::atomic::Ordering;
+
+    use cursive::{
+        backend::Backend,
+        backends::crossterm::crossterm::{
+            event::{
+                self, DisableFocusChange, EnableFocusChange, KeyCode, KeyEvent, KeyEventKind,
+                KeyModifiers, MouseEventKind,
+            },
+            execute,
+        },
+        event::{Event, Key, MouseButton, MouseEvent},
+        theme, CursiveRunnable, Vec2,
+    };
+
+    use super::{FOCUS_GAINED, FOCUS_LOST, REPORTED_FOCUS};
+
+    pub fn crossterm_with_focus_reports() -> CursiveRunnable {
+        CursiveRunnable::new(|| -> std::io::Result<Box<dyn Backend>> {
+            let inner = cursive::backends::crossterm::Backend::init()?;
+            execute!(std::io::stdout(), EnableFocusChange)?;
+            Ok(Box::new(FocusReportingBackend { inner }))
+        })
+    }
+
+    struct FocusReportingBackend {
+        inner: Box<dyn Backend>,
+    }
+
+    impl Drop for FocusReportingBackend {
+        fn drop(&mut self) {
+            // Before the inner backend restores the terminal
+            _ = execute!(std::io::stdout(), DisableFocusChange);
+        }
+    }
+
+    impl Backend for FocusReportingBackend {
+        fn poll_event(&mut self) -> Option<Event> {
+            while event::poll(std::time::Duration::ZERO).ok()? {
+                if let Some(event) = record_focus(event::read().ok()?).and_then(translate_event) {
+                    return Some(event);
+                }
+            }
+            None
+        }
+
+        fn set_title(&mut self, title: String) {
+            self.inner.set_title(title)
+        }
+
+        fn refresh(&mut self) {
+            self.inner.refresh()
+        }
+
+        fn has_colors(&self) -> bool {
+            self.inner.has_colors()
+        }
+
+        fn screen_size(&self) -> Vec2 {
+            self.inner.screen_size()
+        }
+
+        fn move_to(&self, pos: Vec2) {
+            self.inner.move_to(pos)
+        }
+
+        fn print(&self, text: &str) {
+            self.inner.print(text)
+        }
+
+        fn clear(&self, color: theme::Color) {
+            self.inner.clear(color)
+        }
+
+        fn set_color(&self, colors: theme::ColorPair) -> theme::ColorPair {
+            self.inner.set_color(colors)
+        }
+
+        fn set_effect(&self, effect: theme::Effect) {
+            self.inner.set_effect(effect)
+        }
+
+        fn unset_effect(&self, effect: theme::Effect) {
+            self.inner.unset_effect(effect)
+        }
+
+        fn is_persistent(&self) -> bool {
+            self.inner.is_persistent()
+        }
+
+        fn name(&self) -> &str {
+            self.inner.name()
+        }
+    }
+
+    /// Records focus events, and passes on all other events
+    fn record_focus(event: event::Event) -> Option<event::Event> {
+        match event {
+            event::Event::FocusGained => REPORTED_FOCUS.store(FOCUS_GAINED, Ordering::Relaxed),
+            event::Event::FocusLost => REPORTED_FOCUS.store(FOCUS_LOST, Ordering::Relaxed),
+            event => return Some(event),
+        }
+        None
+    }
+
+    /// The same translation as in the cursive crossterm backend
+    fn translate_event(event: event::Event) -> Option<Event> {
+        match event {
+            event::Event::Key(key) => translate_key_event(key),
+            event::Event::Mouse(mouse) => translate_mouse_event(mouse),
+            event::Event::Resize(_, _) => Some(Event::WindowResize),
+            // Bracketed paste is not enabled, and focus events are recorded
+            // before the translation
+            _ => None,
+        }
+    }
+
+    fn translate_key_event(event: KeyEvent) -> Option<Event> {
+        const CTRL_ALT: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::ALT);
+        const CTRL_SHIFT: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::SHIFT);
+        const ALT_SHIFT: KeyModifiers = KeyModifiers::ALT.union(KeyModifiers::SHIFT);
+
+        // Windows reports key releases too
+        if event.kind == KeyEventKind::Release {
+            return None;
+        }
+
+        let event = match (event.modifiers, event.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char(c)) => Event::CtrlChar(c),
+            (KeyModifiers::ALT, KeyCode::Char(c)) => Event::AltChar(c),
+            (KeyModifiers::SHIFT, KeyCode::Char(c)) => Event::Char(c),
+            (_, KeyCode::Char(c)) => Event::Char(c),
+            // Shift is not reported with back tab
+            (_, KeyCode::BackTab) => Event::Shift(Key::Tab),
+            (CTRL_ALT, code) => Event::CtrlAlt(translate_key(code)?),
+            (CTRL_SHIFT, code) => Event::CtrlShift(translate_key(code)?),
+            (ALT_SHIFT, code) => Event::AltShift(translate_key(code)?),
+            (KeyModifiers::CONTROL, code) => Event::Ctrl(translate_key(code)?),
+            (KeyModifiers::ALT, code) => Event::Alt(translate_key(code)?),
+            (KeyModifiers::SHIFT, code) => Event::Shift(translate_key(code)?),
+            (_, code) => Event::Key(translate_key(code)?),
+        };
+        Some(event)
+    }
+
+    fn translate_key(code: KeyCode) -> Option<Key> {
+        Some(match code {
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            KeyCode::Delete => Key::Del,
+            KeyCode::Insert => Key::Ins,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::F(n) => Key::from_f(n),
+            _ => return None,
+        })
+    }
+
+    fn translate_mouse_event(event: event::MouseEvent) -> Option<Event> {
+        let button = |b| match b {
+            event::MouseButton::Left => MouseButton::Left,
+            event::MouseButton::Right => MouseButton::Right,
+            event::MouseButton::Middle => MouseButton::Middle,
+        };
+        let mouse_event = match event.kind {
+            MouseEventKind::Down(b) => MouseEvent::Press(button(b)),
+            MouseEventKind::Up(b) => MouseEvent::Release(button(b)),
+            MouseEventKind::Drag(b) => MouseEvent::Hold(button(b)),
+            MouseEventKind::ScrollUp => MouseEvent::WheelUp,
+            MouseEventKind::ScrollDown => MouseEvent::WheelDown,
+            _ => return None,
+        };
+        Some(Event::Mouse {
+            event: mouse_event,
+            position: Vec2::new(event.column.into(), event.row.into()),
+            offset: Vec2::zero(),
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use cursive::backends::crossterm::crossterm::event::{
+            KeyEventState, MouseButton as CMouseButton, MouseEvent as CMouseEvent,
+        };
+
+        use super::*;
+
+        fn key(code: KeyCode, modifiers: KeyModifiers) -> event::Event {
+            event::Event::Key(KeyEvent::new(code, modifiers))
+        }
+
+        #[test]
+        fn test_record_focus() {
+            assert_eq!(record_focus(event::Event::FocusLost), None);
+            assert_eq!(REPORTED_FOCUS.load(Ordering::Relaxed), FOCUS_LOST);
+            assert_eq!(record_focus(event::Event::FocusGained), None);
+            assert_eq!(REPORTED_FOCUS.load(Ordering::Relaxed), FOCUS_GAINED);
+
+            let resize = event::Event::Resize(80, 24);
+            assert_eq!(record_focus(resize.clone()), Some(resize));
+        }
+
+        #[test]
+        fn test_translate_key_event() {
+            let cases = [
+                (
+                    key(KeyCode::Char('a'), KeyModifiers::NONE),
+                    Event::Char('a'),
+                ),
+                (
+                    key(KeyCode::Char('A'), KeyModifiers::SHIFT),
+                    Event::Char('A'),
+                ),
+                (
+                    key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+                    Event::CtrlChar('c'),
+                ),
+                (
+                    key(KeyCode::Char('x'), KeyModifiers::ALT),
+                    Event::AltChar('x'),
+                ),
+                (
+                    key(KeyCode::BackTab, KeyModifiers::SHIFT),
+                    Event::Shift(Key::Tab),
+                ),
+                (
+                    key(KeyCode::Enter, KeyModifiers::NONE),
+                    Event::Key(Key::Enter),
+                ),
+                (key(KeyCode::F(5), KeyModifiers::NONE), Event::Key(Key::F5)),
+                (
+                    key(KeyCode::Left, KeyModifiers::CONTROL),
+                    Event::Ctrl(Key::Left),
+                ),
+                (key(KeyCode::Up, KeyModifiers::SHIFT), Event::Shift(Key::Up)),
+                (
+                    key(KeyCode::Delete, KeyModifiers::CONTROL | KeyModifiers::ALT),
+                    Event::CtrlAlt(Key::Del),
+                ),
+                (event::Event::Resize(80, 24), Event::WindowResize),
+            ];
+            for (event, expected) in cases {
+                assert_eq!(translate_event(event.clone()), Some(expected), "{event:?}");
+            }
+
+            let release = KeyEvent::new_with_kind_and_state(
+                KeyCode::Char('a'),
+                KeyModifiers::NONE,
+                KeyEventKind::Release,
+                KeyEventState::NONE,
+            );
+            assert_eq!(translate_event(event::Event::Key(release)), None);
+        }
+
+        #[test]
+        fn test_translate_mouse_event() {
+            let mouse = |kind| {
+                event::Event::Mouse(CMouseEvent {
+                    kind,
+                    column: 3,
+                    row: 4,
+                    modifiers: KeyModifiers::NONE,
+                })
+            };
+            let expected = |event| Event::Mouse {
+                event,
+                position: Vec2::new(3, 4),
+                offset: Vec2::zero(),
+            };
+
+            assert_eq!(
+                translate_event(mouse(MouseEventKind::Down(CMouseButton::Left))),
+                Some(expected(MouseEvent::Press(MouseButton::Left)))
+            );
+            assert_eq!(
+                translate_event(mouse(MouseEventKind::Drag(CMouseButton::Right))),
+                Some(expected(MouseEvent::Hold(MouseButton::Right)))
+            );
+            assert_eq!(
+                translate_event(mouse(MouseEventKind::ScrollDown)),
+                Some(expected(MouseEvent::WheelDown))
+            );
+            assert_eq!(translate_event(mouse(MouseEventKind::Moved)), None);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+use x11_focus::X11FocusDetector as FocusDetector;
+
+#[cfg(not(target_os = "linux"))]
+struct FocusDetector;
+
+#[cfg(not(target_os = "linux"))]
+impl FocusDetector {
+    fn new() -> Option<Self> {
+        None
+    }
+
+    fn is_focused(&self) -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11_focus {
+    use x11rb::{
+        connection::Connection,
+        protocol::xproto::{AtomEnum, ConnectionExt, Window},
+        rust_connection::RustConnection,
+    };
+
+    pub struct X11FocusDetector {
+        conn: RustConnection,
+        root: Window,
+        active_window_atom: u32,
+        terminal_window: Window,
+    }
+
+    impl X11FocusDetector {
+        pub fn new() -> Option<Self> {
+            // Under Wayland the X11 active window does not tell anything
+            // reliable about windows outside XWayland
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                return None;
+            }
+
+            let terminal_window = std::env::var("WINDOWID").ok()?.parse().ok()?;
+            let (conn, screen_num) = x11rb::connect(None).ok()?;
+            let root = conn.setup().roots.get(screen_num)?.root;
+            let active_window_atom = conn
+                .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+                .ok()?
+                .reply()
+                .ok()?
+                .atom;
+
+            Some(X11FocusDetector {
+                conn,
+                root,
+                active_window_atom,
+                terminal_window,
+            })
+        }
+
+        pub fn is_focused(&self) -> Option<bool> {
+            let reply = self
+                .conn
+                .get_property(
+                    false,
+                    self.root,
+                    self.active_window_atom,
+                    AtomEnum::WINDOW,
+                    0,
+                    1,
+                )
+                .ok()?
+                .reply()
+                .ok()?;
+            let active_window = reply.value32()?.next()?;
+
+            // WINDOWID may point to a child of the top-level window that
+            // the window manager considers active (e.g. in xterm)
+            let mut window = self.terminal_window;
+            while window != self.root && window != x11rb::NONE {
+                if window == active_window {
+                    return Some(true);
+                }
+                window = self.conn.query_tree(window).ok()?.reply().ok()?.parent;
+            }
+
+            Some(false)
+        }
+    }
+}
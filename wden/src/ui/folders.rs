@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use cursive::{
+    view::{Nameable, Scrollable},
+    views::{Dialog, DummyView, EditView, LinearLayout, SelectView},
+    Cursive,
+};
+
+use super::{
+    collections::{collection_name_matches, CollectionSelection},
+    data::{StatefulUserData, Unlocked},
+    util::cursive_ext::CursiveExt,
+};
+
+const VIEW_NAME_FOLDER_SELECT: &str = "folder_select";
+
+pub fn show_folder_filter<S>(cursive: &mut Cursive, selection_callback: S)
+where
+    S: Fn(&mut Cursive, CollectionSelection) + Clone + 'static + Send + Sync,
+{
+    let ud = cursive.get_user_data().with_unlocked_state().unwrap();
+    let folders = Arc::new(folder_names(&ud));
+
+    let mut sel = SelectView::new();
+    add_folder_items(&mut sel, &folders, "");
+
+    let cb2 = selection_callback.clone();
+    sel.set_on_submit(move |siv, sel| {
+        siv.pop_layer();
+        cb2(siv, sel.clone());
+    });
+
+    let cb3 = selection_callback.clone();
+    let filter_edit = EditView::new()
+        .on_edit(move |siv, filter, _| {
+            siv.call_on_name(
+                VIEW_NAME_FOLDER_SELECT,
+                |sel: &mut SelectView<CollectionSelection>| {
+                    sel.clear();
+                    add_folder_items(sel, &folders, filter);
+                },
+            );
+        })
+        .on_submit(move |siv, _| {
+            let selection = siv
+                .call_on_name(
+                    VIEW_NAME_FOLDER_SELECT,
+                    |sel: &mut SelectView<CollectionSelection>| sel.selection(),
+                )
+                .flatten();
+            if let Some(selection) = selection {
+                siv.pop_layer();
+                cb3(siv, (*selection).clone());
+            }
+        });
+
+    let layout = LinearLayout::vertical()
+        .child(filter_edit)
+        .child(DummyView)
+        .child(sel.with_name(VIEW_NAME_FOLDER_SELECT).scrollable());
+
+    let dialog = Dialog::around(layout)
+        .title("Folders")
+        .dismiss_button("Cancel")
+        .button("Reset", move |siv| {
+            siv.pop_layer();
+            selection_callback(siv, CollectionSelection::All)
+        });
+    cursive.add_layer(dialog);
+}
+
+fn add_folder_items(
+    sel: &mut SelectView<CollectionSelection>,
+    folders: &[(String, String)],
+    filter: &str,
+) {
+    if filter.trim().is_empty() {
+        sel.add_item("All", CollectionSelection::All);
+        sel.add_item("No folder", CollectionSelection::NoFolder);
+    }
+
+    // Nested folders are named with slashes like the collections
+    for (name, id) in folders {
+        if collection_name_matches(name, filter) {
+            sel.add_item(name.clone(), CollectionSelection::Folder(id.clone()));
+        }
+    }
+}
+
+/// Decrypts the names of the folders. Returns (name, id) pairs sorted by
+/// the name.
+pub fn folder_names(ud: &StatefulUserData<Unlocked>) -> Vec<(String, String)> {
+    let Some(user_keys) = ud.decrypt_keys() else {
+        return Vec::new();
+    };
+    let mut names: Vec<_> = ud
+        .folders()
+        .values()
+        .map(|f| (f.name.decrypt_to_string(&user_keys), f.id.clone()))
+        .collect();
+    names.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    names
+}
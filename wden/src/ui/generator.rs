@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use cursive::{
+    theme::PaletteColor,
+    traits::{Nameable, Resizable, Scrollable},
+    views::{Checkbox, Dialog, EditView, LinearLayout, SelectView, TextView},
+    Cursive, View,
+};
+use zeroize::Zeroizing;
+
+use crate::generator::{
+    generate_passphrase, generate_password, GeneratorError, PassphraseOptions, PasswordOptions,
+};
+
+use super::vault_table::show_copy_notification;
+
+const VIEW_NAME_MODE: &str = "generator_mode";
+const VIEW_NAME_LENGTH: &str = "generator_length";
+const VIEW_NAME_LOWERCASE: &str = "generator_lowercase";
+const VIEW_NAME_UPPERCASE: &str = "generator_uppercase";
+const VIEW_NAME_DIGITS: &str = "generator_digits";
+const VIEW_NAME_SYMBOLS: &str = "generator_symbols";
+const VIEW_NAME_AVOID_AMBIGUOUS: &str = "generator_avoid_ambiguous";
+const VIEW_NAME_WORDS: &str = "generator_words";
+const VIEW_NAME_SEPARATOR: &str = "generator_separator";
+const VIEW_NAME_CAPITALIZE: &str = "generator_capitalize";
+const VIEW_NAME_INCLUDE_NUMBER: &str = "generator_include_number";
+const VIEW_NAME_CANDIDATES: &str = "generator_candidates";
+const VIEW_NAME_ERROR: &str = "generator_error";
+
+const CANDIDATE_COUNT: usize = 5;
+const CLIPBOARD_EXPIRY: u64 = 30;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Password,
+    Passphrase,
+}
+
+type InsertCallback = Arc<dyn Fn(&mut Cursive, Zeroizing<String>) + Send + Sync>;
+
+/// Shows the generator. The selected candidate can be copied.
+pub fn show_generator(siv: &mut Cursive) {
+    show_generator_dialog(siv, None);
+}
+
+/// Shows the generator with an Insert button, which closes the generator
+/// and passes the selected candidate to `insert`
+pub fn show_generator_for_field(
+    siv: &mut Cursive,
+    insert: impl Fn(&mut Cursive, Zeroizing<String>) + Send + Sync + 'static,
+) {
+    show_generator_dialog(siv, Some(Arc::new(insert)));
+}
+
+fn show_generator_dialog(siv: &mut Cursive, insert: Option<InsertCallback>) {
+    let password_defaults = PasswordOptions::default();
+    let passphrase_defaults = PassphraseOptions::default();
+
+    let mode = SelectView::new()
+        .popup()
+        .item("Password", Mode::Password)
+        .item("Passphrase", Mode::Passphrase)
+        .on_submit(|siv, _| regenerate(siv))
+        .with_name(VIEW_NAME_MODE);
+
+    let password_options = LinearLayout::vertical()
+        .child(TextView::new("Password").style(PaletteColor::Secondary))
+        .child(number_field(
+            "Length",
+            VIEW_NAME_LENGTH,
+            password_defaults.length,
+        ))
+        .child(option_checkbox(
+            "a-z",
+            VIEW_NAME_LOWERCASE,
+            password_defaults.lowercase,
+        ))
+        .child(option_checkbox(
+            "A-Z",
+            VIEW_NAME_UPPERCASE,
+            password_defaults.uppercase,
+        ))
+        .child(option_checkbox(
+            "0-9",
+            VIEW_NAME_DIGITS,
+            password_defaults.digits,
+        ))
+        .child(option_checkbox(
+            "!@#$%^&*",
+            VIEW_NAME_SYMBOLS,
+            password_defaults.symbols,
+        ))
+        .child(option_checkbox(
+            "Avoid ambiguous characters",
+            VIEW_NAME_AVOID_AMBIGUOUS,
+            password_defaults.avoid_ambiguous,
+        ));
+
+    let passphrase_options = LinearLayout::vertical()
+        .child(TextView::new("Passphrase").style(PaletteColor::Secondary))
+        .child(number_field(
+            "Words",
+            VIEW_NAME_WORDS,
+            passphrase_defaults.words,
+        ))
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Separator "))
+                .child(
+                    EditView::new()
+                        .content(passphrase_defaults.separator.to_string())
+                        .max_content_width(1)
+                        .on_edit(|siv, _, _| regenerate(siv))
+                        .with_name(VIEW_NAME_SEPARATOR)
+                        .fixed_width(3),
+                ),
+        )
+        .child(option_checkbox(
+            "Capitalize",
+            VIEW_NAME_CAPITALIZE,
+            passphrase_defaults.capitalize,
+        ))
+        .child(option_checkbox(
+            "Include a number",
+            VIEW_NAME_INCLUDE_NUMBER,
+            passphrase_defaults.include_number,
+        ));
+
+    let candidates = SelectView::<Zeroizing<String>>::new()
+        .on_submit(copy_candidate)
+        .with_name(VIEW_NAME_CANDIDATES);
+
+    let layout = LinearLayout::vertical()
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Type "))
+                .child(mode),
+        )
+        .child(TextView::new(" "))
+        .child(
+            LinearLayout::horizontal()
+                .child(password_options.fixed_width(32))
+                .child(passphrase_options),
+        )
+        .child(TextView::new(" "))
+        .child(candidates)
+        .child(TextView::new("").with_name(VIEW_NAME_ERROR));
+
+    let mut dialog = Dialog::around(layout.scrollable())
+        .title("Generator")
+        .button("Regenerate", regenerate)
+        .button("Copy", |siv| {
+            if let Some(candidate) = selected_candidate(siv) {
+                copy_candidate(siv, &candidate);
+            }
+        });
+    if let Some(insert) = insert {
+        dialog.add_button("Insert", move |siv| {
+            if let Some(candidate) = selected_candidate(siv) {
+                siv.pop_layer();
+                insert(siv, candidate);
+            }
+        });
+    }
+    siv.add_layer(dialog.dismiss_button("Close").min_width(60));
+
+    regenerate(siv);
+}
+
+fn number_field(label: &str, view_name: &str, value: usize) -> impl View {
+    LinearLayout::horizontal()
+        .child(TextView::new(format!("{label} ")))
+        .child(
+            EditView::new()
+                .content(value.to_string())
+                .on_edit(|siv, _, _| regenerate(siv))
+                .with_name(view_name)
+                .fixed_width(5),
+        )
+}
+
+fn option_checkbox(label: &str, view_name: &str, checked: bool) -> impl View {
+    LinearLayout::horizontal()
+        .child(
+            Checkbox::new()
+                .with_checked(checked)
+                .on_change(|siv, _| regenerate(siv))
+                .with_name(view_name),
+        )
+        .child(TextView::new(format!(" {label}")))
+}
+
+fn is_checked(siv: &mut Cursive, view_name: &str) -> bool {
+    siv.call_on_name(view_name, |v: &mut Checkbox| v.is_checked())
+        .unwrap_or_default()
+}
+
+/// Number fields that can't be parsed are treated as 0, which is out of
+/// range for all the options
+fn number_value(siv: &mut Cursive, view_name: &str) -> usize {
+    siv.call_on_name(view_name, |v: &mut EditView| v.get_content())
+        .and_then(|c| c.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+/// Generates new candidates with the options in the dialog
+fn regenerate(siv: &mut Cursive) {
+    let mode = siv
+        .call_on_name(VIEW_NAME_MODE, |v: &mut SelectView<Mode>| v.selection())
+        .flatten()
+        .map_or(Mode::Password, |m| *m);
+
+    let mut rng = rand::thread_rng();
+    let candidates: Result<Vec<_>, GeneratorError> = match mode {
+        Mode::Password => {
+            let options = PasswordOptions {
+                length: number_value(siv, VIEW_NAME_LENGTH),
+                lowercase: is_checked(siv, VIEW_NAME_LOWERCASE),
+                uppercase: is_checked(siv, VIEW_NAME_UPPERCASE),
+                digits: is_checked(siv, VIEW_NAME_DIGITS),
+                symbols: is_checked(siv, VIEW_NAME_SYMBOLS),
+                avoid_ambiguous: is_checked(siv, VIEW_NAME_AVOID_AMBIGUOUS),
+            };
+            (0..CANDIDATE_COUNT)
+                .map(|_| generate_password(&options, &mut rng))
+                .collect()
+        }
+        Mode::Passphrase => {
+            let separator = siv
+                .call_on_name(VIEW_NAME_SEPARATOR, |v: &mut EditView| {
+                    v.get_content().chars().next()
+                })
+                .flatten()
+                .unwrap_or(' ');
+            let options = PassphraseOptions {
+                words: number_value(siv, VIEW_NAME_WORDS),
+                separator,
+                capitalize: is_checked(siv, VIEW_NAME_CAPITALIZE),
+                include_number: is_checked(siv, VIEW_NAME_INCLUDE_NUMBER),
+            };
+            (0..CANDIDATE_COUNT)
+                .map(|_| generate_passphrase(&options, &mut rng))
+                .collect()
+        }
+    };
+
+    let error = candidates
+        .as_ref()
+        .err()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    siv.call_on_name(VIEW_NAME_ERROR, |v: &mut TextView| v.set_content(error));
+    siv.call_on_name(
+        VIEW_NAME_CANDIDATES,
+        |v: &mut SelectView<Zeroizing<String>>| {
+            v.clear();
+            for candidate in candidates.into_iter().flatten() {
+                v.add_item(candidate.to_string(), candidate);
+            }
+        },
+    );
+}
+
+fn selected_candidate(siv: &mut Cursive) -> Option<Zeroizing<String>> {
+    siv.call_on_name(
+        VIEW_NAME_CANDIDATES,
+        |v: &mut SelectView<Zeroizing<String>>| v.selection(),
+    )
+    .flatten()
+    .map(|c| (*c).clone())
+}
+
+fn copy_candidate(siv: &mut Cursive, candidate: &Zeroizing<String>) {
+    super::clipboard::clip_expiring_string(candidate.to_string(), CLIPBOARD_EXPIRY);
+    show_copy_notification(siv, "Copied");
+}
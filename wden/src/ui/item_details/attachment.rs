@@ -0,0 +1,129 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use cursive::{
+    traits::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, TextView},
+    Cursive,
+};
+
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::{
+        api::ApiClient,
+        cipher::{generate_encrypted_keys, Cipher, EncMacKeys},
+    },
+    ui::{slow_response::slow_response_callback, sync::do_sync, util::cursive_ext::CursiveExt},
+};
+
+const VIEW_NAME_PATH: &str = "attachment_path";
+
+/// The maximum attachment size of Bitwarden
+const MAX_ATTACHMENT_SIZE: u64 = 500 * 1024 * 1024;
+
+pub fn show_attach_file_dialog(siv: &mut Cursive, item_id: &str) {
+    let item_id = item_id.to_string();
+    let item_id2 = item_id.clone();
+    let path_edit = EditView::new()
+        .on_submit(move |siv, _| attach_file(siv, &item_id))
+        .with_name(VIEW_NAME_PATH)
+        .min_width(50);
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("Path of the file"))
+                .child(path_edit),
+        )
+        .title("Attach file")
+        .dismiss_button("Cancel")
+        .button("Attach", move |siv| attach_file(siv, &item_id2)),
+    );
+}
+
+fn attach_file(siv: &mut Cursive, item_id: &str) {
+    let path = siv
+        .call_on_name(VIEW_NAME_PATH, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let path = PathBuf::from(path.trim());
+    let Some(file_name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+        siv.add_layer(Dialog::info("Enter the path of a file"));
+        return;
+    };
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let vault_data = ud.vault_data();
+    let Some(keys) = vault_data
+        .get(item_id)
+        .and_then(|item| ud.get_keys_for_item(item))
+    else {
+        siv.add_layer(Dialog::info("The encryption keys are not available"));
+        return;
+    };
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+    let item_id = item_id.to_string();
+    let on_slow_response = slow_response_callback(siv);
+
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Uploading..."));
+    siv.async_op(
+        async move {
+            let (name, key, contents) =
+                tokio::task::spawn_blocking(move || encrypt_file(&path, &file_name, &keys))
+                    .await
+                    .context("The encryption task failed")??;
+
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?
+            .on_slow_response(on_slow_response);
+            client
+                .create_attachment(&item_id, &name, &key, contents)
+                .await
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok(id) => {
+                    log::info!("Created attachment {id}");
+                    // Sync to get the attachment to the item
+                    do_sync(siv, false);
+                }
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Attaching the file failed: {e:#}")));
+                }
+            }
+        },
+    );
+}
+
+/// Reads and encrypts the file with a new attachment key. Returns the
+/// encrypted file name, the attachment key encrypted with the item keys
+/// and the encrypted contents.
+fn encrypt_file(
+    path: &Path,
+    file_name: &str,
+    keys: &EncMacKeys,
+) -> anyhow::Result<(Cipher, Cipher, Vec<u8>)> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("Reading {} failed", path.display()))?
+        .len();
+    if size > MAX_ATTACHMENT_SIZE {
+        anyhow::bail!("The file is too large, attachments can be at most 500 MB");
+    }
+    let contents = Zeroizing::new(
+        std::fs::read(path).with_context(|| format!("Reading {} failed", path.display()))?,
+    );
+
+    let (attachment_keys, encrypted_key) = generate_encrypted_keys(keys)?;
+    let name = Cipher::encrypt(file_name.as_bytes(), keys)?;
+    let encrypted_contents = Cipher::encrypt(&contents, &attachment_keys)?.encode_bytes();
+    Ok((name, encrypted_key, encrypted_contents))
+}
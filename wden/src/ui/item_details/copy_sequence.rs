@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use cursive::{views::Dialog, Cursive};
+
+use crate::{
+    bitwarden::{
+        api::{CipherData, CipherItem},
+        cipher::EncMacKeys,
+        totp::Totp,
+    },
+    ui::{clipboard, vault_table::show_copy_notification},
+};
+
+/// Name of the custom field that holds the copy sequence of an item
+pub const FIELD_NAME: &str = "wden:copy-sequence";
+
+/// A value copied in one step of a copy sequence
+pub struct CopyStep {
+    label: String,
+    value: StepValue,
+    secret: bool,
+}
+
+enum StepValue {
+    Text(String),
+    /// The code is generated when the step is run, so that it is
+    /// still valid after the earlier steps
+    Totp(Totp),
+}
+
+/// Resolves the copy sequence of the item, if it has one. The steps are
+/// separated with commas or ">", and each step is "username", "password",
+/// "uri", "totp" or the name of another custom field, e.g.
+/// "username > password > totp".
+pub fn resolve(item: &CipherItem, keys: &EncMacKeys) -> Option<Result<Vec<CopyStep>, String>> {
+    let fields: Vec<_> = item
+        .fields
+        .iter()
+        .map(|f| {
+            (
+                f.name.decrypt_to_string(keys),
+                f.value.decrypt_to_string(keys),
+                f.is_hidden(),
+            )
+        })
+        .collect();
+    let (_, sequence, _) = fields.iter().find(|(name, _, _)| name == FIELD_NAME)?;
+
+    let steps = parse_steps(sequence);
+    if steps.is_empty() {
+        return Some(Err("The copy sequence is empty".to_string()));
+    }
+
+    Some(
+        steps
+            .into_iter()
+            .map(|step| {
+                let login = match &item.data {
+                    CipherData::Login(l) => Some(l),
+                    _ => None,
+                };
+                let (label, value, secret) = match (step.to_lowercase().as_str(), login) {
+                    ("username", Some(l)) => (
+                        "username",
+                        StepValue::Text(l.username.decrypt_to_string(keys)),
+                        false,
+                    ),
+                    ("password", Some(l)) => (
+                        "password",
+                        StepValue::Text(l.password.decrypt_to_string(keys)),
+                        true,
+                    ),
+                    ("uri", Some(l)) => {
+                        ("URI", StepValue::Text(l.uri.decrypt_to_string(keys)), false)
+                    }
+                    ("totp", Some(l)) => {
+                        let secret = l.totp.decrypt_to_string(keys);
+                        if secret.is_empty() {
+                            return Err("The item has no TOTP secret".to_string());
+                        }
+                        let totp = Totp::parse(&secret)
+                            .map_err(|e| format!("Parsing the TOTP secret failed: {e}"))?;
+                        ("TOTP code", StepValue::Totp(totp), true)
+                    }
+                    _ => {
+                        let (name, value, hidden) = fields
+                            .iter()
+                            .find(|(name, _, _)| name == step)
+                            .ok_or_else(|| format!("Unknown copy sequence step: {step}"))?;
+                        (name.as_str(), StepValue::Text(value.clone()), *hidden)
+                    }
+                };
+                Ok(CopyStep {
+                    label: label.to_string(),
+                    value,
+                    secret,
+                })
+            })
+            .collect(),
+    )
+}
+
+fn parse_steps(sequence: &str) -> Vec<&str> {
+    sequence
+        .split([',', '>'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Copies the first value of the sequence, and prompts for copying
+/// the next ones. Secret values expire from the clipboard after
+/// `expiry` seconds.
+pub fn run(siv: &mut Cursive, steps: Arc<Vec<CopyStep>>, expiry: u64) {
+    run_step(siv, steps, 0, expiry);
+}
+
+fn run_step(siv: &mut Cursive, steps: Arc<Vec<CopyStep>>, index: usize, expiry: u64) {
+    let step = &steps[index];
+    let value = match &step.value {
+        StepValue::Text(value) => value.clone(),
+        StepValue::Totp(totp) => totp.generate_now().0.to_string(),
+    };
+    if step.secret {
+        clipboard::clip_expiring_string(value, expiry);
+    } else {
+        clipboard::clip_string(value);
+    }
+
+    let Some(next) = steps.get(index + 1) else {
+        show_copy_notification(siv, "Copy sequence done");
+        return;
+    };
+    let text = format!(
+        "Copied {} ({}/{}).\nPaste it, then continue to copy {}.",
+        step.label,
+        index + 1,
+        steps.len(),
+        next.label
+    );
+    let dialog = Dialog::text(text)
+        .title("Copy sequence")
+        .button("Next", move |siv| {
+            siv.pop_layer();
+            run_step(siv, steps.clone(), index + 1, expiry);
+        })
+        .dismiss_button("Cancel");
+    siv.add_layer(dialog);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_steps;
+
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!(
+            parse_steps("username > password, Security answer ,"),
+            vec!["username", "password", "Security answer"]
+        );
+        assert!(parse_steps(" , ").is_empty());
+    }
+}
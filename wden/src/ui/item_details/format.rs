@@ -0,0 +1,124 @@
+use crate::profile::CardNumberFormat;
+
+/// Formats a card number for copying to the clipboard
+pub fn format_card_number(number: &str, format: CardNumberFormat) -> String {
+    match format {
+        CardNumberFormat::AsStored => number.to_owned(),
+        CardNumberFormat::Digits => number.chars().filter(|c| !c.is_whitespace()).collect(),
+        CardNumberFormat::Grouped => group_in_fours(number.chars().filter(|c| !c.is_whitespace())),
+    }
+}
+
+/// Masks all but the last four digits of a card number for display,
+/// e.g. "**** **** **** 1234"
+pub fn mask_card_number(number: &str) -> String {
+    let digits: Vec<char> = number.chars().filter(|c| !c.is_whitespace()).collect();
+    let visible_from = if digits.len() > 4 {
+        digits.len() - 4
+    } else {
+        digits.len()
+    };
+
+    group_in_fours(
+        digits
+            .iter()
+            .enumerate()
+            .map(|(i, c)| if i < visible_from { '*' } else { *c }),
+    )
+}
+
+/// Joins the non-empty parts of each line with spaces, and the non-empty
+/// lines with newlines. Used for composing e.g. postal addresses.
+pub fn compose_lines(lines: &[&[&str]]) -> String {
+    lines
+        .iter()
+        .map(|parts| {
+            parts
+                .iter()
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The first line of the value, shortened to at most `max_chars`
+/// characters, for showing long values in a list
+pub fn preview(value: &str, max_chars: usize) -> String {
+    let first_line = value.lines().next().unwrap_or_default();
+    if first_line.chars().count() <= max_chars && first_line.len() == value.len() {
+        return first_line.to_owned();
+    }
+    let mut res: String = first_line
+        .chars()
+        .take(max_chars.saturating_sub(1))
+        .collect();
+    res.push('…');
+    res
+}
+
+fn group_in_fours(chars: impl Iterator<Item = char>) -> String {
+    let mut res = String::new();
+    for (i, c) in chars.enumerate() {
+        if i > 0 && i % 4 == 0 {
+            res.push(' ');
+        }
+        res.push(c);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_card_number() {
+        let number = "4111 1111 11111111";
+        assert_eq!(
+            format_card_number(number, CardNumberFormat::AsStored),
+            number
+        );
+        assert_eq!(
+            format_card_number(number, CardNumberFormat::Digits),
+            "4111111111111111"
+        );
+        assert_eq!(
+            format_card_number(number, CardNumberFormat::Grouped),
+            "4111 1111 1111 1111"
+        );
+    }
+
+    #[test]
+    fn test_compose_lines() {
+        assert_eq!(
+            compose_lines(&[
+                &["", "John", "", "Doe"],
+                &[""],
+                &["Street 1 "],
+                &["00100", "Helsinki"],
+                &["FI"],
+            ]),
+            "John Doe\nStreet 1\n00100 Helsinki\nFI"
+        );
+        assert_eq!(compose_lines(&[&["", ""], &[]]), "");
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("short", 10), "short");
+        assert_eq!(preview("https://example.com/a/long/path", 10), "https://e…");
+        assert_eq!(preview("first\nsecond", 10), "first…");
+    }
+
+    #[test]
+    fn test_mask_card_number() {
+        assert_eq!(mask_card_number("4111111111111234"), "**** **** **** 1234");
+        assert_eq!(mask_card_number("3782 822463 10005"), "**** **** ***0 005");
+        assert_eq!(mask_card_number("1234"), "****");
+        assert_eq!(mask_card_number(""), "");
+    }
+}
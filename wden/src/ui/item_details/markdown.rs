@@ -0,0 +1,121 @@
+use cursive::{
+    theme::{Effect, Style},
+    utils::markup::StyledString,
+};
+
+use crate::ui::theme;
+
+/// Renders the lightweight markdown commonly used in notes: headings,
+/// list items, code spans and fenced code blocks. Everything else is
+/// shown as is.
+pub fn render(text: &str) -> StyledString {
+    let mut res = StyledString::new();
+    let mut in_code_block = false;
+
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            res.append_plain("\n");
+        }
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            // Fence lines themselves are not shown
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            res.append_styled(line, code_style());
+            continue;
+        }
+
+        if let Some(heading) = heading_text(trimmed) {
+            res.append_styled(heading, Style::from(Effect::Bold));
+        } else if let Some(item) = list_item_text(trimmed) {
+            let indent = &line[..line.len() - trimmed.len()];
+            res.append_plain(format!("{indent}{} ", theme::glyphs().list_bullet));
+            append_inline(&mut res, item);
+        } else {
+            append_inline(&mut res, line);
+        }
+    }
+
+    res
+}
+
+fn heading_text(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    match &line[level..] {
+        "" => Some(""),
+        rest if rest.starts_with(' ') => Some(rest.trim()),
+        _ => None,
+    }
+}
+
+fn list_item_text(line: &str) -> Option<&str> {
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+}
+
+/// Appends text with `code spans` styled
+fn append_inline(res: &mut StyledString, text: &str) {
+    let mut parts = text.split('`');
+    let mut in_code = false;
+    let Some(first) = parts.next() else {
+        return;
+    };
+    res.append_plain(first);
+
+    let rest: Vec<_> = parts.collect();
+    for (i, part) in rest.iter().enumerate() {
+        in_code = !in_code;
+        // An unmatched backtick is shown as is
+        if in_code && i == rest.len() - 1 {
+            res.append_plain(format!("`{part}"));
+        } else if in_code {
+            res.append_styled(*part, code_style());
+        } else {
+            res.append_plain(*part);
+        }
+    }
+}
+
+fn code_style() -> Style {
+    Style::from(Effect::Underline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_markdown() {
+        let rendered = render(
+            "# Title\n- first `code`\n  * nested\nplain # text\n```\n# not a heading\n```\nend `x",
+        );
+        assert_eq!(
+            rendered.source(),
+            "Title\n• first code\n  • nested\nplain # text\n\n# not a heading\n\nend `x"
+        );
+
+        let code_span = rendered
+            .spans()
+            .find(|s| s.content == "code")
+            .expect("Code span not found");
+        assert_eq!(code_span.attr, &code_style());
+        let heading = rendered.spans().next().unwrap();
+        assert_eq!(heading.content, "Title");
+        assert_eq!(heading.attr, &Style::from(Effect::Bold));
+    }
+
+    #[test]
+    fn test_heading_text() {
+        assert_eq!(heading_text("## Heading "), Some("Heading"));
+        assert_eq!(heading_text("#"), Some(""));
+        assert_eq!(heading_text("#hashtag"), None);
+        assert_eq!(heading_text("####### too deep"), None);
+    }
+}
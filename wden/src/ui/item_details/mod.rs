@@ -0,0 +1,690 @@
+mod attachment;
+mod copy_sequence;
+mod format;
+mod markdown;
+mod uri_handler;
+
+use super::{
+    collections::{collection_names, CollectionSelection},
+    confirm::confirm_destructive,
+    data::{StatefulUserData, Unlocked},
+    export::show_item_json_dialog,
+    policy::ItemPolicy,
+    sends::show_new_send_dialog,
+    undo::UndoAction,
+    util::cursive_ext::CursiveExt,
+    vault_table::{reload_vault, set_collection_filter, show_copy_notification},
+};
+use crate::{
+    bitwarden::{
+        api::{ApiClient, CipherData, CipherItem},
+        cipher::{Cipher, EncMacKeys},
+    },
+    datetime::DateDisplay,
+    ui::components::secret_text_view::SecretTextView,
+};
+use cursive::{
+    theme::{ColorStyle, Effect, PaletteColor, Style},
+    traits::{Nameable, Resizable, Scrollable},
+    view::Margins,
+    views::{
+        Dialog, LinearLayout, OnEventView, PaddedView, ScrollView, SelectView, TextView, ViewRef,
+    },
+    Cursive, View,
+};
+use lazy_static::lazy_static;
+use log::warn;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+const VIEW_NAME_NOTES: &str = "notes_textview";
+const VIEW_NAME_PASSWORD: &str = "password_textview";
+
+lazy_static! {
+    static ref VALUE_STYLE: Style = Style::from(Effect::Reverse).combine(ColorStyle::secondary());
+}
+
+pub fn item_detail_dialog(ud: &StatefulUserData<Unlocked>, item_id: &str) -> Option<impl View> {
+    // Find the item
+    let vault_data = ud.vault_data();
+    let item = vault_data
+        .get(item_id)
+        .expect("Item not found in vault data");
+
+    // Find keys that should be used for decrypting details
+    let keys = ud.get_keys_for_item(item);
+    if keys.is_none() {
+        warn!("Error getting keys for item");
+    }
+    let keys = keys?;
+
+    log::info!("Item: {:?}", &item);
+
+    let dialog_contents = match item.data {
+        CipherData::Login(..) => {
+            login_dialog_contents(item, &keys, &ud.global_settings().date_display)
+        }
+        CipherData::SecureNote => note_dialog_contents(item, &keys),
+        CipherData::Card(..) => card_dialog_contents(item, &keys),
+        CipherData::Identity(..) => identity_dialog_contents(item, &keys),
+        CipherData::SshKey(..) => ssh_key_dialog_contents(item, &keys),
+        _ => LinearLayout::vertical(),
+    };
+
+    let policy = ItemPolicy::for_item(ud, item);
+    let expiry = policy.clipboard_expiry();
+    let copy_sequence = copy_sequence::resolve(item, &keys);
+    let collections = collection_names(ud, item.collection_ids.iter());
+
+    let mut key_hint_linear_layout = LinearLayout::vertical();
+
+    let hidden_field_count = item.fields.iter().filter(|f| f.is_hidden()).count();
+    if let CipherData::Login(_) = &item.data {
+        key_hint_linear_layout
+            .add_child(TextView::new("<p> Copy password").style(PaletteColor::Tertiary));
+        key_hint_linear_layout
+            .add_child(TextView::new("<u> Copy username").style(PaletteColor::Tertiary));
+        if policy.allows_reveal() {
+            key_hint_linear_layout.add_child(
+                TextView::new("<s> Toggle password visibility").style(PaletteColor::Tertiary),
+            );
+        }
+    } else if hidden_field_count > 0 && policy.allows_reveal() {
+        key_hint_linear_layout.add_child(
+            TextView::new("<s> Toggle hidden field visibility").style(PaletteColor::Tertiary),
+        );
+    }
+    if let CipherData::Card(_) = &item.data {
+        key_hint_linear_layout
+            .add_child(TextView::new("<n> Copy card number").style(PaletteColor::Tertiary));
+    }
+    if let CipherData::Identity(_) = &item.data {
+        key_hint_linear_layout
+            .add_child(TextView::new("<a> Copy name and address").style(PaletteColor::Tertiary));
+    }
+    if let CipherData::SshKey(_) = &item.data {
+        key_hint_linear_layout
+            .add_child(TextView::new("<k> Copy public key").style(PaletteColor::Tertiary));
+        key_hint_linear_layout
+            .add_child(TextView::new("<i> Copy fingerprint").style(PaletteColor::Tertiary));
+    }
+    if !matches!(item.notes, Cipher::Empty) {
+        key_hint_linear_layout
+            .add_child(TextView::new("<r> Toggle raw notes").style(PaletteColor::Tertiary));
+    }
+    if copy_sequence.is_some() {
+        key_hint_linear_layout
+            .add_child(TextView::new("<c> Run copy sequence").style(PaletteColor::Tertiary));
+    }
+    if !collections.is_empty() {
+        key_hint_linear_layout
+            .add_child(TextView::new("<f> Show collection in vault").style(PaletteColor::Tertiary));
+    }
+    let fields = copyable_fields(item, &keys);
+    if !fields.is_empty() {
+        key_hint_linear_layout
+            .add_child(TextView::new("<v> Copy a field value").style(PaletteColor::Tertiary));
+    }
+    let uri_launches = match &item.data {
+        CipherData::Login(li) => {
+            let uris: Vec<_> = li
+                .uris()
+                .into_iter()
+                .map(|(uri, _)| uri.decrypt_to_string(&keys))
+                .collect();
+            uri_handler::resolve(
+                &uris,
+                &li.username.decrypt_to_string(&keys),
+                &ud.global_settings().uri_handlers,
+            )
+        }
+        _ => vec![],
+    };
+    if !uri_launches.is_empty() {
+        key_hint_linear_layout
+            .add_child(TextView::new("<o> Open URI in a program").style(PaletteColor::Tertiary));
+    }
+    key_hint_linear_layout
+        .add_child(TextView::new("<j> Copy as JSON").style(PaletteColor::Tertiary));
+
+    let mut dialog = Dialog::around(ScrollView::new(
+        LinearLayout::vertical()
+            .child(dialog_contents)
+            .child(custom_fields_view(item, &keys))
+            .child(collections_view(&collections))
+            .child(key_hint_linear_layout),
+    ));
+    if !ud.global_settings().offline {
+        let item_id = item.id.clone();
+        dialog.add_button("Attach file", move |siv| {
+            attachment::show_attach_file_dialog(siv, &item_id)
+        });
+        if let CipherData::SecureNote = item.data {
+            let name = item.name.decrypt_to_string(&keys);
+            let notes = item.notes.decrypt_to_string(&keys);
+            dialog.add_button("Send", move |siv| show_new_send_dialog(siv, &name, &notes));
+        }
+        let item_id = item.id.clone();
+        let name = item.name.decrypt_to_string(&keys);
+        dialog.add_button("Delete", move |siv| confirm_delete(siv, &item_id, &name));
+    }
+    let dialog = dialog
+        .button("Close", |s| {
+            s.pop_layer();
+        })
+        .min_width(40)
+        // Long values are wrapped, and the contents scroll
+        .max_size((100, 40));
+
+    let mut ev = OnEventView::new(dialog);
+
+    if let CipherData::Login(li) = &item.data {
+        let password = li.password.decrypt_to_string(&keys);
+        ev.set_on_event('p', move |siv| {
+            super::clipboard::clip_expiring_string(password.clone(), expiry);
+            show_copy_notification(siv, "Password copied");
+        });
+
+        let username = li.username.decrypt_to_string(&keys);
+        ev.set_on_event('u', move |siv| {
+            super::clipboard::clip_string(username.clone());
+            show_copy_notification(siv, "Username copied");
+        });
+    }
+
+    if matches!(item.data, CipherData::Login(_)) || hidden_field_count > 0 {
+        ev.set_on_event('s', move |siv| {
+            if !policy.allows_reveal() {
+                siv.add_layer(Dialog::info(
+                    "Passwords and hidden fields of items in sensitive collections can't be shown.",
+                ));
+                return;
+            }
+            let view_names = (0..hidden_field_count)
+                .map(hidden_field_view_name)
+                .chain([VIEW_NAME_PASSWORD.to_string()]);
+            for view_name in view_names {
+                let textview: Option<ViewRef<PaddedView<SecretTextView>>> =
+                    siv.find_name(&view_name);
+                if let Some(mut textview) = textview {
+                    textview.get_inner_mut().toggle_hidden();
+                }
+            }
+        });
+    }
+
+    if let CipherData::Card(card) = &item.data {
+        let number = format::format_card_number(
+            &card.number.decrypt_to_string(&keys),
+            ud.global_settings().card_number_format,
+        );
+        ev.set_on_event('n', move |siv| {
+            super::clipboard::clip_expiring_string(number.clone(), expiry);
+            show_copy_notification(siv, "Card number copied");
+        });
+    }
+
+    if let CipherData::Identity(identity) = &item.data {
+        let d = |c: &Cipher| c.decrypt_to_string(&keys);
+        let address_block = format::compose_lines(&[
+            &[
+                &d(&identity.title),
+                &d(&identity.first_name),
+                &d(&identity.middle_name),
+                &d(&identity.last_name),
+            ],
+            &[&d(&identity.company)],
+            &[&d(&identity.address_1)],
+            &[&d(&identity.address_2)],
+            &[&d(&identity.address_3)],
+            &[&d(&identity.postal_code), &d(&identity.city)],
+            &[&d(&identity.state)],
+            &[&d(&identity.country)],
+            &[&d(&identity.phone)],
+            &[&d(&identity.email)],
+        ]);
+        ev.set_on_event('a', move |siv| {
+            super::clipboard::clip_string(address_block.clone());
+            show_copy_notification(siv, "Name and address copied");
+        });
+    }
+
+    if let CipherData::SshKey(ssh_key) = &item.data {
+        let public_key = ssh_key.public_key.decrypt_to_string(&keys);
+        ev.set_on_event('k', move |siv| {
+            super::clipboard::clip_string(public_key.clone());
+            show_copy_notification(siv, "Public key copied");
+        });
+
+        let fingerprint = ssh_key.fingerprint.decrypt_to_string(&keys);
+        ev.set_on_event('i', move |siv| {
+            super::clipboard::clip_string(fingerprint.clone());
+            show_copy_notification(siv, "Fingerprint copied");
+        });
+    }
+
+    if !matches!(item.notes, Cipher::Empty) {
+        let notes = item.notes.decrypt_to_string(&keys);
+        let rendered = markdown::render(&notes);
+        let showing_raw = AtomicBool::new(false);
+        ev.set_on_event('r', move |siv| {
+            let raw = !showing_raw.fetch_xor(true, Ordering::Relaxed);
+            siv.call_on_name(VIEW_NAME_NOTES, |tv: &mut TextView| {
+                if raw {
+                    tv.set_content(notes.clone());
+                } else {
+                    tv.set_content(rendered.clone());
+                }
+            });
+        });
+    }
+
+    if let Some(steps) = copy_sequence {
+        let steps = steps.map(Arc::new);
+        ev.set_on_event('c', move |siv| match &steps {
+            Ok(steps) => copy_sequence::run(siv, steps.clone(), expiry),
+            Err(e) => siv.add_layer(Dialog::info(e.clone())),
+        });
+    }
+
+    if !collections.is_empty() {
+        ev.set_on_event('f', move |siv| show_in_collection(siv, &collections));
+    }
+
+    if !fields.is_empty() {
+        ev.set_on_event('v', move |siv| show_copy_field_dialog(siv, &fields, expiry));
+    }
+
+    if let CipherData::Login(li) = &item.data {
+        if !uri_launches.is_empty() {
+            let password = li.password.decrypt_to_string(&keys);
+            ev.set_on_event('o', move |siv| {
+                uri_handler::show_open_uri(siv, &uri_launches, &password, expiry)
+            });
+        }
+    }
+
+    let item_id = item.id.clone();
+    ev.set_on_event('j', move |siv| {
+        show_item_json_dialog(siv, &item_id, policy.allows_reveal(), expiry)
+    });
+
+    Some(ev)
+}
+
+fn confirm_delete(siv: &mut Cursive, item_id: &str, name: &str) {
+    let item_id = item_id.to_string();
+    confirm_destructive(
+        siv,
+        "Delete item",
+        format!("Move \"{name}\" to the trash?"),
+        "Delete",
+        move |siv| delete_item(siv, item_id.clone()),
+    );
+}
+
+fn delete_item(siv: &mut Cursive, item_id: String) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+
+    siv.add_layer(Dialog::text("Deleting..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            client.soft_delete_cipher(&item_id).await.map(|_| item_id)
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok(item_id) => {
+                    // Close the details, and drop the item from the vault
+                    // without a full sync
+                    siv.pop_layer();
+                    if let Some(mut ud) = siv.get_user_data().with_unlocked_state() {
+                        let item = ud.remove_item(&item_id);
+                        siv.get_user_data()
+                            .undo_stack()
+                            .push(UndoAction::Trash { item_id, item });
+                        reload_vault(siv);
+                    }
+                }
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Deleting the item failed: {e}")));
+                }
+            }
+        },
+    );
+}
+
+/// A value in the "Copy a field value" dialog
+struct CopyableField {
+    name: String,
+    value: String,
+    /// Hidden values are not previewed in the dialog
+    hidden: bool,
+}
+
+/// The values that can be copied with the "Copy a field value" dialog.
+/// The password and the card details have their own copy keys.
+fn copyable_fields(item: &CipherItem, keys: &EncMacKeys) -> Vec<CopyableField> {
+    let d = |c: &Cipher| c.decrypt_to_string(keys);
+    let mut fields = vec![("Name", d(&item.name))];
+    match &item.data {
+        CipherData::Login(login) => {
+            fields.push(("Username", d(&login.username)));
+            fields.extend(login.uris().into_iter().map(|(uri, _)| ("Uri", d(uri))));
+        }
+        CipherData::Card(card) => {
+            fields.push(("Brand", d(&card.brand)));
+            fields.push(("Card holder", d(&card.cardholder_name)));
+        }
+        CipherData::Identity(identity) => fields.extend([
+            ("Title", d(&identity.title)),
+            ("First name", d(&identity.first_name)),
+            ("Middle name", d(&identity.middle_name)),
+            ("Last name", d(&identity.last_name)),
+            ("Phone", d(&identity.phone)),
+            ("Email", d(&identity.email)),
+            ("Address 1", d(&identity.address_1)),
+            ("Address 2", d(&identity.address_2)),
+            ("Address 3", d(&identity.address_3)),
+            ("Postal code", d(&identity.postal_code)),
+            ("City", d(&identity.city)),
+            ("State", d(&identity.state)),
+            ("Country", d(&identity.country)),
+            ("Company", d(&identity.company)),
+            ("SSN", d(&identity.ssn)),
+            ("License number", d(&identity.license_number)),
+            ("Passport number", d(&identity.passport_number)),
+            ("Username", d(&identity.username)),
+        ]),
+        _ => {}
+    }
+    fields.push(("Notes", d(&item.notes)));
+
+    let mut fields: Vec<_> = fields
+        .into_iter()
+        .map(|(name, value)| CopyableField {
+            name: name.to_string(),
+            value,
+            hidden: false,
+        })
+        .collect();
+    fields.extend(
+        item.fields
+            .iter()
+            .filter(|f| !f.is_linked())
+            .map(|f| CopyableField {
+                name: d(&f.name),
+                value: d(&f.value),
+                hidden: f.is_hidden(),
+            }),
+    );
+    fields.retain(|f| !f.value.is_empty());
+    fields
+}
+
+/// Lists the fields with their (shortened) values. The selected value is
+/// copied in full.
+fn show_copy_field_dialog(siv: &mut Cursive, fields: &[CopyableField], expiry: u64) {
+    let mut select = SelectView::new();
+    for field in fields {
+        let preview = if field.hidden {
+            "*******".to_string()
+        } else {
+            format::preview(&field.value, 60)
+        };
+        select.add_item(format!("{}: {preview}", field.name), field.value.clone());
+    }
+    select.set_on_submit(move |siv, value: &String| {
+        siv.pop_layer();
+        super::clipboard::clip_expiring_string(value.clone(), expiry);
+        show_copy_notification(siv, "Value copied");
+    });
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Copy a field value")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn login_dialog_contents(
+    item: &CipherItem,
+    keys: &EncMacKeys,
+    date_display: &DateDisplay,
+) -> LinearLayout {
+    let login = match &item.data {
+        CipherData::Login(l) => l,
+        _ => unreachable!(),
+    };
+    let mut ll = LinearLayout::vertical();
+    add_label_value_text(&mut ll, "Name", &item.name, keys);
+    add_label_value_text(&mut ll, "Username", &login.username, keys);
+    ll.add_child(TextView::new("Password"));
+    ll.add_child(value_secret_textview(&login.password, keys).with_name(VIEW_NAME_PASSWORD));
+    if let Some(date) = item.password_changed_date() {
+        ll.add_child(TextView::new("Password changed"));
+        ll.add_child(PaddedView::new(
+            Margins::tb(0, 1),
+            TextView::new(date_display.format(date)).style(*VALUE_STYLE),
+        ));
+    }
+    for (uri, match_type) in login.uris() {
+        let uri = uri.decrypt_to_string(keys);
+        let value = match match_type {
+            Some(m) => format!("{uri} (match: {})", m.description()),
+            None => uri,
+        };
+        ll.add_child(TextView::new("Uri"));
+        ll.add_child(PaddedView::new(
+            Margins::tb(0, 1),
+            TextView::new(value).style(*VALUE_STYLE),
+        ));
+    }
+    add_notes_text(&mut ll, &item.notes, keys);
+
+    ll
+}
+
+fn note_dialog_contents(item: &CipherItem, keys: &EncMacKeys) -> LinearLayout {
+    let mut ll = LinearLayout::vertical();
+    add_label_value_text(&mut ll, "Name", &item.name, keys);
+    add_notes_text(&mut ll, &item.notes, keys);
+    ll
+}
+
+fn card_dialog_contents(item: &CipherItem, keys: &EncMacKeys) -> LinearLayout {
+    let card = match &item.data {
+        CipherData::Card(c) => c,
+        _ => unreachable!(),
+    };
+
+    let exp_month = card.exp_month.decrypt_to_string(keys);
+    let exp_year = card.exp_year.decrypt_to_string(keys);
+    let expiry = format!("{exp_month} / {exp_year}");
+
+    let mut ll = LinearLayout::vertical();
+    add_label_value_text(&mut ll, "Name", &item.name, keys);
+    add_label_value_text(&mut ll, "Brand", &card.brand, keys);
+    ll.add_child(TextView::new("Number"));
+    ll.add_child(PaddedView::new(
+        Margins::tb(0, 1),
+        TextView::new(format::mask_card_number(
+            &card.number.decrypt_to_string(keys),
+        ))
+        .style(*VALUE_STYLE),
+    ));
+    add_label_value_text(&mut ll, "Code", &card.code, keys);
+    ll.add_child(TextView::new("Expires"));
+    ll.add_child(PaddedView::new(
+        Margins::tb(0, 1),
+        TextView::new(expiry).style(*VALUE_STYLE),
+    ));
+    add_label_value_text(&mut ll, "Card holder", &card.cardholder_name, keys);
+    add_notes_text(&mut ll, &item.notes, keys);
+    ll
+}
+
+fn identity_dialog_contents(item: &CipherItem, keys: &EncMacKeys) -> LinearLayout {
+    let identity = match &item.data {
+        CipherData::Identity(id) => id,
+        _ => unreachable!(),
+    };
+
+    let mut ll = LinearLayout::vertical();
+
+    add_label_value_text(&mut ll, "Name", &item.name, keys);
+
+    add_label_value_text(&mut ll, "Title", &identity.title, keys);
+    add_label_value_text(&mut ll, "First name", &identity.first_name, keys);
+    add_label_value_text(&mut ll, "Middle name", &identity.middle_name, keys);
+    add_label_value_text(&mut ll, "Last name", &identity.last_name, keys);
+
+    add_label_value_text(&mut ll, "Phone", &identity.phone, keys);
+    add_label_value_text(&mut ll, "Email", &identity.email, keys);
+
+    add_label_value_text(&mut ll, "Address 1", &identity.address_1, keys);
+    add_label_value_text(&mut ll, "Address 2", &identity.address_2, keys);
+    add_label_value_text(&mut ll, "Address 3", &identity.address_3, keys);
+    add_label_value_text(&mut ll, "Postal code", &identity.postal_code, keys);
+    add_label_value_text(&mut ll, "City", &identity.city, keys);
+    add_label_value_text(&mut ll, "State", &identity.state, keys);
+    add_label_value_text(&mut ll, "Country", &identity.country, keys);
+
+    add_label_value_text(&mut ll, "Company", &identity.company, keys);
+    add_label_value_text(&mut ll, "SSN", &identity.ssn, keys);
+    add_label_value_text(&mut ll, "License number", &identity.license_number, keys);
+    add_label_value_text(&mut ll, "Passport number", &identity.passport_number, keys);
+    add_label_value_text(&mut ll, "Username", &identity.username, keys);
+
+    add_notes_text(&mut ll, &item.notes, keys);
+
+    ll
+}
+
+/// The private key is not shown
+fn ssh_key_dialog_contents(item: &CipherItem, keys: &EncMacKeys) -> LinearLayout {
+    let ssh_key = match &item.data {
+        CipherData::SshKey(k) => k,
+        _ => unreachable!(),
+    };
+
+    let mut ll = LinearLayout::vertical();
+    add_label_value_text(&mut ll, "Name", &item.name, keys);
+    add_label_value_text(&mut ll, "Public key", &ssh_key.public_key, keys);
+    add_label_value_text(&mut ll, "Fingerprint", &ssh_key.fingerprint, keys);
+    add_notes_text(&mut ll, &item.notes, keys);
+    ll
+}
+
+fn hidden_field_view_name(index: usize) -> String {
+    format!("hidden_field_textview_{index}")
+}
+
+/// The custom fields of the item. Hidden fields are masked like the password.
+fn custom_fields_view(item: &CipherItem, keys: &EncMacKeys) -> LinearLayout {
+    let mut ll = LinearLayout::vertical();
+    let mut hidden_index = 0;
+    for field in &item.fields {
+        ll.add_child(TextView::new(field.name.decrypt_to_string(keys)));
+        if field.is_hidden() {
+            ll.add_child(
+                value_secret_textview(&field.value, keys)
+                    .with_name(hidden_field_view_name(hidden_index)),
+            );
+            hidden_index += 1;
+            continue;
+        }
+        let value = if field.is_boolean() {
+            let checked = field.value.decrypt_to_string(keys) == "true";
+            if checked { "Yes" } else { "No" }.to_string()
+        } else if field.is_linked() {
+            format!(
+                "Linked to {}",
+                field.linked_field_name().unwrap_or("another field")
+            )
+        } else {
+            field.value.decrypt_to_string(keys)
+        };
+        ll.add_child(PaddedView::new(
+            Margins::tb(0, 1),
+            TextView::new(value).style(*VALUE_STYLE),
+        ));
+    }
+    ll
+}
+
+fn collections_view(names: &[(String, String)]) -> LinearLayout {
+    let mut ll = LinearLayout::vertical();
+    if !names.is_empty() {
+        let names: Vec<_> = names.iter().map(|(name, _)| name.as_str()).collect();
+        ll.add_child(TextView::new("Collections"));
+        ll.add_child(PaddedView::new(
+            Margins::tb(0, 1),
+            TextView::new(names.join(", ")).style(*VALUE_STYLE),
+        ));
+    }
+    ll
+}
+
+/// Switches the vault filter to one of the item's collections. With
+/// multiple collections, the collection is selected in a dialog first.
+fn show_in_collection(siv: &mut Cursive, collections: &[(String, String)]) {
+    fn select(siv: &mut Cursive, id: &str) {
+        // Close the item details
+        siv.pop_layer();
+        set_collection_filter(siv, CollectionSelection::Collection(id.to_string()));
+    }
+
+    if let [(_, id)] = collections {
+        select(siv, id);
+        return;
+    }
+
+    let mut select_view = SelectView::new();
+    for (name, id) in collections {
+        select_view.add_item(name.clone(), id.clone());
+    }
+    select_view.set_on_submit(|siv, id: &String| {
+        siv.pop_layer();
+        select(siv, id);
+    });
+    let dialog = Dialog::around(select_view.scrollable())
+        .title("Show collection")
+        .dismiss_button("Cancel");
+    siv.add_layer(dialog);
+}
+
+fn add_label_value_text(ll: &mut LinearLayout, name: &str, value: &Cipher, keys: &EncMacKeys) {
+    ll.add_child(TextView::new(name));
+    ll.add_child(value_textview(value, keys));
+}
+
+fn add_notes_text(ll: &mut LinearLayout, notes: &Cipher, keys: &EncMacKeys) {
+    let tv = TextView::new(markdown::render(&notes.decrypt_to_string(keys))).style(*VALUE_STYLE);
+    ll.add_child(TextView::new("Notes"));
+    ll.add_child(PaddedView::new(
+        Margins::tb(0, 1),
+        tv.with_name(VIEW_NAME_NOTES),
+    ));
+}
+
+fn value_textview(cipher: &Cipher, keys: &EncMacKeys) -> PaddedView<TextView> {
+    let tv = TextView::new(cipher.decrypt_to_string(keys)).style(*VALUE_STYLE);
+    PaddedView::new(Margins::tb(0, 1), tv)
+}
+
+fn value_secret_textview(cipher: &Cipher, keys: &EncMacKeys) -> PaddedView<SecretTextView> {
+    let tv = SecretTextView::new(cipher.decrypt_to_string(keys)).style(*VALUE_STYLE);
+    PaddedView::new(Margins::tb(0, 1), tv)
+}
@@ -0,0 +1,260 @@
+use std::{collections::BTreeMap, process::Stdio};
+
+use cursive::{
+    traits::Scrollable,
+    views::{Dialog, SelectView},
+    Cursive,
+};
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+use tokio::process::Command;
+
+use crate::ui::{clipboard, util::cursive_ext::CursiveExt, vault_table::show_copy_notification};
+
+/// A login URI that can be opened with an external program
+pub struct UriLaunch {
+    uri: String,
+    /// The program and its arguments, or why the URI can't be opened
+    command: Result<Vec<String>, String>,
+}
+
+/// Handlers used unless the profile has one for the scheme. The commands
+/// are split at whitespace, and {uri}, {host}, {port} and {username} are
+/// replaced in each argument.
+fn builtin_handler(scheme: &str) -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        // Terminal and Screen Sharing handle these URLs
+        return matches!(scheme, "ssh" | "vnc").then_some("open {uri}");
+    }
+    match scheme {
+        "ssh" if cfg!(windows) => Some("cmd /C start ssh -p {port} {username}@{host}"),
+        "ssh" => Some("x-terminal-emulator -e ssh -p {port} {username}@{host}"),
+        "rdp" if cfg!(windows) => Some("mstsc /v:{host}:{port}"),
+        "rdp" => Some("xfreerdp /v:{host}:{port} /u:{username}"),
+        "vnc" => Some("vncviewer {host}::{port}"),
+        _ => None,
+    }
+}
+
+/// Characters with a special meaning in cmd.exe command lines
+const CMD_METACHARACTERS: &[char] = &['&', '|', '<', '>', '^', '%', '"', '(', ')'];
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "ssh" => Some(22),
+        "rdp" => Some(3389),
+        "vnc" => Some(5900),
+        _ => None,
+    }
+}
+
+/// The URIs of a login item that have a handler. Web URIs are left out.
+pub fn resolve(
+    uris: &[String],
+    username: &str,
+    handlers: &BTreeMap<String, String>,
+) -> Vec<UriLaunch> {
+    uris.iter()
+        .filter_map(|uri| {
+            let url = Url::parse(uri).ok()?;
+            let template = match handlers.get(url.scheme()) {
+                Some(command) => command.as_str(),
+                None => builtin_handler(url.scheme())?,
+            };
+            if template.trim().is_empty() {
+                // Disabled in the profile
+                return None;
+            }
+            Some(UriLaunch {
+                uri: uri.clone(),
+                command: expand(template, &url, username),
+            })
+        })
+        .collect()
+}
+
+fn expand(template: &str, url: &Url, username: &str) -> Result<Vec<String>, String> {
+    let host = url.host_str().unwrap_or_default();
+    let port = url
+        .port()
+        .or_else(|| default_port(url.scheme()))
+        .map(|p| p.to_string())
+        .unwrap_or_default();
+    // The username of the URI is percent-encoded, e.g. "domain%5Cuser"
+    let uri_username = percent_decode_str(url.username())
+        .decode_utf8()
+        .map_err(|_| "The username of the URI is not valid UTF-8".to_string())?;
+    let username = match &*uri_username {
+        "" => username,
+        u => u,
+    };
+
+    // The values come from the vault, possibly from shared items. They
+    // must not be taken as options of the program.
+    for value in [host, username] {
+        if value.starts_with('-') || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(format!("Refusing to pass \"{value}\" to the program"));
+        }
+    }
+    // Nor run as commands when the handler goes through cmd (the builtin
+    // ssh handler on Windows). The URI is only checked if it is passed,
+    // as it has the username percent-encoded.
+    let mut values = vec![host, &port, username];
+    if template.contains("{uri}") {
+        values.push(url.as_str());
+    }
+    for value in values {
+        if value.contains(CMD_METACHARACTERS) {
+            return Err(format!("Refusing to pass \"{value}\" to the program"));
+        }
+    }
+    if host.is_empty() {
+        return Err("The URI has no host".to_string());
+    }
+
+    Ok(template
+        .split_whitespace()
+        .map(|arg| {
+            // Without a username, "user@host" becomes "host"
+            let arg = if username.is_empty() {
+                arg.replace("{username}@", "")
+            } else {
+                arg.to_string()
+            };
+            arg.replace("{uri}", url.as_str())
+                .replace("{host}", host)
+                .replace("{port}", &port)
+                .replace("{username}", username)
+        })
+        .collect())
+}
+
+/// Opens the URI, or lets the user pick one if there are several. The
+/// password is copied when the program has started, for pasting it there.
+pub fn show_open_uri(siv: &mut Cursive, launches: &[UriLaunch], password: &str, expiry: u64) {
+    if let [launch] = launches {
+        open_uri(siv, launch.command.clone(), password, expiry);
+        return;
+    }
+
+    let mut select = SelectView::new();
+    for launch in launches {
+        select.add_item(launch.uri.clone(), launch.command.clone());
+    }
+    let password = password.to_string();
+    select.set_on_submit(move |siv, command: &Result<Vec<String>, String>| {
+        siv.pop_layer();
+        open_uri(siv, command.clone(), &password, expiry);
+    });
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Open a URI")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn open_uri(siv: &mut Cursive, command: Result<Vec<String>, String>, password: &str, expiry: u64) {
+    let args = match command {
+        Ok(args) => args,
+        Err(e) => {
+            siv.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+    let Some((program, args)) = args.split_first() else {
+        return;
+    };
+    log::info!("Opening a URI with {program}");
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let program = program.clone();
+    let password = password.to_string();
+    siv.async_op(
+        // The program keeps running after wden is closed
+        async move { command.spawn().map(drop) },
+        move |siv, res| match res {
+            Ok(()) if password.is_empty() => {}
+            Ok(()) => {
+                clipboard::clip_expiring_string(password.clone(), expiry);
+                show_copy_notification(siv, "Password copied");
+            }
+            Err(e) => {
+                log::warn!("Starting {program} failed: {e}");
+                siv.add_layer(Dialog::info(format!("Starting {program} failed: {e}")));
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let url = Url::parse("ssh://jump.example.com").unwrap();
+        assert_eq!(
+            expand("ssh -p {port} {username}@{host}", &url, "admin"),
+            Ok(vec![
+                "ssh".to_string(),
+                "-p".to_string(),
+                "22".to_string(),
+                "admin@jump.example.com".to_string()
+            ])
+        );
+        assert_eq!(
+            expand("ssh {username}@{host}", &url, ""),
+            Ok(vec!["ssh".to_string(), "jump.example.com".to_string()])
+        );
+
+        let url = Url::parse("ssh://root@jump.example.com:2222").unwrap();
+        assert_eq!(
+            expand("ssh -p {port} {username}@{host}", &url, "admin").unwrap()[2..],
+            ["2222", "root@jump.example.com"]
+        );
+
+        let url = Url::parse("ssh://dom%5Cadmin@host.example.com").unwrap();
+        assert_eq!(
+            expand("xfreerdp /u:{username}", &url, "").unwrap()[1],
+            "/u:dom\\admin"
+        );
+        // Checked after decoding
+        let url = Url::parse("ssh://%2DoProxyCommand=x@example.com").unwrap();
+        assert!(expand("ssh {username}@{host}", &url, "").is_err());
+        let url = Url::parse("ssh://a%20b@example.com").unwrap();
+        assert!(expand("ssh {username}@{host}", &url, "").is_err());
+
+        let url = Url::parse("ssh://-oProxyCommand=x").unwrap();
+        assert!(expand("ssh {host}", &url, "").is_err());
+
+        let url = Url::parse("ssh://example.com").unwrap();
+        assert!(expand("cmd /C start ssh {username}@{host}", &url, "x&calc").is_err());
+        let url = Url::parse("ssh://x%26calc@example.com").unwrap();
+        assert!(expand("cmd /C start ssh {uri}", &url, "").is_err());
+        assert!(expand("cmd /C start ssh {username}@{host}", &url, "").is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let uris = [
+            "https://example.com".to_string(),
+            "ssh://example.com".to_string(),
+            "vnc://example.com".to_string(),
+            "custom://example.com".to_string(),
+        ];
+        let handlers = BTreeMap::from([
+            ("vnc".to_string(), String::new()),
+            ("custom".to_string(), "custom-client {host}".to_string()),
+        ]);
+        let resolved: Vec<_> = resolve(&uris, "", &handlers)
+            .into_iter()
+            .map(|l| l.uri)
+            .collect();
+        assert_eq!(resolved, ["ssh://example.com", "custom://example.com"]);
+    }
+}
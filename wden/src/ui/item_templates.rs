@@ -0,0 +1,97 @@
+use cursive::{views::Dialog, Cursive};
+use serde::{Deserialize, Serialize};
+
+use crate::bitwarden::cipher::{Cipher, EncMacKeys};
+
+use super::{
+    create_item::NewItemType,
+    data::{StatefulUserData, Unlocked},
+    util::cursive_ext::CursiveExt,
+    vault_table::show_copy_notification,
+};
+
+/// A template for new items, e.g. a server login with hostname and port
+/// fields. The templates are stored in the profile as a JSON array,
+/// encrypted with the user key.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub item_type: NewItemType,
+    pub notes: String,
+    pub fields: Vec<TemplateField>,
+}
+
+/// Custom field that the items created from a template start with
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub name: String,
+    /// Pre-filled value, may be empty
+    pub value: String,
+    pub hidden: bool,
+}
+
+/// The templates of the profile, sorted by name
+pub fn item_templates(
+    ud: &StatefulUserData<Unlocked>,
+    user_keys: &EncMacKeys,
+) -> Vec<ItemTemplate> {
+    let item_templates = match ud.profile_store().load() {
+        Ok(d) => d.item_templates,
+        Err(e) => {
+            log::warn!("Loading the item templates failed: {e}");
+            return vec![];
+        }
+    };
+    if let Cipher::Empty = item_templates {
+        return vec![];
+    }
+
+    item_templates
+        .decrypt(user_keys)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_slice(&json)?))
+        .unwrap_or_else(|e| {
+            // E.g. after the account encryption key was rotated
+            log::warn!("Decrypting the item templates failed: {e}");
+            vec![]
+        })
+}
+
+/// Replaces the templates of the profile with the result of `edit`
+fn edit_item_templates<F>(siv: &mut Cursive, edit: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut Vec<ItemTemplate>),
+{
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let user_keys = ud
+        .decrypt_keys()
+        .ok_or_else(|| anyhow::anyhow!("The user key is not available"))?;
+    let mut templates = item_templates(&ud, &user_keys);
+    edit(&mut templates);
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let json = serde_json::to_vec(&templates)?;
+    let cipher = Cipher::encrypt(&json, &user_keys)?;
+    ud.profile_store().edit(|d| d.item_templates = cipher)
+}
+
+/// Stores the template, replacing an earlier template with the same name
+pub fn save_item_template(siv: &mut Cursive, template: ItemTemplate) {
+    let res = edit_item_templates(siv, |templates| {
+        templates.retain(|t| t.name != template.name);
+        templates.push(template);
+    });
+    match res {
+        Ok(()) => show_copy_notification(siv, "Template saved"),
+        Err(e) => {
+            log::warn!("Storing the item templates failed: {e}");
+            siv.add_layer(Dialog::info(format!(
+                "Storing the item template failed: {e}"
+            )));
+        }
+    }
+}
+
+pub fn remove_item_template(siv: &mut Cursive, name: &str) -> anyhow::Result<()> {
+    edit_item_templates(siv, |templates| templates.retain(|t| t.name != name))
+}
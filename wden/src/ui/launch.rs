@@ -1,55 +1,218 @@
 use std::sync::Arc;
 
-use cursive::{
-    theme::BaseColor, theme::Color, theme::PaletteColor::*, theme::Theme, Cursive, CursiveRunnable,
-};
+use clap::ValueEnum;
+use cursive::{Cursive, CursiveRunnable};
+use tokio::runtime::{Handle, Runtime};
+
+use crate::profile::{GlobalSettings, ProfileData, ProfileOverrides, ProfileStore};
 
-use crate::{
-    bitwarden::server::ServerConfiguration,
-    profile::{GlobalSettings, ProfileData, ProfileStore},
+use super::{
+    alert, autolock, clipboard,
+    data::UserData,
+    focus_lock,
+    login::login_dialog,
+    offline, session,
+    terminal_title::TerminalTitle,
+    theme::{self, GlyphSet},
+    util::keystore,
 };
 
-use super::{autolock, data::UserData, login::login_dialog};
+/// Terminal backends. Only crossterm is always compiled in, the others
+/// need to be enabled with cargo features.
+#[derive(Clone, Copy, Default, ValueEnum, PartialEq, Eq, Debug)]
+pub enum Backend {
+    #[default]
+    Crossterm,
+    Ncurses,
+    Termion,
+}
 
-pub fn launch(
+impl Backend {
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Crossterm => true,
+            Backend::Ncurses => cfg!(feature = "ncurses-backend"),
+            Backend::Termion => cfg!(feature = "termion-backend"),
+        }
+    }
+
+    fn runnable(&self) -> CursiveRunnable {
+        match self {
+            #[cfg(feature = "ncurses-backend")]
+            Backend::Ncurses => CursiveRunnable::ncurses(),
+            #[cfg(feature = "termion-backend")]
+            Backend::Termion => CursiveRunnable::termion(),
+            Backend::Crossterm => focus_lock::crossterm_with_focus_reports(),
+            #[allow(unreachable_patterns)]
+            _ => {
+                log::warn!("Backend {self:?} is not available, using crossterm");
+                focus_lock::crossterm_with_focus_reports()
+            }
+        }
+    }
+}
+
+type InitialScreen = Box<dyn FnOnce(&mut Cursive, &GlobalSettings, &ProfileData)>;
+
+/// Sets up and runs the wden UI.
+///
+/// The UI needs a tokio runtime for its background tasks. By default the
+/// runtime of the calling context is used, or a new one is created if
+/// there is none. [`Launcher::launch`] blocks until the UI exits, so
+/// async programs should call it outside of their async tasks, e.g.
+/// with `spawn_blocking`.
+pub struct Launcher {
     profile: String,
-    server_config: Option<ServerConfiguration>,
+    overrides: ProfileOverrides,
     accept_invalid_certs: bool,
     always_refresh_token_on_sync: bool,
-) {
-    let (global_settings, profile_data, profile_store) = load_profile(
-        profile,
-        server_config,
-        accept_invalid_certs,
-        always_refresh_token_on_sync,
-    );
-    let profile_name = global_settings.profile.clone();
-
-    let mut siv = cursive::default();
-    siv.set_theme(custom_theme());
-    let autolocker =
-        autolock::start_autolocker(siv.cb_sink().clone(), global_settings.autolock_duration);
-    siv.set_user_data(UserData::new(
-        Arc::new(global_settings),
-        Arc::new(profile_store),
-        autolocker,
-    ));
-
-    siv.add_global_callback('§', Cursive::toggle_debug_console);
-    cursive::logger::init();
-    log::set_max_level(log::LevelFilter::Info);
-
-    siv.add_layer(login_dialog(
-        &profile_name,
-        profile_data.saved_email,
-        profile_data.encrypted_api_key.is_some(),
-        false,
-    ));
-
-    run(siv);
+    offline: bool,
+    backend: Backend,
+    glyphs: GlyphSet,
+    set_title: bool,
+    runtime: Option<Handle>,
+    initial_screen: Option<InitialScreen>,
 }
 
-fn run(mut cursive: CursiveRunnable) {
+impl Launcher {
+    pub fn new(profile: impl Into<String>) -> Self {
+        Launcher {
+            profile: profile.into(),
+            overrides: ProfileOverrides::default(),
+            accept_invalid_certs: false,
+            always_refresh_token_on_sync: false,
+            offline: false,
+            backend: Backend::default(),
+            glyphs: GlyphSet::default(),
+            set_title: true,
+            runtime: None,
+            initial_screen: None,
+        }
+    }
+
+    pub fn overrides(mut self, overrides: ProfileOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    pub fn accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    pub fn always_refresh_token_on_sync(mut self, always_refresh: bool) -> Self {
+        self.always_refresh_token_on_sync = always_refresh;
+        self
+    }
+
+    /// Opens the vault from the offline cache instead of logging in
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn glyphs(mut self, glyphs: GlyphSet) -> Self {
+        self.glyphs = glyphs;
+        self
+    }
+
+    /// Show the profile and the lock state in the terminal title
+    pub fn terminal_title(mut self, set_title: bool) -> Self {
+        self.set_title = set_title;
+        self
+    }
+
+    /// Runs the background tasks of the UI on the given runtime
+    pub fn runtime(mut self, handle: Handle) -> Self {
+        self.runtime = Some(handle);
+        self
+    }
+
+    /// Replaces the login dialog (or the offline unlock) as the first screen
+    pub fn initial_screen<F>(mut self, screen: F) -> Self
+    where
+        F: FnOnce(&mut Cursive, &GlobalSettings, &ProfileData) + 'static,
+    {
+        self.initial_screen = Some(Box::new(screen));
+        self
+    }
+
+    /// Runs the UI until it exits
+    pub fn launch(self) {
+        // Keep an own runtime alive for the whole run if one was needed
+        let (handle, _runtime) = match self.runtime.clone().or_else(|| Handle::try_current().ok()) {
+            Some(handle) => (handle, None),
+            None => {
+                let runtime = Runtime::new().expect("Failed to start the tokio runtime");
+                (runtime.handle().clone(), Some(runtime))
+            }
+        };
+        let _guard = handle.enter();
+        self.launch_in_runtime();
+    }
+
+    fn launch_in_runtime(self) {
+        let (global_settings, profile_data, profile_store) = load_profile(
+            self.profile,
+            self.overrides,
+            self.accept_invalid_certs,
+            self.always_refresh_token_on_sync,
+            self.offline,
+        );
+        let global_settings = Arc::new(global_settings);
+
+        theme::set_glyphs(self.glyphs);
+        let mut siv = self.backend.runnable();
+        siv.set_theme(theme::custom_theme(theme::detect_color_support()));
+        let autolocker =
+            autolock::start_autolocker(siv.cb_sink().clone(), global_settings.autolock_duration);
+        if let Some(delay) = global_settings.focus_loss_lock_delay {
+            focus_lock::start_focus_lock_monitor(
+                siv.cb_sink().clone(),
+                global_settings.profile.clone(),
+                delay,
+            );
+        }
+        #[cfg(target_os = "linux")]
+        super::session_lock::start_session_lock_monitor(siv.cb_sink().clone());
+        siv.set_user_data(UserData::new(
+            global_settings.clone(),
+            Arc::new(profile_store),
+            autolocker,
+        ));
+
+        alert::alert_on_clipboard_clear(&mut siv);
+
+        siv.add_global_callback('§', Cursive::toggle_debug_console);
+        cursive::logger::init();
+        log::set_max_level(log::LevelFilter::Info);
+
+        match self.initial_screen {
+            Some(screen) => screen(&mut siv, &global_settings, &profile_data),
+            None if self.offline => offline::start_offline(&mut siv),
+            None => {
+                if !session::restore_session(&mut siv) {
+                    siv.add_layer(login_dialog(
+                        &global_settings,
+                        profile_data.saved_email,
+                        profile_data.encrypted_api_key.is_some(),
+                        false,
+                    ));
+                }
+            }
+        }
+
+        let title = self.set_title.then(TerminalTitle::init);
+        run(siv, title);
+    }
+}
+
+fn run(mut cursive: CursiveRunnable, mut title: Option<TerminalTitle>) {
     let mut cursive = cursive.runner();
 
     cursive.refresh();
@@ -67,48 +230,104 @@ fn run(mut cursive: CursiveRunnable) {
                 }
             });
         }
+
+        if let Some(title) = &mut title {
+            // The profile can be switched on the login screen
+            let state = cursive.with_user_data(|ud: &mut UserData| {
+                let unlocked = ud.with_unlocked_state().is_some();
+                (ud.profile().to_owned(), unlocked)
+            });
+            if let Some((profile, unlocked)) = state {
+                title.update(&profile, unlocked);
+            }
+        }
     }
+
+    clipboard::clear_pending_clips();
 }
 
 pub fn load_profile(
     profile_name: String,
-    server_configuration: Option<ServerConfiguration>,
+    overrides: ProfileOverrides,
     accept_invalid_certs: bool,
     always_refresh_on_sync: bool,
+    offline: bool,
 ) -> (GlobalSettings, ProfileData, ProfileStore) {
     let profile_store = ProfileStore::new(&profile_name);
     let mut profile_data = profile_store.load().unwrap_or_default();
+    let previous_server_configuration = profile_data.server_configuration.clone();
+    let previous_keyring_unlock = profile_data.keyring_unlock;
+    let previous_remember_session = profile_data.remember_session;
+
+    overrides.apply(&mut profile_data);
+
+    // The cache is of no use with a different server
+    if !profile_data.offline_cache
+        || profile_data.server_configuration != previous_server_configuration
+    {
+        profile_store
+            .remove_vault_cache()
+            .expect("Failed to remove the offline vault cache");
+    }
+
+    if !profile_data.record_metrics {
+        profile_store
+            .remove_metrics()
+            .expect("Failed to remove the metrics file");
+    }
+
+    // Revoke the stored master key when keyring unlock is turned off
+    if previous_keyring_unlock
+        && (!profile_data.keyring_unlock
+            || profile_data.server_configuration != previous_server_configuration)
+    {
+        if let Err(e) = keystore::get_platform_keystore().delete_master_key(&profile_name) {
+            log::warn!("Removing the master key from the keystore failed: {e:#}");
+        }
+    }
+
+    // The session is of no use with a different server either
+    if previous_remember_session
+        && (!profile_data.remember_session
+            || profile_data.server_configuration != previous_server_configuration)
+    {
+        session::forget_session(&profile_name);
+    }
+
+    clipboard::set_backend(profile_data.clipboard_backend);
 
     let global_settings = GlobalSettings {
         profile: profile_name,
-        server_configuration: server_configuration.unwrap_or(profile_data.server_configuration),
+        server_configuration: profile_data.server_configuration.clone(),
         autolock_duration: profile_data.autolock_duration,
         device_id: profile_data.device_id.clone(),
+        device_class: profile_data.device_class,
         accept_invalid_certs,
         always_refresh_token_on_sync: always_refresh_on_sync,
         encrypted_api_key: profile_data.encrypted_api_key.clone(),
+        pinned_spki_hashes: profile_data.pinned_spki_hashes.clone(),
+        focus_loss_lock_delay: profile_data.focus_loss_lock_delay,
+        keyfile: profile_data.keyfile.clone(),
+        settings_sync: profile_data.settings_sync,
+        offline_cache: profile_data.offline_cache,
+        forget_master_key: profile_data.forget_master_key,
+        card_number_format: profile_data.card_number_format,
+        enter_action: profile_data.enter_action,
+        alert_style: profile_data.alert_style,
+        environment: profile_data.environment,
+        keyring_unlock: profile_data.keyring_unlock,
+        remember_session: profile_data.remember_session,
+        record_metrics: profile_data.record_metrics,
+        uri_handlers: profile_data.uri_handlers.clone(),
+        date_display: profile_data.date_display(),
+        clipboard_backend: profile_data.clipboard_backend,
+        offline,
     };
 
     // Write new settings
-    profile_data.server_configuration = global_settings.server_configuration.clone();
     profile_store
         .store(&profile_data)
         .expect("Failed to write profile settings");
 
     (global_settings, profile_data, profile_store)
 }
-
-fn custom_theme() -> Theme {
-    let mut t = Theme::default();
-
-    t.palette[Background] = Color::TerminalDefault;
-    t.palette[View] = Color::parse("#f0f4f7").unwrap();
-    t.palette[Primary] = Color::Dark(BaseColor::Black);
-    t.palette[Secondary] = Color::parse("#525252").unwrap();
-    t.palette[TitlePrimary] = Color::parse("#1b98e0").unwrap();
-    t.palette[Highlight] = Color::parse("#126391").unwrap();
-    t.palette[HighlightInactive] = Color::parse("#233d4d").unwrap();
-    t.palette[HighlightText] = Color::parse("#f0f4f7").unwrap();
-
-    t
-}
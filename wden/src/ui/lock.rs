@@ -1,18 +1,40 @@
-use std::sync::Arc;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
 
 use cursive::{
-    theme::{BaseColor, Color},
+    theme::PaletteColor,
     traits::Nameable,
     view::Margins,
     views::{Dialog, EditView, LinearLayout, PaddedView, TextView},
     Cursive,
 };
 
-use crate::bitwarden::cipher::{self, CipherError};
+use crate::{
+    bitwarden::{
+        apikey::ApiKey,
+        cipher::{self, CipherError, KeyDerivationFunction},
+    },
+    profile::{self, record_metric, MetricKind, ProfileStore},
+};
 
-use super::{util::cursive_ext::CursiveExt, vault_table};
+use super::{
+    alert::alert,
+    data::{StatefulUserData, Unlocked},
+    sync::do_sync,
+    util::{cursive_ext::CursiveExt, keystore},
+    vault_table,
+};
 
 const VIEW_NAME_PASSWORD: &str = "password";
+const VIEW_NAME_UNLOCK_ELAPSED: &str = "unlock_elapsed";
 
 pub fn lock_vault(c: &mut Cursive) {
     if c.get_user_data().with_locked_state().is_some() {
@@ -43,7 +65,7 @@ pub fn lock_vault(c: &mut Cursive) {
     c.add_layer(d);
 }
 
-fn unlock_dialog(profile_name: &str, email: &str) -> Dialog {
+pub fn unlock_dialog(profile_name: &str, email: &str) -> Dialog {
     let pw_editview = EditView::new()
         .secret()
         .on_submit(|siv, _| submit_unlock(siv))
@@ -55,10 +77,7 @@ fn unlock_dialog(profile_name: &str, email: &str) -> Dialog {
                 "Vault is locked. Unlock with master password:",
             ))
             .child(PaddedView::new(Margins::tb(1, 1), pw_editview))
-            .child(
-                TextView::new(format!("Signed in user: {email}"))
-                    .style(Color::Light(BaseColor::Black)),
-            ),
+            .child(TextView::new(format!("Signed in user: {email}")).style(PaletteColor::Tertiary)),
     )
     .title(format!("Vault locked ({profile_name})"))
     .button("Unlock", submit_unlock)
@@ -69,59 +88,278 @@ fn submit_unlock(c: &mut Cursive) {
         .call_on_name(VIEW_NAME_PASSWORD, |view: &mut EditView| view.get_content())
         .unwrap();
 
-    c.pop_layer();
-    c.add_layer(Dialog::text("Unlocking..."));
-
     // Get stuff from user data
     let user_data = c.get_user_data().with_locked_state().unwrap();
     let global_settings = user_data.global_settings();
     let pbkdf = user_data.pbkdf();
     let email = user_data.email();
-    let token_key = &user_data.token().key;
+    let token_key = user_data.token().key.clone();
     let api_key = user_data.api_key();
+    let item_count = user_data.item_count();
+    let profile_store = user_data.profile_store();
 
-    let keys_res = derive_and_check_master_key(&email, &password, &pbkdf, token_key);
+    // The KDF can take seconds with high Argon2 parameters. It is run on
+    // a blocking thread, and the dialog shows the elapsed time meanwhile.
+    // The derivation can't be interrupted: after cancelling, its result
+    // is just ignored.
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let done = Arc::new(AtomicBool::new(false));
+    let unlock_start = Instant::now();
 
-    match keys_res {
-        Err(e) => {
-            log::warn!("Unlocking failed: {}", e);
+    c.pop_layer();
+    c.add_layer(unlocking_dialog(
+        &pbkdf,
+        &global_settings.profile,
+        &email,
+        cancelled.clone(),
+    ));
+    start_elapsed_time_updates(c, unlock_start, done.clone());
+
+    let (email2, pbkdf2, profile_store2) = (email.clone(), pbkdf.clone(), profile_store.clone());
+    let keyfile = global_settings.keyfile.clone();
+    c.async_op(
+        async move {
+            tokio::task::spawn_blocking(move || {
+                derive_and_check_master_key(
+                    &email2,
+                    &password,
+                    &pbkdf2,
+                    &token_key,
+                    keyfile.as_deref(),
+                    &profile_store2,
+                )
+            })
+            .await
+            .context("The key derivation task failed")?
+        },
+        move |c, keys_res| {
+            done.store(true, Ordering::Relaxed);
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            c.pop_layer();
+            match keys_res {
+                Err(e) => {
+                    log::warn!("Unlocking failed: {}", e);
+
+                    let err_msg = match e.downcast_ref::<CipherError>() {
+                        Some(CipherError::MacVerificationFailed(_)) => {
+                            "Unlocking failed: invalid password".to_owned()
+                        }
+                        _ => format!("Unlocking failed: {e}"),
+                    };
 
-            let err_msg = match e {
-                CipherError::MacVerificationFailed(_) => {
-                    "Unlocking failed: invalid password".to_owned()
+                    let dialog = Dialog::text(err_msg).button("OK", move |siv| {
+                        siv.pop_layer();
+                        siv.add_layer(unlock_dialog(&global_settings.profile, &email));
+                    });
+
+                    c.add_layer(dialog);
+                    alert(c);
+                }
+                Ok(master_key) => {
+                    if global_settings.record_metrics {
+                        record_metric(
+                            &profile_store,
+                            MetricKind::Unlock,
+                            unlock_start.elapsed(),
+                            Some(item_count),
+                        );
+                    }
+                    if global_settings.keyring_unlock {
+                        store_in_keystore(
+                            &global_settings.profile,
+                            &master_key,
+                            global_settings.keyfile.as_deref(),
+                        );
+                    }
+                    finish_unlock(c, master_key, api_key);
                 }
-                e => format!("Unlocking failed: {e}"),
+            }
+        },
+    );
+}
+
+fn unlocking_dialog(
+    pbkdf: &cipher::PbkdfParameters,
+    profile_name: &str,
+    email: &str,
+    cancelled: Arc<AtomicBool>,
+) -> Dialog {
+    let profile_name = profile_name.to_owned();
+    let email = email.to_owned();
+    Dialog::around(
+        LinearLayout::vertical()
+            .child(TextView::new(format!(
+                "Deriving key ({})...",
+                kdf_description(pbkdf)
+            )))
+            .child(TextView::new("Elapsed: 0 s").with_name(VIEW_NAME_UNLOCK_ELAPSED)),
+    )
+    .title("Unlocking")
+    .button("Cancel", move |siv| {
+        cancelled.store(true, Ordering::Relaxed);
+        siv.pop_layer();
+        siv.add_layer(unlock_dialog(&profile_name, &email));
+    })
+}
+
+fn kdf_description(pbkdf: &cipher::PbkdfParameters) -> String {
+    match pbkdf.kdf {
+        KeyDerivationFunction::Pbkdf2 => format!("PBKDF2, {} iterations", pbkdf.iterations),
+        KeyDerivationFunction::Argon2id => format!(
+            "argon2id, {} MiB × {}, parallelism {}",
+            pbkdf.memory_mib, pbkdf.iterations, pbkdf.parallelism
+        ),
+    }
+}
+
+/// Updates the elapsed time in the unlocking dialog every second, until
+/// the derivation is done
+fn start_elapsed_time_updates(c: &mut Cursive, start: Instant, done: Arc<AtomicBool>) {
+    let cb = c.cb_sink().clone();
+    tokio::spawn(async move {
+        let mut int = tokio::time::interval(Duration::from_secs(1));
+        // The first tick completes immediately
+        int.tick().await;
+        loop {
+            int.tick().await;
+            if done.load(Ordering::Relaxed) {
+                break;
+            }
+            let text = format!("Elapsed: {} s", start.elapsed().as_secs());
+            let update = move |siv: &mut Cursive| {
+                siv.call_on_name(VIEW_NAME_UNLOCK_ELAPSED, |tv: &mut TextView| {
+                    tv.set_content(text)
+                });
             };
+            if cb.send(Box::new(update)).is_err() {
+                // Cursive has stopped
+                break;
+            }
+        }
+    });
+}
 
-            let dialog = Dialog::text(err_msg).button("OK", move |siv| {
-                siv.pop_layer();
-                siv.add_layer(unlock_dialog(&global_settings.profile, &email));
-            });
+/// Unlocks with the master key stored in the platform keystore. Returns
+/// false if there is no usable key, and the password is needed instead.
+pub fn unlock_from_keystore(c: &mut Cursive) -> bool {
+    let user_data = c.get_user_data().with_locked_state().unwrap();
+    let profile = user_data.global_settings().profile.clone();
+    let keystore = keystore::get_platform_keystore();
 
-            c.pop_layer();
-            c.add_layer(dialog);
+    let keyfile = user_data.global_settings().keyfile.clone();
+    let master_key = match keystore.load_master_key(&profile, keyfile.as_deref()) {
+        Ok(Some(master_key)) => master_key,
+        Ok(None) => return false,
+        Err(e) => {
+            log::warn!("Loading the master key from the keystore failed: {e:#}");
+            return false;
         }
-        Ok(master_key) => {
-            // Success, store keys, restore other data and continue
-            let user_data = user_data.into_unlocking(master_key, api_key);
+    };
 
-            let search_term = user_data.decrypt_search_term().unwrap_or_default();
-            let collection_selection = user_data.collection_selection();
-            let _ = user_data.into_unlocked();
+    let check_res = check_master_key(
+        master_key,
+        &user_data.token().key,
+        keyfile.as_deref(),
+        &user_data.profile_store(),
+    );
+    let api_key = user_data.api_key();
 
-            vault_table::show_vault_with_filters(c, search_term, collection_selection);
+    match check_res {
+        Ok(master_key) => {
+            log::info!("Unlocked with the master key from the keystore");
+            finish_unlock(c, master_key, api_key);
+            true
         }
+        Err(e) => {
+            // E.g. the master password has been changed
+            log::warn!("The master key in the keystore can't be used: {e:#}");
+            if let Err(e) = keystore.delete_master_key(&profile) {
+                log::warn!("Removing the master key from the keystore failed: {e:#}");
+            }
+            false
+        }
+    }
+}
+
+fn store_in_keystore(profile: &str, master_key: &cipher::WrappedMasterKey, keyfile: Option<&Path>) {
+    match master_key.unwrap_key() {
+        Ok(master_key) => keystore::store_for_unlock(profile, &master_key, keyfile),
+        Err(e) => log::warn!("Unwrapping the master key failed: {e}"),
+    }
+}
+
+fn finish_unlock(
+    c: &mut Cursive,
+    master_key: Arc<cipher::WrappedMasterKey>,
+    api_key: Option<Arc<ApiKey>>,
+) {
+    // Success, store keys, restore other data and continue
+    let user_data = c.get_user_data().with_locked_state().unwrap();
+    let sync_on_unlock = user_data.sync_on_unlock();
+    let user_data = user_data.into_unlocking(master_key, api_key);
+
+    if sync_on_unlock {
+        let _ = user_data.into_logged_in();
+        do_sync(c, false);
+        return;
+    }
+
+    let search_term = user_data.decrypt_search_term().unwrap_or_default();
+    let collection_selection = user_data.collection_selection();
+    let _ = user_data.into_unlocked();
+
+    vault_table::show_vault_with_filters(c, search_term, collection_selection);
+}
+
+/// Checks the master password while unlocked, e.g. before opening a
+/// sensitive item. The returned check runs the key derivation, so it
+/// should be called on a blocking thread.
+pub fn master_password_checker(
+    ud: &StatefulUserData<Unlocked>,
+) -> impl FnOnce(&str) -> anyhow::Result<()> + Send + 'static {
+    let email = ud.email();
+    let pbkdf = ud.pbkdf();
+    let token_key = ud.token().key.clone();
+    let keyfile = ud.global_settings().keyfile.clone();
+    let profile_store = ud.profile_store();
+
+    move |password| {
+        derive_and_check_master_key(
+            &email,
+            password,
+            &pbkdf,
+            &token_key,
+            keyfile.as_deref(),
+            &profile_store,
+        )
+        .map(|_| ())
     }
 }
 
 fn derive_and_check_master_key(
-    email: &Arc<String>,
-    password: &Arc<String>,
-    pbkdf: &Arc<cipher::PbkdfParameters>,
+    email: &str,
+    password: &str,
+    pbkdf: &cipher::PbkdfParameters,
+    token_key: &cipher::Cipher,
+    keyfile: Option<&Path>,
+    profile_store: &ProfileStore,
+) -> anyhow::Result<Arc<cipher::WrappedMasterKey>> {
+    let master_key = cipher::create_master_key(email, password, pbkdf)?;
+    check_master_key(master_key, token_key, keyfile, profile_store)
+}
+
+fn check_master_key(
+    master_key: cipher::MasterKey,
     token_key: &cipher::Cipher,
-) -> Result<Arc<cipher::MasterKey>, CipherError> {
-    let master_key = Arc::new(cipher::create_master_key(email, password, pbkdf)?);
+    keyfile: Option<&Path>,
+    profile_store: &ProfileStore,
+) -> anyhow::Result<Arc<cipher::WrappedMasterKey>> {
     // Verify that the password was correct by checking if token key can be decrypted
     let _ = cipher::decrypt_symmetric_keys(token_key, &master_key)?;
-    Ok(master_key)
+    if let Some(keyfile) = keyfile {
+        profile::verify_keyfile(keyfile, &master_key, profile_store)?;
+    }
+    Ok(Arc::new(cipher::WrappedMasterKey::wrap(&master_key)?))
 }
@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use cursive::{
+    traits::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, TextView},
+    Cursive,
+};
+
+use crate::profile::ProfileOverrides;
+
+use super::{util::cursive_ext::CursiveExt, vault_table::show_copy_notification};
+
+const VIEW_NAME_AUTOLOCK: &str = "autolock_minutes";
+const VIEW_NAME_FOCUS_LOSS: &str = "focus_loss_seconds";
+
+/// Shows a dialog for changing the autolock and focus loss lock delays
+/// of the profile
+pub fn show_lock_settings_dialog(siv: &mut Cursive) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let profile_data = match ud.profile_store().load() {
+        Ok(d) => d,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Loading the profile failed: {e}")));
+            return;
+        }
+    };
+    let autolock_minutes = profile_data.autolock_duration.as_secs() / 60;
+    let focus_loss_seconds = profile_data
+        .focus_loss_lock_delay
+        .map(|d| d.as_secs().to_string())
+        .unwrap_or_default();
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Lock after being idle for (minutes, 0 = never)",
+                ))
+                .child(
+                    EditView::new()
+                        .content(autolock_minutes.to_string())
+                        .with_name(VIEW_NAME_AUTOLOCK),
+                )
+                .child(TextView::new(
+                    "\nLock when the terminal has been unfocused for \
+                    (seconds, 0 = immediately, empty = never)",
+                ))
+                .child(
+                    EditView::new()
+                        .content(focus_loss_seconds)
+                        .with_name(VIEW_NAME_FOCUS_LOSS),
+                )
+                .child(TextView::new(
+                    "\nThe focus loss setting takes effect the next time wden is started.",
+                ))
+                .fixed_width(60),
+        )
+        .title("Locking")
+        .dismiss_button("Cancel")
+        .button("Save", save_lock_settings),
+    );
+}
+
+fn save_lock_settings(siv: &mut Cursive) {
+    let [autolock, focus_loss] = [VIEW_NAME_AUTOLOCK, VIEW_NAME_FOCUS_LOSS].map(|name| {
+        siv.call_on_name(name, |v: &mut EditView| v.get_content().trim().to_string())
+            .unwrap()
+    });
+    let Ok(autolock_minutes) = autolock.parse::<u64>() else {
+        siv.add_layer(Dialog::info("Enter the autolock delay in whole minutes"));
+        return;
+    };
+    let focus_loss_lock_delay = if focus_loss.is_empty() {
+        None
+    } else if let Ok(secs) = focus_loss.parse() {
+        Some(Duration::from_secs(secs))
+    } else {
+        siv.add_layer(Dialog::info("Enter the focus loss delay in whole seconds"));
+        return;
+    };
+    let autolock_duration = Duration::from_secs(autolock_minutes * 60);
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    // Applied like the command line options, so that settings sync
+    // notices the change
+    let res = ud.profile_store().edit(|d| {
+        ProfileOverrides {
+            autolock_duration: Some(autolock_duration),
+            focus_loss_lock_delay: Some(focus_loss_lock_delay),
+            ..Default::default()
+        }
+        .apply(d)
+    });
+    if let Err(e) = res {
+        log::warn!("Storing the locking settings failed: {e}");
+        siv.add_layer(Dialog::info(format!("Storing the settings failed: {e}")));
+        return;
+    }
+
+    let autolocker = ud.autolocker();
+    let mut autolocker = autolocker.lock().unwrap();
+    autolocker.set_autolock_duration(autolock_duration);
+    // Rearm the timer in case autolock was disabled
+    autolocker.update_next_autolock_time(true);
+    drop(autolocker);
+
+    siv.pop_layer();
+    show_copy_notification(siv, "Locking settings saved");
+}
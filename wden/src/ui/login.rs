@@ -2,10 +2,11 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use cursive::{
+    event::Event,
     theme::Effect,
     traits::{Nameable, Resizable},
     view::{Margins, Selector},
-    views::{Dialog, EditView, LinearLayout, PaddedView, TextView},
+    views::{Dialog, EditView, LinearLayout, OnEventView, PaddedView, TextView},
     Cursive, View,
 };
 use cursive_secret_edit_view::SecretEditView;
@@ -16,23 +17,33 @@ use crate::{
         self,
         api::{ApiClient, TokenResponse, TwoFactorProviderType},
         apikey::ApiKey,
-        cipher::{self, MasterKey, MasterPasswordHash, PbkdfParameters},
+        cipher::{self, MasterPasswordHash, PbkdfParameters, WrappedMasterKey},
     },
     profile::{GlobalSettings, ProfileStore},
 };
 
-use super::{sync::do_sync, two_factor::two_factor_dialog, util::cursive_ext::CursiveExt};
+use super::{
+    components::warning_banner::insecure_http_banner,
+    offline::switch_to_offline,
+    profile_switch::{self, show_profile_switcher},
+    session,
+    slow_response::slow_response_callback,
+    sync::do_sync,
+    two_factor::two_factor_dialog,
+    util::{cursive_ext::CursiveExt, keystore},
+    verification::verification_dialog,
+};
 
 const VIEW_NAME_PASSWORD: &str = "password";
 const VIEW_NAME_EMAIL: &str = "email";
 const VIEW_NAME_PERSONAL_API_KEY: &str = "personal_api_key";
 
 pub fn login_dialog(
-    profile_name: &str,
+    global_settings: &GlobalSettings,
     saved_email: Option<String>,
     api_key_login: bool,
     with_extra_token_field: bool,
-) -> Dialog {
+) -> OnEventView<Dialog> {
     if api_key_login && saved_email.is_none() {
         panic!("Bug: email not present while trying to log in with api keys");
     }
@@ -90,6 +101,13 @@ pub fn login_dialog(
         layout.add_child(token_field);
     }
 
+    if global_settings.server_configuration.uses_insecure_http() {
+        layout.insert_child(
+            0,
+            PaddedView::new(Margins::tb(0, 1), insecure_http_banner()),
+        );
+    }
+
     if should_focus_password {
         let focus_res = layout.focus_view(&Selector::Name(VIEW_NAME_PASSWORD));
         if focus_res.is_err() {
@@ -97,9 +115,16 @@ pub fn login_dialog(
         }
     }
 
-    Dialog::around(layout)
-        .title(format!("Log in ({profile_name})"))
-        .button("Submit", move |siv| submit_callback3(siv))
+    let mut dialog = Dialog::around(layout)
+        .title(format!("Log in ({})", global_settings.profile))
+        .button("Submit", move |siv| submit_callback3(siv));
+
+    // Switching is only offered when there is something to switch to
+    if profile_switch::profile_names().len() > 1 {
+        dialog.add_button("Switch profile (^p)", show_profile_switcher);
+    }
+
+    OnEventView::new(dialog).on_event(Event::CtrlChar('p'), show_profile_switcher)
 }
 
 fn submit_login(c: &mut Cursive) {
@@ -115,7 +140,7 @@ fn submit_login(c: &mut Cursive) {
             // to prevent (accidentally) leaking the data in memory.
             // Copy it to another zeroizing string.
             let content = view.get_content();
-            let mut buf = Zeroizing::new(String::with_capacity(content.as_bytes().len() + 1));
+            let mut buf = Zeroizing::new(String::with_capacity(content.len() + 1));
             buf.push_str(content);
             buf
         })
@@ -134,14 +159,18 @@ fn submit_login(c: &mut Cursive) {
     let ud = c.get_user_data().with_logged_out_state().unwrap();
     let global_settings = ud.global_settings();
     let profile_store = ud.profile_store();
+    let on_slow_response = slow_response_callback(c);
 
     c.async_op(
         async move {
             let client = ApiClient::new(
                 &global_settings.server_configuration,
                 &global_settings.device_id,
+                global_settings.device_class,
                 global_settings.accept_invalid_certs,
-            );
+                &global_settings.pinned_spki_hashes,
+            )?
+            .on_slow_response(on_slow_response);
             async {
                 let (master_key, master_pw_hash, pbkdf) =
                     do_prelogin(&client, &email, &password).await?;
@@ -152,6 +181,7 @@ fn submit_login(c: &mut Cursive) {
                     master_pw_hash.clone(),
                     None,
                     personal_api_key.as_deref(),
+                    None,
                     &profile_store,
                 )
                 .await
@@ -188,7 +218,7 @@ fn submit_api_key_login(c: &mut Cursive, email: String) {
             // to prevent (accidentally) leaking the data in memory.
             // Copy it to another zeroizing string.
             let content = view.get_content();
-            let mut buf = Zeroizing::new(String::with_capacity(content.as_bytes().len() + 1));
+            let mut buf = Zeroizing::new(String::with_capacity(content.len() + 1));
             buf.push_str(content);
             buf
         })
@@ -196,14 +226,18 @@ fn submit_api_key_login(c: &mut Cursive, email: String) {
 
     c.pop_layer();
     c.add_layer(Dialog::text("Signing in..."));
+    let on_slow_response = slow_response_callback(c);
 
     c.async_op(
         async move {
             let client = ApiClient::new(
                 &global_settings.server_configuration,
                 &global_settings.device_id,
+                global_settings.device_class,
                 global_settings.accept_invalid_certs,
-            );
+                &global_settings.pinned_spki_hashes,
+            )?
+            .on_slow_response(on_slow_response);
             async {
                 let api_key = do_api_key_prelogin(&email, &password, &global_settings).await?;
                 do_login_with_api_key(&client, &email, &password, &api_key)
@@ -234,6 +268,14 @@ fn submit_api_key_login(c: &mut Cursive, email: String) {
     )
 }
 
+/// Whether the server couldn't be reached at all
+fn is_connection_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| {
+        c.downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout())
+    })
+}
+
 pub fn handle_login_response(
     cursive: &mut Cursive,
     res: Result<TokenResponse, anyhow::Error>,
@@ -249,24 +291,34 @@ pub fn handle_login_response(
             if let Some(ud) = cursive.get_user_data().with_logging_in_like_state() {
                 ud.into_logged_out();
             }
-            cursive.add_layer(Dialog::text(err_msg).title("Login error").button(
-                "OK",
-                move |siv| {
+            // A refresh of a remembered session fails e.g. when the refresh
+            // token has expired. It can be tried again later if the server
+            // was just unreachable.
+            let global_settings = cursive.get_user_data().global_settings();
+            if global_settings.remember_session && !is_connection_error(&e) {
+                session::forget_session(&global_settings.profile);
+            }
+            let mut dialog = Dialog::text(err_msg)
+                .title("Login error")
+                .button("OK", move |siv| {
                     // Remove this dialog, and show the login dialog again
                     siv.pop_layer();
                     let d = login_dialog(
                         &siv.get_user_data()
                             .with_logged_out_state()
                             .unwrap()
-                            .global_settings()
-                            .profile,
+                            .global_settings(),
                         Some(String::clone(&email)),
                         api_key_login,
                         had_token_field,
                     );
                     siv.add_layer(d);
-                },
-            ));
+                });
+            // The offline vault cache can be opened if the server is unreachable
+            if cursive.get_user_data().global_settings().offline_cache && is_connection_error(&e) {
+                dialog.add_button("Open offline", switch_to_offline);
+            }
+            cursive.add_layer(dialog);
         }
         Result::Ok(token) => {
             match token {
@@ -287,7 +339,32 @@ pub fn handle_login_response(
                         }
                     }
 
-                    ud.into_logged_in(Arc::new(*t));
+                    let ud = ud.into_logged_in(Arc::new(*t));
+
+                    if let Err(e) = ud.verify_keyfile() {
+                        ud.into_logged_out();
+                        handle_login_response(
+                            cursive,
+                            Err(e),
+                            email,
+                            had_token_field,
+                            api_key_login,
+                        );
+                        return;
+                    }
+
+                    if ud.global_settings().keyring_unlock {
+                        if let Some(master_key) = ud.master_key() {
+                            let global_settings = ud.global_settings();
+                            keystore::store_for_unlock(
+                                &global_settings.profile,
+                                &master_key,
+                                global_settings.keyfile.as_deref(),
+                            );
+                        }
+                    }
+
+                    session::remember_session(&ud);
 
                     do_sync(cursive, true);
                 }
@@ -303,11 +380,17 @@ pub fn handle_login_response(
                         two_factor_dialog(types, email, p, captcha_bypass_token.map(Arc::new));
                     cursive.add_layer(dialog);
                 }
+                bitwarden::api::TokenResponse::VerificationRequired(verification) => {
+                    cursive.pop_layer();
+                    let p = &cursive.get_user_data().global_settings().profile;
+                    let dialog = verification_dialog(verification, email, p);
+                    cursive.add_layer(dialog);
+                }
                 bitwarden::api::TokenResponse::CaptchaRequired => {
                     cursive.pop_layer();
                     let ud = cursive.get_user_data().with_logging_in_state().unwrap();
                     let email = ud.email();
-                    let profile_name = ud.global_settings().profile.clone();
+                    let global_settings = ud.global_settings();
                     ud.into_logged_out();
 
                     let dialog = Dialog::text(
@@ -317,8 +400,12 @@ pub fn handle_login_response(
                     )
                     .button("OK", move |siv| {
                         siv.clear_layers();
-                        let dialog =
-                            login_dialog(&profile_name, Some(String::clone(&email)), false, true);
+                        let dialog = login_dialog(
+                            &global_settings,
+                            Some(String::clone(&email)),
+                            false,
+                            true,
+                        );
                         siv.add_layer(dialog);
                     });
                     cursive.add_layer(dialog);
@@ -334,7 +421,7 @@ async fn do_prelogin(
     password: &str,
 ) -> Result<
     (
-        Arc<MasterKey>,
+        Arc<WrappedMasterKey>,
         Arc<MasterPasswordHash>,
         Arc<PbkdfParameters>,
     ),
@@ -343,8 +430,9 @@ async fn do_prelogin(
     let pbkdf_params = client.prelogin(email).await?;
     let master_key = cipher::create_master_key(email, password, &pbkdf_params)?;
     let master_pw_hash = cipher::create_master_password_hash(&master_key, password);
+    let wrapped_master_key = WrappedMasterKey::wrap(&master_key)?;
     Ok((
-        Arc::new(master_key),
+        Arc::new(wrapped_master_key),
         Arc::new(master_pw_hash),
         Arc::new(pbkdf_params),
     ))
@@ -368,6 +456,7 @@ pub async fn do_login(
     master_pw_hash: Arc<MasterPasswordHash>,
     second_factor: Option<(TwoFactorProviderType, &str)>,
     personal_api_key: Option<&str>,
+    new_device_otp: Option<&str>,
     profile_store: &ProfileStore,
 ) -> Result<TokenResponse, anyhow::Error> {
     let mut token_res = if let Some((two_factor_type, two_factor_token)) = second_factor {
@@ -377,6 +466,7 @@ pub async fn do_login(
                 &master_pw_hash.base64_encoded(),
                 Some((two_factor_type, two_factor_token, true)),
                 personal_api_key,
+                new_device_otp,
             )
             .await?
     } else {
@@ -397,6 +487,7 @@ pub async fn do_login(
                 &master_pw_hash.base64_encoded(),
                 two_factor_param,
                 personal_api_key,
+                new_device_otp,
             )
             .await?
     };
@@ -417,17 +508,18 @@ pub async fn do_login_with_api_key(
     email: &str,
     password: &str,
     api_key: &ApiKey,
-) -> Result<(TokenResponse, Arc<MasterKey>, Arc<PbkdfParameters>), anyhow::Error> {
+) -> Result<(TokenResponse, Arc<WrappedMasterKey>, Arc<PbkdfParameters>), anyhow::Error> {
     let token_res = client.get_token_with_api_key(api_key).await?;
 
     let pbkdf_params = token_res
         .pbkdf_parameters()
         .context("Token did not contain pbkdf params")?;
     let master_key = cipher::create_master_key(email, password, &pbkdf_params)?;
+    let wrapped_master_key = WrappedMasterKey::wrap(&master_key)?;
 
     Ok((
         TokenResponse::Success(Box::new(token_res)),
-        Arc::new(master_key),
+        Arc::new(wrapped_master_key),
         Arc::new(pbkdf_params),
     ))
 }
@@ -1,17 +1,47 @@
+mod alert;
 mod autolock;
-mod clipboard;
+pub mod clipboard;
 mod collections;
 pub mod components;
+mod confirm;
+mod create_item;
 mod data;
+mod export;
+mod focus_lock;
+mod folders;
+mod generator;
 mod item_details;
+mod item_templates;
 pub mod launch;
 mod lock;
+mod lock_settings;
 mod login;
+mod notifications;
+mod offline;
+mod organizations;
+mod pending_items;
+mod pinned_items;
+pub mod policy;
+mod profile_switch;
 mod search;
+mod sends;
+mod session;
+#[cfg(target_os = "linux")]
+mod session_lock;
+mod settings_sync;
+mod slow_response;
 mod sync;
+mod terminal_title;
+mod theme;
 mod two_factor;
-mod util;
+mod undo;
+pub(crate) mod util;
+mod vault_diff;
+mod vault_info;
 mod vault_table;
+mod verification;
+mod web_vault;
 pub mod panic_handler;
 
-pub use launch::launch;
+pub use launch::Launcher;
+pub use theme::GlyphSet;
@@ -0,0 +1,171 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use cursive::{CbSink, Cursive};
+use tokio::task::AbortHandle;
+use zeroize::Zeroizing;
+
+use crate::bitwarden::notifications::{Notification, NotificationsClient};
+
+use super::{sync::do_sync, util::cursive_ext::CursiveExt};
+
+/// The profile and the task of the running listener
+static LISTENER: Mutex<Option<(String, AbortHandle)>> = Mutex::new(None);
+
+/// A change was notified, but the vault hasn't been synced yet
+static SYNC_PENDING: AtomicBool = AtomicBool::new(false);
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How often a pending sync is retried while dialogs are open or the
+/// vault is locked
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+enum Session {
+    Token(Zeroizing<String>),
+    /// Logging in, refreshing the token or unlocking
+    Busy,
+    /// Logged out, or switched to another profile
+    Ended,
+}
+
+/// Starts listening to the notifications of the server, so that changes
+/// made on other devices are synced automatically. Does nothing if the
+/// listener of the profile is already running, or in offline mode.
+pub fn start_notifications(siv: &mut Cursive) {
+    let global_settings = siv.get_user_data().global_settings();
+    if global_settings.offline {
+        return;
+    }
+
+    let mut listener = LISTENER.lock().unwrap();
+    if let Some((profile, handle)) = listener.as_ref() {
+        if *profile == global_settings.profile && !handle.is_finished() {
+            return;
+        }
+        handle.abort();
+    }
+
+    let Some(client) = NotificationsClient::new(
+        &global_settings.server_configuration,
+        &global_settings.device_id,
+        global_settings.accept_invalid_certs,
+        &global_settings.pinned_spki_hashes,
+    ) else {
+        log::info!("The notifications hub of the server is not known, live sync disabled");
+        return;
+    };
+
+    SYNC_PENDING.store(false, Ordering::Relaxed);
+    let profile = global_settings.profile.clone();
+    let task = tokio::spawn(listen(client, siv.cb_sink().clone(), profile.clone()));
+    *listener = Some((profile, task.abort_handle()));
+}
+
+async fn listen(client: NotificationsClient, cb_sink: CbSink, profile: String) {
+    let mut retry_delay = MIN_RETRY_DELAY;
+    loop {
+        let token = match session(&cb_sink, &profile).await {
+            Session::Token(token) => token,
+            Session::Busy => {
+                tokio::time::sleep(MIN_RETRY_DELAY).await;
+                continue;
+            }
+            Session::Ended => return,
+        };
+
+        let started = Instant::now();
+        let connection = client.listen(&token, |notification| {
+            let profile = profile.clone();
+            _ = cb_sink.send(Box::new(move |siv| {
+                on_notification(siv, &profile, notification)
+            }));
+        });
+        tokio::pin!(connection);
+        let mut idle_check = tokio::time::interval(IDLE_CHECK_INTERVAL);
+        let res = loop {
+            tokio::select! {
+                res = &mut connection => break res,
+                _ = idle_check.tick() => {
+                    _ = cb_sink.send(Box::new(sync_if_idle));
+                }
+            }
+        };
+        match res {
+            Ok(()) => log::info!("The notifications hub closed the connection"),
+            Err(e) => log::warn!("Listening to notifications failed: {e:#}"),
+        }
+
+        // Back off while the hub is unreachable
+        if started.elapsed() > MAX_RETRY_DELAY {
+            retry_delay = MIN_RETRY_DELAY;
+        }
+        tokio::time::sleep(retry_delay).await;
+        retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Gets the current access token from the UI thread
+async fn session(cb_sink: &CbSink, profile: &str) -> Session {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let profile = profile.to_string();
+    let sent = cb_sink.send(Box::new(move |siv| {
+        _ = tx.send(current_session(siv, &profile));
+    }));
+    if sent.is_err() {
+        return Session::Ended;
+    }
+    rx.await.unwrap_or(Session::Ended)
+}
+
+fn current_session(siv: &mut Cursive, profile: &str) -> Session {
+    let ud = siv.get_user_data();
+    if ud.profile() != profile || ud.with_logged_out_state().is_some() {
+        return Session::Ended;
+    }
+    let token = if let Some(ud) = ud.with_unlocked_state() {
+        ud.token()
+    } else if let Some(ud) = ud.with_locked_state() {
+        ud.token()
+    } else if let Some(ud) = ud.with_logged_in_state() {
+        ud.token()
+    } else {
+        return Session::Busy;
+    };
+    Session::Token(token.access_token.clone())
+}
+
+fn on_notification(siv: &mut Cursive, profile: &str, notification: Notification) {
+    if siv.get_user_data().profile() != profile {
+        return;
+    }
+    match notification {
+        Notification::VaultChanged => {
+            SYNC_PENDING.store(true, Ordering::Relaxed);
+            sync_if_idle(siv);
+        }
+        Notification::LoggedOut => {
+            log::info!("The server ended the sessions of the user");
+        }
+    }
+}
+
+/// Syncs a notified change if the unlocked vault is shown without
+/// dialogs, so that nothing the user is doing gets interrupted
+fn sync_if_idle(siv: &mut Cursive) {
+    if !SYNC_PENDING.load(Ordering::Relaxed)
+        || siv.get_user_data().with_unlocked_state().is_none()
+        || siv.screen().len() != 1
+    {
+        return;
+    }
+    SYNC_PENDING.store(false, Ordering::Relaxed);
+    log::info!("The vault was changed on another device, syncing");
+    do_sync(siv, false);
+}
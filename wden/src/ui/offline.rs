@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use cursive::{views::Dialog, Cursive};
+
+use crate::{
+    bitwarden::api::{SyncResponse, TokenResponseSuccess},
+    profile::ProfileOverrides,
+};
+
+use super::{
+    data::UserData,
+    launch::load_profile,
+    lock::{unlock_dialog, unlock_from_keystore},
+    util::cursive_ext::CursiveExt,
+};
+
+/// Starts in offline mode: loads the vault from the offline cache
+/// and shows the unlock dialog, skipping the login.
+pub fn start_offline(siv: &mut Cursive) {
+    if let Err(e) = load_vault_cache(siv) {
+        log::warn!("Loading the offline vault cache failed: {e:#}");
+        let dialog = Dialog::text(format!("Starting in offline mode failed: {e:#}"))
+            .title("Offline mode")
+            .button("Quit", Cursive::quit);
+        siv.add_layer(dialog);
+    }
+}
+
+/// Reopens the current profile in offline mode, when the server can't
+/// be reached for logging in
+pub fn switch_to_offline(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_logged_out_state() else {
+        return;
+    };
+    let current = ud.global_settings();
+    let autolocker = ud.autolocker();
+
+    let (global_settings, _, profile_store) = load_profile(
+        current.profile.clone(),
+        ProfileOverrides::default(),
+        current.accept_invalid_certs,
+        current.always_refresh_token_on_sync,
+        true,
+    );
+    log::info!("Switched to offline mode");
+    siv.set_user_data(UserData::new(
+        Arc::new(global_settings),
+        Arc::new(profile_store),
+        autolocker,
+    ));
+    siv.clear_layers();
+    start_offline(siv);
+}
+
+fn load_vault_cache(siv: &mut Cursive) -> anyhow::Result<()> {
+    let ud = siv.get_user_data().with_logged_out_state().unwrap();
+    let keyfile = ud.global_settings().keyfile.clone();
+    let cache = ud.profile_store().load_vault_cache(keyfile.as_deref())?;
+    let cache = cache.context(
+        "No offline vault cache found for this profile. \
+        Enable it with --offline-cache, and sync the vault while online.",
+    )?;
+
+    let sync_res = SyncResponse::from_json(&cache.sync_data)?;
+    let token = TokenResponseSuccess::offline(cache.key, cache.private_key);
+    let ud = ud.into_offline_locked(
+        Arc::new(cache.email),
+        Arc::new(cache.pbkdf),
+        Arc::new(token),
+        sync_res,
+    );
+
+    let global_settings = ud.global_settings();
+    let email = ud.email();
+    if !(global_settings.keyring_unlock && unlock_from_keystore(siv)) {
+        siv.add_layer(unlock_dialog(&global_settings.profile, &email));
+    }
+    Ok(())
+}
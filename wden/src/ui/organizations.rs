@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use cursive::{
+    traits::{Resizable, Scrollable},
+    views::{Dialog, LinearLayout, SelectView, TextView},
+    Cursive,
+};
+
+use crate::bitwarden::{
+    api::{ApiClient, OrganizationUser},
+    cipher::{self, PublicKey},
+};
+
+use super::{confirm::confirm_destructive, util::cursive_ext::CursiveExt};
+
+/// Lists the organizations of the user with the membership status.
+/// Owners and admins can confirm the members who have accepted their
+/// invitation.
+pub fn show_organizations(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+
+    let mut orgs: Vec<_> = ud
+        .organizations()
+        .values()
+        .map(|org| {
+            let label = format!("{} ({})", org.name, org.status_description());
+            (label, org.id.clone())
+        })
+        .collect();
+    orgs.sort();
+    if orgs.is_empty() {
+        siv.add_layer(Dialog::info("You are not a member of any organization"));
+        return;
+    }
+
+    let select = SelectView::new()
+        .with_all(orgs)
+        .on_submit(|siv, org_id: &String| show_organization(siv, org_id.clone()));
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Invitations are accepted with the link in the invitation email. \
+                    After that, an owner or an admin of the organization confirms the membership.\n",
+                ))
+                .child(select.scrollable()),
+        )
+        .title("Organizations")
+        .dismiss_button("Close")
+        .max_width(80),
+    );
+}
+
+fn show_organization(siv: &mut Cursive, org_id: String) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let organizations = ud.organizations();
+    let Some(org) = organizations.get(&org_id) else {
+        return;
+    };
+
+    if !org.can_manage_users() {
+        siv.add_layer(Dialog::info(format!(
+            "{}\n\nMembership: {}",
+            org.name,
+            org.status_description()
+        )));
+        return;
+    }
+
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+    siv.add_layer(Dialog::text("Loading members..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            let users = client.get_accepted_org_users(&org_id).await;
+            users.map(|u| (org_id, u))
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok((org_id, users)) => show_accepted_members(siv, org_id, users),
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Loading the members failed: {e}")));
+                }
+            }
+        },
+    );
+}
+
+fn show_accepted_members(siv: &mut Cursive, org_id: String, users: Vec<OrganizationUser>) {
+    if users.is_empty() {
+        siv.add_layer(Dialog::info("No members are waiting for confirmation"));
+        return;
+    }
+
+    let org_id = Arc::new(org_id);
+    let select = SelectView::new()
+        .with_all(users.into_iter().map(|u| {
+            let label = match &u.name {
+                Some(name) => format!("{name} <{}>", u.email),
+                None => u.email.clone(),
+            };
+            (label, Arc::new(u))
+        }))
+        .on_submit(move |siv, user: &Arc<OrganizationUser>| {
+            confirm_member_dialog(siv, org_id.clone(), user.clone())
+        });
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Waiting for confirmation")
+            .dismiss_button("Close")
+            .max_width(80),
+    );
+}
+
+fn confirm_member_dialog(siv: &mut Cursive, org_id: Arc<String>, user: Arc<OrganizationUser>) {
+    let Some(user_id) = user.user_id.clone() else {
+        siv.add_layer(Dialog::info("The member has not accepted the invitation"));
+        return;
+    };
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+
+    siv.add_layer(Dialog::text("Loading the public key of the member..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            client
+                .get_user_public_key(&user_id)
+                .await
+                .map(|k| (user_id, k))
+        },
+        move |siv, res| {
+            siv.pop_layer();
+            let (user_id, public_key) = match res {
+                Ok(r) => r,
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!(
+                        "Loading the public key of the member failed: {e}"
+                    )));
+                    return;
+                }
+            };
+            // The key that was fingerprinted is the one the org key is encrypted with
+            let phrase = cipher::fingerprint_phrase(&public_key, &user_id);
+            let public_key = Arc::new(public_key);
+            confirm_destructive(
+                siv,
+                "Confirm member",
+                format!(
+                    "Fingerprint phrase of {}:\n\n{phrase}\n\n\
+                    Ask the member to check that the phrase matches the one in their \
+                    account settings. Only confirm if it does. The member gets access \
+                    to the organization key.",
+                    user.email
+                ),
+                "Phrase matches, confirm",
+                move |siv| confirm_member(siv, &org_id, &user.id, &public_key),
+            );
+        },
+    );
+}
+
+fn confirm_member(siv: &mut Cursive, org_id: &str, org_user_id: &str, public_key: &[u8]) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let org_keys = ud.get_org_keys_for_vault().remove(&org_id.to_string());
+    let Some(org_keys) = org_keys else {
+        siv.add_layer(Dialog::info("The organization key is not available"));
+        return;
+    };
+
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+    let org_id = org_id.to_string();
+    let org_user_id = org_user_id.to_string();
+    let public_key = public_key.to_vec();
+
+    siv.add_layer(Dialog::text("Confirming..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            let public_key = PublicKey::from_der(&public_key)?;
+            let key = cipher::encrypt_org_keys(&org_keys, &public_key)?;
+            client.confirm_org_user(&org_id, &org_user_id, &key).await
+        },
+        |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok(()) => {
+                    // Back to the organization list
+                    siv.pop_layer();
+                    siv.add_layer(Dialog::info("The member was confirmed"));
+                }
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Confirming the member failed: {e}")));
+                }
+            }
+        },
+    );
+}
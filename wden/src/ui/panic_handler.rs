@@ -6,7 +6,8 @@ pub struct PanicHandler;
 
 impl PanicHandler {
     pub fn new() -> Self {
-        let hook = std::panic::take_hook();
+        // The default hook is not used: the message can contain vault
+        // data, and it ends up in the terminal scrollback
         std::panic::set_hook(Box::new(move |info| {
             use cursive::backends::crossterm::crossterm::*;
             _ = execute!(
@@ -14,13 +15,18 @@ impl PanicHandler {
                 terminal::LeaveAlternateScreen,
                 cursor::Show,
                 event::DisableMouseCapture,
+                event::DisableFocusChange,
             );
             _ = terminal::disable_raw_mode();
+            super::clipboard::clear_pending_clips();
         
-            let msg = format!("{info}");
+            let msg = crate::scrub::scrub(&info.to_string());
+            eprintln!("{msg}");
+            let backtrace = std::backtrace::Backtrace::capture();
+            if backtrace.status() == std::backtrace::BacktraceStatus::Captured {
+                eprintln!("{backtrace}");
+            }
             _ = PANIC_MSG.set(Some(msg));
-        
-            hook(info)
         }));
 
         Self
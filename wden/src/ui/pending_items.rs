@@ -0,0 +1,270 @@
+use cursive::{
+    traits::{Resizable, Scrollable},
+    views::{Dialog, LinearLayout, SelectView, TextView},
+    Cursive,
+};
+
+use crate::{
+    bitwarden::api::ApiClient,
+    profile::{PendingItem, ProfileStore},
+};
+
+use super::{
+    confirm::confirm_destructive,
+    create_item::push_item,
+    data::{StatefulUserData, Unlocked},
+    sync::do_sync,
+    util::cursive_ext::CursiveExt,
+};
+
+/// A pending item, as shown in the review dialog
+struct PendingReview {
+    id: String,
+    name: String,
+    created: String,
+    /// Why the item should not be pushed without a look
+    conflict: Option<&'static str>,
+}
+
+/// Offers to push the items created in offline mode. Called after syncing
+/// the vault, so conflicts are checked against the current items.
+pub fn offer_pending_items(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    if ud.global_settings().offline {
+        return;
+    }
+    let pending = match ud.profile_store().load_pending_items() {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("Loading the pending items failed: {e:#}");
+            return;
+        }
+    };
+    if pending.is_empty() {
+        return;
+    }
+
+    let reviews: Vec<_> = pending.iter().map(|p| review(&ud, p)).collect();
+    show_review_dialog(siv, reviews);
+}
+
+fn review(ud: &StatefulUserData<Unlocked>, pending: &PendingItem) -> PendingReview {
+    let global_settings = ud.global_settings();
+    let organization_id = pending.collection.as_ref().map(|(org_id, _)| org_id);
+    let keys = match organization_id {
+        None => ud.decrypt_keys(),
+        Some(org_id) => ud.get_org_keys_for_vault().remove(org_id),
+    };
+    let mut review = PendingReview {
+        id: pending.id.clone(),
+        name: "<unknown>".to_string(),
+        created: global_settings.date_display.format(&pending.created),
+        conflict: None,
+    };
+    let Some(keys) = keys else {
+        review.conflict = Some("The organization of the item is not available anymore.");
+        return review;
+    };
+    review.name = pending.item.name.decrypt_to_string(&keys);
+
+    // The same item may have been added on another device, or pushed
+    // already if removing it from the queue failed
+    let name_taken = ud.vault_data().values().any(|ci| {
+        ci.organization_id.as_ref() == organization_id
+            && pending.item.data.same_type_as(&ci.data)
+            && ud
+                .get_keys_for_item(ci)
+                .is_some_and(|k| ci.name.decrypt_to_string(&k) == review.name)
+    });
+    if name_taken {
+        review.conflict = Some("The vault already has an item of the same type with this name.");
+    }
+    review
+}
+
+fn show_review_dialog(siv: &mut Cursive, reviews: Vec<PendingReview>) {
+    let count = reviews.len();
+    let without_conflicts: Vec<_> = reviews
+        .iter()
+        .filter(|r| r.conflict.is_none())
+        .map(|r| r.id.clone())
+        .collect();
+
+    let mut select = SelectView::new().on_submit(show_pending_item);
+    for r in reviews {
+        let label = match r.conflict {
+            Some(_) => format!("{} (conflict)", r.name),
+            None => r.name.clone(),
+        };
+        select.add_item(label, r);
+    }
+
+    let text = if count == 1 {
+        "1 item was created in offline mode.".to_string()
+    } else {
+        format!("{count} items were created in offline mode.")
+    };
+    let mut dialog = Dialog::around(
+        LinearLayout::vertical()
+            .child(TextView::new(format!(
+                "{text} Select an item to push or discard it. \
+                 Items with conflicts are only pushed one by one.\n"
+            )))
+            .child(select.scrollable().max_height(15)),
+    )
+    .title("Items created offline")
+    .dismiss_button("Later");
+    if !without_conflicts.is_empty() {
+        dialog.add_button("Push all without conflicts", move |siv| {
+            siv.pop_layer();
+            push_items(siv, without_conflicts.clone());
+        });
+    }
+    siv.add_layer(dialog.max_width(80));
+}
+
+fn show_pending_item(siv: &mut Cursive, review: &PendingReview) {
+    let mut text = format!("{}\nCreated offline: {}", review.name, review.created);
+    if let Some(conflict) = review.conflict {
+        text.push_str("\n\n");
+        text.push_str(conflict);
+    }
+
+    let push_id = review.id.clone();
+    let discard_id = review.id.clone();
+    let name = review.name.clone();
+    siv.add_layer(
+        Dialog::text(text)
+            .title("Item created offline")
+            .dismiss_button("Cancel")
+            .button("Discard", move |siv| {
+                let id = discard_id.clone();
+                confirm_destructive(
+                    siv,
+                    "Discard item",
+                    format!("Discard \"{name}\"? It has not been pushed to the server."),
+                    "Discard",
+                    move |siv| discard_item(siv, &id),
+                );
+            })
+            .button("Push", move |siv| {
+                // Close the item and the list
+                siv.pop_layer();
+                siv.pop_layer();
+                push_items(siv, vec![push_id.clone()]);
+            })
+            .max_width(80),
+    );
+}
+
+fn remove_pending(profile_store: &ProfileStore, ids: &[String]) -> anyhow::Result<()> {
+    let mut pending = profile_store.load_pending_items()?;
+    pending.retain(|p| !ids.contains(&p.id));
+    profile_store.store_pending_items(&pending)
+}
+
+fn discard_item(siv: &mut Cursive, id: &str) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let profile_store = ud.profile_store();
+    if let Err(e) = remove_pending(&profile_store, &[id.to_string()]) {
+        siv.add_layer(Dialog::info(format!("Discarding the item failed: {e:#}")));
+        return;
+    }
+    // Close the item and the list, and list the remaining items again
+    siv.pop_layer();
+    siv.pop_layer();
+    offer_pending_items(siv);
+}
+
+fn push_items(siv: &mut Cursive, ids: Vec<String>) {
+    // E.g. locked by the autolock while the dialog was open
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let profile_store = ud.profile_store();
+    let pending = match profile_store.load_pending_items() {
+        Ok(p) => p,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!(
+                "Loading the pending items failed: {e:#}"
+            )));
+            return;
+        }
+    };
+    let to_push: Vec<_> = pending
+        .into_iter()
+        .filter(|p| ids.contains(&p.id))
+        .collect();
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+
+    siv.add_layer(Dialog::text("Pushing..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            let mut results = Vec::with_capacity(to_push.len());
+            for p in &to_push {
+                let res = push_item(&client, &p.item, p.collection.as_ref()).await;
+                results.push((p.id.clone(), res));
+            }
+            Ok::<_, anyhow::Error>(results)
+        },
+        move |siv, res| {
+            siv.pop_layer();
+            let results = match res {
+                Ok(r) => r,
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Pushing the items failed: {e}")));
+                    return;
+                }
+            };
+
+            let mut pushed = vec![];
+            let mut errors = vec![];
+            for (id, res) in results {
+                match res {
+                    Ok(_) => pushed.push(id),
+                    Err(e) => errors.push(e.to_string()),
+                }
+            }
+            // If this fails, the pushed items are shown as conflicts
+            // after the sync
+            if let Err(e) = remove_pending(&profile_store, &pushed) {
+                log::warn!("Removing the pushed items from the queue failed: {e:#}");
+            }
+
+            if errors.is_empty() {
+                do_sync(siv, false);
+                return;
+            }
+            // The sync replaces all layers, so it waits for the errors
+            // to be dismissed
+            siv.add_layer(
+                Dialog::text(format!(
+                    "Pushing {} of the items failed. They are kept for later.\n\n{}",
+                    errors.len(),
+                    errors.join("\n")
+                ))
+                .title("Items created offline")
+                .button("OK", move |siv| {
+                    siv.pop_layer();
+                    if !pushed.is_empty() {
+                        do_sync(siv, false);
+                    }
+                })
+                .max_width(80),
+            );
+        },
+    );
+}
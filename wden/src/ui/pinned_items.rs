@@ -0,0 +1,74 @@
+use std::collections::BTreeSet;
+
+use cursive::{views::Dialog, Cursive};
+
+use crate::bitwarden::cipher::{Cipher, EncMacKeys};
+
+use super::{
+    data::{StatefulUserData, Unlocked},
+    util::cursive_ext::CursiveExt,
+    vault_table::{reload_vault, show_copy_notification},
+};
+
+/// Ids of the items pinned to the top of the vault table. The ids are
+/// stored in the profile as a JSON array, encrypted with the user key.
+pub fn pinned_item_ids(
+    ud: &StatefulUserData<Unlocked>,
+    user_keys: &EncMacKeys,
+) -> BTreeSet<String> {
+    let pinned_items = match ud.profile_store().load() {
+        Ok(d) => d.pinned_items,
+        Err(e) => {
+            log::warn!("Loading the pinned items failed: {e}");
+            return BTreeSet::new();
+        }
+    };
+    if let Cipher::Empty = pinned_items {
+        return BTreeSet::new();
+    }
+
+    pinned_items
+        .decrypt(user_keys)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_slice(&json)?))
+        .unwrap_or_else(|e| {
+            // E.g. after the account encryption key was rotated
+            log::warn!("Decrypting the pinned items failed: {e}");
+            BTreeSet::new()
+        })
+}
+
+/// Pins the item to the top of the vault table, or unpins it
+pub fn toggle_pinned_item(siv: &mut Cursive, item_id: &str) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let Some(user_keys) = ud.decrypt_keys() else {
+        return;
+    };
+    let mut ids = pinned_item_ids(&ud, &user_keys);
+    let pinned = ids.insert(item_id.to_string());
+    if !pinned {
+        ids.remove(item_id);
+    }
+    // Drop the items that have been deleted since
+    let vault_data = ud.vault_data();
+    ids.retain(|id| vault_data.contains_key(id));
+
+    let res = serde_json::to_vec(&ids)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(Cipher::encrypt(&json, &user_keys)?))
+        .and_then(|cipher| ud.profile_store().edit(|d| d.pinned_items = cipher));
+    if let Err(e) = res {
+        log::warn!("Storing the pinned items failed: {e}");
+        siv.add_layer(Dialog::info(format!(
+            "Storing the pinned items failed: {e}"
+        )));
+        return;
+    }
+
+    reload_vault(siv);
+    if pinned {
+        show_copy_notification(siv, "Item pinned to the top");
+    } else {
+        show_copy_notification(siv, "Item unpinned");
+    }
+}
@@ -0,0 +1,167 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use anyhow::Context;
+use cursive::{
+    traits::Nameable,
+    view::Margins,
+    views::{Dialog, EditView, LinearLayout, PaddedView, TextView},
+    Cursive,
+};
+
+use crate::bitwarden::{api::CipherItem, cipher::CipherError};
+
+use super::{
+    data::{StatefulUserData, Unlocked},
+    lock::master_password_checker,
+    util::cursive_ext::CursiveExt,
+};
+
+const VIEW_NAME_REPROMPT_PASSWORD: &str = "reprompt_password";
+
+/// Restrictions for an item, consulted by the copy and reveal actions.
+/// Items in the collections marked as sensitive in the profile have a
+/// shorter clipboard expiry, their passwords can't be revealed, and the
+/// master password is asked again before they are opened or their
+/// secrets copied. Items with master password reprompt enabled in
+/// Bitwarden also ask for the password.
+#[derive(Clone, Copy, Debug)]
+pub struct ItemPolicy {
+    sensitive: bool,
+    reprompt: bool,
+}
+
+impl ItemPolicy {
+    pub fn for_item(ud: &StatefulUserData<Unlocked>, item: &CipherItem) -> Self {
+        match ud.profile_store().load() {
+            Ok(d) => Self::with_sensitive_collections(item, &d.sensitive_collections),
+            Err(e) => {
+                // Don't relax the policy because of a broken profile file
+                log::warn!("Loading the sensitive collections failed: {e}");
+                ItemPolicy {
+                    sensitive: !item.collection_ids.is_empty(),
+                    reprompt: item.reprompt,
+                }
+            }
+        }
+    }
+
+    /// The policy of an item, given the collections marked as sensitive
+    /// in the profile. Used by the command line subcommands.
+    pub fn with_sensitive_collections(
+        item: &CipherItem,
+        sensitive_collections: &BTreeSet<String>,
+    ) -> Self {
+        ItemPolicy {
+            sensitive: item
+                .collection_ids
+                .iter()
+                .any(|id| sensitive_collections.contains(id)),
+            reprompt: item.reprompt,
+        }
+    }
+
+    /// The policy of the items in sensitive collections
+    pub fn sensitive() -> Self {
+        ItemPolicy {
+            sensitive: true,
+            reprompt: false,
+        }
+    }
+
+    pub fn for_item_id(ud: &StatefulUserData<Unlocked>, item_id: &str) -> Self {
+        ud.vault_data()
+            .get(item_id)
+            .map(|item| Self::for_item(ud, item))
+            .unwrap_or(ItemPolicy {
+                sensitive: false,
+                reprompt: false,
+            })
+    }
+
+    /// Seconds until copied secrets are cleared from the clipboard
+    pub fn clipboard_expiry(self) -> u64 {
+        if self.sensitive {
+            10
+        } else {
+            30
+        }
+    }
+
+    pub fn allows_reveal(self) -> bool {
+        !self.sensitive
+    }
+
+    pub fn requires_reprompt(self) -> bool {
+        self.sensitive || self.reprompt
+    }
+}
+
+/// Runs the action, after asking for the master password again if the
+/// policy requires it
+pub fn with_reprompt<F>(siv: &mut Cursive, policy: ItemPolicy, action: F)
+where
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    if !policy.requires_reprompt() {
+        action(siv);
+        return;
+    }
+
+    let action: Arc<dyn Fn(&mut Cursive) + Send + Sync> = Arc::new(action);
+    let action2 = action.clone();
+    let password_field = EditView::new()
+        .secret()
+        .on_submit(move |siv, _| submit_reprompt(siv, action.clone()))
+        .with_name(VIEW_NAME_REPROMPT_PASSWORD);
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Enter the master password again to continue:",
+                ))
+                .child(PaddedView::new(Margins::tb(1, 0), password_field)),
+        )
+        .title("Master password required")
+        .dismiss_button("Cancel")
+        .button("OK", move |siv| submit_reprompt(siv, action2.clone())),
+    );
+}
+
+fn submit_reprompt(siv: &mut Cursive, action: Arc<dyn Fn(&mut Cursive) + Send + Sync>) {
+    let password = siv
+        .call_on_name(VIEW_NAME_REPROMPT_PASSWORD, |view: &mut EditView| {
+            view.get_content()
+        })
+        .unwrap();
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let check = master_password_checker(&ud);
+
+    siv.pop_layer();
+    siv.add_layer(Dialog::text("Checking the master password..."));
+    siv.async_op(
+        async move {
+            tokio::task::spawn_blocking(move || check(&password))
+                .await
+                .context("The key derivation task failed")?
+        },
+        move |siv, res| {
+            siv.pop_layer();
+            match res {
+                Ok(()) => action(siv),
+                Err(e) => {
+                    log::warn!("Master password check failed: {e}");
+                    let msg = match e.downcast_ref::<CipherError>() {
+                        Some(CipherError::MacVerificationFailed(_)) => {
+                            "Invalid master password".to_owned()
+                        }
+                        _ => format!("Checking the master password failed: {e}"),
+                    };
+                    siv.add_layer(Dialog::info(msg));
+                }
+            }
+        },
+    );
+}
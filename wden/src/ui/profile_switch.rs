@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use cursive::{
+    traits::Scrollable,
+    views::{Dialog, SelectView},
+    Cursive,
+};
+
+use crate::profile::{ProfileOverrides, ProfileStore};
+
+use super::{
+    data::UserData, focus_lock, launch::load_profile, login::login_dialog, session,
+    util::cursive_ext::CursiveExt,
+};
+
+/// Names of all stored profiles
+pub fn profile_names() -> Vec<String> {
+    let mut names: Vec<_> = ProfileStore::get_all_profiles()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(file_name, _)| file_name.trim_end_matches(".json").to_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Shows the list of the other profiles. Selecting one replaces the
+/// login dialog with the login dialog of that profile.
+pub fn show_profile_switcher(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_logged_out_state() else {
+        return;
+    };
+    let current = ud.global_settings().profile.clone();
+
+    let mut select = SelectView::new();
+    for name in profile_names().into_iter().filter(|n| *n != current) {
+        select.add_item(name.clone(), name);
+    }
+    if select.is_empty() {
+        siv.add_layer(Dialog::info("There are no other profiles"));
+        return;
+    }
+
+    select.set_on_submit(|siv, name: &String| {
+        siv.pop_layer();
+        switch_profile(siv, name.clone());
+    });
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Switch profile")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn switch_profile(siv: &mut Cursive, profile: String) {
+    let Some(ud) = siv.get_user_data().with_logged_out_state() else {
+        return;
+    };
+    let current = ud.global_settings();
+    let autolocker = ud.autolocker();
+
+    // --allow-insecure-http is checked before the UI is started, so
+    // it can't be given for the other profiles
+    let server_configuration = ProfileStore::new(&profile)
+        .load()
+        .map(|p| p.server_configuration)
+        .unwrap_or_default();
+    if server_configuration.uses_insecure_http()
+        && !current.server_configuration.uses_insecure_http()
+    {
+        siv.add_layer(Dialog::info(format!(
+            "Profile {profile} uses an unencrypted HTTP server. To use it, \
+                start wden with --profile {profile} --allow-insecure-http."
+        )));
+        return;
+    }
+
+    let (global_settings, profile_data, profile_store) = load_profile(
+        profile,
+        ProfileOverrides::default(),
+        current.accept_invalid_certs,
+        current.always_refresh_token_on_sync,
+        false,
+    );
+    let global_settings = Arc::new(global_settings);
+    log::info!("Switched to profile {}", global_settings.profile);
+
+    autolocker
+        .lock()
+        .unwrap()
+        .set_autolock_duration(global_settings.autolock_duration);
+    if let Some(delay) = global_settings.focus_loss_lock_delay {
+        focus_lock::start_focus_lock_monitor(
+            siv.cb_sink().clone(),
+            global_settings.profile.clone(),
+            delay,
+        );
+    }
+
+    siv.set_user_data(UserData::new(
+        global_settings.clone(),
+        Arc::new(profile_store),
+        autolocker,
+    ));
+    siv.clear_layers();
+    if !session::restore_session(siv) {
+        siv.add_layer(login_dialog(
+            &global_settings,
+            profile_data.saved_email,
+            profile_data.encrypted_api_key.is_some(),
+            false,
+        ));
+    }
+}
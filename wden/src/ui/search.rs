@@ -1,61 +1,220 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use simsearch::SimSearch;
+use simsearch::{SearchOptions, SimSearch};
+use zeroize::Zeroizing;
 
-use crate::bitwarden::{self, api::CipherData};
+use crate::bitwarden::{
+    self,
+    api::{CipherData, UriMatchType},
+    cipher::{Cipher, DecryptBuffer},
+};
 
 use super::data::{StatefulUserData, Unlocked};
 
-pub fn search_items(term: &str, simsearch: &SimSearch<String>) -> Option<Vec<String>> {
+/// Item field that matched a search term
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchField {
+    Name,
+    Username,
+    Uri,
+}
+
+/// Words match a search term word when their Jaro-Winkler similarity
+/// is above this. The default of simsearch.
+const MATCH_THRESHOLD: f64 = 0.8;
+
+pub struct SearchIndex {
+    combined: SimSearch<String>,
+    // Per-field indexes, only used to tell which field matched.
+    // In priority order.
+    fields: Vec<(SearchField, SimSearch<String>)>,
+    // The names and usernames, for finding the positions of the matches
+    shown_fields: HashMap<String, ShownFields>,
+}
+
+#[derive(Default)]
+struct ShownFields {
+    name: Zeroizing<String>,
+    username: Zeroizing<String>,
+}
+
+/// An item that matches the search term
+pub struct SearchMatch {
+    pub id: String,
+    /// The first field that matched on its own. None if the term only
+    /// matches the fields combined.
+    pub field: Option<SearchField>,
+    /// Byte ranges of the matching words in the name and the username
+    pub name_matches: Vec<Range<usize>>,
+    pub username_matches: Vec<Range<usize>>,
+}
+
+/// Returns the matching items, in the order of relevance
+pub fn search_items(term: &str, index: &SearchIndex) -> Option<Vec<SearchMatch>> {
     if term.is_empty() {
         return None;
     }
 
-    Some(simsearch.search(term))
+    let field_matches: Vec<(SearchField, HashSet<String>)> = index
+        .fields
+        .iter()
+        .map(|(field, ss)| (*field, ss.search(term).into_iter().collect()))
+        .collect();
+
+    let term_words: Vec<String> = term.split_whitespace().map(str::to_lowercase).collect();
+
+    let results = index
+        .combined
+        .search(term)
+        .into_iter()
+        .map(|id| {
+            let field = field_matches
+                .iter()
+                .find(|(_, ids)| ids.contains(&id))
+                .map(|(field, _)| *field);
+            let (name_matches, username_matches) = match index.shown_fields.get(&id) {
+                Some(shown) => (
+                    match_ranges(&shown.name, &term_words),
+                    match_ranges(&shown.username, &term_words),
+                ),
+                None => Default::default(),
+            };
+            SearchMatch {
+                id,
+                field,
+                name_matches,
+                username_matches,
+            }
+        })
+        .collect();
+
+    Some(results)
 }
 
-pub fn get_search_index(ud: &StatefulUserData<Unlocked>) -> SimSearch<String> {
-    let mut ss = SimSearch::new();
+/// Byte ranges of the words of `text` that match one of the (lowercase)
+/// term words. Words are matched the same way as in the search index:
+/// split at whitespace and compared case-insensitively by similarity.
+fn match_ranges(text: &str, term_words: &[String]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let word_len = rest[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(rest.len() - start);
+        let word = rest[start..start + word_len].to_lowercase();
+        if term_words
+            .iter()
+            .any(|t| strsim::jaro_winkler(&word, t) > MATCH_THRESHOLD)
+        {
+            let offset = text.len() - rest.len();
+            ranges.push(offset + start..offset + start + word_len);
+        }
+        rest = &rest[start + word_len..];
+    }
+    ranges
+}
+
+pub fn get_search_index(ud: &StatefulUserData<Unlocked>) -> SearchIndex {
+    let options = || SearchOptions::new().threshold(MATCH_THRESHOLD);
+    let mut index = SearchIndex {
+        combined: SimSearch::new_with(options()),
+        fields: [SearchField::Name, SearchField::Username, SearchField::Uri]
+            .into_iter()
+            .map(|field| (field, SimSearch::new_with(options())))
+            .collect(),
+        shown_fields: HashMap::new(),
+    };
 
     if let Some(tokenized_rows) = get_tokenized_rows(ud) {
         for (k, tokens) in tokenized_rows {
             // SimSearch will still tokenize (split) each of the tokens
             // that are passed here. Passing them this way just avoids
             // concatenating them into a string.
-            let tokens: Vec<_> = tokens.iter().map(|s| s.as_str()).collect();
-            ss.insert_tokens(k.clone(), &tokens);
+            let all_tokens: Vec<_> = tokens.iter().map(|(_, s)| s.as_str()).collect();
+            index.combined.insert_tokens(k.clone(), &all_tokens);
+
+            let mut shown = ShownFields::default();
+            for (field, token) in tokens {
+                if let Some((_, ss)) = index.fields.iter_mut().find(|(f, _)| *f == field) {
+                    ss.insert_tokens(k.clone(), &[token.as_str()]);
+                }
+                match field {
+                    SearchField::Name => shown.name = token,
+                    SearchField::Username => shown.username = token,
+                    SearchField::Uri => {}
+                }
+            }
+            index.shown_fields.insert(k, shown);
         }
     }
 
-    ss
+    index
 }
 
-fn get_tokenized_rows(ud: &StatefulUserData<Unlocked>) -> Option<HashMap<String, Vec<String>>> {
+fn get_tokenized_rows(
+    ud: &StatefulUserData<Unlocked>,
+) -> Option<HashMap<String, Vec<(SearchField, Zeroizing<String>)>>> {
     let vd = ud.vault_data();
     let org_keys = ud.get_org_keys_for_vault();
     let user_keys = ud.decrypt_keys()?;
 
     let res = vd
         .par_iter()
-        .filter_map(|(k, v)| {
+        .map_init(DecryptBuffer::default, |buf, (k, v)| {
             // Get appropriate keys for this item
             let item_keys =
                 bitwarden::keys::resolve_item_keys(v, (&user_keys).into(), |oid, _uk| {
                     org_keys.get(oid).map(|k| k.into())
                 })?;
 
+            // Copied from the decryption buffer into zeroizing strings
+            let mut decrypt = |cipher: &Cipher| {
+                Zeroizing::new(cipher.decrypt_to_str_buffered(&item_keys, buf).to_owned())
+            };
+
             // All items: name
-            let mut tokens = vec![v.name.decrypt_to_string(&item_keys)];
+            let mut tokens = vec![(SearchField::Name, decrypt(&v.name))];
             // Login items: url and username
             if let CipherData::Login(l) = &v.data {
-                tokens.push(l.username.decrypt_to_string(&item_keys));
-                tokens.push(l.uri.decrypt_to_string(&item_keys));
+                tokens.push((SearchField::Username, decrypt(&l.username)));
+                // URIs that are set to never match are not searched
+                for (uri, _) in l
+                    .uris()
+                    .into_iter()
+                    .filter(|(_, m)| *m != Some(UriMatchType::Never))
+                {
+                    tokens.push((SearchField::Uri, decrypt(uri)));
+                }
             };
 
             Some((k.clone(), tokens))
         })
+        .flatten()
         .collect();
 
     Some(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::match_ranges;
+
+    fn ranges(text: &str, term: &str) -> Vec<std::ops::Range<usize>> {
+        let words: Vec<String> = term.split_whitespace().map(str::to_lowercase).collect();
+        match_ranges(text, &words)
+    }
+
+    #[test]
+    fn test_match_ranges() {
+        assert_eq!(ranges("GitHub work", "github"), vec![0..6]);
+        assert_eq!(ranges("My GitHub", "GTIHUB"), vec![3..9]);
+        assert_eq!(ranges("GitHub  work", "work git"), vec![0..6, 8..12]);
+        assert_eq!(ranges("Pörssi", "pörssi"), vec![0..7]);
+        assert!(ranges("short", "much longer").is_empty());
+        assert!(ranges("", "term").is_empty());
+    }
+}
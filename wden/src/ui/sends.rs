@@ -0,0 +1,275 @@
+use std::time::Duration;
+
+use cursive::{
+    traits::{Nameable, Resizable, Scrollable},
+    views::{Checkbox, Dialog, EditView, LinearLayout, SelectView, TextArea, TextView},
+    Cursive,
+};
+use zeroize::Zeroizing;
+
+use crate::bitwarden::{
+    api::ApiClient,
+    send::{access_url, NewTextSend, SendItem},
+};
+
+use super::{
+    confirm::confirm_destructive,
+    data::{StatefulUserData, Unlocked},
+    util::cursive_ext::CursiveExt,
+    vault_table::show_copy_notification,
+};
+
+const VIEW_NAME_SEND_LIST: &str = "send_list";
+const VIEW_NAME_SEND_NAME: &str = "send_name";
+const VIEW_NAME_SEND_TEXT: &str = "send_text";
+const VIEW_NAME_SEND_DAYS: &str = "send_days";
+const VIEW_NAME_SEND_HIDDEN: &str = "send_hidden";
+
+const DEFAULT_DELETION_DAYS: &str = "7";
+const MAX_DELETION_DAYS: u64 = 31;
+const LINK_CLIPBOARD_EXPIRY: u64 = 30;
+
+fn api_client(ud: &StatefulUserData<Unlocked>) -> anyhow::Result<ApiClient> {
+    let global_settings = ud.global_settings();
+    ApiClient::with_token(
+        &global_settings.server_configuration,
+        &global_settings.device_id,
+        global_settings.device_class,
+        &ud.token().access_token,
+        global_settings.accept_invalid_certs,
+        &global_settings.pinned_spki_hashes,
+    )
+}
+
+/// Lists the text Sends of the user. Selecting one copies its link.
+pub fn show_sends(siv: &mut Cursive) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    if ud.global_settings().offline {
+        siv.add_layer(Dialog::info("Sends are not available in offline mode."));
+        return;
+    }
+    let client = api_client(&ud);
+
+    siv.add_layer(Dialog::text("Loading Sends..."));
+    siv.async_op(async move { client?.list_sends().await }, |siv, res| {
+        siv.pop_layer();
+        match res {
+            Ok(sends) => show_send_list(siv, sends),
+            Err(e) => siv.add_layer(Dialog::info(format!("Loading the Sends failed: {e}"))),
+        }
+    });
+}
+
+fn show_send_list(siv: &mut Cursive, sends: Vec<SendItem>) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let Some(user_keys) = ud.decrypt_keys() else {
+        return;
+    };
+    let global_settings = ud.global_settings();
+    let server = &global_settings.server_configuration;
+
+    let mut rows: Vec<_> = sends
+        .iter()
+        .filter(|s| s.is_text())
+        .filter_map(|s| {
+            let (key_material, keys) = s
+                .decrypt_keys(&user_keys)
+                .inspect_err(|e| log::warn!("Decrypting the key of Send {} failed: {e}", s.id))
+                .ok()?;
+            let name = s.name.decrypt_to_string(&keys);
+            let deletion_date = global_settings.date_display.format(&s.deletion_date);
+            let label = format!("{name} (deleted {deletion_date}, {} views)", s.access_count);
+            let url = access_url(server, &s.access_id, &key_material)?;
+            Some((label, (s.id.clone(), Zeroizing::new(url))))
+        })
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut list = SelectView::new().on_submit(|siv, (_, url): &(String, Zeroizing<String>)| {
+        super::clipboard::clip_expiring_string(url.to_string(), LINK_CLIPBOARD_EXPIRY);
+        show_copy_notification(siv, "Send link copied");
+    });
+    if rows.is_empty() {
+        list.add_item("No text Sends", (String::new(), Zeroizing::default()));
+        list.set_enabled(false);
+    }
+    list.add_all(rows);
+
+    siv.add_layer(
+        Dialog::around(list.with_name(VIEW_NAME_SEND_LIST).scrollable())
+            .title("Sends")
+            .button("New", |siv| show_new_send_dialog(siv, "", ""))
+            .button("Delete", confirm_delete_send)
+            .dismiss_button("Close")
+            .min_width(50),
+    );
+}
+
+fn confirm_delete_send(siv: &mut Cursive) {
+    let selected = siv
+        .call_on_name(
+            VIEW_NAME_SEND_LIST,
+            |list: &mut SelectView<(String, Zeroizing<String>)>| {
+                list.selection().filter(|_| list.is_enabled())
+            },
+        )
+        .flatten();
+    let Some(selected) = selected else {
+        return;
+    };
+    let id = selected.0.clone();
+
+    confirm_destructive(
+        siv,
+        "Delete Send",
+        "Delete the selected Send? Its link stops working.".to_string(),
+        "Delete",
+        move |siv| delete_send(siv, id.clone()),
+    );
+}
+
+fn delete_send(siv: &mut Cursive, id: String) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let client = api_client(&ud);
+
+    siv.add_layer(Dialog::text("Deleting..."));
+    siv.async_op(async move { client?.delete_send(&id).await }, |siv, res| {
+        siv.pop_layer();
+        match res {
+            Ok(()) => {
+                // Reload the list
+                siv.pop_layer();
+                show_sends(siv);
+            }
+            Err(e) => siv.add_layer(Dialog::info(format!("Deleting the Send failed: {e}"))),
+        }
+    });
+}
+
+/// Shows a form for creating a text Send, filled with the given name and
+/// text, e.g. from a secure note
+pub fn show_new_send_dialog(siv: &mut Cursive, name: &str, text: &str) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    if ud.global_settings().offline {
+        siv.add_layer(Dialog::info("Sends can't be created in offline mode."));
+        return;
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Name"))
+        .child(
+            EditView::new()
+                .content(name)
+                .with_name(VIEW_NAME_SEND_NAME)
+                .fixed_width(50),
+        )
+        .child(TextView::new("Text"))
+        .child(
+            TextArea::new()
+                .content(text)
+                .with_name(VIEW_NAME_SEND_TEXT)
+                .fixed_width(50)
+                .min_height(5),
+        )
+        .child(TextView::new(format!(
+            "Days until deletion (1-{MAX_DELETION_DAYS})"
+        )))
+        .child(
+            EditView::new()
+                .content(DEFAULT_DELETION_DAYS)
+                .with_name(VIEW_NAME_SEND_DAYS)
+                .fixed_width(5),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(Checkbox::new().with_name(VIEW_NAME_SEND_HIDDEN))
+                .child(TextView::new(" Hide the text by default")),
+        );
+
+    siv.add_layer(
+        Dialog::around(layout.scrollable())
+            .title("New text Send")
+            .dismiss_button("Cancel")
+            .button("Create", create_send),
+    );
+}
+
+fn create_send(siv: &mut Cursive) {
+    let name = siv
+        .call_on_name(VIEW_NAME_SEND_NAME, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let text = siv
+        .call_on_name(VIEW_NAME_SEND_TEXT, |v: &mut TextArea| {
+            Zeroizing::new(v.get_content().to_string())
+        })
+        .unwrap();
+    let days = siv
+        .call_on_name(VIEW_NAME_SEND_DAYS, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let hidden = siv
+        .call_on_name(VIEW_NAME_SEND_HIDDEN, |v: &mut Checkbox| v.is_checked())
+        .unwrap();
+
+    if name.trim().is_empty() {
+        siv.add_layer(Dialog::info("The name is required"));
+        return;
+    }
+    let days = match days.trim().parse::<u64>() {
+        Ok(d) if (1..=MAX_DELETION_DAYS).contains(&d) => d,
+        _ => {
+            siv.add_layer(Dialog::info(format!(
+                "The days until deletion must be a number from 1 to {MAX_DELETION_DAYS}"
+            )));
+            return;
+        }
+    };
+
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let Some(user_keys) = ud.decrypt_keys() else {
+        siv.add_layer(Dialog::info("The encryption keys are not available"));
+        return;
+    };
+    let lifetime = Duration::from_secs(days * 24 * 60 * 60);
+    let (new_send, key_material) =
+        match NewTextSend::encrypt(name.trim(), &text, hidden, lifetime, &user_keys) {
+            Ok(s) => s,
+            Err(e) => {
+                siv.add_layer(Dialog::info(format!("Encrypting the Send failed: {e}")));
+                return;
+            }
+        };
+    let server = ud.global_settings().server_configuration.clone();
+    let client = api_client(&ud);
+
+    siv.add_layer(Dialog::text("Creating the Send..."));
+    siv.async_op(
+        async move { client?.create_send(&new_send).await },
+        move |siv, res| {
+            siv.pop_layer();
+            let send = match res {
+                Ok(send) => send,
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!("Creating the Send failed: {e}")));
+                    return;
+                }
+            };
+            // Close the form
+            siv.pop_layer();
+            match access_url(&server, &send.access_id, &key_material) {
+                Some(url) => {
+                    super::clipboard::clip_expiring_string(url.clone(), LINK_CLIPBOARD_EXPIRY);
+                    siv.add_layer(
+                        Dialog::around(TextView::new(format!(
+                            "The link was copied to the clipboard:\n\n{url}"
+                        )))
+                        .title("Send created")
+                        .dismiss_button("OK"),
+                    );
+                }
+                None => siv.add_layer(Dialog::info(
+                    "The Send was created, but its link can't be determined from the server URLs.",
+                )),
+            }
+        },
+    );
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use cursive::Cursive;
+
+use crate::bitwarden::api::TokenResponseSuccess;
+
+use super::{
+    data::{LoggedIn, StatefulUserData},
+    lock::{unlock_dialog, unlock_from_keystore},
+    util::{
+        cursive_ext::CursiveExt,
+        keystore::{self, RememberedSession},
+    },
+};
+
+/// Stores the session in the platform keystore, when the profile
+/// remembers sessions. API key logins are not remembered, as they don't
+/// get a refresh token. Failures are only logged: the next start then
+/// just asks to log in.
+pub fn remember_session(ud: &StatefulUserData<LoggedIn>) {
+    let global_settings = ud.global_settings();
+    if !global_settings.remember_session || ud.api_key().is_some() {
+        return;
+    }
+    let token = ud.token();
+    let Some(refresh_token) = token.refresh_token.clone() else {
+        return;
+    };
+
+    let session = RememberedSession {
+        email: ud.email().to_string(),
+        pbkdf: (*ud.pbkdf()).clone(),
+        key: token.key.clone(),
+        private_key: token.private_key.clone(),
+        refresh_token,
+    };
+    let store_res =
+        keystore::get_platform_keystore().store_session(&global_settings.profile, &session);
+    if let Err(e) = store_res {
+        log::warn!("Storing the session in the keystore failed: {e:#}");
+    }
+}
+
+pub fn forget_session(profile: &str) {
+    if let Err(e) = keystore::get_platform_keystore().delete_session(profile) {
+        log::warn!("Removing the session from the keystore failed: {e:#}");
+    }
+}
+
+/// Starts from the session remembered for the profile, instead of the
+/// login dialog: the vault is locked, and synced once it's unlocked.
+/// Returns false if there is no remembered session.
+pub fn restore_session(siv: &mut Cursive) -> bool {
+    let Some(ud) = siv.get_user_data().with_logged_out_state() else {
+        return false;
+    };
+    let global_settings = ud.global_settings();
+    if !global_settings.remember_session {
+        return false;
+    }
+    let session = match keystore::get_platform_keystore().load_session(&global_settings.profile) {
+        Ok(Some(session)) => session,
+        Ok(None) => return false,
+        Err(e) => {
+            log::warn!("Loading the session from the keystore failed: {e:#}");
+            return false;
+        }
+    };
+
+    let token =
+        TokenResponseSuccess::remembered(session.key, session.private_key, session.refresh_token);
+    let ud = ud.into_remembered_locked(
+        Arc::new(session.email),
+        Arc::new(session.pbkdf),
+        Arc::new(token),
+    );
+    log::info!("Restored the remembered session");
+
+    let email = ud.email();
+    if !(global_settings.keyring_unlock && unlock_from_keystore(siv)) {
+        siv.add_layer(unlock_dialog(&global_settings.profile, &email));
+    }
+    true
+}
@@ -0,0 +1,114 @@
+use cursive::{CbSink, Cursive};
+use futures_util::StreamExt;
+use zbus::{
+    proxy,
+    zvariant::{OwnedFd, OwnedObjectPath},
+    Connection,
+};
+
+use super::{lock::lock_vault, util::cursive_ext::CursiveExt};
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn get_session_by_pid(&self, pid: u32) -> zbus::Result<OwnedObjectPath>;
+
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait Session {
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+}
+
+/// Locks the vault when the system is about to suspend or the session is
+/// locked, as told by systemd-logind over the system D-Bus. Does nothing
+/// if logind is not available.
+///
+/// A delay inhibitor is held while the vault could be unlocked, so that
+/// logind waits for the vault to be locked before suspending.
+pub fn start_session_lock_monitor(cb_sink: CbSink) {
+    tokio::spawn(async move {
+        if let Err(e) = monitor(cb_sink).await {
+            log::warn!("Listening to logind signals failed, not locking on suspend: {e}");
+        }
+    });
+}
+
+async fn monitor(cb_sink: CbSink) -> zbus::Result<()> {
+    let connection = Connection::system().await?;
+    let manager = ManagerProxy::new(&connection).await?;
+    let mut sleep = manager.receive_prepare_for_sleep().await?;
+    let mut inhibitor = take_sleep_inhibitor(&manager).await;
+
+    // wden may run outside of a logind session, e.g. in a container
+    let mut session_lock = match manager.get_session_by_pid(std::process::id()).await {
+        Ok(path) => {
+            let session = SessionProxy::builder(&connection)
+                .path(path)?
+                .build()
+                .await?;
+            Some(session.receive_lock().await?)
+        }
+        Err(e) => {
+            log::info!("No logind session found, locking only on suspend: {e}");
+            None
+        }
+    };
+
+    loop {
+        let (reason, release) = tokio::select! {
+            signal = sleep.next() => match signal {
+                Some(signal) if signal.args()?.start => ("The system is suspending", true),
+                Some(_) => {
+                    // Resumed, delay the next suspend again
+                    if inhibitor.is_none() {
+                        inhibitor = take_sleep_inhibitor(&manager).await;
+                    }
+                    continue;
+                }
+                None => return Ok(()),
+            },
+            Some(_) = async { session_lock.as_mut()?.next().await } => {
+                ("The session was locked", false)
+            }
+        };
+        // Released after locking, so that the suspend can proceed
+        let suspend_inhibitor = if release { inhibitor.take() } else { None };
+
+        let lock = move |siv: &mut Cursive| {
+            if siv.get_user_data().with_unlocked_state().is_some() {
+                log::info!("{reason}, locking");
+                lock_vault(siv);
+            }
+            drop(suspend_inhibitor);
+        };
+        if cb_sink.send(Box::new(lock)).is_err() {
+            // Cursive has stopped
+            return Ok(());
+        }
+    }
+}
+
+async fn take_sleep_inhibitor(manager: &ManagerProxy<'_>) -> Option<OwnedFd> {
+    match manager
+        .inhibit("sleep", "wden", "lock vault", "delay")
+        .await
+    {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            log::info!("Taking the sleep inhibitor failed, not delaying suspend: {e}");
+            None
+        }
+    }
+}
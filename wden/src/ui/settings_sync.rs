@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use cursive::{views::Dialog, Cursive};
+
+use crate::{
+    bitwarden::{
+        api::{ApiClient, CipherData, CipherItem},
+        cipher::{Cipher, EncMacKeys},
+        keys::resolve_item_keys,
+    },
+    profile::{
+        self, settings_revision_now, SettingsNote, SettingsSyncAction, SyncedSettings,
+        SETTINGS_ITEM_NAME,
+    },
+};
+
+use super::util::cursive_ext::CursiveExt;
+
+#[derive(Clone)]
+struct SettingsItem {
+    id: String,
+    revision_date: Option<String>,
+    note: SettingsNote,
+}
+
+/// Pulls the settings from the vault, or pushes them there, if settings
+/// sync is enabled for the profile. Called after syncing the vault.
+pub fn sync_settings(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    if !ud.global_settings().settings_sync {
+        return;
+    }
+    let Some(user_keys) = ud.decrypt_keys() else {
+        return;
+    };
+
+    let profile_data = match ud.profile_store().load() {
+        Ok(d) => d,
+        Err(e) => {
+            log::warn!("Loading profile for settings sync failed: {e}");
+            return;
+        }
+    };
+    let remote = find_settings_item(&ud.vault_data(), &user_keys);
+
+    let action = profile::resolve_settings_sync(
+        profile_data.settings_revision,
+        profile_data.settings_synced_revision,
+        remote.as_ref().map(|r| &r.note),
+    );
+    log::info!("Settings sync: {action:?}");
+
+    match (action, remote) {
+        (SettingsSyncAction::UpToDate, _) => {}
+        (SettingsSyncAction::Pull, Some(remote)) => pull_settings(siv, &remote.note),
+        (SettingsSyncAction::Push, remote) => push_settings(siv, remote),
+        (SettingsSyncAction::Conflict, Some(remote)) => show_conflict_dialog(siv, remote),
+        (_, None) => {}
+    }
+}
+
+fn find_settings_item(
+    vault_data: &HashMap<String, CipherItem>,
+    user_keys: &EncMacKeys,
+) -> Option<SettingsItem> {
+    vault_data
+        .values()
+        .filter(|ci| ci.organization_id.is_none() && matches!(ci.data, CipherData::SecureNote))
+        .find_map(|ci| {
+            let item_keys = resolve_item_keys(ci, user_keys.into(), |_, _| None)?;
+            if ci.name.decrypt_to_string(&item_keys) != SETTINGS_ITEM_NAME {
+                return None;
+            }
+
+            let note = serde_json::from_str(&ci.notes.decrypt_to_string(&item_keys))
+                .inspect_err(|e| log::warn!("Parsing settings note failed: {e}"))
+                .ok()?;
+            Some(SettingsItem {
+                id: ci.id.clone(),
+                revision_date: ci.revision_date.clone(),
+                note,
+            })
+        })
+}
+
+fn pull_settings(siv: &mut Cursive, note: &SettingsNote) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+
+    let res = ud.profile_store().edit(|d| {
+        note.settings.apply(d);
+        d.settings_revision = note.revision;
+        d.settings_synced_revision = note.revision;
+    });
+    if let Err(e) = res {
+        log::warn!("Storing settings from the vault failed: {e}");
+        return;
+    }
+
+    // The autolock duration can be changed on the fly, other
+    // settings take effect on the next launch
+    ud.autolocker()
+        .lock()
+        .unwrap()
+        .set_autolock_duration(note.settings.autolock_duration);
+    log::info!("Applied settings from the vault");
+}
+
+fn push_settings(siv: &mut Cursive, remote: Option<SettingsItem>) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let global_settings = ud.global_settings();
+    let profile_store = ud.profile_store();
+    let token = ud.token();
+    let Some(user_keys) = ud.decrypt_keys() else {
+        return;
+    };
+    let profile_data = match profile_store.load() {
+        Ok(d) => d,
+        Err(e) => {
+            log::warn!("Loading profile for settings sync failed: {e}");
+            return;
+        }
+    };
+
+    let revision = match profile_data.settings_revision {
+        0 => settings_revision_now(),
+        r => r,
+    };
+    let note = SettingsNote {
+        revision,
+        settings: SyncedSettings::from_profile(&profile_data),
+    };
+
+    let encrypted = serde_json::to_vec(&note)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| {
+            Ok((
+                Cipher::encrypt(SETTINGS_ITEM_NAME.as_bytes(), &user_keys)?,
+                Cipher::encrypt(&json, &user_keys)?,
+            ))
+        });
+    let (name, notes) = match encrypted {
+        Ok(e) => e,
+        Err(e) => {
+            log::warn!("Encrypting settings failed: {e}");
+            return;
+        }
+    };
+
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+
+            match remote {
+                Some(item) => {
+                    client
+                        .update_secure_note(&item.id, &name, &notes, item.revision_date.as_deref())
+                        .await?
+                }
+                None => {
+                    client.create_secure_note(&name, &notes).await?;
+                }
+            }
+
+            profile_store.edit(|d| {
+                d.settings_revision = revision;
+                d.settings_synced_revision = revision;
+            })
+        },
+        |_, res| match res {
+            Ok(()) => log::info!("Stored settings to the vault"),
+            Err(e) => log::warn!("Storing settings to the vault failed: {e}"),
+        },
+    );
+}
+
+fn show_conflict_dialog(siv: &mut Cursive, remote: SettingsItem) {
+    let remote2 = remote.clone();
+    let dialog = Dialog::text(
+        "The settings have been changed both on this machine and in the vault \
+        since they were last synced.",
+    )
+    .title("Settings sync conflict")
+    .button("Use vault settings", move |siv| {
+        siv.pop_layer();
+        pull_settings(siv, &remote.note);
+    })
+    .button("Keep local settings", move |siv| {
+        siv.pop_layer();
+        push_settings(siv, Some(remote2.clone()));
+    })
+    .dismiss_button("Decide later");
+
+    siv.add_layer(dialog);
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use cursive::{
+    traits::Nameable,
+    views::{Dialog, LayerPosition},
+    Cursive,
+};
+
+use crate::bitwarden::api::Endpoint;
+
+use super::util::cursive_ext::{CursiveCallbackExt, CursiveExt};
+
+const VIEW_NAME_SLOW_RESPONSE_HINT: &str = "slow_response_hint";
+const HINT_DURATION: Duration = Duration::from_secs(4);
+
+/// Returns a callback for [`crate::bitwarden::api::ApiClient::on_slow_response`].
+/// It stores a hint that [`show_slow_response_hint`] shows later: adding a
+/// layer while e.g. a sync is running would break the layer handling of
+/// the operation.
+pub fn slow_response_callback(
+    siv: &Cursive,
+) -> impl Fn(Endpoint, Duration) + Send + Sync + 'static {
+    let cb_sink = siv.cb_sink().clone();
+    move |endpoint, duration| {
+        let hint = format!(
+            "Slow response: {endpoint} took {:.1}s — {}",
+            duration.as_secs_f32(),
+            endpoint.slow_reason()
+        );
+        cb_sink.send_msg(Box::new(move |siv| {
+            siv.get_user_data().set_slow_response_hint(hint)
+        }));
+    }
+}
+
+/// Shows the stored slow response hint, if any. The hint closes by itself.
+pub fn show_slow_response_hint(siv: &mut Cursive) {
+    let Some(hint) = siv.get_user_data().take_slow_response_hint() else {
+        return;
+    };
+    siv.add_layer(Dialog::info(hint).with_name(VIEW_NAME_SLOW_RESPONSE_HINT));
+
+    let cb = siv.cb_sink().clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(HINT_DURATION).await;
+        cb.send_msg(Box::new(|siv| {
+            let sc = siv.screen_mut();
+            if let Some(LayerPosition::FromBack(l)) =
+                sc.find_layer_from_name(VIEW_NAME_SLOW_RESPONSE_HINT)
+            {
+                if l == sc.len() - 1 {
+                    siv.pop_layer();
+                }
+            }
+        }));
+    });
+}
@@ -1,12 +1,31 @@
-use std::sync::Arc;
+use std::time::Instant;
 
 use cursive::{views::Dialog, Cursive};
 
-use crate::{bitwarden::api::ApiClient, ui::login};
+use crate::{
+    bitwarden::api::{ApiClient, SyncResponse},
+    profile::{record_metric, MetricKind, VaultCache},
+    ui::login,
+};
 
-use super::{util::cursive_ext::CursiveExt, vault_table::show_vault};
+use super::{
+    alert::alert,
+    data::{index_by_id, LoggedIn, StatefulUserData},
+    notifications::start_notifications,
+    pending_items::offer_pending_items,
+    settings_sync::sync_settings,
+    slow_response::{show_slow_response_hint, slow_response_callback},
+    util::cursive_ext::CursiveExt,
+    vault_diff::offer_vault_changes,
+    vault_table::{self, show_vault},
+};
 
 pub fn do_sync(cursive: &mut Cursive, just_refreshed_token: bool) {
+    // Keep the selected item and scroll offset for the new vault view
+    if let Some(position) = vault_table::get_position(cursive) {
+        cursive.get_user_data().store_vault_position(position);
+    }
+
     // Remove all layers first
     cursive.clear_layers();
     cursive.add_layer(Dialog::text("Syncing..."));
@@ -39,14 +58,18 @@ pub fn do_sync(cursive: &mut Cursive, just_refreshed_token: bool) {
     if !just_refreshed_token && (should_refresh || global_settings.always_refresh_token_on_sync) {
         let _ = user_data.into_refreshing();
         let is_api_key_login = api_key.is_some();
+        let on_slow_response = slow_response_callback(cursive);
         cursive.async_op(
             async move {
                 log::info!("Refreshing access token");
                 let client = ApiClient::new(
                     &global_settings.server_configuration,
                     &global_settings.device_id,
+                    global_settings.device_class,
                     global_settings.accept_invalid_certs,
-                );
+                    &global_settings.pinned_spki_hashes,
+                )?
+                .on_slow_response(on_slow_response);
 
                 client.refresh_token(&token, api_key.as_deref()).await
             },
@@ -59,52 +82,85 @@ pub fn do_sync(cursive: &mut Cursive, just_refreshed_token: bool) {
     }
 
     // Do sync, no need to worry about refreshing
+    let sync_start = Instant::now();
+    let on_slow_response = slow_response_callback(cursive);
     cursive.async_op(
         async move {
             let client = ApiClient::with_token(
                 &global_settings.server_configuration,
                 &global_settings.device_id,
+                global_settings.device_class,
                 &token.access_token,
                 global_settings.accept_invalid_certs,
-            );
+                &global_settings.pinned_spki_hashes,
+            )?
+            .on_slow_response(on_slow_response);
 
-            client.sync().await
+            let sync_json = client.sync_json().await?;
+            let sync_res = SyncResponse::from_json(&sync_json)?;
+            Ok::<_, anyhow::Error>((sync_res, sync_json))
         },
-        |c, sync_res| match sync_res {
-            Ok(sync_res) => {
+        move |c, sync_res| match sync_res {
+            Ok((sync_res, sync_json)) => {
+                let sync_duration = sync_start.elapsed();
+                c.get_user_data().set_last_sync_duration(sync_duration);
+                c.get_user_data().set_org_policies(sync_res.policies);
                 let ud = c.get_user_data().with_logged_in_state().unwrap();
-                let vault_data = Arc::new(
-                    sync_res
-                        .ciphers
-                        .into_iter()
-                        .map(|ci| (ci.id.clone(), ci))
-                        .collect(),
-                );
-                let organizations = Arc::new(
-                    sync_res
-                        .profile
-                        .organizations
-                        .into_iter()
-                        .map(|o| (o.id.clone(), o))
-                        .collect(),
-                );
-                let collections = Arc::new(
-                    sync_res
-                        .collections
-                        .into_iter()
-                        .map(|c| (c.id.clone(), c))
-                        .collect(),
-                );
-
-                ud.into_unlocked(vault_data, organizations, collections);
+                if ud.global_settings().record_metrics {
+                    record_metric(
+                        &ud.profile_store(),
+                        MetricKind::Sync,
+                        sync_duration,
+                        Some(sync_res.ciphers.len()),
+                    );
+                }
+                if ud.global_settings().offline_cache {
+                    store_vault_cache(&ud, sync_json);
+                }
+
+                let vault_data = index_by_id(sync_res.ciphers, |ci| &ci.id);
+                let organizations = index_by_id(sync_res.profile.organizations, |o| &o.id);
+                let collections = index_by_id(sync_res.collections, |c| &c.id);
+                let folders = index_by_id(sync_res.folders, |f| &f.id);
+
+                ud.into_unlocked(vault_data, organizations, collections, folders);
 
                 c.pop_layer();
                 show_vault(c);
+                sync_settings(c);
+                offer_vault_changes(c);
+                offer_pending_items(c);
+                show_slow_response_hint(c);
+                start_notifications(c);
             }
             Err(sync_err) => {
+                // The error explains the situation better than a stale hint
+                c.get_user_data().take_slow_response_hint();
                 let err_msg = format!("Error syncing: {sync_err}");
                 c.add_layer(Dialog::text(err_msg));
+                alert(c);
             }
         },
     );
 }
+
+fn store_vault_cache(ud: &StatefulUserData<LoggedIn>, sync_json: serde_json::Value) {
+    let token = ud.token();
+    let cache = VaultCache {
+        email: ud.email().to_string(),
+        pbkdf: (*ud.pbkdf()).clone(),
+        key: token.key.clone(),
+        private_key: token.private_key.clone(),
+        sync_data: sync_json,
+        keyfile_wrapped: false,
+    };
+
+    let keyfile = ud.global_settings().keyfile.clone();
+    match ud
+        .profile_store()
+        .store_vault_cache(cache, keyfile.as_deref())
+    {
+        Ok(()) => log::info!("Stored the offline vault cache"),
+        Err(e) => log::warn!("Storing the offline vault cache failed: {e}"),
+    }
+}
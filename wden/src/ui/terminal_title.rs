@@ -0,0 +1,46 @@
+use std::io::Write;
+
+/// Shows the profile and the lock state in the terminal window title.
+/// The original title is restored when this is dropped.
+pub struct TerminalTitle {
+    shown: Option<(String, bool)>,
+}
+
+impl TerminalTitle {
+    pub fn init() -> Self {
+        // Save the current title on the terminal's title stack (xterm extension)
+        write_escape("\x1b[22;0t");
+        TerminalTitle { shown: None }
+    }
+
+    pub fn update(&mut self, profile: &str, unlocked: bool) {
+        if self
+            .shown
+            .as_ref()
+            .is_some_and(|(p, u)| p == profile && *u == unlocked)
+        {
+            return;
+        }
+        self.shown = Some((profile.to_owned(), unlocked));
+
+        let state = if unlocked { "unlocked" } else { "locked" };
+        write_escape(&format!("\x1b]0;wden — {profile} ({state})\x07"));
+    }
+}
+
+impl Drop for TerminalTitle {
+    fn drop(&mut self) {
+        // Clear the title, and restore the saved one if the terminal supports it
+        write_escape("\x1b]0;\x07\x1b[23;0t");
+    }
+}
+
+fn write_escape(seq: &str) {
+    let mut stdout = std::io::stdout();
+    if let Err(e) = stdout
+        .write_all(seq.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        log::warn!("Setting the terminal title failed: {e}");
+    }
+}
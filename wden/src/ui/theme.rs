@@ -0,0 +1,336 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use cursive::theme::{BaseColor, Color, PaletteColor::*, Theme};
+
+/// Glyphs used in the UI. Some terminals render emoji with the wrong width,
+/// which breaks the layout, so ASCII fallbacks are available.
+pub struct Glyphs {
+    pub organization_item: &'static str,
+    pub personal_item: &'static str,
+    pub search: &'static str,
+    pub list_bullet: &'static str,
+    pub changed_item: &'static str,
+    pub pinned_item: &'static str,
+}
+
+const EMOJI_GLYPHS: Glyphs = Glyphs {
+    organization_item: "👥",
+    personal_item: "👤",
+    search: "🔍",
+    list_bullet: "•",
+    changed_item: "●",
+    pinned_item: "▲",
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    organization_item: "O",
+    personal_item: "",
+    search: "/ ",
+    list_bullet: "-",
+    changed_item: "*",
+    pinned_item: "^",
+};
+
+#[derive(Clone, Copy, Default, ValueEnum, PartialEq, Eq, Debug)]
+pub enum GlyphSet {
+    /// Emoji if the locale uses UTF-8, otherwise ASCII
+    #[default]
+    Auto,
+    Emoji,
+    Ascii,
+}
+
+static GLYPHS: OnceLock<&'static Glyphs> = OnceLock::new();
+
+pub fn set_glyphs(glyph_set: GlyphSet) {
+    let glyphs = match glyph_set {
+        GlyphSet::Auto if locale_is_utf8() => &EMOJI_GLYPHS,
+        GlyphSet::Emoji => &EMOJI_GLYPHS,
+        GlyphSet::Auto | GlyphSet::Ascii => &ASCII_GLYPHS,
+    };
+    _ = GLYPHS.set(glyphs);
+}
+
+fn locale_is_utf8() -> bool {
+    // The Windows console is set to UTF-8 by crossterm, and the locale
+    // variables are usually not set there
+    if cfg!(windows) {
+        return true;
+    }
+    locale_is_utf8_from(|name| std::env::var(name).ok())
+}
+
+/// Checks the locale the same way as setlocale: LC_ALL overrides LC_CTYPE,
+/// which overrides LANG. Unset means the "C" locale, which is ASCII only.
+fn locale_is_utf8_from(var: impl Fn(&str) -> Option<String>) -> bool {
+    let Some(locale) = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(var)
+        .find(|v| !v.is_empty())
+    else {
+        return false;
+    };
+    let locale = locale.to_ascii_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+pub fn glyphs() -> &'static Glyphs {
+    GLYPHS.get().copied().unwrap_or(&EMOJI_GLYPHS)
+}
+
+/// Color capabilities of the terminal, from least to most capable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// NO_COLOR is set, or the terminal has no colors at all
+    Monochrome,
+    /// The 8/16 basic ANSI colors
+    Basic,
+    Colors256,
+    TrueColor,
+}
+
+pub fn detect_color_support() -> ColorSupport {
+    if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return ColorSupport::Monochrome;
+    }
+
+    if matches!(
+        std::env::var("COLORTERM").as_deref(),
+        Ok("truecolor") | Ok("24bit")
+    ) {
+        return ColorSupport::TrueColor;
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    match terminfo::max_colors(&term) {
+        Some(c) if c >= 1 << 24 => ColorSupport::TrueColor,
+        Some(c) if c >= 256 => ColorSupport::Colors256,
+        Some(c) if c >= 8 => ColorSupport::Basic,
+        Some(_) => ColorSupport::Monochrome,
+        None if term.contains("256color") => ColorSupport::Colors256,
+        // The Windows console does not use terminfo, but supports true colors
+        None if cfg!(windows) => ColorSupport::TrueColor,
+        None => ColorSupport::Basic,
+    }
+}
+
+pub fn custom_theme(color_support: ColorSupport) -> Theme {
+    let mut t = Theme::default();
+
+    if color_support == ColorSupport::Monochrome {
+        t.shadow = false;
+        t.palette[Background] = Color::TerminalDefault;
+        t.palette[Shadow] = Color::TerminalDefault;
+        t.palette[View] = Color::TerminalDefault;
+        t.palette[Primary] = Color::TerminalDefault;
+        t.palette[Secondary] = Color::TerminalDefault;
+        t.palette[Tertiary] = Color::TerminalDefault;
+        t.palette[TitlePrimary] = Color::TerminalDefault;
+        t.palette[TitleSecondary] = Color::TerminalDefault;
+        // The selection must stay visible, so use plain black on white
+        t.palette[Highlight] = Color::Light(BaseColor::White);
+        t.palette[HighlightInactive] = Color::Light(BaseColor::White);
+        t.palette[HighlightText] = Color::Dark(BaseColor::Black);
+        return t;
+    }
+
+    let c = |rgb, basic| rgb_color(color_support, rgb, basic);
+
+    t.palette[Background] = Color::TerminalDefault;
+    t.palette[View] = c((0xf0, 0xf4, 0xf7), Color::Light(BaseColor::White));
+    t.palette[Primary] = Color::Dark(BaseColor::Black);
+    t.palette[Secondary] = c((0x52, 0x52, 0x52), Color::Light(BaseColor::Black));
+    t.palette[Tertiary] = Color::Light(BaseColor::Black);
+    t.palette[TitlePrimary] = c((0x1b, 0x98, 0xe0), Color::Light(BaseColor::Blue));
+    t.palette[Highlight] = c((0x12, 0x63, 0x91), Color::Dark(BaseColor::Blue));
+    t.palette[HighlightInactive] = c((0x23, 0x3d, 0x4d), Color::Light(BaseColor::Black));
+    t.palette[HighlightText] = c((0xf0, 0xf4, 0xf7), Color::Light(BaseColor::White));
+
+    t
+}
+
+fn rgb_color(color_support: ColorSupport, (r, g, b): (u8, u8, u8), basic: Color) -> Color {
+    // Scale 0-255 to the 0-5 range of the 6x6x6 color cube
+    let low_res = |v: u8| ((v as u16 * 5 + 127) / 255) as u8;
+
+    match color_support {
+        ColorSupport::TrueColor => Color::Rgb(r, g, b),
+        ColorSupport::Colors256 => Color::RgbLowRes(low_res(r), low_res(g), low_res(b)),
+        ColorSupport::Basic | ColorSupport::Monochrome => basic,
+    }
+}
+
+/// Minimal reader for compiled terminfo entries, only for looking up
+/// the number of colors the terminal supports.
+mod terminfo {
+    use std::path::PathBuf;
+
+    const MAGIC_16BIT: i16 = 0o432;
+    const MAGIC_32BIT: i16 = 0o1036;
+    const HEADER_LEN: usize = 12;
+    const MAX_COLORS_INDEX: usize = 13;
+
+    pub fn max_colors(term: &str) -> Option<u32> {
+        if term.is_empty() || term.contains(['/', '\\']) {
+            return None;
+        }
+        let data = find_entry(term)?;
+        parse_max_colors(&data)
+    }
+
+    fn search_dirs() -> Vec<PathBuf> {
+        let mut dirs = vec![];
+        if let Some(dir) = std::env::var_os("TERMINFO") {
+            dirs.push(dir.into());
+        }
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(PathBuf::from(home).join(".terminfo"));
+        }
+        if let Ok(var) = std::env::var("TERMINFO_DIRS") {
+            dirs.extend(var.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+        }
+        dirs.extend(
+            [
+                "/etc/terminfo",
+                "/lib/terminfo",
+                "/usr/share/terminfo",
+                "/usr/lib/terminfo",
+            ]
+            .map(PathBuf::from),
+        );
+        dirs
+    }
+
+    fn find_entry(term: &str) -> Option<Vec<u8>> {
+        let first = term.chars().next()?;
+        // Entries are in subdirectories named by the first character,
+        // or by its hex code on some systems (e.g. macOS)
+        let subdirs = [first.to_string(), format!("{:x}", first as u32)];
+
+        search_dirs().into_iter().find_map(|dir| {
+            subdirs
+                .iter()
+                .find_map(|sub| std::fs::read(dir.join(sub).join(term)).ok())
+        })
+    }
+
+    pub(super) fn parse_max_colors(data: &[u8]) -> Option<u32> {
+        let read_i16 = |offset: usize| {
+            data.get(offset..offset + 2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        };
+        let read_len = |offset: usize| read_i16(offset).and_then(|v| usize::try_from(v).ok());
+
+        let number_size = match read_i16(0)? {
+            MAGIC_16BIT => 2,
+            MAGIC_32BIT => 4,
+            _ => return None,
+        };
+        let names_len = read_len(2)?;
+        let bools_count = read_len(4)?;
+        let numbers_count = read_len(6)?;
+        if numbers_count <= MAX_COLORS_INDEX {
+            return None;
+        }
+
+        // The numbers section is aligned to an even offset
+        let mut offset = HEADER_LEN + names_len + bools_count;
+        offset += offset % 2;
+        offset += MAX_COLORS_INDEX * number_size;
+
+        let value = if number_size == 2 {
+            read_i16(offset)? as i32
+        } else {
+            i32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?)
+        };
+
+        // Negative values mean that the capability is absent
+        u32::try_from(value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_is_utf8() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert!(locale_is_utf8_from(env(&[("LANG", "en_US.UTF-8")])));
+        assert!(locale_is_utf8_from(env(&[("LC_CTYPE", "fi_FI.utf8")])));
+        assert!(!locale_is_utf8_from(env(&[])));
+        assert!(!locale_is_utf8_from(env(&[("LANG", "C")])));
+        assert!(!locale_is_utf8_from(env(&[
+            ("LC_ALL", "POSIX"),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        assert!(locale_is_utf8_from(env(&[
+            ("LC_ALL", ""),
+            ("LANG", "en_US.UTF-8")
+        ])));
+    }
+
+    fn terminfo_entry(magic: i16, number_size: usize, max_colors: i32) -> Vec<u8> {
+        let names = b"test|Test terminal\0";
+        let bools = [1u8; 3];
+        let numbers_count = 15i16;
+
+        let mut data = vec![];
+        for v in [
+            magic,
+            names.len() as i16,
+            bools.len() as i16,
+            numbers_count,
+            0,
+            0,
+        ] {
+            data.extend(v.to_le_bytes());
+        }
+        data.extend(names);
+        data.extend(bools);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        for i in 0..numbers_count as usize {
+            let v = if i == 13 { max_colors } else { -1 };
+            data.extend(&v.to_le_bytes()[..number_size]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_terminfo_max_colors() {
+        let data = terminfo_entry(0o432, 2, 256);
+        assert_eq!(terminfo::parse_max_colors(&data), Some(256));
+
+        let data = terminfo_entry(0o1036, 4, 0x1000000);
+        assert_eq!(terminfo::parse_max_colors(&data), Some(0x1000000));
+
+        let data = terminfo_entry(0o432, 2, -1);
+        assert_eq!(terminfo::parse_max_colors(&data), None);
+
+        assert_eq!(terminfo::parse_max_colors(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_rgb_color_degrades() {
+        let basic = Color::Dark(BaseColor::Blue);
+        let rgb = (0x12, 0x63, 0x91);
+        assert_eq!(
+            rgb_color(ColorSupport::TrueColor, rgb, basic),
+            Color::Rgb(0x12, 0x63, 0x91)
+        );
+        assert_eq!(
+            rgb_color(ColorSupport::Colors256, rgb, basic),
+            Color::RgbLowRes(0, 2, 3)
+        );
+        assert_eq!(rgb_color(ColorSupport::Basic, rgb, basic), basic);
+    }
+}
@@ -47,8 +47,12 @@ pub fn two_factor_dialog(
         .button("Cancel", move |siv| {
             let ud = siv.get_user_data().with_logging_in_state().unwrap();
             let ud = ud.into_logged_out();
-            let pn = &ud.global_settings().profile;
-            let d = login_dialog(pn, Some(email3.to_string()), false, had_captcha_token);
+            let d = login_dialog(
+                &ud.global_settings(),
+                Some(email3.to_string()),
+                false,
+                had_captcha_token,
+            );
             siv.clear_layers();
             siv.add_layer(d);
         })
@@ -72,24 +76,57 @@ fn submit_two_factor(c: &mut Cursive, email: Arc<String>, personal_api_key: Opti
     let profile_store = ud.profile_store();
     let master_pw_hash = ud.master_password_hash();
     let email2 = email.clone();
+    let personal_api_key2 = personal_api_key.clone();
 
     c.async_op(
         async move {
             let client = ApiClient::new(
                 &global_settings.server_configuration,
                 &global_settings.device_id,
+                global_settings.device_class,
                 global_settings.accept_invalid_certs,
-            );
+                &global_settings.pinned_spki_hashes,
+            )?;
             do_login(
                 &client,
                 &email,
                 master_pw_hash,
                 Some((TwoFactorProviderType::Authenticator, &code)),
                 personal_api_key.as_deref().map(|s| s.as_str()),
+                None,
                 &profile_store,
             )
             .await
         },
-        move |siv, res| handle_login_response(siv, res, email2, false, false),
+        move |siv, res| match res {
+            Err(e) => show_two_factor_error(siv, e, email2, personal_api_key2),
+            res => handle_login_response(siv, res, email2, false, false),
+        },
+    );
+}
+
+/// Shows a failed two-factor login, e.g. with a mistyped code. The state
+/// stays LoggingIn with the derived keys, so that trying again only
+/// repeats the token request.
+fn show_two_factor_error(
+    siv: &mut Cursive,
+    e: anyhow::Error,
+    email: Arc<String>,
+    captcha_token: Option<Arc<String>>,
+) {
+    siv.pop_layer();
+    let profile = siv.get_user_data().global_settings().profile.clone();
+    siv.add_layer(
+        Dialog::text(format!("Error: {e:?}"))
+            .title("Two-factor login error")
+            .button("Try again", move |siv| {
+                siv.pop_layer();
+                siv.add_layer(two_factor_dialog(
+                    vec![TwoFactorProviderType::Authenticator],
+                    email.clone(),
+                    &profile,
+                    captcha_token.clone(),
+                ));
+            }),
     );
 }
@@ -0,0 +1,124 @@
+use cursive::{views::Dialog, Cursive};
+
+use crate::bitwarden::api::{ApiClient, CipherItem};
+
+use super::{util::cursive_ext::CursiveExt, vault_table::reload_vault};
+
+const MAX_UNDO_ACTIONS: usize = 20;
+
+/// A destructive action of this session that can be undone with Ctrl+Z
+pub enum UndoAction {
+    /// An item was moved to the trash. The item is kept (encrypted) for
+    /// showing it again without a sync, if it was removed from the vault data.
+    Trash {
+        item_id: String,
+        item: Option<CipherItem>,
+    },
+}
+
+/// The latest destructive actions, newest last. Only kept in memory, so
+/// the actions of earlier sessions can't be undone.
+#[derive(Default)]
+pub struct UndoStack(Vec<UndoAction>);
+
+impl UndoStack {
+    pub fn push(&mut self, action: UndoAction) {
+        if self.0.len() == MAX_UNDO_ACTIONS {
+            self.0.remove(0);
+        }
+        self.0.push(action);
+    }
+
+    pub fn pop(&mut self) -> Option<UndoAction> {
+        self.0.pop()
+    }
+}
+
+/// Reverts the latest destructive action with the corresponding API call
+pub fn undo_last_action(siv: &mut Cursive) {
+    let Some(action) = siv.get_user_data().undo_stack().pop() else {
+        siv.add_layer(Dialog::info("Nothing to undo"));
+        return;
+    };
+    match action {
+        UndoAction::Trash { item_id, item } => restore_item(siv, item_id, item),
+    }
+}
+
+fn restore_item(siv: &mut Cursive, item_id: String, item: Option<CipherItem>) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let global_settings = ud.global_settings();
+    let token = ud.token();
+
+    let api_item_id = item_id.clone();
+    siv.add_layer(Dialog::text("Restoring the deleted item..."));
+    siv.async_op(
+        async move {
+            let client = ApiClient::with_token(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                &token.access_token,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            client.restore_cipher(&api_item_id).await
+        },
+        move |siv, res| {
+            siv.pop_layer();
+            if let Err(e) = res {
+                // Kept for trying again
+                siv.get_user_data()
+                    .undo_stack()
+                    .push(UndoAction::Trash { item_id, item });
+                siv.add_layer(Dialog::info(format!("Restoring the item failed: {e}")));
+                return;
+            }
+
+            let Some(mut ud) = siv.get_user_data().with_unlocked_state() else {
+                return;
+            };
+            match item {
+                Some(item) => ud.insert_item(item),
+                None => log::warn!("The restored item is shown after the next sync"),
+            }
+            reload_vault(siv);
+            siv.add_layer(Dialog::info("The deleted item was restored from the trash"));
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trashed(id: usize) -> UndoAction {
+        UndoAction::Trash {
+            item_id: id.to_string(),
+            item: None,
+        }
+    }
+
+    fn item_id(action: UndoAction) -> String {
+        match action {
+            UndoAction::Trash { item_id, .. } => item_id,
+        }
+    }
+
+    #[test]
+    fn test_undo_stack() {
+        let mut stack = UndoStack::default();
+        for i in 0..MAX_UNDO_ACTIONS + 2 {
+            stack.push(trashed(i));
+        }
+
+        // Newest first, and the oldest ones are dropped
+        assert_eq!(
+            item_id(stack.pop().unwrap()),
+            (MAX_UNDO_ACTIONS + 1).to_string()
+        );
+        let rest: Vec<_> = std::iter::from_fn(|| stack.pop()).map(item_id).collect();
+        assert_eq!(rest.len(), MAX_UNDO_ACTIONS - 1);
+        assert_eq!(rest.last().unwrap(), "2");
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    bitwarden::cipher::{Cipher, MasterKey, PbkdfParameters},
+    profile,
+};
+
+const SERVICE: &str = "wden";
+
+/// Master keys stored in the platform keystore: the Secret Service
+/// (e.g. GNOME Keyring or KWallet) on Linux, the Credential Manager
+/// on Windows and the login keychain on macOS. There is one key per
+/// profile. With a profile keyfile, the key is stored encrypted with the
+/// keys derived from the keyfile.
+pub struct PlatformKeystore;
+
+/// A login session remembered in the keystore (`--remember-session`).
+/// The keys are stored as received from the server, encrypted.
+#[derive(Serialize, Deserialize)]
+pub struct RememberedSession {
+    pub email: String,
+    pub pbkdf: PbkdfParameters,
+    /// User symmetric key, encrypted with the master key
+    pub key: Cipher,
+    /// User private key, encrypted with the user symmetric key
+    pub private_key: Cipher,
+    pub refresh_token: Zeroizing<String>,
+}
+
+pub fn get_platform_keystore() -> PlatformKeystore {
+    PlatformKeystore
+}
+
+/// Stores the master key for keyring unlock. Failures are only logged:
+/// unlocking then just needs the password.
+pub fn store_for_unlock(profile: &str, master_key: &MasterKey, keyfile: Option<&Path>) {
+    if let Err(e) = get_platform_keystore().store_master_key(profile, master_key, keyfile) {
+        log::warn!("Storing the master key in the keystore failed: {e:#}");
+    }
+}
+
+impl PlatformKeystore {
+    fn entry(&self, profile: &str) -> anyhow::Result<keyring::Entry> {
+        keyring::Entry::new(SERVICE, &format!("master-key:{profile}"))
+            .context("Opening the platform keystore failed")
+    }
+
+    pub fn store_master_key(
+        &self,
+        profile: &str,
+        master_key: &MasterKey,
+        keyfile: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        let encoded = match keyfile {
+            Some(keyfile) => Zeroizing::new(
+                master_key
+                    .encrypt(&profile::keyfile_keys(keyfile)?)?
+                    .encode(),
+            ),
+            None => master_key.base64_encoded(),
+        };
+        self.entry(profile)?
+            .set_password(&encoded)
+            .context("Storing the master key failed")
+    }
+
+    /// Returns `None` if no key is stored for the profile
+    pub fn load_master_key(
+        &self,
+        profile: &str,
+        keyfile: Option<&Path>,
+    ) -> anyhow::Result<Option<MasterKey>> {
+        match self.entry(profile)?.get_password() {
+            Ok(encoded) => {
+                let encoded = Zeroizing::new(encoded);
+                let master_key = match keyfile {
+                    Some(keyfile) => {
+                        let cipher: Cipher = encoded
+                            .parse()
+                            .context("The stored master key is not protected with the keyfile")?;
+                        MasterKey::decrypt(&cipher, &profile::keyfile_keys(keyfile)?)
+                            .context("Decrypting the stored master key with the keyfile failed")?
+                    }
+                    None => MasterKey::from_base64(&encoded)
+                        .context("The stored master key is not valid")?,
+                };
+                Ok(Some(master_key))
+            }
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).context("Reading the master key failed"),
+        }
+    }
+
+    pub fn delete_master_key(&self, profile: &str) -> anyhow::Result<()> {
+        match self.entry(profile)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e).context("Removing the master key failed"),
+        }
+    }
+
+    fn session_entry(&self, profile: &str) -> anyhow::Result<keyring::Entry> {
+        keyring::Entry::new(SERVICE, &format!("session:{profile}"))
+            .context("Opening the platform keystore failed")
+    }
+
+    pub fn store_session(&self, profile: &str, session: &RememberedSession) -> anyhow::Result<()> {
+        let json = Zeroizing::new(serde_json::to_vec(session)?);
+        // Stored as bytes: the Credential Manager would store a password
+        // as UTF-16, and the private key would not fit in the size limit
+        self.session_entry(profile)?
+            .set_secret(&json)
+            .context("Storing the session failed")
+    }
+
+    /// Returns `None` if no session is stored for the profile
+    pub fn load_session(&self, profile: &str) -> anyhow::Result<Option<RememberedSession>> {
+        match self.session_entry(profile)?.get_secret() {
+            Ok(json) => {
+                let json = Zeroizing::new(json);
+                let session =
+                    serde_json::from_slice(&json).context("The stored session is not valid")?;
+                Ok(Some(session))
+            }
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).context("Reading the session failed"),
+        }
+    }
+
+    pub fn delete_session(&self, profile: &str) -> anyhow::Result<()> {
+        match self.session_entry(profile)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e).context("Removing the session failed"),
+        }
+    }
+}
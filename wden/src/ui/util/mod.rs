@@ -1 +1,2 @@
 pub mod cursive_ext;
+pub mod keystore;
@@ -0,0 +1,295 @@
+use std::{collections::HashMap, fmt::Write, mem::discriminant};
+
+use cursive::{
+    traits::{Resizable, Scrollable},
+    views::{Dialog, TextView},
+    Cursive,
+};
+use zeroize::Zeroizing;
+
+use crate::bitwarden::{
+    api::{CipherData, CipherItem},
+    cipher::{constant_time, Cipher, EncMacKeys},
+};
+
+use super::{
+    data::{StatefulUserData, Unlocked},
+    util::cursive_ext::CursiveExt,
+};
+
+/// Item ids that differ between two versions of the vault
+#[derive(Default)]
+struct VaultDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+impl VaultDiff {
+    fn new(old: &HashMap<String, CipherItem>, new: &HashMap<String, CipherItem>) -> Self {
+        let mut diff = VaultDiff::default();
+        for (id, item) in new {
+            match old.get(id) {
+                None => diff.added.push(id.clone()),
+                Some(old_item) if old_item.revision_date != item.revision_date => {
+                    diff.modified.push(id.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|id| !new.contains_key(*id))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.modified.len()
+    }
+}
+
+/// Offers the changes screen after a sync, if the sync changed anything
+pub fn offer_vault_changes(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let Some(previous) = ud.previous_vault_data() else {
+        return;
+    };
+    let count = VaultDiff::new(&previous, &ud.vault_data()).len();
+    if count == 0 {
+        return;
+    }
+
+    let text = if count == 1 {
+        "1 item changed in the sync.".to_string()
+    } else {
+        format!("{count} items changed in the sync.")
+    };
+    siv.add_layer(
+        Dialog::text(text)
+            .title("Vault synced")
+            .button("What changed", |siv| {
+                siv.pop_layer();
+                show_vault_changes(siv);
+            })
+            .dismiss_button("Close"),
+    );
+}
+
+/// Lists the items that the last sync added, removed or modified. Only
+/// the names of the changed fields are shown, never their values.
+pub fn show_vault_changes(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let Some(previous) = ud.previous_vault_data() else {
+        siv.add_layer(Dialog::info(
+            "There is no earlier sync to compare with. Changes are shown after the vault has been synced while unlocked.",
+        ));
+        return;
+    };
+    let vault_data = ud.vault_data();
+    let diff = VaultDiff::new(&previous, &vault_data);
+
+    let item_name = |item: &CipherItem| {
+        ud.get_keys_for_item(item)
+            .map(|keys| item.name.decrypt_to_string(&keys))
+            .unwrap_or_else(|| "<unknown>".to_string())
+    };
+    let global_settings = ud.global_settings();
+    let revision_date = |item: &CipherItem| match &item.revision_date {
+        Some(date) => format!(" ({})", global_settings.date_display.format(date)),
+        None => String::new(),
+    };
+    let sorted_names = |ids: &[String], items: &HashMap<String, CipherItem>| {
+        let mut names: Vec<_> = ids.iter().map(|id| item_name(&items[id])).collect();
+        names.sort();
+        names
+    };
+
+    let mut text = String::new();
+    if diff.len() == 0 {
+        text.push_str("Nothing changed in the last sync.");
+    }
+    if !diff.added.is_empty() {
+        _ = writeln!(text, "Added ({})", diff.added.len());
+        let mut added: Vec<_> = diff
+            .added
+            .iter()
+            .map(|id| (item_name(&vault_data[id]), revision_date(&vault_data[id])))
+            .collect();
+        added.sort();
+        for (name, date) in added {
+            _ = writeln!(text, "  {name}{date}");
+        }
+    }
+    if !diff.removed.is_empty() {
+        _ = writeln!(text, "Removed ({})", diff.removed.len());
+        for name in sorted_names(&diff.removed, &previous) {
+            _ = writeln!(text, "  {name}");
+        }
+    }
+    if !diff.modified.is_empty() {
+        _ = writeln!(text, "Modified ({})", diff.modified.len());
+        let mut modified: Vec<_> = diff
+            .modified
+            .iter()
+            .map(|id| {
+                let new = &vault_data[id];
+                (
+                    item_name(new),
+                    changed_fields_text(&ud, &previous[id], new),
+                    revision_date(new),
+                )
+            })
+            .collect();
+        modified.sort();
+        for (name, fields, date) in modified {
+            _ = writeln!(text, "  {name}: {fields}{date}");
+        }
+    }
+
+    siv.add_layer(
+        Dialog::around(TextView::new(text.trim_end()).scrollable())
+            .title("What changed")
+            .dismiss_button("Close")
+            .min_width(50)
+            .max_height(30),
+    );
+}
+
+fn changed_fields_text(
+    ud: &StatefulUserData<Unlocked>,
+    old: &CipherItem,
+    new: &CipherItem,
+) -> String {
+    // The item key or the organization may have changed too
+    let fields = match (ud.get_keys_for_item(old), ud.get_keys_for_item(new)) {
+        (Some(old_keys), Some(new_keys)) => changed_fields(old, &old_keys, new, &new_keys),
+        _ => vec![],
+    };
+    if fields.is_empty() {
+        "details changed".to_string()
+    } else {
+        fields.join(", ")
+    }
+}
+
+/// Names of the fields that differ between the two versions of an item.
+/// Encrypted values are compared decrypted, because re-encrypting a value
+/// changes the cipher string.
+fn changed_fields(
+    old: &CipherItem,
+    old_keys: &EncMacKeys,
+    new: &CipherItem,
+    new_keys: &EncMacKeys,
+) -> Vec<&'static str> {
+    let mut changed = vec![];
+    if discriminant(&old.data) != discriminant(&new.data) {
+        changed.push("type");
+    }
+    let new_values = encrypted_fields(new);
+    for (name, old_values) in encrypted_fields(old) {
+        let differs = match new_values.iter().find(|(n, _)| *n == name) {
+            Some((_, new_values)) => values_differ(&old_values, old_keys, new_values, new_keys),
+            None => true,
+        };
+        if differs {
+            changed.push(name);
+        }
+    }
+    for (name, differs) in [
+        ("folder", old.folder_id != new.folder_id),
+        ("collections", old.collection_ids != new.collection_ids),
+        ("organization", old.organization_id != new.organization_id),
+        ("favorite", old.favorite != new.favorite),
+        ("reprompt", old.reprompt != new.reprompt),
+        (
+            "attachments",
+            old.attachments.len() != new.attachments.len(),
+        ),
+    ] {
+        if differs {
+            changed.push(name);
+        }
+    }
+    changed
+}
+
+fn encrypted_fields(item: &CipherItem) -> Vec<(&'static str, Vec<&Cipher>)> {
+    let mut fields = vec![("name", vec![&item.name]), ("notes", vec![&item.notes])];
+    match &item.data {
+        CipherData::Login(l) => fields.extend([
+            ("username", vec![&l.username]),
+            ("password", vec![&l.password]),
+            ("URIs", l.uris().into_iter().map(|(uri, _)| uri).collect()),
+            ("TOTP", vec![&l.totp]),
+        ]),
+        CipherData::Card(c) => fields.extend([
+            ("cardholder name", vec![&c.cardholder_name]),
+            ("brand", vec![&c.brand]),
+            ("number", vec![&c.number]),
+            ("expiration", vec![&c.exp_month, &c.exp_year]),
+            ("security code", vec![&c.code]),
+        ]),
+        CipherData::Identity(i) => fields.push((
+            "identity",
+            vec![
+                &i.title,
+                &i.first_name,
+                &i.middle_name,
+                &i.last_name,
+                &i.username,
+                &i.company,
+                &i.email,
+                &i.phone,
+                &i.address_1,
+                &i.address_2,
+                &i.address_3,
+                &i.city,
+                &i.state,
+                &i.postal_code,
+                &i.country,
+                &i.ssn,
+                &i.passport_number,
+                &i.license_number,
+            ],
+        )),
+        CipherData::SshKey(k) => fields.extend([
+            ("private key", vec![&k.private_key]),
+            ("public key", vec![&k.public_key]),
+            ("fingerprint", vec![&k.fingerprint]),
+        ]),
+        CipherData::SecureNote | CipherData::None => {}
+    }
+    fields.push((
+        "custom fields",
+        item.fields
+            .iter()
+            .flat_map(|f| [&f.name, &f.value])
+            .collect(),
+    ));
+    fields
+}
+
+fn values_differ(
+    old: &[&Cipher],
+    old_keys: &EncMacKeys,
+    new: &[&Cipher],
+    new_keys: &EncMacKeys,
+) -> bool {
+    old.len() != new.len()
+        || old
+            .iter()
+            .zip(new)
+            .any(|(o, n)| match (o.decrypt(old_keys), n.decrypt(new_keys)) {
+                (Ok(o), Ok(n)) => {
+                    let (o, n) = (Zeroizing::new(o), Zeroizing::new(n));
+                    !constant_time::eq(&o, &n)
+                }
+                _ => true,
+            })
+}
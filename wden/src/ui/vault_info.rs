@@ -0,0 +1,207 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use cursive::{
+    traits::{Resizable, Scrollable},
+    views::{Dialog, LinearLayout, SelectView, TextView},
+    Cursive,
+};
+
+use crate::bitwarden::{api::CipherData, cipher::EncType};
+
+use super::{item_details::item_detail_dialog, util::cursive_ext::CursiveExt};
+
+/// Shows item, organization, collection and attachment counts of the
+/// unlocked vault
+pub fn show_vault_info(siv: &mut Cursive) {
+    let last_sync_duration = siv.get_user_data().last_sync_duration();
+    let org_policies = siv.get_user_data().org_policies();
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let vault_data = ud.vault_data();
+    let organizations = ud.organizations();
+    let collections = ud.collections();
+
+    let mut by_type = [
+        ("Logins", 0),
+        ("Secure notes", 0),
+        ("Cards", 0),
+        ("Identities", 0),
+        ("SSH keys", 0),
+    ];
+    let mut by_org: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    let mut personal = 0;
+    let mut attachment_count = 0;
+    let mut attachment_size = 0;
+    let mut legacy_count = 0;
+
+    for item in vault_data.values() {
+        match item.data {
+            CipherData::Login(_) => by_type[0].1 += 1,
+            CipherData::SecureNote => by_type[1].1 += 1,
+            CipherData::Card(_) => by_type[2].1 += 1,
+            CipherData::Identity(_) => by_type[3].1 += 1,
+            CipherData::SshKey(_) => by_type[4].1 += 1,
+            CipherData::None => {}
+        }
+        match item
+            .organization_id
+            .as_ref()
+            .and_then(|id| organizations.get(id))
+        {
+            Some(org) => by_org.entry(&org.name).or_default().0 += 1,
+            None => personal += 1,
+        }
+        attachment_count += item.attachments.len();
+        attachment_size += item.attachments.iter().map(|a| a.size()).sum::<u64>();
+        if !item.legacy_enc_types().is_empty() {
+            legacy_count += 1;
+        }
+    }
+    for coll in collections.values() {
+        if let Some(org) = organizations.get(&coll.organization_id) {
+            by_org.entry(&org.name).or_default().1 += 1;
+        }
+    }
+
+    let mut text = format!("Items: {}\n", vault_data.len());
+    for (name, count) in by_type {
+        _ = writeln!(text, "  {name}: {count}");
+    }
+    _ = writeln!(text, "\nPersonal items: {personal}");
+    for (name, (items, colls)) in &by_org {
+        _ = writeln!(text, "{name}: {items} items, {colls} collections");
+    }
+    _ = writeln!(text, "\nCollections: {}", collections.len());
+    _ = writeln!(
+        text,
+        "Attachments: {attachment_count} ({})",
+        format_size(attachment_size)
+    );
+    _ = writeln!(text, "Items with legacy encryption: {legacy_count}");
+
+    let mut active_policies: Vec<_> = org_policies
+        .iter()
+        .filter(|p| p.enabled)
+        .map(|p| {
+            let org_name = organizations
+                .get(&p.organization_id)
+                .map(|o| o.name.as_str())
+                .unwrap_or(&p.organization_id);
+            match p.maximum_vault_timeout() {
+                Some(timeout) => format!(
+                    "{org_name}: {} (max. {} min)",
+                    p.name(),
+                    timeout.as_secs() / 60
+                ),
+                None => format!("{org_name}: {}", p.name()),
+            }
+        })
+        .collect();
+    active_policies.sort();
+    if !active_policies.is_empty() {
+        _ = writeln!(text, "\nOrganization policies:");
+        for policy in active_policies {
+            _ = writeln!(text, "  {policy}");
+        }
+        _ = writeln!(text);
+    }
+    _ = write!(
+        text,
+        "Last sync: {}",
+        match last_sync_duration {
+            Some(d) => format!("{} ms", d.as_millis()),
+            None => "not synced in this session".to_string(),
+        }
+    );
+
+    let mut dialog = Dialog::around(TextView::new(text).scrollable()).title("About vault");
+    if legacy_count > 0 {
+        dialog.add_button("Show legacy items", show_legacy_items);
+    }
+    siv.add_layer(dialog.dismiss_button("Close"));
+}
+
+/// Lists the items that have values encrypted with the legacy types.
+/// Editing and saving such an item in another Bitwarden client
+/// re-encrypts it with the current type.
+fn show_legacy_items(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let vault_data = ud.vault_data();
+
+    let mut items: Vec<_> = vault_data
+        .values()
+        .filter_map(|item| {
+            let types = item.legacy_enc_types();
+            if types.is_empty() {
+                return None;
+            }
+            let name = ud
+                .get_keys_for_item(item)
+                .map(|keys| item.name.decrypt_to_string(&keys))
+                .unwrap_or_else(|| "<unknown>".to_string());
+            let types: Vec<_> = types.iter().map(enc_type_name).collect();
+            Some((format!("{name} ({})", types.join(", ")), item.id.clone()))
+        })
+        .collect();
+    items.sort();
+
+    let select = SelectView::new()
+        .with_all(items)
+        .on_submit(|siv, id: &String| {
+            let ud = siv.get_user_data().with_unlocked_state().unwrap();
+            if let Some(d) = item_detail_dialog(&ud, id) {
+                siv.add_layer(d);
+            }
+        });
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "These items use encryption types of very old clients. \
+                    To re-encrypt an item, edit and save it in another Bitwarden client.\n",
+                ))
+                .child(select.scrollable()),
+        )
+        .title("Legacy encryption")
+        .dismiss_button("Close")
+        .max_width(80),
+    );
+}
+
+fn enc_type_name(enc_type: &EncType) -> &'static str {
+    match enc_type {
+        EncType::AesCbc256B64 => "AES-256 without MAC",
+        EncType::AesCbc128HmacSha256B64 => "AES-128",
+        _ => "other",
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_size;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+}
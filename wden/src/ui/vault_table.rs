@@ -1,50 +1,105 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use crate::bitwarden::{
     self,
     api::CipherItem,
-    cipher::{Cipher, EncMacKeys},
+    cipher::{Cipher, DecryptBuffer, EncMacKeys},
     keys::resolve_item_keys,
+    totp::Totp,
 };
+use crate::profile::{settings_revision_now, ClipboardBackend, EnterAction, ProfileData};
 use bitwarden::api::CipherData;
+
 use cursive::{
-    event::Event,
-    theme::{BaseColor, Color, PaletteColor},
+    event::{Event, EventResult, EventTrigger, Key, MouseButton, MouseEvent},
+    menu,
+    theme::PaletteColor,
     traits::{Finder, Nameable, Resizable},
-    view::{Margins, ViewWrapper},
+    view::{scroll::Scroller, Margins, Position, ViewWrapper},
     views::{
-        Dialog, EditView, LayerPosition, LinearLayout, OnEventView, PaddedView, Panel, TextView,
+        Dialog, EditView, LayerPosition, LinearLayout, MenuPopup, NamedView, OnEventView,
+        PaddedView, Panel, TextView,
     },
-    wrap_impl, Cursive, View,
+    wrap_impl, Cursive, Vec2, View,
 };
 use cursive_table_view::{TableView, TableViewItem};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use simsearch::SimSearch;
 use zeroize::Zeroize;
 
 use super::{
     collections::{show_collection_filter, CollectionSelection},
+    components::{hint_button::HintButton, warning_banner::insecure_http_banner},
     util::cursive_ext::CursiveExt,
 };
 use super::{
+    create_item::show_create_item_dialog,
     data::{StatefulUserData, Unlocked},
+    export::show_export_dialog,
+    folders::{folder_names, show_folder_filter},
+    generator::show_generator,
     item_details::item_detail_dialog,
     lock::lock_vault,
-    search,
+    lock_settings::show_lock_settings_dialog,
+    organizations::show_organizations,
+    pinned_items::{pinned_item_ids, toggle_pinned_item},
+    policy::{with_reprompt, ItemPolicy},
+    search::{self, SearchField},
+    sends::show_sends,
     sync::do_sync,
+    theme,
+    undo::undo_last_action,
     util::cursive_ext::CursiveCallbackExt,
+    vault_diff::show_vault_changes,
+    vault_info::show_vault_info,
+    web_vault::open_web_vault,
 };
 
 struct VaultView {
     view: OnEventView<LinearLayout>,
+    table_focused: Option<bool>,
     rows: Vec<Row>,
-    simsearch: SimSearch<String>,
+    search_index: search::SearchIndex,
     search_term: String,
     collection_selection: CollectionSelection,
+    // Applied on the next layout, when the table knows its size
+    pending_scroll_offset: Option<usize>,
+}
+
+/// The selected item and scroll offset of the vault table
+pub struct VaultPosition {
+    selected_item_id: Option<String>,
+    scroll_offset: usize,
 }
 
 impl ViewWrapper for VaultView {
     wrap_impl!(self.view: OnEventView<LinearLayout>);
+
+    fn wrap_layout(&mut self, size: Vec2) {
+        // Focus can move both with events and with callbacks (focus_name),
+        // so the indicator is updated here, before each layout
+        self.update_focus_indicator();
+        self.view.layout(size);
+
+        if let Some(offset) = self.pending_scroll_offset.take() {
+            if let Some(mut vt) = self.find_name::<TableView<Row, VaultTableColumn>>("vault_table")
+            {
+                let row = vt.row().unwrap_or(0);
+                let height = vt.get_scroller().content_viewport().height();
+                // Keep the selected row visible, the table may be shorter
+                // than before
+                let offset = offset.min(row).max((row + 1).saturating_sub(height));
+                vt.get_scroller_mut().set_offset((0, offset));
+            }
+        }
+    }
 }
 
 impl VaultView {
@@ -58,13 +113,15 @@ impl VaultView {
         // These are stored in user_data. Only the filter results are stored
         // as the table's rows.
         let rows = create_rows(user_data, user_keys);
-        let simsearch = search::get_search_index(user_data);
+        let search_index = search::get_search_index(user_data);
         let view = vault_view(&search_term, &collection_selection, user_data);
 
         let mut vv = VaultView {
             view,
+            table_focused: None,
+            pending_scroll_offset: None,
             rows,
-            simsearch,
+            search_index,
             collection_selection,
             search_term,
         };
@@ -105,6 +162,74 @@ impl VaultView {
             // without this
             vt.set_selected_row(0);
         }
+        self.update_search_match_label();
+    }
+
+    fn clear_changed_marker(&mut self, item_id: &str) {
+        if let Some(row) = self.rows.iter_mut().find(|r| r.id == item_id) {
+            row.changed = false;
+        }
+        if let Some(mut vt) = self.find_name::<TableView<Row, VaultTableColumn>>("vault_table") {
+            if let Some(row) = vt.borrow_items_mut().iter_mut().find(|r| r.id == item_id) {
+                row.changed = false;
+            }
+        }
+    }
+
+    fn update_search_match_label(&mut self) {
+        let text = self
+            .find_name::<TableView<Row, VaultTableColumn>>("vault_table")
+            .and_then(|vt| {
+                vt.item()
+                    .and_then(|i| vt.borrow_item(i).map(search_match_text))
+            })
+            .unwrap_or_default();
+
+        if let Some(mut label) = self.find_name::<TextView>("search_match_label") {
+            label.set_content(text);
+        }
+    }
+
+    fn position(&mut self) -> Option<VaultPosition> {
+        let vt = self.find_name::<TableView<Row, VaultTableColumn>>("vault_table")?;
+        Some(VaultPosition {
+            selected_item_id: vt
+                .item()
+                .and_then(|i| vt.borrow_item(i))
+                .map(|r| r.id.clone()),
+            scroll_offset: vt.get_scroller().content_viewport().top(),
+        })
+    }
+
+    fn restore_position(&mut self, position: &VaultPosition) {
+        if let Some(mut vt) = self.find_name::<TableView<Row, VaultTableColumn>>("vault_table") {
+            // The item may have been deleted in the sync
+            let index = position
+                .selected_item_id
+                .as_ref()
+                .and_then(|id| vt.borrow_items().iter().position(|r| &r.id == id));
+            if let Some(index) = index {
+                vt.set_selected_item(index);
+            }
+        }
+        self.pending_scroll_offset = Some(position.scroll_offset);
+        self.update_search_match_label();
+    }
+
+    fn update_focus_indicator(&mut self) {
+        let table_focused = self.view.get_inner().get_focus_index() == VAULT_TABLE_INDEX;
+        if self.table_focused == Some(table_focused) {
+            return;
+        }
+        self.table_focused = Some(table_focused);
+
+        if let Some(mut indicator) = self.find_name::<TextView>("focus_indicator") {
+            indicator.set_content(if table_focused {
+                "shortcuts active, </> to search"
+            } else {
+                "typing, <Enter> to use shortcuts"
+            });
+        }
     }
 
     fn search_rows(&self) -> Vec<Row> {
@@ -113,16 +238,29 @@ impl VaultView {
                 CollectionSelection::All => true,
                 CollectionSelection::Unassigned => row.collection_ids.is_empty(),
                 CollectionSelection::Collection(coll) => row.collection_ids.contains(coll),
+                CollectionSelection::NoFolder => row.folder_id.is_none(),
+                CollectionSelection::Folder(folder) => row.folder_id.as_ref() == Some(folder),
             }
         }
 
-        match search::search_items(&self.search_term, &self.simsearch) {
-            Some(matching_items) => matching_items
-                .into_iter()
-                .filter_map(|id| self.rows.iter().find(|r| r.id == id))
-                .filter(|row| collection_matches(&self.collection_selection, row))
-                .cloned()
-                .collect(),
+        match search::search_items(&self.search_term, &self.search_index) {
+            Some(matching_items) => {
+                let mut rows: Vec<_> = matching_items
+                    .into_iter()
+                    .filter_map(|search_match| {
+                        let row = self.rows.iter().find(|r| r.id == search_match.id)?;
+                        let mut row = row.clone();
+                        row.search_match = search_match.field;
+                        row.name_matches = search_match.name_matches;
+                        row.username_matches = search_match.username_matches;
+                        Some(row)
+                    })
+                    .filter(|row| collection_matches(&self.collection_selection, row))
+                    .collect();
+                // Pinned matches first, otherwise in the order of relevance
+                rows.sort_by_key(|row| !row.pinned);
+                rows
+            }
             None => self
                 .rows
                 .iter()
@@ -135,9 +273,12 @@ impl VaultView {
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 enum VaultTableColumn {
+    Changed,
     ItemType,
     Name,
     Username,
+    Folder,
+    PasswordChanged,
     IsInOrganization,
 }
 
@@ -150,6 +291,23 @@ struct Row {
     item_type: String,
     is_in_organization: bool,
     collection_ids: Vec<String>,
+    folder_id: Option<String>,
+    folder: String,
+    // Date of the last password change of a login, YYYY-MM-DD
+    password_changed: String,
+    #[zeroize(skip)]
+    search_match: Option<SearchField>,
+    // Byte ranges of the words matching the search term in the name and
+    // the username, underlined in the table
+    #[zeroize(skip)]
+    name_matches: Vec<Range<usize>>,
+    #[zeroize(skip)]
+    username_matches: Vec<Range<usize>>,
+    // Added or modified in the last sync, and not viewed yet
+    #[zeroize(skip)]
+    changed: bool,
+    #[zeroize(skip)]
+    pinned: bool,
 }
 
 impl PartialEq for Row {
@@ -165,20 +323,34 @@ impl PartialOrd for Row {
 }
 impl Ord for Row {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.name.cmp(&other.name)
+        // Pinned items first
+        other
+            .pinned
+            .cmp(&self.pinned)
+            .then_with(|| self.name.cmp(&other.name))
     }
 }
 
 impl TableViewItem<VaultTableColumn> for Row {
     fn to_column(&self, column: VaultTableColumn) -> String {
         match column {
+            VaultTableColumn::Changed => if self.changed {
+                theme::glyphs().changed_item
+            } else if self.pinned {
+                theme::glyphs().pinned_item
+            } else {
+                ""
+            }
+            .to_string(),
             VaultTableColumn::ItemType => self.item_type.clone(),
-            VaultTableColumn::Name => self.name.clone(),
-            VaultTableColumn::Username => self.username.clone(),
+            VaultTableColumn::Name => underline_matches(&self.name, &self.name_matches),
+            VaultTableColumn::Username => underline_matches(&self.username, &self.username_matches),
+            VaultTableColumn::Folder => self.folder.clone(),
+            VaultTableColumn::PasswordChanged => self.password_changed.clone(),
             VaultTableColumn::IsInOrganization => if self.is_in_organization {
-                "👥"
+                theme::glyphs().organization_item
             } else {
-                "👤"
+                theme::glyphs().personal_item
             }
             .to_string(),
         }
@@ -189,9 +361,12 @@ impl TableViewItem<VaultTableColumn> for Row {
         Self: Sized,
     {
         match column {
+            VaultTableColumn::Changed => self.changed.cmp(&other.changed),
             VaultTableColumn::ItemType => self.item_type.cmp(&other.item_type),
             VaultTableColumn::Name => self.name.cmp(&other.name),
             VaultTableColumn::Username => self.username.cmp(&other.username),
+            VaultTableColumn::Folder => self.folder.cmp(&other.folder),
+            VaultTableColumn::PasswordChanged => self.password_changed.cmp(&other.password_changed),
             VaultTableColumn::IsInOrganization => {
                 self.is_in_organization.cmp(&other.is_in_organization)
             }
@@ -204,47 +379,419 @@ fn vault_view(
     collection: &CollectionSelection,
     user_data: &StatefulUserData<Unlocked>,
 ) -> OnEventView<LinearLayout> {
-    let table = vault_table_view();
+    let (show_folders, show_password_changed) = user_data
+        .profile_store()
+        .load()
+        .map(|d| (d.show_folder_column, d.show_password_changed_column))
+        .unwrap_or_default();
+    let table = vault_table_view(show_folders, show_password_changed);
 
-    let ll = LinearLayout::vertical()
+    let mut ll = LinearLayout::vertical()
         .child(search_edit_view(search_term))
         .child(active_collection_filter_view(collection, user_data))
         .child(table)
         .weight(100)
-        .child(key_hint_view());
+        .child(search_match_view())
+        .child(key_hint_view(user_data.global_settings().offline));
+    if user_data
+        .global_settings()
+        .server_configuration
+        .uses_insecure_http()
+    {
+        ll.insert_child(0, insecure_http_banner());
+    }
 
+    // Key combinations with ctrl are never typed as text, so they can
+    // be handled regardless of the focus
     OnEventView::new(ll)
-        .on_event('/', |siv| {
-            if let Some(mut edit) = siv.find_name::<EditView>("search_edit") {
-                edit.set_content("");
+        .on_event(Event::CtrlChar('s'), sync_vault)
+        .on_event(Event::CtrlChar('l'), lock_vault)
+        .on_event(Event::CtrlChar('z'), undo_last_action)
+}
+
+/// Adds the single-key shortcuts. They are only handled when the table
+/// has focus, so that they can't be triggered when typing e.g. a search.
+fn with_table_shortcuts(table: impl View) -> impl View {
+    let view = OnEventView::new(table)
+        .on_event('/', focus_search)
+        .on_event('q', show_quit_dialog)
+        .on_event('p', |siv| {
+            copy_current_item_field(siv, Copyable::Password);
+        })
+        .on_event('u', |siv| {
+            copy_current_item_field(siv, Copyable::Username);
+        })
+        .on_event('U', |siv| {
+            copy_current_item_field(siv, Copyable::Uri);
+        })
+        .on_event('n', |siv| {
+            copy_current_item_field(siv, Copyable::Notes);
+        })
+        .on_event('t', |siv| {
+            copy_current_item_field(siv, Copyable::Totp);
+        })
+        .on_event('c', show_collections)
+        .on_event('f', show_folders)
+        .on_event('F', toggle_folder_column)
+        .on_event('D', toggle_password_changed_column)
+        .on_event('a', show_create_item_dialog)
+        .on_event('i', show_vault_info)
+        .on_event('o', show_organizations)
+        .on_event('w', open_web_vault)
+        .on_event('e', show_sends)
+        .on_event('g', show_generator)
+        .on_event('d', show_vault_changes)
+        .on_event('x', show_export_dialog)
+        .on_event('P', show_pin_collection_dialog)
+        .on_event('S', show_sensitive_collection_dialog)
+        .on_event('L', show_lock_settings_dialog)
+        .on_event('T', toggle_current_item_pinned);
+
+    (1..=9).fold(view, |view, key| {
+        let c = char::from_digit(key, 10).unwrap();
+        view.on_event(c, move |siv| select_pinned_collection(siv, key as u8))
+    })
+}
+
+type TableEventView = OnEventView<NamedView<TableView<Row, VaultTableColumn>>>;
+
+/// Opens the details with a double click, and shows a context menu for
+/// the selected item with a right click
+fn with_mouse_actions(view: TableEventView) -> TableEventView {
+    let last_click: Mutex<Option<(Instant, usize)>> = Mutex::new(None);
+
+    view.on_pre_event_inner(EventTrigger::mouse(), move |table, event| {
+        match *event {
+            Event::Mouse {
+                event: MouseEvent::Release(MouseButton::Left),
+                ..
+            } => {
+                // The row was selected when the button was pressed
+                let row = table.get_mut().item()?;
+                let now = Instant::now();
+                let mut last_click = last_click.lock().unwrap();
+                match *last_click {
+                    Some((time, r)) if r == row && now - time < DOUBLE_CLICK_INTERVAL => {
+                        *last_click = None;
+                        Some(EventResult::with_cb(open_selected_item))
+                    }
+                    _ => {
+                        *last_click = Some((now, row));
+                        None
+                    }
+                }
             }
-            siv.focus_name("search_edit").unwrap();
+            Event::Mouse {
+                event: MouseEvent::Release(MouseButton::Right),
+                position,
+                ..
+            } => {
+                table.get_mut().item()?;
+                Some(EventResult::with_cb(move |siv| {
+                    show_context_menu(siv, position)
+                }))
+            }
+            _ => None,
+        }
+    })
+}
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Moves the selection a page or half a page at a time. The page size is
+/// the number of visible rows.
+fn with_paging_keys(view: TableEventView) -> TableEventView {
+    [
+        (Event::Key(Key::PageUp), TableMove::PageUp),
+        (Event::Key(Key::PageDown), TableMove::PageDown),
+        (Event::CtrlChar('u'), TableMove::HalfPageUp),
+        (Event::CtrlChar('d'), TableMove::HalfPageDown),
+        (Event::Key(Key::Home), TableMove::First),
+        (Event::Key(Key::End), TableMove::Last),
+    ]
+    .into_iter()
+    .fold(view, |view, (event, movement)| {
+        view.on_pre_event_inner(event, move |table, _| {
+            move_selection(&mut table.get_mut(), movement)
         })
-        .on_event('q', |siv| {
-            let dialog = Dialog::text("Quit?")
-                .dismiss_button("Cancel")
-                .button("Quit", |siv| siv.quit());
-            siv.add_layer(dialog);
+    })
+}
+
+#[derive(Clone, Copy)]
+enum TableMove {
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    First,
+    Last,
+}
+
+fn move_selection(
+    table: &mut TableView<Row, VaultTableColumn>,
+    movement: TableMove,
+) -> Option<EventResult> {
+    let last = table.len().checked_sub(1)?;
+    let row = table.row().unwrap_or(0);
+    let page = table.get_scroller().content_viewport().height().max(2);
+
+    let target = match movement {
+        TableMove::PageUp => row.saturating_sub(page),
+        TableMove::PageDown => row + page,
+        TableMove::HalfPageUp => row.saturating_sub(page / 2),
+        TableMove::HalfPageDown => row + page / 2,
+        TableMove::First => 0,
+        TableMove::Last => last,
+    }
+    .min(last);
+    table.set_selected_row(target);
+
+    // set_selected_row does not run the select callback
+    Some(EventResult::with_cb(|siv| {
+        siv.call_on_name("vault_view", VaultView::update_search_match_label);
+    }))
+}
+
+fn show_context_menu(siv: &mut Cursive, position: Vec2) {
+    let menu = menu::Tree::new()
+        .leaf("Open details", open_selected_item)
+        .delimiter()
+        .leaf("Copy password", |siv| {
+            copy_current_item_field(siv, Copyable::Password)
         })
-        .on_event(Event::CtrlChar('s'), |siv| {
-            do_sync(siv, false);
+        .leaf("Copy username", |siv| {
+            copy_current_item_field(siv, Copyable::Username)
         })
-        .on_event(Event::CtrlChar('l'), |siv| {
-            lock_vault(siv);
+        .leaf("Copy URI", |siv| {
+            copy_current_item_field(siv, Copyable::Uri)
         })
-        .on_event('p', |siv| {
-            copy_current_item_field(siv, Copyable::Password);
+        .leaf("Copy notes", |siv| {
+            copy_current_item_field(siv, Copyable::Notes)
         })
-        .on_event('u', |siv| {
-            copy_current_item_field(siv, Copyable::Username);
+        .leaf("Copy TOTP code", |siv| {
+            copy_current_item_field(siv, Copyable::Totp)
         })
-        .on_event('c', |siv| {
-            show_collection_filter(siv, |siv, sel| {
-                let mut vault_view = siv.find_name::<VaultView>("vault_view").unwrap();
-                let user_data = siv.get_user_data().with_unlocked_state().unwrap();
-                vault_view.set_collection_selection(sel, &user_data);
+        .delimiter()
+        .leaf("Pin to top / unpin", toggle_current_item_pinned);
+
+    siv.screen_mut()
+        .add_layer_at(Position::absolute(position), MenuPopup::new(Arc::new(menu)));
+}
+
+fn open_selected_item(siv: &mut Cursive) {
+    let sink = siv.cb_sink().clone();
+    siv.call_on_name("vault_table", |t: &mut TableView<Row, VaultTableColumn>| {
+        if let Some(row) = t.item().and_then(|i| t.borrow_item(i)) {
+            show_item_details(sink, row);
+        }
+    });
+}
+
+fn focus_search(siv: &mut Cursive) {
+    if let Some(mut edit) = siv.find_name::<EditView>("search_edit") {
+        edit.set_content("");
+    }
+    siv.focus_name("search_edit").unwrap();
+}
+
+fn show_quit_dialog(siv: &mut Cursive) {
+    let dialog = Dialog::text("Quit?")
+        .dismiss_button("Cancel")
+        .button("Quit", |siv| siv.quit());
+    siv.add_layer(dialog);
+}
+
+fn show_collections(siv: &mut Cursive) {
+    show_collection_filter(siv, set_collection_filter);
+}
+
+fn show_folders(siv: &mut Cursive) {
+    show_folder_filter(siv, set_collection_filter);
+}
+
+fn toggle_folder_column(siv: &mut Cursive) {
+    toggle_column(siv, "folder", |d| &mut d.show_folder_column);
+}
+
+fn toggle_password_changed_column(siv: &mut Cursive) {
+    toggle_column(siv, "password changed", |d| {
+        &mut d.show_password_changed_column
+    });
+}
+
+fn toggle_column(siv: &mut Cursive, name: &str, setting: fn(&mut ProfileData) -> &mut bool) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let res = ud.profile_store().edit(|d| {
+        let show = setting(d);
+        *show = !*show;
+    });
+    match res {
+        Ok(()) => reload_vault(siv),
+        Err(e) => {
+            log::warn!("Storing the {name} column setting failed: {e}");
+            siv.add_layer(Dialog::info(format!(
+                "Storing the {name} column setting failed: {e}"
+            )));
+        }
+    }
+}
+
+pub fn set_collection_filter(siv: &mut Cursive, sel: CollectionSelection) {
+    let mut vault_view = siv.find_name::<VaultView>("vault_view").unwrap();
+    let user_data = siv.get_user_data().with_unlocked_state().unwrap();
+    vault_view.set_collection_selection(sel, &user_data);
+}
+
+fn sync_vault(siv: &mut Cursive) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let offline = ud.global_settings().offline;
+    if offline {
+        siv.add_layer(Dialog::info(
+            "Syncing is not available in offline mode. Restart wden without --offline to sync.",
+        ));
+        return;
+    }
+    do_sync(siv, false);
+}
+
+/// Switches to the collection pinned to the key. If that collection
+/// is already selected, the collection filter is reset.
+fn select_pinned_collection(siv: &mut Cursive, key: u8) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let pinned_collection_id = ud
+        .profile_store()
+        .load()
+        .ok()
+        .and_then(|d| d.pinned_collections.get(&key).cloned());
+
+    let collection_id = match pinned_collection_id {
+        Some(id) if ud.collections().contains_key(&id) => id,
+        Some(_) => {
+            siv.add_layer(Dialog::info(format!(
+                "The collection pinned to key {key} was not found in the vault."
+            )));
+            return;
+        }
+        None => {
+            siv.add_layer(Dialog::info(format!(
+                "No collection is pinned to key {key}. \
+                Select a collection with <c>, and pin it with <P>."
+            )));
+            return;
+        }
+    };
+
+    let mut vault_view = siv.find_name::<VaultView>("vault_view").unwrap();
+    let sel = match &vault_view.collection_selection {
+        CollectionSelection::Collection(id) if *id == collection_id => CollectionSelection::All,
+        _ => CollectionSelection::Collection(collection_id),
+    };
+    let user_data = siv.get_user_data().with_unlocked_state().unwrap();
+    vault_view.set_collection_selection(sel, &user_data);
+}
+
+fn show_pin_collection_dialog(siv: &mut Cursive) {
+    let Some((_, CollectionSelection::Collection(collection_id))) = get_filters(siv) else {
+        siv.add_layer(Dialog::info(
+            "Select a collection with <c> first, and then pin it with <P>.",
+        ));
+        return;
+    };
+
+    let dialog = Dialog::text("Press a number key (1-9) to pin the selected collection to it.")
+        .title("Pin collection")
+        .dismiss_button("Cancel");
+
+    let dialog = (1..=9).fold(OnEventView::new(dialog), |dialog, key| {
+        let c = char::from_digit(key, 10).unwrap();
+        let collection_id = collection_id.clone();
+        dialog.on_event(c, move |siv| {
+            siv.pop_layer();
+            let ud = siv.get_user_data().with_unlocked_state().unwrap();
+            let res = ud.profile_store().edit(|d| {
+                d.pinned_collections
+                    .insert(key as u8, collection_id.clone());
+                d.settings_revision = settings_revision_now();
             });
+            match res {
+                Ok(()) => show_copy_notification(siv, "Collection pinned"),
+                Err(e) => {
+                    log::warn!("Storing the pinned collection failed: {e}");
+                    siv.add_layer(Dialog::info(format!("Pinning the collection failed: {e}")));
+                }
+            }
+        })
+    });
+
+    siv.add_layer(dialog);
+}
+
+/// Marks the selected collection as sensitive, or removes the mark after
+/// asking for the master password
+fn show_sensitive_collection_dialog(siv: &mut Cursive) {
+    let Some((_, CollectionSelection::Collection(collection_id))) = get_filters(siv) else {
+        siv.add_layer(Dialog::info(
+            "Select a collection with <c> first, and then mark it with <S>.",
+        ));
+        return;
+    };
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let profile_store = ud.profile_store();
+    let is_sensitive = profile_store
+        .load()
+        .is_ok_and(|d| d.sensitive_collections.contains(&collection_id));
+
+    let set_sensitive = move |siv: &mut Cursive, sensitive: bool| {
+        let res = profile_store.edit(|d| {
+            if sensitive {
+                d.sensitive_collections.insert(collection_id.clone());
+            } else {
+                d.sensitive_collections.remove(&collection_id);
+            }
+        });
+        match res {
+            Ok(()) if sensitive => show_copy_notification(siv, "Collection marked as sensitive"),
+            Ok(()) => show_copy_notification(siv, "Sensitive mark removed"),
+            Err(e) => {
+                log::warn!("Storing the sensitive collections failed: {e}");
+                siv.add_layer(Dialog::info(format!("Storing the setting failed: {e}")));
+            }
+        }
+    };
+
+    if is_sensitive {
+        with_reprompt(siv, ItemPolicy::sensitive(), move |siv| {
+            set_sensitive(siv, false)
+        });
+    } else {
+        let dialog = Dialog::text(
+            "Mark the selected collection as sensitive? Copied values of its items are \
+            cleared from the clipboard after 10 seconds, passwords can't be shown, and the \
+            master password is asked before opening the items or copying their secrets.",
+        )
+        .title("Sensitive collection")
+        .dismiss_button("Cancel")
+        .button("Mark as sensitive", move |siv| {
+            siv.pop_layer();
+            set_sensitive(siv, true);
         })
+        .max_width(80);
+        siv.add_layer(dialog);
+    }
+}
+
+/// Recreates the vault view from the vault data, keeping the filters
+/// and the position
+pub fn reload_vault(cursive: &mut Cursive) {
+    if let Some(position) = get_position(cursive) {
+        cursive.get_user_data().store_vault_position(position);
+    }
+    let (search_term, collection_selection) = get_filters(cursive).unwrap_or_default();
+    show_vault_with_filters(cursive, search_term, collection_selection);
+}
+
+pub fn get_position(cursive: &mut Cursive) -> Option<VaultPosition> {
+    cursive.find_name::<VaultView>("vault_view")?.position()
 }
 
 pub fn get_filters(cursive: &mut Cursive) -> Option<(String, CollectionSelection)> {
@@ -255,15 +802,41 @@ pub fn get_filters(cursive: &mut Cursive) -> Option<(String, CollectionSelection
     ))
 }
 
-fn copy_current_item_field(siv: &mut Cursive, field: Copyable) {
-    let table = siv
+fn toggle_current_item_pinned(siv: &mut Cursive) {
+    let item_id = siv
         .find_name::<TableView<Row, VaultTableColumn>>("vault_table")
-        .unwrap();
-    let row = table.borrow_item(table.item().unwrap()).unwrap();
+        .and_then(|t| Some(t.borrow_item(t.item()?)?.id.clone()));
+    if let Some(item_id) = item_id {
+        toggle_pinned_item(siv, &item_id);
+    }
+}
+
+fn copy_current_item_field(siv: &mut Cursive, field: Copyable) {
+    let item_id = {
+        let table = siv
+            .find_name::<TableView<Row, VaultTableColumn>>("vault_table")
+            .unwrap();
+        table.borrow_item(table.item().unwrap()).unwrap().id.clone()
+    };
+
     let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let policy = ItemPolicy::for_item_id(&ud, &item_id);
+    match field {
+        Copyable::Password | Copyable::Notes | Copyable::Totp => {
+            with_reprompt(siv, policy, move |siv| {
+                copy_item_field(siv, &item_id, field, policy)
+            });
+        }
+        Copyable::Username | Copyable::Uri => copy_item_field(siv, &item_id, field, policy),
+    }
+}
+
+fn copy_item_field(siv: &mut Cursive, item_id: &str, field: Copyable, policy: ItemPolicy) {
+    let ud = siv.get_user_data().with_unlocked_state().unwrap();
+    let expiry = policy.clipboard_expiry();
 
     let vd = ud.vault_data();
-    match (vd.get(&row.id), field) {
+    match (vd.get(item_id), field) {
         (
             Some(
                 ci @ CipherItem {
@@ -274,7 +847,10 @@ fn copy_current_item_field(siv: &mut Cursive, field: Copyable) {
             Copyable::Password,
         ) => {
             let item_keys = ud.get_keys_for_item(ci).unwrap();
-            super::clipboard::clip_expiring_string(li.password.decrypt_to_string(&item_keys), 30);
+            super::clipboard::clip_expiring_string(
+                li.password.decrypt_to_string(&item_keys),
+                expiry,
+            );
             show_copy_notification(siv, "Password copied");
         }
         (
@@ -290,13 +866,91 @@ fn copy_current_item_field(siv: &mut Cursive, field: Copyable) {
             super::clipboard::clip_string(li.username.decrypt_to_string(&item_keys));
             show_copy_notification(siv, "Username copied");
         }
+        (
+            Some(
+                ci @ CipherItem {
+                    data: CipherData::Login(li),
+                    ..
+                },
+            ),
+            Copyable::Uri,
+        ) => {
+            let item_keys = ud.get_keys_for_item(ci).unwrap();
+            super::clipboard::clip_string(li.uri.decrypt_to_string(&item_keys));
+            show_copy_notification(siv, "URI copied");
+        }
+        (
+            Some(
+                ci @ CipherItem {
+                    data: CipherData::Login(li),
+                    ..
+                },
+            ),
+            Copyable::Totp,
+        ) => {
+            let item_keys = ud.get_keys_for_item(ci).unwrap();
+            let secret = li.totp.decrypt_to_string(&item_keys);
+            if secret.is_empty() {
+                show_copy_notification(siv, "The item has no TOTP secret");
+                return;
+            }
+            match Totp::parse(&secret) {
+                Ok(totp) => {
+                    // Expire the code from the clipboard when it's no longer valid
+                    let (code, remaining) = totp.generate_now();
+                    super::clipboard::clip_expiring_string(code.to_string(), remaining.min(expiry));
+                    show_copy_notification(siv, "TOTP code copied");
+                }
+                Err(e) => {
+                    siv.add_layer(Dialog::info(format!(
+                        "Generating the TOTP code failed: {e}"
+                    )));
+                }
+            }
+        }
+        (Some(_), Copyable::Totp) => {
+            show_copy_notification(siv, "The item has no TOTP secret");
+        }
+        (Some(ci), Copyable::Notes) => {
+            let item_keys = ud.get_keys_for_item(ci).unwrap();
+            let notes = ci.notes.decrypt_to_string(&item_keys);
+            if notes.is_empty() {
+                show_copy_notification(siv, "The item has no notes");
+            } else if notes.chars().count() > NOTES_COPY_CONFIRM_LENGTH {
+                let dialog = Dialog::text(format!(
+                    "The notes are {} characters long. Copy them anyway?",
+                    notes.chars().count()
+                ))
+                .title("Copy notes")
+                .dismiss_button("Cancel")
+                .button("Copy", move |siv| {
+                    siv.pop_layer();
+                    super::clipboard::clip_expiring_string(notes.clone(), expiry);
+                    show_copy_notification(siv, "Notes copied");
+                });
+                siv.add_layer(dialog);
+            } else {
+                super::clipboard::clip_expiring_string(notes, expiry);
+                show_copy_notification(siv, "Notes copied");
+            }
+        }
         _ => (),
     };
 }
 
+/// Position of the table in the vault view layout
+const VAULT_TABLE_INDEX: usize = 2;
+
+/// Copying notes longer than this needs to be confirmed
+const NOTES_COPY_CONFIRM_LENGTH: usize = 1000;
+
+#[derive(Clone, Copy)]
 enum Copyable {
     Password,
     Username,
+    Uri,
+    Notes,
+    Totp,
 }
 
 fn search_edit_view(search_term: &str) -> impl View {
@@ -314,9 +968,14 @@ fn search_edit_view(search_term: &str) -> impl View {
         .with_name("search_edit")
         .full_width();
 
+    let focus_indicator = TextView::new("")
+        .style(PaletteColor::Tertiary)
+        .with_name("focus_indicator");
+
     LinearLayout::horizontal()
-        .child(TextView::new("🔍"))
+        .child(TextView::new(theme::glyphs().search))
         .child(search_edit)
+        .child(PaddedView::new(Margins::lr(2, 0), focus_indicator))
 }
 
 fn active_collection_filter_view(
@@ -345,28 +1004,105 @@ fn active_collection_filter_label_text(
                 .unwrap_or_else(|| "<unknown>".to_string());
             format!("Collection: {collection_name}")
         }
+        CollectionSelection::NoFolder => "Folder: No folder".to_string(),
+        CollectionSelection::Folder(folder_id) => {
+            let folder_name = user_data
+                .folders()
+                .get(folder_id)
+                .and_then(|f| Some(f.name.decrypt_to_string(&user_data.decrypt_keys()?)))
+                .unwrap_or_else(|| "<unknown>".to_string());
+            format!("Folder: {folder_name}")
+        }
     }
 }
 
-fn vault_table_view() -> impl View {
-    let tv: TableView<Row, VaultTableColumn> = TableView::new()
+fn vault_table_view(show_folders: bool, show_password_changed: bool) -> impl View {
+    let mut tv: TableView<Row, VaultTableColumn> = TableView::new()
         .sorting_disabled()
+        .column(VaultTableColumn::Changed, "", |c| c.width(1))
         .column(VaultTableColumn::ItemType, "T", |c| c.width(1))
         .column(VaultTableColumn::Name, "Name", |c| c)
-        .column(VaultTableColumn::Username, "Username", |c| c)
+        .column(VaultTableColumn::Username, "Username", |c| c);
+    if show_folders {
+        tv.add_column(VaultTableColumn::Folder, "Folder", |c| c);
+    }
+    if show_password_changed {
+        tv.add_column(VaultTableColumn::PasswordChanged, "Pw changed", |c| {
+            c.width(10)
+        });
+    }
+    let tv = tv
         .column(VaultTableColumn::IsInOrganization, "O", |c| c.width(2))
         .on_submit(|siv: &mut Cursive, _, index| {
+            let enter_action = siv
+                .get_user_data()
+                .with_unlocked_state()
+                .map(|ud| ud.global_settings().enter_action)
+                .unwrap_or_default();
             let sink = siv.cb_sink().clone();
-            siv.call_on_name(
-                "vault_table",
-                move |t: &mut TableView<Row, VaultTableColumn>| {
-                    show_item_details(sink, t.borrow_item(index).unwrap());
-                },
-            )
-            .unwrap();
+            let copy_password = siv
+                .call_on_name(
+                    "vault_table",
+                    move |t: &mut TableView<Row, VaultTableColumn>| {
+                        let row = t.borrow_item(index).unwrap();
+                        if enter_action == EnterAction::CopyPassword && row.item_type == "L" {
+                            true
+                        } else {
+                            show_item_details(sink, row);
+                            false
+                        }
+                    },
+                )
+                .unwrap();
+            if copy_password {
+                copy_current_item_field(siv, Copyable::Password);
+            }
+        })
+        .on_select(|siv: &mut Cursive, _, _| {
+            siv.call_on_name("vault_view", VaultView::update_search_match_label);
         });
 
-    tv.with_name("vault_table").full_height()
+    let table = OnEventView::new(tv.with_name("vault_table"));
+    let table = with_paging_keys(with_mouse_actions(table));
+    with_table_shortcuts(table).full_height()
+}
+
+/// The table cells are plain strings, so the characters matching the
+/// search are underlined with a combining low line after each of them
+fn underline_matches(text: &str, ranges: &[Range<usize>]) -> String {
+    const COMBINING_LOW_LINE: char = '\u{332}';
+
+    let mut underlined = String::with_capacity(text.len());
+    for (i, c) in text.char_indices() {
+        underlined.push(c);
+        if ranges.iter().any(|r| r.contains(&i)) {
+            underlined.push(COMBINING_LOW_LINE);
+        }
+    }
+    underlined
+}
+
+fn search_match_view() -> impl View {
+    PaddedView::new(
+        Margins::lr(2, 2),
+        TextView::new("")
+            .style(PaletteColor::Tertiary)
+            .with_name("search_match_label")
+            .fixed_height(1),
+    )
+}
+
+/// Tells which field the selected search result matched, when the
+/// match is not highlighted in the table
+fn search_match_text(row: &Row) -> &'static str {
+    if !row.name_matches.is_empty() || !row.username_matches.is_empty() {
+        return "";
+    }
+    match row.search_match {
+        Some(SearchField::Username) => "matched: username",
+        Some(SearchField::Uri) => "matched: URI",
+        Some(SearchField::Name) | None => "",
+    }
 }
 
 fn create_rows(user_data: &StatefulUserData<Unlocked>, user_keys: EncMacKeys) -> Vec<Row> {
@@ -375,33 +1111,61 @@ fn create_rows(user_data: &StatefulUserData<Unlocked>, user_keys: EncMacKeys) ->
     // Find all organization keys we will need
     let org_keys = user_data.get_org_keys_for_vault();
     let vault_data = user_data.vault_data();
+    let changed_item_ids = user_data.changed_item_ids();
+    let pinned_item_ids = pinned_item_ids(user_data, &user_keys);
+    let folders: HashMap<_, _> = folder_names(user_data)
+        .into_iter()
+        .map(|(name, id)| (id, name))
+        .collect();
 
     let mut rows: Vec<Row> = vault_data
         .par_iter()
-        .filter_map(|(id, ci)| {
+        .map_init(DecryptBuffer::default, |buf, (id, ci)| {
             let item_keys = resolve_item_keys(ci, (&user_keys).into(), |oid, _uk| {
                 org_keys.get(oid).map(|k| k.into())
             })?;
             Some(Row {
                 id: id.clone(),
-                name: ci.name.decrypt_to_string(&item_keys),
+                // Copied straight from the decryption buffer into the
+                // row, which is zeroized when dropped
+                name: ci.name.decrypt_to_str_buffered(&item_keys, buf).into(),
                 username: match &ci.data {
                     CipherData::Login(l) => &l.username,
                     _ => &Cipher::Empty,
                 }
-                .decrypt_to_string(&item_keys),
+                .decrypt_to_str_buffered(&item_keys, buf)
+                .into(),
                 item_type: match ci.data {
                     CipherData::Login(_) => "L",
                     CipherData::Card(_) => "C",
                     CipherData::Identity(_) => "I",
                     CipherData::SecureNote => "N",
+                    CipherData::SshKey(_) => "K",
                     _ => "",
                 }
                 .to_string(),
                 is_in_organization: ci.organization_id.is_some(),
                 collection_ids: ci.collection_ids.clone(),
+                folder_id: ci.folder_id.clone(),
+                folder: ci
+                    .folder_id
+                    .as_ref()
+                    .and_then(|id| folders.get(id))
+                    .cloned()
+                    .unwrap_or_default(),
+                password_changed: ci
+                    .password_changed_date()
+                    .and_then(|date| date.get(..10))
+                    .unwrap_or_default()
+                    .to_string(),
+                search_match: None,
+                name_matches: vec![],
+                username_matches: vec![],
+                changed: changed_item_ids.contains(id),
+                pinned: pinned_item_ids.contains(id),
             })
         })
+        .flatten()
         .collect();
     rows.sort();
 
@@ -417,30 +1181,71 @@ fn show_item_details(cb: cursive::CbSink, row: &Row) {
     let item_id = row.id.clone();
     cb.send_msg(Box::new(move |siv: &mut Cursive| {
         let ud = siv.get_user_data().with_unlocked_state().unwrap();
-        let dialog = item_detail_dialog(&ud, &item_id);
-        if let Some(d) = dialog {
-            siv.add_layer(d);
-        }
+        let policy = ItemPolicy::for_item_id(&ud, &item_id);
+        with_reprompt(siv, policy, move |siv| open_item_details(siv, &item_id));
     }));
 }
 
-fn key_hint_view() -> impl View {
+fn open_item_details(siv: &mut Cursive, item_id: &str) {
+    let mut ud = siv.get_user_data().with_unlocked_state().unwrap();
+    ud.mark_item_seen(item_id);
+    let dialog = item_detail_dialog(&ud, item_id);
+    if let Some(d) = dialog {
+        siv.add_layer(d);
+    }
+    siv.call_on_name("vault_view", |vv: &mut VaultView| {
+        vv.clear_changed_marker(item_id)
+    });
+}
+
+fn key_hint_view(offline: bool) -> impl View {
     fn hint_text(content: &str) -> impl View {
         PaddedView::new(
             Margins::lr(2, 2),
-            TextView::new(content).style(Color::Light(BaseColor::Black)),
+            TextView::new(content).style(PaletteColor::Tertiary),
         )
     }
 
-    LinearLayout::horizontal()
-        .child(hint_text("</> Search"))
-        .child(hint_text("<c> Collections"))
-        .child(hint_text("<p> Copy password"))
-        .child(hint_text("<u> Copy username"))
-        .child(hint_text("<q> Quit"))
-        .child(hint_text("<^s> Sync"))
-        .child(hint_text("<^l> Lock"))
-        .full_width()
+    // The hints can be clicked. The actions work on the table, so
+    // the focus is moved back there first.
+    fn hint_button<F>(content: &str, action: F) -> impl View
+    where
+        F: Fn(&mut Cursive) + Send + Sync + 'static,
+    {
+        let button = HintButton::new(content, move |siv| {
+            _ = siv.focus_name("vault_table");
+            action(siv);
+        });
+        PaddedView::new(Margins::lr(2, 2), button)
+    }
+
+    let mut ll = LinearLayout::horizontal()
+        .child(hint_button("</> Search", focus_search))
+        .child(hint_button("<c> Collections", show_collections))
+        .child(hint_button("<f> Folders", show_folders))
+        .child(hint_button("<a> Add item", show_create_item_dialog))
+        .child(hint_button("<p> Copy password", |siv| {
+            copy_current_item_field(siv, Copyable::Password)
+        }))
+        .child(hint_button("<u> Copy username", |siv| {
+            copy_current_item_field(siv, Copyable::Username)
+        }))
+        .child(hint_button("<U> Copy URI", |siv| {
+            copy_current_item_field(siv, Copyable::Uri)
+        }))
+        .child(hint_button("<n> Copy notes", |siv| {
+            copy_current_item_field(siv, Copyable::Notes)
+        }))
+        .child(hint_button("<t> Copy TOTP", |siv| {
+            copy_current_item_field(siv, Copyable::Totp)
+        }))
+        .child(hint_button("<q> Quit", show_quit_dialog));
+    if offline {
+        ll.add_child(hint_text("Offline"));
+    } else {
+        ll.add_child(hint_button("<^s> Sync", sync_vault));
+    }
+    ll.child(hint_button("<^l> Lock", lock_vault)).full_width()
 }
 
 pub fn show_copy_notification(cursive: &mut Cursive, message: &'static str) {
@@ -471,6 +1276,7 @@ pub fn show_vault_with_filters(
     search_term: String,
     collection_selection: CollectionSelection,
 ) {
+    let position = cursive.get_user_data().take_vault_position();
     let ud = cursive.get_user_data().with_unlocked_state().unwrap();
     ud.autolocker()
         .lock()
@@ -478,14 +1284,99 @@ pub fn show_vault_with_filters(
         .update_next_autolock_time(true);
     let global_settings = ud.global_settings();
 
-    let view =
-        VaultView::new_with_filters(&ud, collection_selection, search_term).with_name("vault_view");
+    let mut view = VaultView::new_with_filters(&ud, collection_selection, search_term);
+    if let Some(position) = position {
+        view.restore_position(&position);
+    }
+    let view = view.with_name("vault_view");
 
-    let panel = Panel::new(view)
-        .title(format!("Vault ({})", &global_settings.profile))
-        .full_screen();
+    let title = if global_settings.offline {
+        format!("Vault ({}, offline)", &global_settings.profile)
+    } else {
+        format!("Vault ({})", &global_settings.profile)
+    };
+    let panel = Panel::new(view).title(title).full_screen();
 
     // Clear all, and add the vault
     cursive.clear_layers();
     cursive.add_fullscreen_layer(panel);
+
+    if global_settings.clipboard_backend == ClipboardBackend::Osc52 {
+        show_osc52_warning(cursive);
+    } else {
+        show_remote_clipboard_warning(cursive);
+    }
+}
+
+/// Warns once per session if the terminal is known to drop the OSC 52
+/// sequence, as nothing tells when copying does not work
+fn show_osc52_warning(cursive: &mut Cursive) {
+    static SHOWN: AtomicBool = AtomicBool::new(false);
+
+    let Some(warning) = super::clipboard::osc52_support_warning() else {
+        return;
+    };
+    if SHOWN.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    cursive.add_layer(
+        Dialog::text(format!(
+            "{warning}. Values copied with wden may not reach the clipboard.\n\n\
+            Use --clipboard-backend system to copy to the system clipboard instead."
+        ))
+        .title("Terminal clipboard may not work")
+        .dismiss_button("OK")
+        .max_width(80),
+    );
+}
+
+/// Warns once per session, before anything is copied, if the clipboard
+/// is shared over a forwarded or remote display connection.
+fn show_remote_clipboard_warning(cursive: &mut Cursive) {
+    static SHOWN: AtomicBool = AtomicBool::new(false);
+
+    let Some(reason) = super::clipboard::remote_display_reason() else {
+        return;
+    };
+    let ud = cursive.get_user_data().with_unlocked_state().unwrap();
+    let profile_store = ud.profile_store();
+    let hidden = profile_store
+        .load()
+        .is_ok_and(|d| d.hide_remote_clipboard_warning);
+    if hidden || SHOWN.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    let dialog = Dialog::text(format!(
+        "{reason}.\n\nValues copied with wden are sent over that connection to the clipboard \
+        of the remote display, and may be readable there. Consider viewing the values in the \
+        item details instead of copying them (<s> shows the password), or starting wden with \
+        --clipboard-backend osc52 to copy to the clipboard of your local terminal instead."
+    ))
+    .title("Clipboard is shared remotely")
+    .dismiss_button("OK")
+    .button("Don't show again", move |siv| {
+        siv.pop_layer();
+        if let Err(e) = profile_store.edit(|d| d.hide_remote_clipboard_warning = true) {
+            log::warn!("Storing the warning setting failed: {e}");
+        }
+    })
+    .max_width(80);
+    cursive.add_layer(dialog);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_underline_matches() {
+        assert_eq!(underline_matches("github", &[]), "github");
+        assert_eq!(
+            underline_matches("my github", &[3..6]),
+            "my g\u{332}i\u{332}t\u{332}hub"
+        );
+        // Multi-byte characters are underlined as a whole
+        assert_eq!(underline_matches("äö x", &[0..4]), "ä\u{332}ö\u{332} x");
+    }
 }
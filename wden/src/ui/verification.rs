@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use cursive::{
+    traits::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, TextView},
+    Cursive,
+};
+
+use crate::bitwarden::api::{ApiClient, Verification};
+
+use super::{
+    login::{do_login, handle_login_response, login_dialog},
+    util::cursive_ext::CursiveExt,
+};
+
+const VIEW_NAME_VERIFICATION_CODE: &str = "verification_code";
+
+/// Explains a verification the server requires by email, and retries the
+/// login once it's done. The state stays LoggingIn, so the keys don't
+/// have to be derived again.
+pub fn verification_dialog(
+    verification: Verification,
+    email: Arc<String>,
+    profile_name: &str,
+) -> Dialog {
+    let mut content = LinearLayout::vertical();
+    match verification {
+        Verification::NewDevice => {
+            content.add_child(TextView::new(format!(
+                "The server requires verifying logins on new devices. A verification \
+                code has been sent to {email}. Enter the code below and retry."
+            )));
+            content.add_child(TextView::new("\nVerification code:"));
+            let email = email.clone();
+            content.add_child(
+                EditView::new()
+                    .on_submit(move |siv, _| retry_login(siv, verification, email.clone()))
+                    .with_name(VIEW_NAME_VERIFICATION_CODE),
+            );
+        }
+        Verification::Email => {
+            content.add_child(TextView::new(format!(
+                "The server requires verifying the email address of the account. \
+                Follow the link in the verification email sent to {email}, \
+                or send a new one from the web vault, and then retry."
+            )));
+        }
+    }
+
+    let email2 = email.clone();
+    Dialog::around(content.max_width(70))
+        .title(format!("Verification required ({profile_name})"))
+        .button("I've verified, retry", move |siv| {
+            retry_login(siv, verification, email.clone())
+        })
+        .button("Cancel", move |siv| {
+            let ud = siv.get_user_data().with_logging_in_state().unwrap();
+            let ud = ud.into_logged_out();
+            let d = login_dialog(
+                &ud.global_settings(),
+                Some(email2.to_string()),
+                false,
+                false,
+            );
+            siv.clear_layers();
+            siv.add_layer(d);
+        })
+}
+
+fn retry_login(c: &mut Cursive, verification: Verification, email: Arc<String>) {
+    let code = c
+        .call_on_name(VIEW_NAME_VERIFICATION_CODE, |view: &mut EditView| {
+            view.get_content().trim().to_string()
+        })
+        .filter(|code| !code.is_empty());
+    if verification == Verification::NewDevice && code.is_none() {
+        c.add_layer(Dialog::info("Enter the verification code from the email"));
+        return;
+    }
+
+    c.pop_layer();
+    c.add_layer(Dialog::text("Signing in..."));
+
+    let ud = c.get_user_data().with_logging_in_state().unwrap();
+    let global_settings = ud.global_settings();
+    let profile_store = ud.profile_store();
+    let master_pw_hash = ud.master_password_hash();
+    let email2 = email.clone();
+
+    c.async_op(
+        async move {
+            let client = ApiClient::new(
+                &global_settings.server_configuration,
+                &global_settings.device_id,
+                global_settings.device_class,
+                global_settings.accept_invalid_certs,
+                &global_settings.pinned_spki_hashes,
+            )?;
+            do_login(
+                &client,
+                &email,
+                master_pw_hash,
+                None,
+                None,
+                code.as_deref(),
+                &profile_store,
+            )
+            .await
+        },
+        move |siv, res| match res {
+            Err(e) => show_verification_error(siv, e, verification, email2),
+            res => handle_login_response(siv, res, email2, false, false),
+        },
+    );
+}
+
+/// Shows a failed retry, e.g. with a mistyped code, keeping the LoggingIn
+/// state like after a failed two-factor login
+fn show_verification_error(
+    siv: &mut Cursive,
+    e: anyhow::Error,
+    verification: Verification,
+    email: Arc<String>,
+) {
+    siv.pop_layer();
+    let profile = siv.get_user_data().global_settings().profile.clone();
+    siv.add_layer(
+        Dialog::text(format!("Error: {e:?}"))
+            .title("Verification error")
+            .button("Try again", move |siv| {
+                siv.pop_layer();
+                siv.add_layer(verification_dialog(verification, email.clone(), &profile));
+            }),
+    );
+}
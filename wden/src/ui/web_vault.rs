@@ -0,0 +1,46 @@
+use std::process::Stdio;
+
+use cursive::{views::Dialog, Cursive};
+use tokio::process::Command;
+
+use super::util::cursive_ext::CursiveExt;
+
+/// Opens the web vault of the server in the default browser, for the
+/// features that wden doesn't support
+pub fn open_web_vault(siv: &mut Cursive) {
+    let Some(ud) = siv.get_user_data().with_unlocked_state() else {
+        return;
+    };
+    let Some(url) = ud.global_settings().server_configuration.web_vault_url() else {
+        siv.add_layer(Dialog::info(
+            "The address of the web vault can't be determined from the server URLs.",
+        ));
+        return;
+    };
+    let url = url.to_string();
+
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        // The empty argument is the window title
+        c.args(["/C", "start", ""]);
+        c
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(&url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    siv.async_op(async move { command.status().await }, move |siv, res| {
+        if !matches!(res, Ok(status) if status.success()) {
+            log::warn!("Opening the web vault failed: {res:?}");
+            siv.add_layer(Dialog::info(format!(
+                "Opening the browser failed. The web vault is at\n{url}"
+            )));
+        }
+    });
+}